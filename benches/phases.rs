@@ -0,0 +1,162 @@
+//! Scanning, parsing and interpreting, each timed on its own. Run with
+//! `cargo bench`, optionally naming the benches to run.
+//!
+//! Binary operators used to clone both operands to try them as numbers, and
+//! `+` cloned them again, building a type error for every string or list.
+//! Converting operands only as the operator needs them measured, in µs per
+//! iteration on one core:
+//!
+//! | bench                       | before | after |
+//! |-----------------------------|--------|-------|
+//! | scan_program                | 800    | 805   |
+//! | parse_program               | 90     | 89    |
+//! | interpret_sum_loop          | 2740   | 2670  |
+//! | interpret_nested_arithmetic | 2095   | 1835  |
+//! | interpret_string_concat     | 710    | 360   |
+//!
+//! Sharing string values as `Rc<str>` so literals clone cheaply was tried
+//! too, it made the numeric benches about 40% slower.
+//!
+//! Interning names as `Symbol`s, so environments hash and compare a pointer
+//! rather than the whole name and tokens clone without allocating:
+//!
+//! | bench                       | before | after |
+//! |-----------------------------|--------|-------|
+//! | scan_program                | 83     | 84    |
+//! | parse_program               | 89     | 77    |
+//! | interpret_sum_loop          | 2815   | 2625  |
+//! | interpret_nested_arithmetic | 1905   | 1840  |
+//! | interpret_string_concat     | 365    | 370   |
+//!
+//! Running blocks in the interpreter itself, with its environment swapped
+//! for the block's, rather than in a new interpreter per block. Measured on
+//! a noisier machine, so only the interpret benches are comparable:
+//!
+//! | bench                       | before | after |
+//! |-----------------------------|--------|-------|
+//! | interpret_sum_loop          | 3900   | 3400  |
+//! | interpret_nested_arithmetic | 3600   | 3350  |
+//! | interpret_string_concat     | 950    | 500   |
+//!
+//! Reusing the environments of finished blocks didn't show up above the
+//! noise, each loop iteration still allocates one.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use j_bread::{Interpreter, Parser, Resolver, Scanner};
+
+/// Times a routine the way libtest's bencher did, which needs nightly: runs
+/// it for about a second and reports the mean time per iteration.
+struct Bencher {
+    elapsed: Duration,
+    iterations: u32,
+}
+
+impl Bencher {
+    fn iter<T>(&mut self, mut routine: impl FnMut() -> T) {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(1) {
+            black_box(routine());
+            self.iterations += 1;
+        }
+        self.elapsed = start.elapsed();
+    }
+}
+
+/// Sums 1 to 10000 in a loop.
+const SUM_LOOP: &str = "
+var total = 0;
+var i = 1;
+repeat (10000) {
+    total = total + i;
+    i = i + 1;
+}
+";
+
+/// Builds up a string in a loop, which clones it for every `+`.
+const STRING_CONCAT: &str = "
+var text = \"\";
+var i = 0;
+repeat (1000) {
+    text = text + \"ab\";
+    i = i + 1;
+}
+";
+
+/// An expression of 200 additions and multiplications, evaluated 100 times.
+fn nested_arithmetic() -> String {
+    let mut source = "1".to_string();
+    for depth in 0..200 {
+        let operator = if depth % 2 == 0 { "+" } else { "*" };
+        source = format!("{} {} (1 - 0)", source, operator);
+    }
+    format!("var result; repeat (100) {{ result = {}; }}", source)
+}
+
+fn interpret(bencher: &mut Bencher, source: &str) {
+    let (tokens, errors) = Scanner::new(source).scan();
+    assert!(errors.is_empty());
+    let stmts = Parser::new(&tokens).parse().unwrap();
+    bencher.iter(|| {
+        let mut interpreter = Interpreter::default();
+        let resolution = Resolver::default().resolve(&stmts).unwrap();
+        interpreter.set_locals(resolution.locals);
+        interpreter.interpret(black_box(&stmts)).unwrap();
+        interpreter
+    });
+}
+
+fn scan_program(bencher: &mut Bencher) {
+    let source = format!("{}{}{}", SUM_LOOP, STRING_CONCAT, nested_arithmetic());
+    bencher.iter(|| Scanner::new(black_box(&source)).scan());
+}
+
+fn parse_program(bencher: &mut Bencher) {
+    let source = format!("{}{}{}", SUM_LOOP, STRING_CONCAT, nested_arithmetic());
+    let (tokens, _) = Scanner::new(&source).scan();
+    bencher.iter(|| Parser::new(black_box(&tokens)).parse().unwrap());
+}
+
+fn interpret_sum_loop(bencher: &mut Bencher) {
+    interpret(bencher, SUM_LOOP);
+}
+
+fn interpret_nested_arithmetic(bencher: &mut Bencher) {
+    interpret(bencher, &nested_arithmetic());
+}
+
+fn interpret_string_concat(bencher: &mut Bencher) {
+    interpret(bencher, STRING_CONCAT);
+}
+
+type Bench = fn(&mut Bencher);
+
+fn main() {
+    let benches: [(&str, Bench); 5] = [
+        ("scan_program", scan_program),
+        ("parse_program", parse_program),
+        ("interpret_sum_loop", interpret_sum_loop),
+        ("interpret_nested_arithmetic", interpret_nested_arithmetic),
+        ("interpret_string_concat", interpret_string_concat),
+    ];
+    // Cargo passes `--bench`, and any names given after `--`.
+    let filters: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+    for (name, bench) in benches {
+        if !filters.is_empty() && !filters.iter().any(|filter| name.contains(filter.as_str())) {
+            continue;
+        }
+        let mut bencher = Bencher {
+            elapsed: Duration::ZERO,
+            iterations: 0,
+        };
+        bench(&mut bencher);
+        let micros = bencher.elapsed.as_secs_f64() * 1e6 / f64::from(bencher.iterations.max(1));
+        println!("{:<28} {:>10.0} µs/iter", name, micros);
+    }
+}
@@ -0,0 +1,84 @@
+//! A lint written outside the crate: flags comparisons against `nil`, like
+//! `x == nil`, in a script.
+//!
+//! ```sh
+//! cargo run --example nil_comparisons -- examples/nil_comparisons.jbr
+//! ```
+
+use std::{env, fs, process};
+
+use j_bread::{
+    ast::{Binary, Expr},
+    walk::{walk_expr, walk_program, ExprWalker, StmtWalker},
+    Parser, Scanner, TokenTypes,
+};
+
+/// The lines of every `==` or `!=` with `nil` on either side.
+#[derive(Default)]
+struct NilComparisons {
+    lines: Vec<u32>,
+}
+
+fn is_nil(expr: &Expr) -> bool {
+    matches!(expr, Expr::Literal(literal) if literal.value.is_none())
+}
+
+impl ExprWalker for NilComparisons {
+    fn visit_expr_binary(&mut self, expr: &Binary) {
+        let equality = matches!(
+            expr.operator.token_type,
+            TokenTypes::EqualEqual | TokenTypes::BangEqual
+        );
+        if equality && (is_nil(&expr.left) || is_nil(&expr.right)) {
+            self.lines.push(expr.operator.line());
+        }
+        walk_expr(self, &expr.left);
+        walk_expr(self, &expr.right);
+    }
+}
+
+impl StmtWalker for NilComparisons {}
+
+fn nil_comparisons(source: &str) -> Result<Vec<u32>, String> {
+    let mut scanner = Scanner::new(source);
+    let program = Parser::new(scanner.scan_tokens())
+        .parse()
+        .map_err(|error| error.to_string())?;
+    let mut lint = NilComparisons::default();
+    walk_program(&mut lint, &program);
+    Ok(lint.lines)
+}
+
+fn main() {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("Usage: nil_comparisons <script>");
+        process::exit(64);
+    };
+    let source = fs::read_to_string(&path).unwrap_or_else(|error| {
+        eprintln!("Could not read {}: {}", path, error);
+        process::exit(66);
+    });
+    match nil_comparisons(&source) {
+        Ok(lines) => {
+            for line in &lines {
+                println!("{}:{}: comparison against nil", path, line);
+            }
+            println!("{} found", lines.len());
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(65);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture() {
+        let source = include_str!("nil_comparisons.jbr");
+        assert_eq!(nil_comparisons(source).unwrap(), [3, 4, 7, 7, 12]);
+    }
+}
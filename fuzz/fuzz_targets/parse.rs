@@ -0,0 +1,14 @@
+//! Scans and parses arbitrary text, which gives a program or errors but
+//! never panics.
+
+#![no_main]
+
+use j_bread::{Parser, Scanner};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens();
+    let _ = Parser::new(&tokens).parse();
+    let _ = Parser::new(&tokens).parse_recovering();
+});
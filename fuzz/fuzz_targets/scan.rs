@@ -0,0 +1,11 @@
+//! Scans arbitrary text, which gives tokens or errors but never panics.
+
+#![no_main]
+
+use j_bread::Scanner;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens();
+});
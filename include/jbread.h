@@ -0,0 +1,111 @@
+#ifndef JBREAD_H
+#define JBREAD_H
+
+/* Declares src/ffi.rs, ffi::tests::test_header_in_sync checks the two agree. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define JBREAD_OK 0
+
+/**
+ * The source failed to parse, resolve or run, see the errors.
+ */
+#define JBREAD_ERROR 1
+
+/**
+ * A pointer was null, text wasn't UTF-8 or a name isn't an identifier.
+ */
+#define JBREAD_INVALID_ARGUMENT 2
+
+/**
+ * No variable has the name.
+ */
+#define JBREAD_UNDEFINED 3
+
+/**
+ * The variable holds something other than what was asked for.
+ */
+#define JBREAD_TYPE_MISMATCH 4
+
+/**
+ * The call panicked, which poisoned the handle.
+ */
+#define JBREAD_PANICKED 5
+
+/**
+ * An earlier call panicked, the handle can only be freed.
+ */
+#define JBREAD_POISONED 6
+
+/**
+ * An interpreter together with the text its last calls handed out, which
+ * stays valid until the call that replaces it.
+ */
+typedef struct JBreadHandle JBreadHandle;
+
+/**
+ * What a call did, one of the `JBREAD_*` constants.
+ */
+typedef int JBreadStatus;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates an interpreter, null if that failed.
+ */
+JBreadHandle *jbread_new(void);
+
+/**
+ * Frees an interpreter, doing nothing for null.
+ */
+void jbread_free(JBreadHandle *handle);
+
+/**
+ * Runs `len` bytes of UTF-8 source, keeping the variables it defines for
+ * the next call.
+ */
+JBreadStatus jbread_eval(JBreadHandle *handle, const char *source, uintptr_t len);
+
+/**
+ * What the last [`jbread_eval`] printed, valid until the next one. Null
+ * for a null handle.
+ */
+const char *jbread_last_output(const JBreadHandle *handle);
+
+/**
+ * A JSON array of what went wrong in the last call, empty if it succeeded,
+ * valid until the next call. Null for a null handle.
+ */
+const char *jbread_last_errors_json(const JBreadHandle *handle);
+
+/**
+ * Defines a global number, replacing any variable with the name.
+ */
+JBreadStatus jbread_define_number(JBreadHandle *handle, const char *name, double value);
+
+/**
+ * Defines a global string, replacing any variable with the name.
+ */
+JBreadStatus jbread_define_string(JBreadHandle *handle, const char *name, const char *value);
+
+/**
+ * Reads a number variable into `out`.
+ */
+JBreadStatus jbread_get_number(JBreadHandle *handle, const char *name, double *out);
+
+/**
+ * Points `out` at the UTF-8 of a string variable, valid until the next
+ * call of this function.
+ */
+JBreadStatus jbread_get_string(JBreadHandle *handle, const char *name, const char **out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* JBREAD_H */
@@ -0,0 +1,270 @@
+//! Everything an editor asks about a source in one call, without running it.
+//!
+//! Unlike [`Pipeline::analyze`](crate::Pipeline::analyze), which stops at the
+//! first error, [`analyze`] keeps going on broken input: the statements that
+//! parsed are kept and every problem becomes a [`Diagnostic`].
+
+use crate::{
+    ast::{Assign, Class, Const, Function, MultiAssign, MultiVar, Stmt, Var, Variable},
+    errors::{JBreadErrors, JBreadWarning},
+    parser::Parser,
+    walk::{walk_expr, walk_program, walk_stmt, ExprWalker, StmtWalker},
+    LanguageProfile, Position, Scanner, SourceRef, Span, Token, UNNAMED_SOURCE,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found while scanning or parsing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub position: Position,
+    /// Offsets of the offending source, `None` when unknown.
+    pub span: Option<Span>,
+}
+
+impl From<&JBreadErrors> for Diagnostic {
+    fn from(error: &JBreadErrors) -> Self {
+        let error = error.error();
+        Self {
+            severity: Severity::Error,
+            message: error.message().to_string(),
+            position: Position::new(error.line(), error.column()),
+            span: error.span(),
+        }
+    }
+}
+
+impl From<JBreadWarning> for Diagnostic {
+    fn from(warning: JBreadWarning) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: warning.message,
+            position: warning.position,
+            span: warning.span,
+        }
+    }
+}
+
+/// A place a variable name is written in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occurrence {
+    pub name: String,
+    pub position: Position,
+    pub span: Span,
+}
+
+impl From<&Token> for Occurrence {
+    fn from(token: &Token) -> Self {
+        Self {
+            name: token.lexeme.to_string(),
+            position: Position::new(token.line(), token.column()),
+            span: token.span,
+        }
+    }
+}
+
+/// What [`analyze`] found in a source.
+#[derive(Debug)]
+pub struct SourceAnalysis {
+    pub tokens: Vec<Token>,
+    /// The statements that parsed, those with errors being left out.
+    pub statements: Vec<Stmt>,
+    /// Scan and parse errors followed by the parser's warnings.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Names declared by variables, constants, functions, parameters and
+    /// classes, in source order.
+    pub definitions: Vec<Occurrence>,
+    /// Names read or assigned, in source order.
+    pub references: Vec<Occurrence>,
+}
+
+/// Scans and parses `source` and gathers its names. Nothing is printed and
+/// nothing runs.
+pub fn analyze(source: &str) -> SourceAnalysis {
+    analyze_with(source, &LanguageProfile::default())
+}
+
+/// [`analyze`] for programs written for `profile`, whose disabled keywords
+/// and limits become diagnostics.
+pub fn analyze_with(source: &str, profile: &LanguageProfile) -> SourceAnalysis {
+    let source = SourceRef::new(UNNAMED_SOURCE, source);
+    let (tokens, scan_errors) = Scanner::with_profile(source, profile).scan();
+    let mut parser = Parser::new(&tokens).with_profile(profile);
+    let outcome = parser.parse_recovering();
+
+    let mut diagnostics: Vec<Diagnostic> = scan_errors
+        .iter()
+        .chain(outcome.errors.iter())
+        .map(Diagnostic::from)
+        .collect();
+    diagnostics.extend(
+        parser
+            .take_diagnostics()
+            .warnings()
+            .iter()
+            .cloned()
+            .map(Diagnostic::from),
+    );
+
+    let mut names = Names::default();
+    walk_program(&mut names, &outcome.statements);
+
+    SourceAnalysis {
+        statements: outcome.statements,
+        tokens,
+        diagnostics,
+        definitions: names.definitions,
+        references: names.references,
+    }
+}
+
+/// Collects definitions and references without resolving which reference
+/// goes with which definition.
+#[derive(Default)]
+struct Names {
+    definitions: Vec<Occurrence>,
+    references: Vec<Occurrence>,
+}
+
+impl ExprWalker for Names {
+    fn visit_expr_variable(&mut self, expr: &Variable) {
+        self.references.push((&expr.name).into());
+    }
+
+    fn visit_expr_assign(&mut self, expr: &Assign) {
+        self.references.push((&expr.name).into());
+        walk_expr(self, &expr.value);
+    }
+}
+
+impl StmtWalker for Names {
+    fn visit_stmt_var(&mut self, stmt: &Var) {
+        self.definitions.push((&stmt.name).into());
+        if let Some(initializer) = &stmt.initializer {
+            walk_expr(self, initializer);
+        }
+    }
+
+    fn visit_stmt_const(&mut self, stmt: &Const) {
+        self.definitions.push((&stmt.name).into());
+        walk_expr(self, &stmt.initializer);
+    }
+
+    fn visit_stmt_multi_var(&mut self, stmt: &MultiVar) {
+        self.definitions
+            .extend(stmt.names.iter().map(Occurrence::from));
+        for initializer in stmt.initializers.iter() {
+            walk_expr(self, initializer);
+        }
+    }
+
+    fn visit_stmt_multi_assign(&mut self, stmt: &MultiAssign) {
+        self.references.extend(
+            stmt.targets
+                .iter()
+                .map(|target| Occurrence::from(&target.name)),
+        );
+        for value in stmt.values.iter() {
+            walk_expr(self, value);
+        }
+    }
+
+    fn visit_stmt_function(&mut self, stmt: &Function) {
+        self.definitions.push((&stmt.name).into());
+        self.function_body(stmt);
+    }
+
+    fn visit_stmt_class(&mut self, stmt: &Class) {
+        self.definitions.push((&stmt.name).into());
+        // Methods are looked up on instances, their names aren't variables.
+        for method in stmt.methods.iter() {
+            self.function_body(method);
+        }
+    }
+}
+
+impl Names {
+    fn function_body(&mut self, function: &Function) {
+        self.definitions
+            .extend(function.params.iter().map(Occurrence::from));
+        for stmt in function.body.iter() {
+            walk_stmt(self, stmt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_broken_declaration() {
+        let analysis = analyze("var x = ;");
+        assert_eq!(analysis.tokens.len(), 5);
+        assert!(analysis.statements.is_empty());
+        assert_eq!(analysis.diagnostics.len(), 1);
+        assert_eq!(analysis.diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn test_analyze_with_profile() {
+        let profile = LanguageProfile::default().without(crate::TokenTypes::Class);
+        let analysis = analyze_with("var a = 1;\nclass A {}\nprint a;", &profile);
+        assert_eq!(analysis.statements.len(), 2);
+        assert_eq!(analysis.diagnostics.len(), 1);
+        assert_eq!(
+            analysis.diagnostics[0].message,
+            "The 'class' statement is disabled in this environment."
+        );
+        assert_eq!(analysis.diagnostics[0].position, Position::new(2, 1));
+    }
+
+    #[test]
+    fn test_analyze_fixture() {
+        let analysis =
+            analyze("var a = 1;\nvar b = ;\nprint a + a;\nprint a * ;\nfun f(n) { var c = a; }");
+
+        let errors: Vec<Position> = analysis
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.position)
+            .collect();
+        assert_eq!(errors, [Position::new(2, 9), Position::new(4, 11)]);
+
+        let definitions: Vec<(&str, Position)> = analysis
+            .definitions
+            .iter()
+            .map(|found| (found.name.as_str(), found.position))
+            .collect();
+        assert_eq!(
+            definitions,
+            [
+                ("a", Position::new(1, 5)),
+                ("f", Position::new(5, 5)),
+                ("n", Position::new(5, 7)),
+                ("c", Position::new(5, 16)),
+            ]
+        );
+
+        let references: Vec<(&str, Position)> = analysis
+            .references
+            .iter()
+            .map(|found| (found.name.as_str(), found.position))
+            .collect();
+        assert_eq!(
+            references,
+            [
+                ("a", Position::new(3, 7)),
+                ("a", Position::new(3, 11)),
+                ("a", Position::new(5, 20)),
+            ]
+        );
+        assert_eq!(analysis.references[2].span, Span::new(65, 66));
+    }
+}
@@ -1,78 +1,532 @@
-use crate::{define_ast, AstNode, AstStmt, Literal as LiteralEnum, Token};
+use std::rc::Rc;
+
+#[cfg(feature = "serde")]
+use crate::errors::{Error, ErrorKind, JBreadErrors, JBreadResult};
+use crate::{define_ast, AstNode, AstStmt, LiteralValue, StripSpans, Token, Walk};
 
 define_ast!(
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     AstNode,
     VisitorExpr,
     Expr,
+    span [#[cfg_attr(feature = "serde", serde(default))]],
+    walker ExprWalker: BodyWalker,
     [
         Binary {
             left: Box<Expr>,
             operator: Token,
             right: Box<Expr>
         },
-        visit_expr_binary
+        visit_expr_binary,
+        binary
     ],
     [
         Grouping {
             expression: Box<Expr>
         },
-        visit_expr_grouping
+        visit_expr_grouping,
+        grouping
     ],
     [
         Literal {
-            value: Option<LiteralEnum>
+            value: Option<LiteralValue>
         },
-        visit_expr_literal
+        visit_expr_literal,
+        literal
     ],
     [
         Unary {
             operator: Token,
             right: Box<Expr>
         },
-        visit_expr_unary
+        visit_expr_unary,
+        unary
     ],
     [
         Variable {
             name: Token
         },
-        visit_expr_variable
+        visit_expr_variable,
+        variable
     ],
     [
         Assign {
             name: Token,
             value: Box<Expr>
         },
-        visit_expr_assign
+        visit_expr_assign,
+        assign
+    ],
+    [
+        Call {
+            callee: Box<Expr>,
+            paren: Token,
+            arguments: Vec<Expr>
+        },
+        visit_expr_call,
+        call
+    ],
+    [
+        Get {
+            object: Box<Expr>,
+            name: Token
+        },
+        visit_expr_get,
+        get
+    ],
+    [
+        Set {
+            object: Box<Expr>,
+            name: Token,
+            value: Box<Expr>
+        },
+        visit_expr_set,
+        set
+    ],
+    [
+        ListLiteral {
+            elements: Vec<Expr>
+        },
+        visit_expr_list_literal,
+        list_literal
+    ],
+    [
+        MapLiteral {
+            brace: Token,
+            entries: Vec<MapEntry>
+        },
+        visit_expr_map_literal,
+        map_literal
+    ],
+    [
+        Index {
+            object: Box<Expr>,
+            bracket: Token,
+            index: Box<Expr>
+        },
+        visit_expr_index,
+        index
+    ],
+    [
+        IndexAssign {
+            object: Box<Expr>,
+            bracket: Token,
+            index: Box<Expr>,
+            value: Box<Expr>
+        },
+        visit_expr_index_assign,
+        index_assign
+    ],
+    [
+        This {
+            keyword: Token
+        },
+        visit_expr_this,
+        this
+    ],
+    [
+        Interpolation {
+            parts: Vec<Expr>
+        },
+        visit_expr_interpolation,
+        interpolation
+    ],
+    [
+        Lambda {
+            keyword: Box<Token>,
+            params: Vec<Token>,
+            body: LambdaBody
+        },
+        visit_expr_lambda,
+        lambda
     ],
 );
 
 define_ast!(
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     AstStmt,
     VisitorStmt,
     Stmt,
+    span [#[cfg_attr(feature = "serde", serde(default))]],
+    walker StmtWalker: ExprWalker,
     [
         Expression {
             expression: Box<Expr>
         },
-        visit_stmt_expression
+        visit_stmt_expression,
+        expression
     ],
     [
         Print {
-            expression: Box<Expr>
+            // Trees saved before `print` took several values hold one.
+            #[cfg_attr(
+                feature = "serde",
+                serde(alias = "expression", deserialize_with = "one_or_more")
+            )]
+            expressions: Vec<Expr>
+        },
+        visit_stmt_print,
+        print
+    ],
+    [
+        Assert {
+            keyword: Token,
+            condition: Box<Expr>,
+            message: Option<Box<Expr>>
         },
-        visit_stmt_print
+        visit_stmt_assert,
+        assert
     ],
     [
         Var {
             name: Token,
             initializer: Option<Box<Expr>>
         },
-        visit_stmt_var
+        visit_stmt_var,
+        var
+    ],
+    [
+        Const {
+            name: Token,
+            initializer: Box<Expr>
+        },
+        visit_stmt_const,
+        constant
+    ],
+    [
+        Import {
+            keyword: Token,
+            path: Box<Token>
+        },
+        visit_stmt_import,
+        import
+    ],
+    [
+        MultiVar {
+            names: Vec<Token>,
+            initializers: Vec<Expr>
+        },
+        visit_stmt_multi_var,
+        multi_var
+    ],
+    [
+        MultiAssign {
+            targets: Vec<Variable>,
+            values: Vec<Expr>
+        },
+        visit_stmt_multi_assign,
+        multi_assign
     ],
     [
         Block {
             statements: Vec<Stmt>
         },
-        visit_stmt_block
+        visit_stmt_block,
+        block
+    ],
+    [
+        Switch {
+            keyword: Box<Token>,
+            subject: Box<Expr>,
+            arms: Vec<SwitchArm>
+        },
+        visit_stmt_switch,
+        switch
+    ],
+    [
+        If {
+            keyword: Token,
+            condition: Box<Expr>,
+            then_branch: Box<Stmt>,
+            else_branch: Option<Box<Stmt>>
+        },
+        visit_stmt_if,
+        if_stmt
+    ],
+    [
+        Repeat {
+            keyword: Token,
+            count: Box<Expr>,
+            body: Box<Stmt>
+        },
+        visit_stmt_repeat,
+        repeat
+    ],
+    [
+        Function {
+            name: Token,
+            params: Vec<Token>,
+            body: Rc<Vec<Stmt>>
+        },
+        visit_stmt_function,
+        function
+    ],
+    [
+        Return {
+            keyword: Token,
+            value: Option<Box<Expr>>
+        },
+        visit_stmt_return,
+        return_stmt
+    ],
+    [
+        Class {
+            name: Token,
+            methods: Vec<Function>
+        },
+        visit_stmt_class,
+        class
     ],
 );
+
+/// An arm of a [`Switch`], run when `value` equals the subject. The
+/// `default` arm has no value.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchArm {
+    pub value: Option<Expr>,
+    pub body: Vec<Stmt>,
+}
+
+impl<W: StmtWalker> Walk<W> for SwitchArm {
+    fn walk(&self, walker: &mut W) {
+        self.value.walk(walker);
+        self.body.walk(walker);
+    }
+}
+
+impl StripSpans for SwitchArm {
+    fn strip_spans(&mut self) {
+        self.value.strip_spans();
+        self.body.strip_spans();
+    }
+}
+
+/// A `key: value` entry of a [`MapLiteral`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapEntry {
+    pub key: Expr,
+    pub value: Expr,
+}
+
+impl<W: ExprWalker> Walk<W> for MapEntry {
+    fn walk(&self, walker: &mut W) {
+        self.key.walk(walker);
+        self.value.walk(walker);
+    }
+}
+
+impl StripSpans for MapEntry {
+    fn strip_spans(&mut self) {
+        self.key.strip_spans();
+        self.value.strip_spans();
+    }
+}
+
+/// The statements of a [`Lambda`], shared with the functions made from it.
+/// An arrow lambda's expression is the value of a `return` here.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LambdaBody(pub Rc<Vec<Stmt>>);
+
+impl From<Vec<Stmt>> for LambdaBody {
+    fn from(statements: Vec<Stmt>) -> Self {
+        Self(Rc::new(statements))
+    }
+}
+
+impl<W: ExprWalker> Walk<W> for LambdaBody {
+    fn walk(&self, walker: &mut W) {
+        walker.walk_body(&self.0)
+    }
+}
+
+impl StripSpans for LambdaBody {
+    fn strip_spans(&mut self) {
+        self.0.strip_spans();
+    }
+}
+
+/// Lets an [`ExprWalker`] into the statements of a [`Lambda`]'s body, which
+/// only a [`StmtWalker`] can walk, so every `ExprWalker` is one too.
+pub trait BodyWalker {
+    fn walk_body(&mut self, body: &[Stmt]);
+}
+
+impl<W: StmtWalker> BodyWalker for W {
+    fn walk_body(&mut self, body: &[Stmt]) {
+        body.iter().for_each(|stmt| stmt.walk(self));
+    }
+}
+
+/// Reads a list of expressions, or a single one as a list of it.
+#[cfg(feature = "serde")]
+fn one_or_more<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<Expr>, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMore {
+        One(Box<Expr>),
+        More(Vec<Expr>),
+    }
+
+    Ok(match serde::Deserialize::deserialize(deserializer)? {
+        OneOrMore::One(expr) => vec![*expr],
+        OneOrMore::More(exprs) => exprs,
+    })
+}
+
+/// Asserts that `left` and `right` are the same tree, wherever their nodes
+/// were parsed from. Tokens are still compared whole.
+#[cfg(all(test, feature = "serde"))]
+pub(crate) fn assert_ast_eq_ignoring_spans<T>(left: &T, right: &T)
+where
+    T: StripSpans + Clone + PartialEq + std::fmt::Debug,
+{
+    let (mut left, mut right) = (left.clone(), right.clone());
+    left.strip_spans();
+    right.strip_spans();
+    assert_eq!(left, right);
+}
+
+impl Expr {
+    /// The line of the first token the expression holds, `None` for
+    /// literals, which hold none.
+    pub fn line(&self) -> Option<u32> {
+        match self {
+            Expr::Binary(expr) => expr.left.line(),
+            Expr::Grouping(expr) => expr.expression.line(),
+            Expr::Literal(_) => None,
+            Expr::Unary(expr) => Some(expr.operator.line()),
+            Expr::Variable(expr) => Some(expr.name.line()),
+            Expr::Assign(expr) => Some(expr.name.line()),
+            Expr::Call(expr) => expr.callee.line(),
+            Expr::Get(expr) => expr.object.line(),
+            Expr::Set(expr) => expr.object.line(),
+            Expr::ListLiteral(expr) => expr.elements.iter().find_map(Expr::line),
+            Expr::MapLiteral(expr) => Some(expr.brace.line()),
+            Expr::Index(expr) => expr.object.line(),
+            Expr::IndexAssign(expr) => expr.object.line(),
+            Expr::This(expr) => Some(expr.keyword.line()),
+            Expr::Interpolation(expr) => expr.parts.iter().find_map(Expr::line),
+            Expr::Lambda(expr) => Some(expr.keyword.line()),
+        }
+    }
+}
+
+impl Stmt {
+    /// The line of the first token the statement holds, `None` when it holds
+    /// none, e.g. `print 1;`.
+    pub fn line(&self) -> Option<u32> {
+        match self {
+            Stmt::Expression(stmt) => stmt.expression.line(),
+            Stmt::Print(stmt) => stmt.expressions.iter().find_map(Expr::line),
+            Stmt::Assert(stmt) => Some(stmt.keyword.line()),
+            Stmt::Var(stmt) => Some(stmt.name.line()),
+            Stmt::Const(stmt) => Some(stmt.name.line()),
+            Stmt::Import(stmt) => Some(stmt.keyword.line()),
+            Stmt::MultiVar(stmt) => stmt.names.first().map(Token::line),
+            Stmt::MultiAssign(stmt) => stmt.targets.first().map(|target| target.name.line()),
+            Stmt::Block(stmt) => stmt.statements.iter().find_map(Stmt::line),
+            Stmt::Switch(stmt) => Some(stmt.keyword.line()),
+            Stmt::If(stmt) => Some(stmt.keyword.line()),
+            Stmt::Repeat(stmt) => Some(stmt.keyword.line()),
+            Stmt::Function(stmt) => Some(stmt.name.line()),
+            Stmt::Return(stmt) => Some(stmt.keyword.line()),
+            Stmt::Class(stmt) => Some(stmt.name.line()),
+        }
+    }
+}
+
+/// Serializes a parsed program to JSON.
+#[cfg(feature = "serde")]
+pub fn to_json(stmts: &[Stmt]) -> String {
+    serde_json::to_string(stmts).expect("AST nodes are always serializable")
+}
+
+/// Loads a program previously produced by [`to_json`].
+#[cfg(feature = "serde")]
+pub fn from_json(json: &str) -> JBreadResult<Vec<Stmt>> {
+    serde_json::from_str(json).map_err(|err| {
+        JBreadErrors::ParseError(Error::new(
+            ErrorKind::InvalidJson,
+            err.line() as u32,
+            err.column() as u32,
+            "JSON".to_string(),
+            err.to_string(),
+        ))
+    })
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    #[cfg(feature = "interpreter")]
+    use crate::interpreter::{Interpreter, Value};
+    use crate::{parser::Parser, Scanner, TokenTypes};
+
+    const PROGRAM: &str =
+        "var a = 1; var b = \"two\"; { var c = a + 2; a = c * inf; } b = b + \"!\";";
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let mut scanner = Scanner::new(source);
+        let mut parser = Parser::new(scanner.scan_tokens());
+        parser.parse().unwrap()
+    }
+
+    #[cfg(feature = "interpreter")]
+    fn lookup(interpreter: &Interpreter, name: &str) -> Value {
+        let token = Token::new(TokenTypes::Identifier, name.to_string(), None, 1);
+        interpreter.environment.borrow().get(&token).unwrap()
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let stmts = parse(PROGRAM);
+        let loaded = from_json(&to_json(&stmts)).unwrap();
+        assert_eq!(stmts, loaded);
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_json_round_trip_runs_identically() {
+        let stmts = parse(PROGRAM);
+        let loaded = from_json(&to_json(&stmts)).unwrap();
+
+        let mut parsed_interpreter = Interpreter::default();
+        let mut loaded_interpreter = Interpreter::default();
+        parsed_interpreter.interpret(&stmts).unwrap();
+        loaded_interpreter.interpret(&loaded).unwrap();
+
+        for name in ["a", "b"] {
+            assert_eq!(
+                lookup(&parsed_interpreter, name),
+                lookup(&loaded_interpreter, name)
+            );
+        }
+        assert_eq!(
+            lookup(&loaded_interpreter, "a"),
+            Value::Number(f64::INFINITY)
+        );
+    }
+
+    #[test]
+    fn test_json_invalid_input() {
+        assert!(from_json("{ not json").is_err());
+    }
+
+    #[test]
+    fn test_json_fixture_from_before_value_rename() {
+        // Serialized while the value enum was still called `Literal`.
+        const FIXTURE: &str = r#"[{"Var":{"name":{"token_type":"Identifier","lexeme":"a","literal":null,"line":1,"column":5,"span":{"start":4,"end":5}},"initializer":{"Literal":{"value":{"String":"hi"}}}}},{"Print":{"expression":{"Variable":{"name":{"token_type":"Identifier","lexeme":"a","literal":null,"line":1,"column":21,"span":{"start":20,"end":21}}}}}}]"#;
+        // ...and before nodes carried spans, so those load as empty.
+        assert_ast_eq_ignoring_spans(
+            &from_json(FIXTURE).unwrap(),
+            &parse("var a = \"hi\"; print a;"),
+        );
+    }
+}
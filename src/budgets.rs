@@ -0,0 +1,130 @@
+//! Budgets for the size of tokens and AST nodes and for how often tokens get
+//! cloned, so changes that make them bigger or clone more do so on purpose.
+//!
+//! To re-baseline after a deliberate change, run
+//! `cargo test budgets -- --nocapture`, which prints the current
+//! measurements, update the consts below and mention why in the commit.
+
+use std::mem::size_of;
+
+use crate::{
+    ast::{Expr, Stmt},
+    parser::Parser,
+    token::TOKEN_CLONES,
+    Scanner, Token,
+};
+
+// Measured on a 64-bit target. Runtime values live outside `LiteralValue`,
+// so these are the same with or without the interpreter.
+const TOKEN_SIZE_BUDGET: usize = 80;
+const EXPR_SIZE_BUDGET: usize = 128;
+const STMT_SIZE_BUDGET: usize = 128;
+
+/// Token clones while parsing and while running [`REFERENCE_PROGRAM`].
+const PARSE_CLONE_BUDGET: usize = 73;
+#[cfg(feature = "interpreter")]
+const INTERPRET_CLONE_BUDGET: usize = 11;
+
+/// Touches every kind of statement and most expressions.
+const REFERENCE_PROGRAM: &str = "
+var total = 0;
+var a, b = 1, 2;
+a, b = b, a;
+fun add(x, y) { return x + y; }
+class Counter {
+    init(start) { this.count = start; }
+    bump() { this.count = this.count + 1; return this.count; }
+}
+var counter = Counter(0);
+var xs = [1, 2, 3];
+repeat (len(xs)) {
+    var step = xs[counter.bump() - 1] ** 2;
+    total = add(total, step);
+    xs[0] = -xs[0];
+}
+{
+    var label = \"total: \";
+    print label + \"done\";
+    print total >= 14 == !false;
+}
+";
+
+/// Fails with how to re-baseline when `measured` is over `budget`.
+fn check(what: &str, measured: usize, budget: usize, name: &str) {
+    println!("{}: {} (budget {})", what, measured, budget);
+    assert!(
+        measured <= budget,
+        "{} is {}, over its budget of {}. If that's deliberate, raise {} in src/budgets.rs \
+         and say why in the commit, otherwise find what grew.",
+        what,
+        measured,
+        budget,
+        name
+    );
+}
+
+fn clones_during<T>(run: impl FnOnce() -> T) -> (T, usize) {
+    let before = TOKEN_CLONES.with(|clones| clones.get());
+    let result = run();
+    (result, TOKEN_CLONES.with(|clones| clones.get()) - before)
+}
+
+fn scan(source: &str) -> Vec<Token> {
+    let mut scanner = Scanner::new(source);
+    scanner.scan_tokens().to_vec()
+}
+
+#[test]
+fn test_sizes_within_budget() {
+    check(
+        "size_of::<Token>()",
+        size_of::<Token>(),
+        TOKEN_SIZE_BUDGET,
+        "TOKEN_SIZE_BUDGET",
+    );
+    check(
+        "size_of::<Expr>()",
+        size_of::<Expr>(),
+        EXPR_SIZE_BUDGET,
+        "EXPR_SIZE_BUDGET",
+    );
+    check(
+        "size_of::<Stmt>()",
+        size_of::<Stmt>(),
+        STMT_SIZE_BUDGET,
+        "STMT_SIZE_BUDGET",
+    );
+}
+
+#[test]
+fn test_parse_clones_within_budget() {
+    let tokens = scan(REFERENCE_PROGRAM);
+    let (stmts, clones) = clones_during(|| Parser::new(&tokens).parse());
+    assert!(stmts.is_ok());
+    check(
+        "Token clones while parsing",
+        clones,
+        PARSE_CLONE_BUDGET,
+        "PARSE_CLONE_BUDGET",
+    );
+}
+
+#[cfg(feature = "interpreter")]
+#[test]
+fn test_interpret_clones_within_budget() {
+    use crate::{interpreter::Interpreter, Resolver};
+
+    let tokens = scan(REFERENCE_PROGRAM);
+    let stmts = Parser::new(&tokens).parse().unwrap();
+    let resolution = Resolver::default().resolve(&stmts).unwrap();
+    let mut interpreter = Interpreter::default();
+    interpreter.set_locals(resolution.locals);
+    let (result, clones) = clones_during(|| interpreter.interpret(&stmts));
+    assert!(result.is_ok());
+    check(
+        "Token clones while interpreting",
+        clones,
+        INTERPRET_CLONE_BUDGET,
+        "INTERPRET_CLONE_BUDGET",
+    );
+}
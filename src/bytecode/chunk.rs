@@ -0,0 +1,128 @@
+use std::fmt::Display;
+
+use crate::{LiteralValue, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OpCode {
+    /// Pushes `constants[index]`.
+    Constant(usize),
+    Nil,
+    /// Pushes the value of a variable declared without one, which can't be
+    /// read until it's assigned.
+    Uninitialized,
+    Pop,
+    /// Defines the global named by the instruction's token with the popped
+    /// value, or without one for an [`Uninitialized`](Self::Uninitialized).
+    DefineGlobal,
+    GetGlobal,
+    /// Assigns the value on top of the stack, leaving it there.
+    SetGlobal,
+    /// Pushes the local in stack slot `slot`.
+    GetLocal(usize),
+    /// Stores the value on top of the stack in `slot`, leaving it there.
+    SetLocal(usize),
+    /// Fails when the left operand on top of the stack is nil, before the
+    /// right one is evaluated.
+    CheckOperand,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    Negate,
+    Not,
+    BitNot,
+    /// Pops `count` values and prints them on one line.
+    Print(usize),
+    /// Replaces the repeat count on top of the stack with the number of
+    /// iterations left.
+    RepeatCount,
+    /// Pops the iterations left and jumps to `target` once there are none,
+    /// otherwise counts one off.
+    RepeatNext(usize),
+    /// Continues at instruction `target`.
+    Jump(usize),
+    /// Pops a condition and continues at instruction `target` when it's
+    /// falsy.
+    JumpIfFalse(usize),
+}
+
+/// A compiled program. Instructions that can fail point at the token they
+/// were compiled from, so runtime errors carry the same position as the
+/// tree-walker's.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Chunk {
+    pub code: Vec<OpCode>,
+    /// Index into `tokens` for each instruction in `code`.
+    pub origins: Vec<Option<usize>>,
+    pub constants: Vec<LiteralValue>,
+    pub tokens: Vec<Token>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, op: OpCode, origin: Option<&Token>) -> usize {
+        let origin = origin.map(|token| {
+            self.tokens.push(token.clone());
+            self.tokens.len() - 1
+        });
+        self.code.push(op);
+        self.origins.push(origin);
+        self.code.len() - 1
+    }
+
+    pub fn add_constant(&mut self, value: LiteralValue) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    /// The line of the closest instruction up to `offset` compiled from a
+    /// token, 0 if there's none.
+    pub fn line(&self, offset: usize) -> u32 {
+        self.origins[..=offset]
+            .iter()
+            .rev()
+            .find_map(|origin| origin.map(|index| self.tokens[index].line()))
+            .unwrap_or(0)
+    }
+
+    /// The token instruction `offset` was compiled from.
+    pub fn token(&self, offset: usize) -> &Token {
+        let index = self.origins[offset].expect("only instructions that can fail have a token");
+        &self.tokens[index]
+    }
+}
+
+impl Display for Chunk {
+    /// One instruction per line with its offset and source line, e.g.
+    /// `0002    1 GetGlobal a`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (offset, op) in self.code.iter().enumerate() {
+            let origin = self.origins[offset].map(|index| &self.tokens[index]);
+            match origin {
+                Some(token) => write!(f, "{:04} {:4} ", offset, token.line())?,
+                None => write!(f, "{:04}    | ", offset)?,
+            }
+            match op {
+                OpCode::Constant(index) => {
+                    writeln!(f, "Constant {} ({})", index, self.constants[*index])?
+                }
+                OpCode::DefineGlobal | OpCode::GetGlobal | OpCode::SetGlobal => {
+                    writeln!(f, "{:?} {}", op, origin.map_or("", |token| &token.lexeme))?
+                }
+                _ => writeln!(f, "{:?}", op)?,
+            }
+        }
+        Ok(())
+    }
+}
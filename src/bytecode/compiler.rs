@@ -0,0 +1,334 @@
+use crate::{
+    ast::{
+        Assert, Assign, Binary, Block, Call, Class, Const, Expression, Function, Get, Grouping, If,
+        Import, Index, IndexAssign, Interpolation, Lambda, ListLiteral, Literal, MapLiteral,
+        MultiAssign, MultiVar, Print, Repeat, Return, Set, Stmt, Switch, This, Unary, Var,
+        Variable, VisitorExpr, VisitorStmt,
+    },
+    bytecode::{Chunk, OpCode},
+    errors::{Error, ErrorKind, JBreadErrors, JBreadResult},
+    AstNode, AstStmt, Token, TokenTypes,
+};
+
+struct Local {
+    name: String,
+    depth: usize,
+}
+
+/// Compiles a program to a [`Chunk`] for the [`Vm`](super::Vm).
+///
+/// Globals stay in the interpreter's environment and are looked up by name,
+/// variables declared in blocks live in stack slots resolved here.
+#[derive(Default)]
+pub struct Compiler {
+    chunk: Chunk,
+    locals: Vec<Local>,
+    depth: usize,
+}
+
+impl Compiler {
+    /// The value of a trailing expression statement stays on the stack for
+    /// the VM to return.
+    pub fn compile(mut self, stmts: &[Stmt]) -> JBreadResult<Chunk> {
+        for (index, stmt) in stmts.iter().enumerate() {
+            match stmt {
+                Stmt::Expression(statement) if index + 1 == stmts.len() => {
+                    statement.expression.accept(&mut self)?
+                }
+                stmt => stmt.accept(&mut self)?,
+            }
+        }
+        Ok(self.chunk)
+    }
+
+    fn emit(&mut self, op: OpCode, origin: Option<&Token>) -> usize {
+        self.chunk.write(op, origin)
+    }
+
+    fn resolve_local(&self, name: &str) -> Option<usize> {
+        self.locals.iter().rposition(|local| local.name == name)
+    }
+
+    /// The value on top of the stack becomes the slot of the next local.
+    fn add_local(&mut self, name: &str) {
+        self.locals.push(Local {
+            name: name.to_string(),
+            depth: self.depth,
+        });
+    }
+
+    fn unsupported(&self, token: Option<&Token>, what: &str) -> JBreadErrors {
+        let message = format!("{} can't be compiled to bytecode yet", what);
+        let error = match token {
+            Some(token) => Error::at(
+                ErrorKind::Unsupported,
+                token,
+                token.lexeme.to_string(),
+                message,
+            ),
+            None => Error::new(ErrorKind::Unsupported, 0, 0, String::new(), message),
+        };
+        JBreadErrors::CompileError(error)
+    }
+}
+
+impl VisitorExpr for Compiler {
+    type Result = JBreadResult<()>;
+
+    fn visit_expr_binary(&mut self, expr: &Binary) -> Self::Result {
+        let op = match expr.operator.token_type {
+            TokenTypes::Plus => OpCode::Add,
+            TokenTypes::Minus => OpCode::Subtract,
+            TokenTypes::Star => OpCode::Multiply,
+            TokenTypes::Slash => OpCode::Divide,
+            TokenTypes::StarStar => OpCode::Power,
+            TokenTypes::Ampersand => OpCode::BitAnd,
+            TokenTypes::Pipe => OpCode::BitOr,
+            TokenTypes::Caret => OpCode::BitXor,
+            TokenTypes::LessLess => OpCode::ShiftLeft,
+            TokenTypes::GreaterGreater => OpCode::ShiftRight,
+            TokenTypes::Greater => OpCode::Greater,
+            TokenTypes::GreaterEqual => OpCode::GreaterEqual,
+            TokenTypes::Less => OpCode::Less,
+            TokenTypes::LessEqual => OpCode::LessEqual,
+            TokenTypes::EqualEqual => OpCode::Equal,
+            TokenTypes::BangEqual => OpCode::NotEqual,
+            _ => return Err(self.unsupported(Some(&expr.operator), "This operator")),
+        };
+        expr.left.accept(self)?;
+        self.emit(OpCode::CheckOperand, Some(&expr.operator));
+        expr.right.accept(self)?;
+        self.emit(op, Some(&expr.operator));
+        Ok(())
+    }
+
+    fn visit_expr_grouping(&mut self, expr: &Grouping) -> Self::Result {
+        expr.expression.accept(self)
+    }
+
+    fn visit_expr_literal(&mut self, expr: &Literal) -> Self::Result {
+        match &expr.value {
+            Some(value) => {
+                let index = self.chunk.add_constant(value.clone());
+                self.emit(OpCode::Constant(index), None);
+            }
+            None => {
+                self.emit(OpCode::Nil, None);
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_expr_unary(&mut self, expr: &Unary) -> Self::Result {
+        let op = match expr.operator.token_type {
+            TokenTypes::Minus => OpCode::Negate,
+            TokenTypes::Bang => OpCode::Not,
+            TokenTypes::Tilde => OpCode::BitNot,
+            _ => return Err(self.unsupported(Some(&expr.operator), "This operator")),
+        };
+        expr.right.accept(self)?;
+        self.emit(op, Some(&expr.operator));
+        Ok(())
+    }
+
+    fn visit_expr_variable(&mut self, expr: &Variable) -> Self::Result {
+        match self.resolve_local(&expr.name.lexeme) {
+            Some(slot) => self.emit(OpCode::GetLocal(slot), Some(&expr.name)),
+            None => self.emit(OpCode::GetGlobal, Some(&expr.name)),
+        };
+        Ok(())
+    }
+
+    fn visit_expr_assign(&mut self, expr: &Assign) -> Self::Result {
+        expr.value.accept(self)?;
+        match self.resolve_local(&expr.name.lexeme) {
+            Some(slot) => self.emit(OpCode::SetLocal(slot), Some(&expr.name)),
+            None => self.emit(OpCode::SetGlobal, Some(&expr.name)),
+        };
+        Ok(())
+    }
+
+    fn visit_expr_call(&mut self, expr: &Call) -> Self::Result {
+        Err(self.unsupported(Some(&expr.paren), "Calls"))
+    }
+
+    fn visit_expr_get(&mut self, expr: &Get) -> Self::Result {
+        Err(self.unsupported(Some(&expr.name), "Properties"))
+    }
+
+    fn visit_expr_set(&mut self, expr: &Set) -> Self::Result {
+        Err(self.unsupported(Some(&expr.name), "Properties"))
+    }
+
+    fn visit_expr_this(&mut self, expr: &This) -> Self::Result {
+        Err(self.unsupported(Some(&expr.keyword), "'this'"))
+    }
+
+    fn visit_expr_interpolation(&mut self, _expr: &Interpolation) -> Self::Result {
+        Err(self.unsupported(None, "Interpolated strings"))
+    }
+
+    fn visit_expr_lambda(&mut self, expr: &Lambda) -> Self::Result {
+        Err(self.unsupported(Some(&expr.keyword), "Lambdas"))
+    }
+
+    fn visit_expr_list_literal(&mut self, _expr: &ListLiteral) -> Self::Result {
+        Err(self.unsupported(None, "Lists"))
+    }
+
+    fn visit_expr_map_literal(&mut self, expr: &MapLiteral) -> Self::Result {
+        Err(self.unsupported(Some(&expr.brace), "Maps"))
+    }
+
+    fn visit_expr_index(&mut self, expr: &Index) -> Self::Result {
+        Err(self.unsupported(Some(&expr.bracket), "Lists"))
+    }
+
+    fn visit_expr_index_assign(&mut self, expr: &IndexAssign) -> Self::Result {
+        Err(self.unsupported(Some(&expr.bracket), "Lists"))
+    }
+}
+
+impl VisitorStmt for Compiler {
+    type Result = JBreadResult<()>;
+
+    fn visit_stmt_expression(&mut self, expr: &Expression) -> Self::Result {
+        expr.expression.accept(self)?;
+        self.emit(OpCode::Pop, None);
+        Ok(())
+    }
+
+    fn visit_stmt_print(&mut self, expr: &Print) -> Self::Result {
+        for expression in expr.expressions.iter() {
+            expression.accept(self)?;
+        }
+        self.emit(OpCode::Print(expr.expressions.len()), None);
+        Ok(())
+    }
+
+    fn visit_stmt_assert(&mut self, expr: &Assert) -> Self::Result {
+        Err(self.unsupported(Some(&expr.keyword), "Assertions"))
+    }
+
+    fn visit_stmt_var(&mut self, expr: &Var) -> Self::Result {
+        match &expr.initializer {
+            Some(initializer) => initializer.accept(self)?,
+            None => {
+                self.emit(OpCode::Uninitialized, None);
+            }
+        }
+        if self.depth == 0 {
+            self.emit(OpCode::DefineGlobal, Some(&expr.name));
+        } else {
+            self.add_local(&expr.name.lexeme);
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_const(&mut self, expr: &Const) -> Self::Result {
+        Err(self.unsupported(Some(&expr.name), "Constants"))
+    }
+
+    fn visit_stmt_switch(&mut self, expr: &Switch) -> Self::Result {
+        Err(self.unsupported(Some(&expr.keyword), "Switch statements"))
+    }
+
+    fn visit_stmt_import(&mut self, expr: &Import) -> Self::Result {
+        Err(self.unsupported(Some(&expr.keyword), "Imports"))
+    }
+
+    fn visit_stmt_multi_var(&mut self, expr: &MultiVar) -> Self::Result {
+        for initializer in expr.initializers.iter() {
+            initializer.accept(self)?;
+        }
+        for _ in expr.initializers.len()..expr.names.len() {
+            self.emit(OpCode::Uninitialized, None);
+        }
+        if self.depth == 0 {
+            // Globals pop their values off the stack, last name first.
+            for name in expr.names.iter().rev() {
+                self.emit(OpCode::DefineGlobal, Some(name));
+            }
+        } else {
+            for name in expr.names.iter() {
+                self.add_local(&name.lexeme);
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_multi_assign(&mut self, expr: &MultiAssign) -> Self::Result {
+        for value in expr.values.iter() {
+            value.accept(self)?;
+        }
+        for target in expr.targets.iter().rev() {
+            match self.resolve_local(&target.name.lexeme) {
+                Some(slot) => self.emit(OpCode::SetLocal(slot), Some(&target.name)),
+                None => self.emit(OpCode::SetGlobal, Some(&target.name)),
+            };
+            self.emit(OpCode::Pop, None);
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_block(&mut self, expr: &Block) -> Self::Result {
+        self.depth += 1;
+        for stmt in expr.statements.iter() {
+            stmt.accept(self)?;
+        }
+        self.depth -= 1;
+        while self
+            .locals
+            .last()
+            .is_some_and(|local| local.depth > self.depth)
+        {
+            self.locals.pop();
+            self.emit(OpCode::Pop, None);
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_if(&mut self, expr: &If) -> Self::Result {
+        expr.condition.accept(self)?;
+        let skip_then = self.emit(OpCode::JumpIfFalse(0), None);
+        expr.then_branch.accept(self)?;
+        let Some(else_branch) = &expr.else_branch else {
+            self.chunk.code[skip_then] = OpCode::JumpIfFalse(self.chunk.code.len());
+            return Ok(());
+        };
+        let skip_else = self.emit(OpCode::Jump(0), None);
+        self.chunk.code[skip_then] = OpCode::JumpIfFalse(self.chunk.code.len());
+        else_branch.accept(self)?;
+        self.chunk.code[skip_else] = OpCode::Jump(self.chunk.code.len());
+        Ok(())
+    }
+
+    fn visit_stmt_repeat(&mut self, expr: &Repeat) -> Self::Result {
+        expr.count.accept(self)?;
+        self.emit(OpCode::RepeatCount, Some(&expr.keyword));
+        // The iterations left take a slot no name can resolve to.
+        self.add_local("");
+
+        let start = self.chunk.code.len();
+        let next = self.emit(OpCode::RepeatNext(0), None);
+        expr.body.accept(self)?;
+        self.emit(OpCode::Jump(start), None);
+        self.chunk.code[next] = OpCode::RepeatNext(self.chunk.code.len());
+
+        // `RepeatNext` pops the slot once the loop is done.
+        self.locals.pop();
+        Ok(())
+    }
+
+    fn visit_stmt_function(&mut self, expr: &Function) -> Self::Result {
+        Err(self.unsupported(Some(&expr.name), "Functions"))
+    }
+
+    fn visit_stmt_class(&mut self, expr: &Class) -> Self::Result {
+        Err(self.unsupported(Some(&expr.name), "Classes"))
+    }
+
+    fn visit_stmt_return(&mut self, expr: &Return) -> Self::Result {
+        Err(self.unsupported(Some(&expr.keyword), "Return statements"))
+    }
+}
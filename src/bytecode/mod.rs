@@ -0,0 +1,251 @@
+mod chunk;
+mod compiler;
+mod vm;
+
+pub use chunk::*;
+pub use compiler::*;
+pub use vm::*;
+
+/// Runs `source` on both engines and checks they print the same lines and
+/// fail the same way. Programs using anything the compiler doesn't support
+/// yet are skipped.
+#[cfg(test)]
+pub(crate) fn assert_engines_agree(source: &str, resolve: bool) {
+    use crate::{
+        interpreter::{ExecutionEngine, Interpreter, InterpreterOptions, OutputBuffer},
+        parser::Parser,
+        ErrorKind, Resolver, Scanner,
+    };
+
+    let mut scanner = Scanner::new(source);
+    let Ok(stmts) = Parser::new(scanner.scan_tokens()).parse() else {
+        return;
+    };
+    if let Err(error) = Compiler::default().compile(&stmts) {
+        assert_eq!(error.kind(), &ErrorKind::Unsupported, "{}", source);
+        return;
+    }
+    let locals = match resolve {
+        true => match Resolver::default().resolve(&stmts) {
+            Ok(resolution) => resolution.locals,
+            Err(_) => return,
+        },
+        false => Default::default(),
+    };
+
+    let run = |engine| {
+        let output = OutputBuffer::new();
+        let mut interpreter = Interpreter::with_options(InterpreterOptions {
+            engine,
+            ..Default::default()
+        })
+        .with_output(output.clone());
+        interpreter.set_deterministic(true);
+        interpreter.set_locals(locals.clone());
+        let result = interpreter
+            .interpret(&stmts)
+            .map_err(|error| (error.kind().clone(), error.to_string()));
+        (output.take(), result)
+    };
+    assert_eq!(
+        run(ExecutionEngine::TreeWalk),
+        run(ExecutionEngine::Bytecode),
+        "engines disagree on {}",
+        source
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::{
+        interpreter::{ExecutionEngine, Interpreter, InterpreterOptions, OutputBuffer},
+        parser::Parser,
+        ErrorKind, Scanner,
+    };
+
+    fn compile(source: &str) -> crate::JBreadResult<Chunk> {
+        let mut scanner = Scanner::new(source);
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        Compiler::default().compile(&stmts)
+    }
+
+    const PROGRAMS: &[&str] = &[
+        // Semantics
+        "print 1 + 2 * 3 - 4 / 8;",
+        "print (1 + 2) * -3; print !true; print !!false;",
+        "print \"jun\" + \"ior\"; print 1 == 1; print \"a\" != \"a\"; print nil == nil;",
+        "print 1 < 2; print 2 <= 2; print 3 > 4; print 4 >= 5;",
+        "print \"a\" < \"b\"; print \"b\" <= \"a\"; print \"ab\" > \"a\"; print NaN >= NaN;",
+        "print 0 / 0; print 1 / 0; print -1 / 0; print inf - inf; print NaN == NaN;",
+        "print 1 == \"1\"; print true == 1;",
+        "print 1, \"two\", nil, 1 + 2;",
+        "print 6 & 3 | 8; print 6 ^ 3; print ~0; print 1 << 4 >> 2; print -1 >> 1;",
+        "print 2 ** 3 ** 2; print -2 ** 2; print 0 ** 0; print 2 ** -1; print 1.5e2 * 2;",
+        // Variables and scope
+        "var a = 1; var b; b = a = 2; print a; print b;",
+        "var a = 1; var a = a + 1; print a;",
+        "var a = \"global\"; { var a = \"outer\"; { var a = \"inner\"; print a; } print a; } print a;",
+        "var a = 1; { var b = a + 1; { a = b * 10; var c = a; print c; } } print a;",
+        "{ var a = 1; { var a = a + 1; print a; } print a; }",
+        "var total = 0; { var x = 2; { var y = 3; total = x * y; } } print total;",
+        // Repeat
+        "var n = 0; repeat (3) { var step = 2; n = n + step; } print n;",
+        "var n = 0; repeat (2) repeat (3) n = n + 1; print n;",
+        "var i = 0; repeat (3) { var j = i; repeat (j) print j; i = i + 1; }",
+        "repeat (0) print 1; print 2;",
+        // If
+        "var a = 2; if (a == 1) print 1; elif (a == 2) { var b = a; print b; } else print 3;",
+        "if (nil) print 1; if (0) print 2; else print 3; if (false) {} else if (\"\") print 4;",
+        // Errors
+        "print 1; print 1 - \"a\"; print 2;",
+        "print \"a\" - 1;",
+        "print 1 + true;",
+        "print -\"a\";\n",
+        "print !1;",
+        "print nil + 1;",
+        "var a; print 1;\nprint a + 1;",
+        "var a = nil; var b = 1; { print a == b; }",
+        "{ var a; print a; }",
+        "var a = nil; print a == nil; { var b = nil; print b; var c; c = nil; print c; }",
+        "{ var a; var b = 1; { var c; print b; } print a; }",
+        "var a, b; a = 1; print a; print b;",
+        "{ var a, b; b = 2; print b; print a; }",
+        "print missing;",
+        "missing = 1;",
+        "{ var a = 1; b = a; }",
+        "print \"a\" / 2;",
+        "print 2 ** \"a\";",
+        "print 1.5 | 0;",
+        "print 1 << 64;",
+        "print ~nil;",
+        "repeat (1.5) print 1;",
+        "repeat (\"three\") print 1;",
+        "var n = 0; repeat (-1) n = n + 1;",
+        "var a = 1;\n{\n  var b = a;\n  print b + \"x\" - 1;\n}",
+    ];
+
+    #[test]
+    fn test_engines_agree() {
+        for program in PROGRAMS {
+            assert_engines_agree(program, true);
+            assert_engines_agree(program, false);
+        }
+    }
+
+    #[test]
+    fn test_disassembly() {
+        let chunk = compile("var a = 1;\n{ var b = a; print -b; }").unwrap();
+        assert_eq!(
+            chunk.to_string(),
+            [
+                "0000    | Constant 0 (1)",
+                "0001    1 DefineGlobal a",
+                "0002    2 GetGlobal a",
+                "0003    2 GetLocal(0)",
+                "0004    2 Negate",
+                "0005    | Print(1)",
+                "0006    | Pop",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_unsupported_rejected_before_running() {
+        let error = compile("print 1;\nfun f() {}").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Unsupported);
+        assert_eq!(
+            error.to_string(),
+            "\"Functions can't be compiled to bytecode yet\" at 2:5 in f"
+        );
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let mut scanner = Scanner::new("var n = 0;\nrepeat (1e12) n = n + 1;");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let error = Interpreter::with_options(InterpreterOptions {
+            engine: ExecutionEngine::Bytecode,
+            ..Default::default()
+        })
+        .with_step_limit(1_000)
+        .interpret(&stmts)
+        .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::BudgetExceeded);
+        assert_eq!(error.error().line(), 2);
+    }
+
+    #[test]
+    fn test_globals_persist_between_runs() {
+        let output = OutputBuffer::new();
+        let mut interpreter = Interpreter::with_options(InterpreterOptions {
+            engine: ExecutionEngine::Bytecode,
+            ..Default::default()
+        })
+        .with_output(output.clone());
+        for source in ["var a = 1;", "a = a + 1;", "{ var b = a; print b; }"] {
+            let mut scanner = Scanner::new(source);
+            let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+            interpreter.interpret(&stmts).unwrap();
+        }
+        assert_eq!(output.take(), "2\n");
+    }
+
+    #[test]
+    fn test_transactional_run_rolls_back_globals() {
+        let output = OutputBuffer::new();
+        let mut interpreter = Interpreter::with_options(InterpreterOptions {
+            engine: ExecutionEngine::Bytecode,
+            ..Default::default()
+        })
+        .with_output(output.clone());
+        for (source, transactional) in [
+            ("var a = 1;", false),
+            ("var b = 2; { a = a + b; } print -nil;", true),
+            ("print a;", false),
+        ] {
+            let mut scanner = Scanner::new(source);
+            let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+            match transactional {
+                true => assert!(interpreter.interpret_transactional(&stmts).is_err()),
+                false => interpreter.interpret(&stmts).map(drop).unwrap(),
+            }
+        }
+        assert_eq!(output.take(), "1\n");
+    }
+
+    /// Not a real benchmark, but it keeps the reason for the engine honest:
+    /// on an arithmetic-heavy loop the VM should beat the tree-walker by a
+    /// wide margin (about three times in debug builds). Wall-clock times
+    /// vary with the machine's load, so it only runs when asked for with
+    /// `cargo test --features bytecode -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_bytecode_faster_on_arithmetic() {
+        let mut scanner = Scanner::new(
+            "var total = 0; repeat (20000) { var x = total; total = x * 2 - x + 3 - 2; }",
+        );
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let time = |engine| {
+            let mut interpreter = Interpreter::with_options(InterpreterOptions {
+                engine,
+                ..Default::default()
+            });
+            let start = Instant::now();
+            interpreter.interpret(&stmts).unwrap();
+            start.elapsed()
+        };
+        let tree_walk = time(ExecutionEngine::TreeWalk);
+        let bytecode = time(ExecutionEngine::Bytecode);
+        assert!(
+            bytecode < tree_walk,
+            "tree-walk {:?}, bytecode {:?}",
+            tree_walk,
+            bytecode
+        );
+    }
+}
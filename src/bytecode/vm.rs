@@ -0,0 +1,196 @@
+use crate::{
+    bytecode::{Chunk, OpCode},
+    errors::JBreadResult,
+    interpreter::{environment::Environment, Interpreter, Value},
+};
+
+/// Runs a [`Chunk`] on a value stack, sharing globals, natives and operator
+/// semantics with the [`Interpreter`] it runs for.
+pub struct Vm<'a> {
+    interpreter: &'a mut Interpreter,
+    stack: Vec<Value>,
+    /// Slots of variables declared without a value and not assigned since,
+    /// in increasing order.
+    uninitialized: Vec<usize>,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Self {
+            interpreter,
+            stack: Vec::new(),
+            uninitialized: Vec::new(),
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        let value = self
+            .stack
+            .pop()
+            .expect("the compiler keeps the stack balanced");
+        if self.uninitialized.last() == Some(&self.stack.len()) {
+            self.uninitialized.pop();
+        }
+        value
+    }
+
+    /// Pops the value a variable is declared with, `None` when it has none.
+    fn pop_declared(&mut self) -> Option<Value> {
+        let initialized = self.uninitialized.last() != Some(&(self.stack.len() - 1));
+        let value = self.pop();
+        initialized.then_some(value)
+    }
+
+    fn peek(&self) -> &Value {
+        self.stack
+            .last()
+            .expect("the compiler keeps the stack balanced")
+    }
+
+    /// Runs `chunk`, returning what's left on the stack: the value of a
+    /// trailing expression statement, see [`Compiler::compile`].
+    pub fn run(&mut self, chunk: &Chunk) -> JBreadResult<Option<Value>> {
+        let mut ip = 0;
+        while let Some(op) = chunk.code.get(ip).copied() {
+            let offset = ip;
+            ip += 1;
+            if !self.interpreter.step() {
+                return Err(self.interpreter.budget_exceeded(chunk.line(offset)));
+            }
+            match op {
+                OpCode::Constant(index) => self.stack.push(Value::from(&chunk.constants[index])),
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::Uninitialized => {
+                    self.uninitialized.push(self.stack.len());
+                    self.stack.push(Value::Nil);
+                }
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::DefineGlobal => {
+                    let value = self.pop_declared();
+                    let environment = self.interpreter.environment.clone();
+                    let name = &chunk.token(offset).lexeme;
+                    match value {
+                        Some(value) => self.interpreter.define(&environment, name, value)?,
+                        None => self.interpreter.declare(&environment, name)?,
+                    }
+                }
+                OpCode::GetGlobal => {
+                    let value = self.interpreter.look_up_global(chunk.token(offset))?;
+                    self.stack.push(value);
+                }
+                OpCode::SetGlobal => {
+                    let value = self.peek().clone();
+                    self.interpreter.assign_global(chunk.token(offset), value)?;
+                }
+                OpCode::GetLocal(slot) => {
+                    if self.uninitialized.contains(&slot) {
+                        return Err(Environment::undefined(chunk.token(offset)));
+                    }
+                    self.stack.push(self.stack[slot].clone());
+                }
+                OpCode::SetLocal(slot) => {
+                    self.uninitialized
+                        .retain(|uninitialized| *uninitialized != slot);
+                    self.stack[slot] = self.peek().clone();
+                }
+                OpCode::CheckOperand => {
+                    if let Value::Nil = self.peek() {
+                        let operator = chunk.token(offset);
+                        self.interpreter.operand(
+                            Value::Nil,
+                            operator,
+                            "Left value is not a literal",
+                        )?;
+                    }
+                }
+                OpCode::Add
+                | OpCode::Subtract
+                | OpCode::Multiply
+                | OpCode::Divide
+                | OpCode::Power
+                | OpCode::BitAnd
+                | OpCode::BitOr
+                | OpCode::BitXor
+                | OpCode::ShiftLeft
+                | OpCode::ShiftRight
+                | OpCode::Greater
+                | OpCode::GreaterEqual
+                | OpCode::Less
+                | OpCode::LessEqual
+                | OpCode::Equal
+                | OpCode::NotEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let value = match (op, &left, &right) {
+                        // Plain arithmetic skips the checks the general
+                        // path goes through.
+                        (OpCode::Add, Value::Number(l), Value::Number(r)) => {
+                            Value::from_number(l + r)
+                        }
+                        (OpCode::Subtract, Value::Number(l), Value::Number(r)) => {
+                            Value::from_number(l - r)
+                        }
+                        (OpCode::Multiply, Value::Number(l), Value::Number(r)) => {
+                            Value::from_number(l * r)
+                        }
+                        (OpCode::Less, Value::Number(l), Value::Number(r)) => Value::Bool(l < r),
+                        _ => {
+                            let operator = chunk.token(offset);
+                            let interpreter = &*self.interpreter;
+                            let left = interpreter.operand(
+                                left,
+                                operator,
+                                "Left value is not a literal",
+                            )?;
+                            let right = interpreter.operand(
+                                right,
+                                operator,
+                                "Right value is not a literal",
+                            )?;
+                            interpreter.binary(operator, left, right)?
+                        }
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::Negate | OpCode::Not | OpCode::BitNot => {
+                    let operator = chunk.token(offset);
+                    let right = self.pop();
+                    let right = self.interpreter.operand(
+                        right,
+                        operator,
+                        "Right value is not a literal",
+                    )?;
+                    let value = self.interpreter.unary(operator, right)?;
+                    self.stack.push(value);
+                }
+                OpCode::Print(count) => {
+                    let values = self.stack.split_off(self.stack.len() - count);
+                    self.interpreter.print_values(&values)?;
+                }
+                OpCode::RepeatCount => {
+                    let count = self.pop();
+                    let count = self.interpreter.repeat_count(count, chunk.token(offset))?;
+                    self.stack.push(Value::Number(count));
+                }
+                OpCode::RepeatNext(target) => match self.stack.last_mut() {
+                    Some(Value::Number(remaining)) if *remaining > 0.0 => {
+                        *remaining -= 1.0;
+                    }
+                    _ => {
+                        self.pop();
+                        ip = target;
+                    }
+                },
+                OpCode::Jump(target) => ip = target,
+                OpCode::JumpIfFalse(target) => {
+                    if !self.pop().is_truthy() {
+                        ip = target;
+                    }
+                }
+            }
+        }
+        Ok(self.stack.pop())
+    }
+}
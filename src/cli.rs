@@ -0,0 +1,312 @@
+use crate::ExplainSection;
+
+pub const USAGE: &str = "\
+Usage: j_bread [--dump-tokens] [--dump-ast] [--no-history] [--export-globals file.json]
+               [--optimize] [script.jbr | - | -e program]
+       j_bread --dump-dot [script.jbr | - | -e program]
+       j_bread --explain-pipeline | --explain=<sections> script.jbr
+       j_bread --generate-reference
+       j_bread fmt [--check] [script.jbr | -]";
+
+/// What to print about a program before it runs, to stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TraceOptions {
+    /// `--dump-tokens`, the scanned tokens.
+    pub tokens: bool,
+    /// `--dump-ast`, the parsed statements.
+    pub ast: bool,
+    /// `--dump-dot`, the parsed statements as a Graphviz graph, printed to
+    /// stdout instead of running them.
+    pub dot: bool,
+}
+
+/// What the command line asks for.
+#[derive(Debug, PartialEq)]
+pub enum Mode {
+    /// No program given, an interactive session.
+    Repl,
+    /// `script.jbr`, runs the file.
+    File(String),
+    /// `-`, runs the program read from stdin.
+    Stdin,
+    /// `-e program`, runs the program given inline.
+    Eval(String),
+    /// `--explain-pipeline` or `--explain=sections`, shows what each stage
+    /// makes of the file.
+    Explain {
+        sections: Vec<ExplainSection>,
+        path: String,
+    },
+    /// `--generate-reference`, prints the language reference.
+    GenerateReference,
+    /// `fmt script.jbr`, rewrites the file formatted, or prints stdin
+    /// formatted for `-`. With `--check` nothing is written and the status
+    /// tells whether formatting would change anything.
+    Format { path: String, check: bool },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub mode: Mode,
+    pub trace: TraceOptions,
+    /// Cleared by `--no-history`.
+    pub history: bool,
+    /// `--export-globals file.json`, where the globals are written once the
+    /// program ran successfully.
+    pub export_globals: Option<String>,
+    /// `--optimize`, folds constant expressions before running.
+    pub optimize: bool,
+}
+
+/// Parses the arguments after the program name, the error saying what's
+/// wrong with them.
+pub fn parse_args(args: &[String]) -> Result<Args, String> {
+    if let Some(("fmt", rest)) = args
+        .split_first()
+        .map(|(first, rest)| (first.as_str(), rest))
+    {
+        return parse_format_args(rest);
+    }
+    let mut trace = TraceOptions::default();
+    let mut history = true;
+    let mut sections = None;
+    let mut generate_reference = false;
+    let mut program = None;
+    let mut export_globals = None;
+    let mut optimize = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let given = match arg.as_str() {
+            "--dump-tokens" => {
+                trace.tokens = true;
+                continue;
+            }
+            "--dump-ast" => {
+                trace.ast = true;
+                continue;
+            }
+            "--dump-dot" => {
+                trace.dot = true;
+                continue;
+            }
+            "--no-history" => {
+                history = false;
+                continue;
+            }
+            "--optimize" => {
+                optimize = true;
+                continue;
+            }
+            "--generate-reference" => {
+                generate_reference = true;
+                continue;
+            }
+            "--explain-pipeline" => {
+                sections = Some(ExplainSection::ALL.to_vec());
+                continue;
+            }
+            "--export-globals" => match args.next() {
+                Some(path) => {
+                    export_globals = Some(path.clone());
+                    continue;
+                }
+                None => return Err("--export-globals needs a file".to_string()),
+            },
+            "-e" => match args.next() {
+                Some(source) => Mode::Eval(source.clone()),
+                None => return Err("-e needs a program".to_string()),
+            },
+            "-" => Mode::Stdin,
+            arg => match arg.strip_prefix("--explain=") {
+                Some(list) => {
+                    sections = Some(ExplainSection::parse_list(list)?);
+                    continue;
+                }
+                None if arg.starts_with('-') => {
+                    return Err(format!("Unknown option {}", arg));
+                }
+                None => Mode::File(arg.to_string()),
+            },
+        };
+        if program.replace(given).is_some() {
+            return Err("Only one program can be run at a time".to_string());
+        }
+    }
+
+    let mode = match (generate_reference, sections, program) {
+        (true, _, _) => Mode::GenerateReference,
+        (false, Some(sections), Some(Mode::File(path))) => Mode::Explain { sections, path },
+        (false, Some(_), _) => return Err("Explaining needs a script file".to_string()),
+        (false, None, program) => program.unwrap_or(Mode::Repl),
+    };
+    let runs_program = matches!(mode, Mode::File(_) | Mode::Stdin | Mode::Eval(_));
+    if export_globals.is_some() && !runs_program {
+        return Err("Exporting globals needs a program to run".to_string());
+    }
+    if trace.dot && !runs_program {
+        return Err("Dumping DOT needs a program".to_string());
+    }
+    Ok(Args {
+        mode,
+        trace,
+        history,
+        export_globals,
+        optimize,
+    })
+}
+
+/// The arguments after `fmt`.
+fn parse_format_args(args: &[String]) -> Result<Args, String> {
+    let mut check = false;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--check" => check = true,
+            "-" => {}
+            arg if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => {}
+        }
+        if arg != "--check" && path.replace(arg.clone()).is_some() {
+            return Err("Only one file can be formatted at a time".to_string());
+        }
+    }
+    let Some(path) = path else {
+        return Err("Formatting needs a script file or -".to_string());
+    };
+    Ok(Args {
+        mode: Mode::Format { path, check },
+        trace: TraceOptions::default(),
+        history: true,
+        export_globals: None,
+        optimize: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Args, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+        parse_args(&args)
+    }
+
+    fn mode(args: &str) -> Mode {
+        parse(args).unwrap().mode
+    }
+
+    #[test]
+    fn test_parse_args_modes() {
+        assert_eq!(mode(""), Mode::Repl);
+        assert_eq!(mode("--no-history"), Mode::Repl);
+        assert_eq!(mode("script.jbr"), Mode::File("script.jbr".to_string()));
+        assert_eq!(mode("-"), Mode::Stdin);
+        assert_eq!(
+            parse_args(&["-e".to_string(), "print 1+1;".to_string()])
+                .unwrap()
+                .mode,
+            Mode::Eval("print 1+1;".to_string())
+        );
+        assert_eq!(
+            mode("--explain=ast,tokens a.jbr"),
+            Mode::Explain {
+                sections: vec![ExplainSection::Ast, ExplainSection::Tokens],
+                path: "a.jbr".to_string()
+            }
+        );
+        assert_eq!(
+            mode("a.jbr --explain-pipeline"),
+            Mode::Explain {
+                sections: ExplainSection::ALL.to_vec(),
+                path: "a.jbr".to_string()
+            }
+        );
+        assert_eq!(mode("a.jbr --generate-reference"), Mode::GenerateReference);
+        assert_eq!(
+            mode("fmt a.jbr"),
+            Mode::Format {
+                path: "a.jbr".to_string(),
+                check: false
+            }
+        );
+        assert_eq!(
+            mode("fmt --check -"),
+            Mode::Format {
+                path: "-".to_string(),
+                check: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_args_flags() {
+        let args = parse("--dump-ast a.jbr --no-history").unwrap();
+        assert_eq!(
+            args.trace,
+            TraceOptions {
+                tokens: false,
+                ast: true,
+                dot: false
+            }
+        );
+        assert!(!args.history);
+        assert!(!args.optimize);
+        assert!(parse("--optimize a.jbr").unwrap().optimize);
+
+        let args = parse("--dump-tokens --dump-ast -").unwrap();
+        assert_eq!(
+            args.trace,
+            TraceOptions {
+                tokens: true,
+                ast: true,
+                dot: false
+            }
+        );
+        assert!(args.history);
+        assert_eq!(args.export_globals, None);
+
+        let args = parse("--export-globals out.json a.jbr").unwrap();
+        assert_eq!(args.export_globals, Some("out.json".to_string()));
+        assert_eq!(args.mode, Mode::File("a.jbr".to_string()));
+
+        let args = parse("--dump-dot a.jbr").unwrap();
+        assert!(args.trace.dot);
+        assert_eq!(args.mode, Mode::File("a.jbr".to_string()));
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        for (args, error) in [
+            ("-e", "-e needs a program"),
+            ("a.jbr b.jbr", "Only one program can be run at a time"),
+            ("- a.jbr", "Only one program can be run at a time"),
+            ("--verbose", "Unknown option --verbose"),
+            ("-x a.jbr", "Unknown option -x"),
+            ("--explain-pipeline", "Explaining needs a script file"),
+            ("--explain=ast -", "Explaining needs a script file"),
+            ("--explain=bytes a.jbr", "Unknown explain section \"bytes\""),
+            ("a.jbr --export-globals", "--export-globals needs a file"),
+            (
+                "--export-globals out.json",
+                "Exporting globals needs a program to run",
+            ),
+            (
+                "--export-globals out.json --explain-pipeline a.jbr",
+                "Exporting globals needs a program to run",
+            ),
+            ("--dump-dot", "Dumping DOT needs a program"),
+            // Only a leading `fmt` formats.
+            ("a.jbr fmt", "Only one program can be run at a time"),
+            ("fmt", "Formatting needs a script file or -"),
+            ("fmt --check", "Formatting needs a script file or -"),
+            (
+                "fmt a.jbr b.jbr",
+                "Only one file can be formatted at a time",
+            ),
+            ("fmt --dump-ast a.jbr", "Unknown option --dump-ast"),
+        ] {
+            assert_eq!(parse(args), Err(error.to_string()), "{}", args);
+        }
+    }
+}
@@ -1,48 +1,765 @@
+use std::{fmt::Display, io, sync::Arc};
+
+use crate::{LintDiagnostic, Position, SourceRef, Span, Token};
+
+/// What went wrong, so embedders can tell errors apart without matching on
+/// messages.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    UnexpectedCharacter,
+    UnterminatedString,
+    /// A `${` in a string without its closing `}`.
+    UnterminatedInterpolation,
+    MalformedNumber,
+    /// A number literal too large for a double, e.g. `1e999`.
+    NumberOutOfRange,
+    UnexpectedToken,
+    /// The input ended where more was expected, e.g. an unclosed block.
+    UnexpectedEof,
+    InvalidAssignmentTarget,
+    /// A keyword where a name should be, e.g. `var class = 1;`.
+    ReservedWord,
+    /// A comparison whose left operand is another, e.g. `0 < x < 10`.
+    ChainedComparison,
+    /// A keyword the [`LanguageProfile`](crate::LanguageProfile) in use
+    /// disables, e.g. `class` in a rules language.
+    DisabledKeyword,
+    IdentifierTooLong,
+    NestingTooDeep,
+    /// Calls nested past the interpreter's call depth, usually a function
+    /// recursing without end.
+    StackOverflow,
+    /// A scope was used while being written to, a bug in the interpreter
+    /// rather than in the program.
+    EnvironmentBusy,
+    UndefinedVariable,
+    /// Assigning a variable that was never declared, e.g. `x = 5;` without
+    /// `var x;`.
+    UndeclaredAssignment,
+    ConstantAssignment,
+    VariableRedeclared,
+    UseBeforeDeclaration,
+    TypeMismatch {
+        expected: String,
+        found: String,
+    },
+    InvalidOperator,
+    InvalidOperand,
+    DivisionByZero,
+    InvalidRepeatCount,
+    NotCallable,
+    ArityMismatch,
+    ReturnOutsideFunction,
+    ThisOutsideClass,
+    /// A second `default` arm in a `switch`.
+    DuplicateDefault,
+    /// An `import` inside a block or a function.
+    ImportOutsideTopLevel,
+    UndefinedProperty,
+    InvalidArgument,
+    InvalidIndex,
+    IndexOutOfBounds,
+    InvalidJson,
+    Unsupported,
+    /// An `assert` statement's condition wasn't truthy.
+    AssertionFailed,
+    /// The program ran longer than the interpreter's step limit allows.
+    BudgetExceeded,
+    /// The program called `exit` with this status.
+    Exit(i32),
+    /// A file or stream couldn't be read.
+    Io,
+    /// An imported file couldn't be read or has errors.
+    ImportFailed,
+    /// A file imports itself, directly or through others.
+    ImportCycle,
+    /// A file handed over as source looks like an executable or an image.
+    BinaryContent,
+    InvalidUtf8,
+    TooManyLines,
+}
+
+/// The source text of a run along with the byte offset at which each line
+/// starts, used to show the offending line when rendering an error. Lines
+/// end with `\n`, a `\r` before it being part of the line it ends, while a
+/// lone `\r` is no line break.
+#[derive(Debug)]
+pub struct SourceMap {
+    source: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(offset, _)| offset + 1));
+        Self {
+            source: source.to_string(),
+            line_starts,
+        }
+    }
+
+    /// The source text, which spans are byte offsets into.
+    pub(crate) fn text(&self) -> &str {
+        &self.source
+    }
+
+    /// Lines counted as [`str::lines`] does, a final newline not starting
+    /// another.
+    pub fn line_count(&self) -> usize {
+        match self.source.as_bytes().last() {
+            None => 0,
+            Some(b'\n') => self.line_starts.len() - 1,
+            Some(_) => self.line_starts.len(),
+        }
+    }
+
+    /// 1-based line and column of the character at byte `offset`, a
+    /// newline being the last character of its line. Columns count
+    /// characters, an offset within one being at its column.
+    pub fn position(&self, offset: usize) -> (u32, u32) {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let index = self.line_index(offset);
+        let column = self.source[self.line_starts[index]..offset].chars().count() + 1;
+        (index as u32 + 1, column as u32)
+    }
+
+    /// Returns the 0-based index of the line containing `offset`.
+    fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        }
+    }
+
+    fn line_text(&self, index: usize) -> &str {
+        let start = self.line_starts[index];
+        let end = match self.line_starts.get(index + 1) {
+            Some(next) => next - 1,
+            None => self.source.len(),
+        };
+        let text = &self.source[start..end];
+        text.strip_suffix('\r').unwrap_or(text)
+    }
+}
+
+/// A call an error happened in, the function's name and the line it was
+/// called from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallFrame {
+    pub function: String,
+    pub line: u32,
+}
+
 #[derive(Debug, Clone)]
 pub struct Error {
+    kind: Box<ErrorKind>,
     line: u32,
+    column: u32,
     message: String,
     where_: String,
+    span: Option<Span>,
+    /// The source the span is in, when it came from a scanned token.
+    source: Option<Arc<SourceRef>>,
+    /// The calls a runtime error happened in, innermost first. Boxed, as
+    /// errors are returned through every level of a deep evaluation.
+    trace: Option<Box<[CallFrame]>>,
 }
 
 impl Error {
-    pub fn new(line: u32, where_: String, message: String) -> Self {
+    /// Creates an error at `line`, `column` is 1-based and 0 when unknown.
+    pub fn new(kind: ErrorKind, line: u32, column: u32, where_: String, message: String) -> Self {
         Self {
+            kind: Box::new(kind),
             line,
+            column,
             message,
             where_,
+            span: None,
+            source: None,
+            trace: None,
         }
     }
-}
 
-impl ToString for Error {
-    fn to_string(&self) -> String {
+    /// Creates an error at `token`, keeping the source it was scanned from
+    /// to render against.
+    pub fn at(kind: ErrorKind, token: &Token, where_: String, message: String) -> Self {
+        Self {
+            source: token.source().cloned(),
+            ..Self::new(kind, token.line(), token.column(), where_, message).with_span(token.span)
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The line the error is at, 0 when unknown.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// The 1-based column the error is at, 0 when unknown.
+    pub fn column(&self) -> u32 {
+        self.column
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Renders the error against `source`, which its span is in.
+    pub fn with_source(mut self, source: &Arc<SourceRef>) -> Self {
+        self.source = Some(source.clone());
+        self
+    }
+
+    pub fn source(&self) -> Option<&Arc<SourceRef>> {
+        self.source.as_ref()
+    }
+
+    /// The calls the error happened in, innermost first.
+    pub fn trace(&self) -> &[CallFrame] {
+        self.trace.as_deref().unwrap_or_default()
+    }
+
+    /// Records that the error left a call of `function` made at `line`.
+    #[cfg(feature = "interpreter")]
+    pub(crate) fn push_frame(&mut self, function: &str, line: u32) {
+        let mut trace = self.trace.take().map(Vec::from).unwrap_or_default();
+        trace.push(CallFrame {
+            function: function.to_string(),
+            line,
+        });
+        self.trace = Some(trace.into_boxed_slice());
+    }
+
+    /// The calls the error happened in, one per line below a heading and
+    /// innermost first, or nothing outside of calls. A call repeating the
+    /// one before, as in runaway recursion, is counted instead of listed.
+    pub fn backtrace(&self) -> String {
+        if self.trace().is_empty() {
+            return String::new();
+        }
+        let mut lines = vec!["Backtrace, most recent call first:".to_string()];
+        let mut frames = self.trace().iter().peekable();
+        while let Some(frame) = frames.next() {
+            lines.push(format!(
+                "  in {}() called at line {}",
+                frame.function, frame.line
+            ));
+            let mut repeats = 0;
+            while frames.next_if_eq(&frame).is_some() {
+                repeats += 1;
+            }
+            if repeats > 0 {
+                lines.push(format!("  ... repeated {} more times", repeats));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Renders the error followed by the offending source line and a caret
+    /// underline below the span, e.g.
+    ///
+    /// ```text
+    /// "Expected a variable name" at 1:5 in =
+    /// line 1: var = 1;
+    ///             ^
+    /// ```
+    ///
+    /// Falls back to the plain message when the error has no span.
+    pub fn render(&self, source: &SourceMap) -> String {
+        let span = match self.span {
+            Some(span) if source.source.is_char_boundary(span.start) => span,
+            _ => return self.to_string(),
+        };
+        let index = source.line_index(span.start);
+        let text = source.line_text(index);
+        let prefix = format!("line {}: ", index + 1);
+
+        // Spans are in bytes, the underline is in characters.
+        let column = span.start - source.line_starts[index];
+        let end = span.end.saturating_sub(source.line_starts[index]);
+        let width = text
+            .get(column..end.min(text.len()))
+            .map_or(0, |spanned| spanned.chars().count());
+        let padding: String = text
+            .get(..column)
+            .unwrap_or(text)
+            .chars()
+            .map(|chr| if chr == '\t' { '\t' } else { ' ' })
+            .collect();
+
         format!(
-            "\"{}\" at line: {} in {}",
-            self.message, self.line, self.where_
+            "{}\n{}{}\n{}{}^{}",
+            self,
+            prefix,
+            text,
+            " ".repeat(prefix.len()),
+            padding,
+            "~".repeat(width.saturating_sub(1))
         )
     }
 }
 
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.column == 0 {
+            write!(
+                f,
+                "\"{}\" at line: {} in {}",
+                self.message, self.line, self.where_
+            )
+        } else {
+            write!(
+                f,
+                "\"{}\" at {}:{} in {}",
+                self.message, self.line, self.column, self.where_
+            )
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum JBreadErrors {
     ParseError(Error),
+    ResolveError(Error),
+    CompileError(Error),
     RunTimeException(Error),
+    Io(Error),
+    /// Not a failure: the program called `exit`, the status being the
+    /// [`ErrorKind::Exit`] of the error. It stops the program like an error
+    /// does, and is left to whoever ran it to act on.
+    Exit(Error),
 }
 
-impl ToString for JBreadErrors {
-    fn to_string(&self) -> String {
+impl Display for JBreadErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            JBreadErrors::ParseError(error) => error.to_string(),
-            JBreadErrors::RunTimeException(error) => error.to_string(),
+            // Not about a place in a program.
+            JBreadErrors::Io(error) => write!(f, "{}", error.message),
+            _ => self.error().fmt(f),
         }
     }
 }
 
+impl std::error::Error for JBreadErrors {}
+
 impl JBreadErrors {
-    pub fn report(&self) {
-        eprintln!("{:?}\n{}", self, self.to_string());
+    pub fn error(&self) -> &Error {
+        match self {
+            JBreadErrors::ParseError(error) => error,
+            JBreadErrors::ResolveError(error) => error,
+            JBreadErrors::CompileError(error) => error,
+            JBreadErrors::RunTimeException(error) => error,
+            JBreadErrors::Io(error) => error,
+            JBreadErrors::Exit(error) => error,
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        self.error().kind()
+    }
+
+    /// Writes the error to `out`, rendered against its source when it has
+    /// one.
+    pub fn report(&self, out: &mut dyn io::Write) -> io::Result<()> {
+        match self.error().source() {
+            Some(source) => self.report_with_source(source.map(), out),
+            None => writeln!(out, "{}", self.with_backtrace(self.to_string())),
+        }
+    }
+
+    /// The error rendered against `source`, followed by the
+    /// [`backtrace`](Error::backtrace) of the calls it happened in.
+    pub fn render(&self, source: &SourceMap) -> String {
+        self.with_backtrace(self.error().render(source))
+    }
+
+    fn with_backtrace(&self, rendered: String) -> String {
+        match self.error().backtrace() {
+            backtrace if backtrace.is_empty() => rendered,
+            backtrace => format!("{}\n{}", rendered, backtrace),
+        }
+    }
+
+    pub fn report_with_source(
+        &self,
+        source: &SourceMap,
+        out: &mut dyn io::Write,
+    ) -> io::Result<()> {
+        writeln!(out, "{}", self.render(source))
+    }
+
+    /// The stage that failed, as it appears in [`to_json`](Self::to_json).
+    pub fn stage(&self) -> &'static str {
+        match self {
+            JBreadErrors::ParseError(_) => "parse",
+            JBreadErrors::ResolveError(_) => "resolve",
+            JBreadErrors::CompileError(_) => "compile",
+            JBreadErrors::RunTimeException(_) => "runtime",
+            JBreadErrors::Io(_) => "io",
+            JBreadErrors::Exit(_) => "exit",
+        }
+    }
+
+    /// Failing to read `path`.
+    pub fn io(path: &str, error: &std::io::Error) -> Self {
+        JBreadErrors::Io(Error::new(
+            ErrorKind::Io,
+            0,
+            0,
+            path.to_string(),
+            format!("Couldn't read {}: {}", path, error),
+        ))
+    }
+
+    /// The exit status for a program that stopped with this error, as in
+    /// sysexits.h: 65 for a bad program, 70 if it failed while running and 74
+    /// if it couldn't be read or its output couldn't be written. A program
+    /// that called `exit` gets the status it passed.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            JBreadErrors::Exit(error) => match *error.kind {
+                ErrorKind::Exit(code) => code,
+                _ => 0,
+            },
+            JBreadErrors::ParseError(_)
+            | JBreadErrors::ResolveError(_)
+            | JBreadErrors::CompileError(_) => 65,
+            JBreadErrors::RunTimeException(_) => 70,
+            JBreadErrors::Io(_) => 74,
+        }
+    }
+
+    /// A JSON object with the stage, kind, message and position, for hosts
+    /// that read diagnostics without linking against the crate.
+    pub fn to_json(&self) -> String {
+        let error = self.error();
+        let kind = format!("{:?}", error.kind);
+        let kind = kind.split([' ', '{']).next().unwrap_or_default();
+        format!(
+            "{{\"stage\":\"{}\",\"kind\":\"{}\",\"message\":{},\"line\":{},\"column\":{},\"where\":{}}}",
+            self.stage(),
+            kind,
+            json_string(&error.message),
+            error.line,
+            error.column,
+            json_string(&error.where_)
+        )
+    }
+}
+
+/// Quotes `text` as a JSON string.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for chr in text.chars() {
+        match chr {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            chr if (chr as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", chr as u32)),
+            chr => quoted.push(chr),
+        }
     }
+    quoted.push('"');
+    quoted
 }
 
 pub type JBreadResult<T> = Result<T, JBreadErrors>;
+
+/// Code for expression statements that only read a literal or a variable.
+pub const NO_EFFECT: &str = "no-effect";
+/// Code for conditions made of an assignment, which usually meant `==`.
+pub const ASSIGNMENT_IN_CONDITION: &str = "assignment-in-condition";
+
+/// What a warning is about. Unlike an [`ErrorKind`], none of these stop a
+/// program from running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningKind {
+    NoEffect,
+    AssignmentInCondition,
+}
+
+impl WarningKind {
+    /// The code a `jbread-allow` directive names to suppress the warning.
+    pub fn code(&self) -> &'static str {
+        match self {
+            WarningKind::NoEffect => NO_EFFECT,
+            WarningKind::AssignmentInCondition => ASSIGNMENT_IN_CONDITION,
+        }
+    }
+}
+
+/// Something suspicious about a program that still runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JBreadWarning {
+    pub kind: WarningKind,
+    pub message: String,
+    pub position: Position,
+    /// Offsets of the offending source, `None` when unknown.
+    pub span: Option<Span>,
+}
+
+impl JBreadWarning {
+    pub fn new(kind: WarningKind, message: &str, position: Position) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+            position,
+            span: None,
+        }
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn report(&self, out: &mut dyn io::Write) -> io::Result<()> {
+        writeln!(out, "{}", self)
+    }
+}
+
+impl Display for JBreadWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "warning[{}]: {} at {}:{}",
+            self.kind.code(),
+            self.message,
+            self.position.line,
+            self.position.column
+        )
+    }
+}
+
+impl From<JBreadWarning> for LintDiagnostic {
+    fn from(warning: JBreadWarning) -> Self {
+        LintDiagnostic::new(warning.kind.code(), &warning.message, warning.position)
+    }
+}
+
+/// Warnings collected while a program goes through the stages, in the order
+/// they were found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics {
+    warnings: Vec<JBreadWarning>,
+}
+
+impl Diagnostics {
+    pub fn push(&mut self, warning: JBreadWarning) {
+        self.warnings.push(warning);
+    }
+
+    /// Takes back the latest warning.
+    pub fn pop(&mut self) -> Option<JBreadWarning> {
+        self.warnings.pop()
+    }
+
+    pub fn warnings(&self) -> &[JBreadWarning] {
+        &self.warnings
+    }
+
+    /// The warnings as lint diagnostics, so `jbread-allow` directives apply
+    /// to them.
+    pub fn into_lint(self) -> Vec<LintDiagnostic> {
+        self.warnings
+            .into_iter()
+            .map(LintDiagnostic::from)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, Scanner, ScannerOptions};
+
+    fn render_parse_error(source: &str) -> String {
+        let mut scanner = Scanner::new(source);
+        let mut parser = Parser::new(scanner.scan_tokens());
+        parser.parse().unwrap_err().render(&SourceMap::new(source))
+    }
+
+    #[test]
+    fn test_render_mid_line() {
+        assert_eq!(
+            render_parse_error("print 1;\nprint 2; var = 3; print 4;\nprint 5;"),
+            [
+                "\"Expected a variable name\" at 2:14 in =",
+                "line 2: print 2; var = 3; print 4;",
+                "                     ^",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_render_last_line_without_newline() {
+        assert_eq!(
+            render_parse_error("var a = 1;\nvar bee"),
+            [
+                "\"Expected ';' after variable declaration\" at 2:8 in ",
+                "line 2: var bee",
+                "               ^",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_render_multi_character_span() {
+        let error = Error::new(
+            ErrorKind::UnexpectedToken,
+            1,
+            7,
+            "world".to_string(),
+            "Oops".to_string(),
+        )
+        .with_span(Span::new(6, 11));
+        assert_eq!(
+            error.render(&SourceMap::new("hello\tworld;\n")),
+            "\"Oops\" at 1:7 in world\nline 1: hello\tworld;\n             \t^~~~~"
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let error = JBreadErrors::RunTimeException(Error::new(
+            ErrorKind::TypeMismatch {
+                expected: "number".to_string(),
+                found: "string".to_string(),
+            },
+            2,
+            5,
+            "+".to_string(),
+            "Can't add \"a\"\n".to_string(),
+        ));
+        assert_eq!(
+            error.to_json(),
+            r#"{"stage":"runtime","kind":"TypeMismatch","message":"Can't add \"a\"\n","line":2,"column":5,"where":"+"}"#
+        );
+        assert_eq!(json_string("\u{1}é"), "\"\\u0001é\"");
+    }
+
+    #[test]
+    fn test_errors_from_several_sources() {
+        let parse = |name: &str, text: &str| {
+            let source = SourceRef::new(name, text);
+            let (tokens, _) = Scanner::with_source(source, ScannerOptions::default()).scan();
+            Parser::new(&tokens).parse_recovering().errors
+        };
+        let mut errors = parse("b.jbr", "print 1;\nvar = 2;");
+        errors.extend(parse("a.jbr", "var x\nprint 3;\nprint ;"));
+        errors.sort_by_key(|error| {
+            let error = error.error();
+            let name = error.source().map(|source| source.name().to_string());
+            (name, error.line, error.column)
+        });
+
+        // Each renders against the source it came from.
+        let rendered: Vec<String> = errors
+            .iter()
+            .map(|error| {
+                let source = error.error().source().unwrap();
+                format!("{}: {}", source.name(), error.render(source.map()))
+            })
+            .collect();
+        assert_eq!(
+            rendered,
+            [
+                "a.jbr: \"Expected ';' after variable declaration\" at 1:5 in x\n\
+                 line 1: var x\n            ^",
+                "a.jbr: \"Expected Expression\" at 3:7 in ;\nline 3: print ;\n              ^",
+                "b.jbr: \"Expected a variable name\" at 2:5 in =\nline 2: var = 2;\n            ^",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_after_multibyte_characters() {
+        // Spans are byte offsets while carets line up by character.
+        assert_eq!(
+            render_parse_error("var s = \"h\u{e9}llo\" s;"),
+            [
+                "\"Expected ';' after variable declaration\" at 1:17 in s",
+                "line 1: var s = \"h\u{e9}llo\" s;",
+                "                        ^",
+            ]
+            .join("\n")
+        );
+        let (tokens, _) = Scanner::new("var s = \"h\u{e9}llo\";").scan();
+        assert_eq!(tokens[3].span, Span::new(8, 16));
+        let error = Error::at(
+            ErrorKind::InvalidOperand,
+            &tokens[3],
+            "".to_string(),
+            "Oops".to_string(),
+        );
+        assert_eq!(
+            error.render(tokens[3].source().unwrap().map()),
+            "\"Oops\" at 1:9 in \nline 1: var s = \"h\u{e9}llo\";\n                ^~~~~~~"
+        );
+    }
+
+    #[test]
+    fn test_report_writes_to_sink() {
+        let mut scanner = Scanner::new("var = 1;");
+        let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+        let mut out = Vec::new();
+        error.report(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\"Expected a variable name\" at 1:5 in =\nline 1: var = 1;\n            ^\n"
+        );
+    }
+
+    #[test]
+    fn test_render_without_span() {
+        let error = Error::new(
+            ErrorKind::UnexpectedToken,
+            1,
+            0,
+            "".to_string(),
+            "Oops".to_string(),
+        );
+        assert_eq!(error.render(&SourceMap::new("1;")), error.to_string());
+    }
+
+    #[test]
+    fn test_render_warning() {
+        let mut scanner = Scanner::new("var a = 1;\n  a;");
+        let mut parser = Parser::new(scanner.scan_tokens());
+        parser.parse().unwrap();
+        let diagnostics = parser.take_diagnostics();
+        assert_eq!(
+            diagnostics.warnings()[0].to_string(),
+            "warning[no-effect]: Statement has no effect at 2:3"
+        );
+        assert_eq!(
+            diagnostics.into_lint(),
+            vec![LintDiagnostic::new(
+                NO_EFFECT,
+                "Statement has no effect",
+                Position::new(2, 3)
+            )]
+        );
+        assert!(parser.take_diagnostics().warnings().is_empty());
+    }
+}
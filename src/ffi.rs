@@ -0,0 +1,724 @@
+//! The C interface for embedding the interpreter, declared in
+//! `include/jbread.h`.
+//!
+//! Every function checks its pointers and UTF-8 before using them, and no
+//! panic unwinds into the caller: a call that panics returns
+//! [`JBREAD_PANICKED`] and poisons its handle, which then only answers
+//! [`jbread_last_output`], [`jbread_last_errors_json`] and [`jbread_free`]
+//! and returns [`JBREAD_POISONED`] from everything else.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    rc::Rc,
+};
+
+use crate::{
+    errors::json_string,
+    interpreter::{HostInterface, Interpreter, SystemHost, Value},
+    parser::Parser,
+    JBreadErrors, Resolver, Scanner, Token, TokenTypes,
+};
+
+/// What a call did, one of the `JBREAD_*` constants.
+pub type JBreadStatus = c_int;
+
+pub const JBREAD_OK: JBreadStatus = 0;
+/// The source failed to parse, resolve or run, see the errors.
+pub const JBREAD_ERROR: JBreadStatus = 1;
+/// A pointer was null, text wasn't UTF-8 or a name isn't an identifier.
+pub const JBREAD_INVALID_ARGUMENT: JBreadStatus = 2;
+/// No variable has the name.
+pub const JBREAD_UNDEFINED: JBreadStatus = 3;
+/// The variable holds something other than what was asked for.
+pub const JBREAD_TYPE_MISMATCH: JBreadStatus = 4;
+/// The call panicked, which poisoned the handle.
+pub const JBREAD_PANICKED: JBreadStatus = 5;
+/// An earlier call panicked, the handle can only be freed.
+pub const JBREAD_POISONED: JBreadStatus = 6;
+
+#[cfg(test)]
+thread_local! {
+    /// Makes the next eval on this thread panic, to test containment.
+    pub(crate) static PANIC_ON_EVAL: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Collects printed lines instead of writing them to stdout.
+struct CapturingHost {
+    printed: Rc<RefCell<String>>,
+}
+
+impl HostInterface for CapturingHost {
+    fn random_seed(&self) -> u64 {
+        SystemHost.random_seed()
+    }
+
+    fn print(&self, line: &str) {
+        let mut printed = self.printed.borrow_mut();
+        printed.push_str(line);
+        printed.push('\n');
+    }
+
+    fn write(&self, text: &str) {
+        self.printed.borrow_mut().push_str(text);
+    }
+}
+
+/// Why a call failed, with each error as a JSON object.
+struct Failure {
+    status: JBreadStatus,
+    errors: Vec<String>,
+}
+
+impl Failure {
+    fn new(status: JBreadStatus, message: &str) -> Self {
+        Self {
+            status,
+            errors: vec![format!(
+                "{{\"stage\":\"ffi\",\"kind\":\"{}\",\"message\":{},\"line\":0,\"column\":0,\"where\":\"\"}}",
+                match status {
+                    JBREAD_UNDEFINED => "UndefinedVariable",
+                    JBREAD_TYPE_MISMATCH => "TypeMismatch",
+                    JBREAD_PANICKED => "Panic",
+                    _ => "InvalidArgument",
+                },
+                json_string(message)
+            )],
+        }
+    }
+
+    fn from_errors(errors: &[JBreadErrors]) -> Self {
+        Self {
+            status: JBREAD_ERROR,
+            errors: errors.iter().map(JBreadErrors::to_json).collect(),
+        }
+    }
+}
+
+/// An interpreter together with the text its last calls handed out, which
+/// stays valid until the call that replaces it.
+pub struct JBreadHandle {
+    interpreter: Interpreter,
+    printed: Rc<RefCell<String>>,
+    output: CString,
+    errors: CString,
+    string: CString,
+    poisoned: bool,
+}
+
+impl JBreadHandle {
+    fn new() -> Self {
+        let printed = Rc::new(RefCell::new(String::new()));
+        Self {
+            interpreter: Interpreter::with_host(CapturingHost {
+                printed: printed.clone(),
+            }),
+            printed,
+            output: CString::default(),
+            errors: c_string("[]"),
+            string: CString::default(),
+            poisoned: false,
+        }
+    }
+
+    fn eval(&mut self, source: &str) -> Result<(), Failure> {
+        self.printed.borrow_mut().clear();
+        let result = self.run(source);
+        // What was printed before a runtime error is still output.
+        self.output = c_string(&self.printed.borrow());
+        result
+    }
+
+    fn run(&mut self, source: &str) -> Result<(), Failure> {
+        #[cfg(test)]
+        if PANIC_ON_EVAL.with(|panic| panic.take()) {
+            panic!("Injected panic");
+        }
+        let (tokens, mut errors) = Scanner::new(source).scan();
+        let outcome = Parser::new(&tokens).parse_recovering();
+        errors.extend(outcome.errors);
+        if !errors.is_empty() {
+            return Err(Failure::from_errors(&errors));
+        }
+        let resolution = Resolver::default()
+            .resolve(&outcome.statements)
+            .map_err(|error| Failure::from_errors(&[error]))?;
+        self.interpreter.set_locals(resolution.locals);
+        self.interpreter
+            .interpret(&outcome.statements)
+            .map(drop)
+            .map_err(|error| Failure::from_errors(&[error]))
+    }
+
+    fn define(&mut self, name: &str, value: Value) {
+        self.interpreter
+            .environment
+            .borrow_mut()
+            .define(name, value);
+    }
+
+    fn get(&self, name: &str) -> Result<Value, Failure> {
+        let token = Token::new(TokenTypes::Identifier, name.to_string(), None, 1);
+        self.interpreter
+            .environment
+            .borrow()
+            .get(&token)
+            .map_err(|_| Failure::new(JBREAD_UNDEFINED, &format!("{} is not defined", name)))
+    }
+}
+
+/// `text` as a C string, with any NUL replaced so it can't cut it short.
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "\u{FFFD}")).unwrap_or_default()
+}
+
+/// Runs `call` against the handle, turning what it returns or a panic into
+/// a status and recording the errors for [`jbread_last_errors_json`].
+///
+/// # Safety
+///
+/// `handle` must be null or come from [`jbread_new`] and not be freed.
+unsafe fn with_handle(
+    handle: *mut JBreadHandle,
+    call: impl FnOnce(&mut JBreadHandle) -> Result<(), Failure>,
+) -> JBreadStatus {
+    let Some(handle) = handle.as_mut() else {
+        return JBREAD_INVALID_ARGUMENT;
+    };
+    if handle.poisoned {
+        return JBREAD_POISONED;
+    }
+    let failure = match panic::catch_unwind(AssertUnwindSafe(|| call(&mut *handle))) {
+        Ok(Ok(())) => None,
+        Ok(Err(failure)) => Some(failure),
+        Err(payload) => {
+            handle.poisoned = true;
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Panicked".to_string());
+            Some(Failure::new(JBREAD_PANICKED, &message))
+        }
+    };
+    let (status, errors) = match failure {
+        Some(failure) => (failure.status, failure.errors),
+        None => (JBREAD_OK, Vec::new()),
+    };
+    handle.errors = c_string(&format!("[{}]", errors.join(",")));
+    status
+}
+
+/// Reads `len` bytes at `text` as UTF-8.
+///
+/// # Safety
+///
+/// `text` must be null or point to `len` readable bytes.
+unsafe fn read_utf8<'a>(text: *const c_char, len: usize) -> Result<&'a str, Failure> {
+    if text.is_null() {
+        return Err(Failure::new(JBREAD_INVALID_ARGUMENT, "Source is null"));
+    }
+    let bytes = std::slice::from_raw_parts(text.cast::<u8>(), len);
+    std::str::from_utf8(bytes).map_err(|error| {
+        Failure::new(
+            JBREAD_INVALID_ARGUMENT,
+            &format!("Source is not UTF-8: {}", error),
+        )
+    })
+}
+
+/// Reads a NUL-terminated variable name, which has to be an identifier.
+///
+/// # Safety
+///
+/// `name` must be null or point to a NUL-terminated string.
+unsafe fn read_name<'a>(name: *const c_char) -> Result<&'a str, Failure> {
+    if name.is_null() {
+        return Err(Failure::new(JBREAD_INVALID_ARGUMENT, "Name is null"));
+    }
+    let name = CStr::from_ptr(name)
+        .to_str()
+        .map_err(|_| Failure::new(JBREAD_INVALID_ARGUMENT, "Name is not UTF-8"))?;
+    let (tokens, errors) = Scanner::new(name).scan();
+    match tokens.as_slice() {
+        [identifier, _] if errors.is_empty() && identifier.token_type == TokenTypes::Identifier => {
+            Ok(name)
+        }
+        _ => Err(Failure::new(
+            JBREAD_INVALID_ARGUMENT,
+            &format!("{:?} is not an identifier", name),
+        )),
+    }
+}
+
+/// Creates an interpreter, null if that failed.
+#[no_mangle]
+pub extern "C" fn jbread_new() -> *mut JBreadHandle {
+    panic::catch_unwind(|| Box::into_raw(Box::new(JBreadHandle::new()))).unwrap_or(ptr::null_mut())
+}
+
+/// Frees an interpreter, doing nothing for null.
+///
+/// # Safety
+///
+/// `handle` must be null or come from [`jbread_new`] and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn jbread_free(handle: *mut JBreadHandle) {
+    if !handle.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(handle))));
+    }
+}
+
+/// Runs `len` bytes of UTF-8 source, keeping the variables it defines for
+/// the next call.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, `source` null or `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn jbread_eval(
+    handle: *mut JBreadHandle,
+    source: *const c_char,
+    len: usize,
+) -> JBreadStatus {
+    with_handle(handle, |handle| {
+        let source = read_utf8(source, len)?;
+        handle.eval(source)
+    })
+}
+
+/// What the last [`jbread_eval`] printed, valid until the next one. Null
+/// for a null handle.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn jbread_last_output(handle: *const JBreadHandle) -> *const c_char {
+    match handle.as_ref() {
+        Some(handle) => handle.output.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// A JSON array of what went wrong in the last call, empty if it succeeded,
+/// valid until the next call. Null for a null handle.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn jbread_last_errors_json(handle: *const JBreadHandle) -> *const c_char {
+    match handle.as_ref() {
+        Some(handle) => handle.errors.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// Defines a global number, replacing any variable with the name.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, `name` null or NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn jbread_define_number(
+    handle: *mut JBreadHandle,
+    name: *const c_char,
+    value: f64,
+) -> JBreadStatus {
+    with_handle(handle, |handle| {
+        let name = read_name(name)?;
+        handle.define(name, Value::Number(value));
+        Ok(())
+    })
+}
+
+/// Defines a global string, replacing any variable with the name.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, `name` and `value` null or
+/// NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn jbread_define_string(
+    handle: *mut JBreadHandle,
+    name: *const c_char,
+    value: *const c_char,
+) -> JBreadStatus {
+    with_handle(handle, |handle| {
+        let name = read_name(name)?;
+        if value.is_null() {
+            return Err(Failure::new(JBREAD_INVALID_ARGUMENT, "Value is null"));
+        }
+        let value = CStr::from_ptr(value)
+            .to_str()
+            .map_err(|_| Failure::new(JBREAD_INVALID_ARGUMENT, "Value is not UTF-8"))?;
+        handle.define(name, Value::String(value.to_string()));
+        Ok(())
+    })
+}
+
+/// Reads a number variable into `out`.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, `name` null or NUL-terminated and
+/// `out` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn jbread_get_number(
+    handle: *mut JBreadHandle,
+    name: *const c_char,
+    out: *mut f64,
+) -> JBreadStatus {
+    with_handle(handle, |handle| {
+        let name = read_name(name)?;
+        if out.is_null() {
+            return Err(Failure::new(JBREAD_INVALID_ARGUMENT, "Out is null"));
+        }
+        *out = match handle.get(name)? {
+            Value::Number(number) => number,
+            Value::NaN => f64::NAN,
+            _ => {
+                return Err(Failure::new(
+                    JBREAD_TYPE_MISMATCH,
+                    &format!("{} is not a number", name),
+                ))
+            }
+        };
+        Ok(())
+    })
+}
+
+/// Points `out` at the UTF-8 of a string variable, valid until the next
+/// call of this function.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, `name` null or NUL-terminated and
+/// `out` null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn jbread_get_string(
+    handle: *mut JBreadHandle,
+    name: *const c_char,
+    out: *mut *const c_char,
+) -> JBreadStatus {
+    with_handle(handle, |handle| {
+        let name = read_name(name)?;
+        if out.is_null() {
+            return Err(Failure::new(JBREAD_INVALID_ARGUMENT, "Out is null"));
+        }
+        let string = match handle.get(name)? {
+            Value::String(string) => CString::new(string).map_err(|_| {
+                Failure::new(
+                    JBREAD_TYPE_MISMATCH,
+                    &format!("{} holds a NUL, which C strings can't", name),
+                )
+            })?,
+            _ => {
+                return Err(Failure::new(
+                    JBREAD_TYPE_MISMATCH,
+                    &format!("{} is not a string", name),
+                ))
+            }
+        };
+        handle.string = string;
+        *out = handle.string.as_ptr();
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The C spelling of a Rust type in the signatures above.
+    fn c_type(rust: &str) -> String {
+        let pointer = |pointee: String| match pointee.ends_with('*') {
+            true => format!("{}*", pointee),
+            false => format!("{} *", pointee),
+        };
+        if let Some(pointee) = rust.strip_prefix("*mut ") {
+            return pointer(c_type(pointee));
+        }
+        if let Some(pointee) = rust.strip_prefix("*const ") {
+            return pointer(format!("const {}", c_type(pointee)));
+        }
+        match rust {
+            "c_char" => "char",
+            "f64" => "double",
+            "usize" => "uintptr_t",
+            other => other,
+        }
+        .to_string()
+    }
+
+    /// `name` declared as a `c_type`, `char *name` rather than `char * name`.
+    fn declare(c_type: &str, name: &str) -> String {
+        match c_type.ends_with('*') {
+            true => format!("{}{}", c_type, name),
+            false => format!("{} {}", c_type, name),
+        }
+    }
+
+    /// The declarations cbindgen writes for the constants and functions in
+    /// this file.
+    fn declarations() -> Vec<String> {
+        let source = include_str!("ffi.rs");
+        let source = &source[..source.find("#[cfg(test)]\nmod tests").unwrap()];
+        let mut declarations = Vec::new();
+        for line in source.lines() {
+            if let Some(constant) = line.strip_prefix("pub const ") {
+                let (name, value) = constant.split_once(": JBreadStatus = ").unwrap();
+                declarations.push(format!("#define {} {}", name, value.trim_end_matches(';')));
+            }
+        }
+        for signature in source.split("extern \"C\" fn ").skip(1) {
+            let signature = signature[..signature.find('{').unwrap()]
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            let (name, rest) = signature.split_once('(').unwrap();
+            let (params, returns) = rest.rsplit_once(')').unwrap();
+            let params: Vec<String> = params
+                .split(',')
+                .map(str::trim)
+                .filter(|param| !param.is_empty())
+                .map(|param| {
+                    let (name, rust) = param.split_once(": ").unwrap();
+                    declare(&c_type(rust), name)
+                })
+                .collect();
+            let returns = match returns.trim().strip_prefix("-> ") {
+                Some(rust) => c_type(rust),
+                None => "void".to_string(),
+            };
+            let params = match params.is_empty() {
+                true => "void".to_string(),
+                false => params.join(", "),
+            };
+            declarations.push(format!("{}({});", declare(&returns, name), params));
+        }
+        declarations
+    }
+
+    #[test]
+    fn test_header_in_sync() {
+        let header = include_str!("../include/jbread.h");
+        let declarations = declarations();
+        assert_eq!(declarations.len(), 16);
+        for declaration in declarations.iter() {
+            assert!(
+                header.lines().any(|line| line == declaration),
+                "include/jbread.h is missing `{}`",
+                declaration
+            );
+        }
+        // Nothing the header declares has gone from the Rust side.
+        for line in header.lines() {
+            let constant = line.starts_with("#define JBREAD_") && line != "#define JBREAD_H";
+            let function = line.contains("jbread_") && !line.starts_with([' ', '/']);
+            if constant || function {
+                assert!(
+                    declarations.iter().any(|declaration| declaration == line),
+                    "{}",
+                    line
+                );
+            }
+        }
+    }
+
+    fn text(pointer: *const c_char) -> String {
+        assert!(!pointer.is_null());
+        unsafe { CStr::from_ptr(pointer) }
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    fn eval(handle: *mut JBreadHandle, source: &str) -> JBreadStatus {
+        unsafe { jbread_eval(handle, source.as_ptr().cast(), source.len()) }
+    }
+
+    #[test]
+    fn test_eval_and_variables() {
+        let handle = jbread_new();
+        unsafe {
+            assert_eq!(
+                jbread_define_number(handle, c"width".as_ptr(), 3.0),
+                JBREAD_OK
+            );
+            assert_eq!(
+                jbread_define_string(handle, c"unit".as_ptr(), c"cm".as_ptr()),
+                JBREAD_OK
+            );
+            assert_eq!(
+                eval(handle, "var area = width * width; print area; print unit;"),
+                JBREAD_OK
+            );
+            assert_eq!(text(jbread_last_output(handle)), "9\ncm\n");
+            assert_eq!(text(jbread_last_errors_json(handle)), "[]");
+
+            let mut area = 0.0;
+            assert_eq!(
+                jbread_get_number(handle, c"area".as_ptr(), &mut area),
+                JBREAD_OK
+            );
+            assert_eq!(area, 9.0);
+
+            // Variables carry over from one eval to the next.
+            assert_eq!(eval(handle, "var label = \"é\" + unit;"), JBREAD_OK);
+            let mut label = ptr::null();
+            assert_eq!(
+                jbread_get_string(handle, c"label".as_ptr(), &mut label),
+                JBREAD_OK
+            );
+            assert_eq!(text(label), "écm");
+            assert_eq!(text(jbread_last_output(handle)), "");
+
+            assert_eq!(
+                jbread_get_number(handle, c"unit".as_ptr(), &mut area),
+                JBREAD_TYPE_MISMATCH
+            );
+            assert_eq!(
+                jbread_get_string(handle, c"area".as_ptr(), &mut label),
+                JBREAD_TYPE_MISMATCH
+            );
+            assert_eq!(
+                jbread_get_number(handle, c"depth".as_ptr(), &mut area),
+                JBREAD_UNDEFINED
+            );
+            assert!(
+                text(jbread_last_errors_json(handle)).contains("\"kind\":\"UndefinedVariable\"")
+            );
+            assert_eq!(area, 9.0);
+            jbread_free(handle);
+        }
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let handle = jbread_new();
+        unsafe {
+            assert_eq!(eval(handle, "var = 1;\nprint ;"), JBREAD_ERROR);
+            let errors = text(jbread_last_errors_json(handle));
+            assert!(errors.starts_with("[{\"stage\":\"parse\""), "{}", errors);
+            assert_eq!(errors.matches("\"stage\"").count(), 2, "{}", errors);
+
+            // Scan errors fail the call like parse errors.
+            assert_eq!(eval(handle, "print \"open;"), JBREAD_ERROR);
+            let errors = text(jbread_last_errors_json(handle));
+            assert!(
+                errors.contains("\"kind\":\"UnterminatedString\""),
+                "{}",
+                errors
+            );
+
+            // Output before a runtime error is kept.
+            assert_eq!(eval(handle, "print 1; print 1 + \"a\";"), JBREAD_ERROR);
+            assert_eq!(text(jbread_last_output(handle)), "1\n");
+            assert!(text(jbread_last_errors_json(handle)).contains("\"stage\":\"runtime\""));
+            jbread_free(handle);
+        }
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let handle = jbread_new();
+        let mut number = 0.0;
+        let mut string = ptr::null();
+        unsafe {
+            assert_eq!(
+                jbread_eval(ptr::null_mut(), c"".as_ptr(), 0),
+                JBREAD_INVALID_ARGUMENT
+            );
+            assert_eq!(jbread_eval(handle, ptr::null(), 0), JBREAD_INVALID_ARGUMENT);
+            let invalid = [b'p', 0xff];
+            assert_eq!(
+                jbread_eval(handle, invalid.as_ptr().cast(), 2),
+                JBREAD_INVALID_ARGUMENT
+            );
+            assert!(text(jbread_last_errors_json(handle)).contains("not UTF-8"));
+
+            for name in [c"", c"print", c"two words", c"1st", c"\xff"] {
+                assert_eq!(
+                    jbread_define_number(handle, name.as_ptr(), 1.0),
+                    JBREAD_INVALID_ARGUMENT,
+                    "{:?}",
+                    name
+                );
+            }
+            assert_eq!(
+                jbread_define_number(handle, ptr::null(), 1.0),
+                JBREAD_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                jbread_define_string(handle, c"a".as_ptr(), ptr::null()),
+                JBREAD_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                jbread_get_number(handle, c"a".as_ptr(), ptr::null_mut()),
+                JBREAD_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                jbread_get_string(handle, c"a".as_ptr(), ptr::null_mut()),
+                JBREAD_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                jbread_get_number(ptr::null_mut(), c"a".as_ptr(), &mut number),
+                JBREAD_INVALID_ARGUMENT
+            );
+            assert_eq!(
+                jbread_get_string(ptr::null_mut(), c"a".as_ptr(), &mut string),
+                JBREAD_INVALID_ARGUMENT
+            );
+            assert!(jbread_last_output(ptr::null()).is_null());
+            assert!(jbread_last_errors_json(ptr::null()).is_null());
+            jbread_free(ptr::null_mut());
+
+            // A string C can't hold is refused rather than cut short.
+            assert_eq!(eval(handle, "var nul = \"a\0b\";"), JBREAD_OK);
+            assert_eq!(
+                jbread_get_string(handle, c"nul".as_ptr(), &mut string),
+                JBREAD_TYPE_MISMATCH
+            );
+            assert!(string.is_null());
+            jbread_free(handle);
+        }
+    }
+
+    #[test]
+    fn test_panic_poisons_handle() {
+        let handle = jbread_new();
+        let mut number = 0.0;
+        unsafe {
+            assert_eq!(eval(handle, "var a = 1;"), JBREAD_OK);
+            PANIC_ON_EVAL.with(|panic| panic.set(true));
+            assert_eq!(eval(handle, "print a;"), JBREAD_PANICKED);
+            let errors = text(jbread_last_errors_json(handle));
+            assert!(
+                errors.contains("\"kind\":\"Panic\",\"message\":\"Injected panic\""),
+                "{}",
+                errors
+            );
+
+            assert_eq!(eval(handle, "print a;"), JBREAD_POISONED);
+            assert_eq!(
+                jbread_define_number(handle, c"b".as_ptr(), 1.0),
+                JBREAD_POISONED
+            );
+            assert_eq!(
+                jbread_get_number(handle, c"a".as_ptr(), &mut number),
+                JBREAD_POISONED
+            );
+            assert_eq!(text(jbread_last_errors_json(handle)), errors);
+            jbread_free(handle);
+        }
+
+        // Other handles are unaffected.
+        let handle = jbread_new();
+        assert_eq!(eval(handle, "print 2;"), JBREAD_OK);
+        unsafe { jbread_free(handle) };
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    rc::Rc,
+};
+
+use crate::{
+    interpreter::{Function, Value},
+    Symbol,
+};
+
+/// Name of the method run when a class is instantiated.
+pub const INITIALIZER: &str = "init";
+
+pub struct Class {
+    name: Symbol,
+    methods: HashMap<Symbol, Rc<Function>>,
+}
+
+impl Class {
+    pub(crate) fn new(name: Symbol, methods: HashMap<Symbol, Rc<Function>>) -> Self {
+        Self { name, methods }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn find_method(&self, name: impl Into<Symbol>) -> Option<&Rc<Function>> {
+        self.methods.get(&name.into())
+    }
+
+    /// Number of arguments taken when called, those of `init` if any.
+    pub fn arity(&self) -> usize {
+        self.find_method(INITIALIZER).map_or(0, |init| init.arity())
+    }
+}
+
+impl Debug for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Class({})", self.name)
+    }
+}
+
+impl Display for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+pub struct Instance {
+    class: Rc<Class>,
+    fields: RefCell<HashMap<Symbol, Value>>,
+}
+
+impl Instance {
+    pub(crate) fn new(class: Rc<Class>) -> Self {
+        Self {
+            class,
+            fields: RefCell::default(),
+        }
+    }
+
+    pub fn class(&self) -> &Rc<Class> {
+        &self.class
+    }
+
+    /// Looks up a field, then a method bound to this instance. `None` when
+    /// neither exists.
+    pub fn get(self: &Rc<Self>, name: &Symbol) -> Option<Value> {
+        if let Some(value) = self.fields.borrow().get(name) {
+            return Some(value.clone());
+        }
+        let method = self.class.find_method(name)?;
+        Some(Value::Function(Rc::new(method.bind(self.clone()))))
+    }
+
+    pub fn set(&self, name: &Symbol, value: Value) {
+        self.fields.borrow_mut().insert(name.clone(), value);
+    }
+}
+
+/// Fields can hold the instance itself, so they're left out.
+impl Debug for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Instance({})", self.class.name)
+    }
+}
+
+impl Display for Instance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
+
+/// Instances are only equal to themselves.
+impl PartialEq for Instance {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
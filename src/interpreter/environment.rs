@@ -1,67 +1,696 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::{Ref, RefCell, RefMut},
+    collections::HashMap,
+    rc::Rc,
+};
 
 use crate::{
-    errors::{Error, JBreadErrors, JBreadResult},
-    Literal as LiteralEnum, Token,
+    errors::{Error, ErrorKind, JBreadErrors, JBreadResult},
+    interpreter::Value,
+    Symbol, Token,
 };
 
+/// A variable's value, whether it may be assigned and whether it has been
+/// given a value. A variable declared without one can't be read until it's
+/// assigned, while one holding nil reads as nil.
+#[derive(Debug, Clone)]
+struct Binding {
+    value: Value,
+    mutable: bool,
+    initialized: bool,
+}
+
+/// The bindings of a scope at some point, see [`Environment::checkpoint`].
 #[derive(Debug)]
-pub struct Environment {
-    values: HashMap<String, Option<LiteralEnum>>,
-    encolosing: Option<Rc<RefCell<Environment>>>,
+pub struct Checkpoint(HashMap<Symbol, Binding>);
+
+/// A binding as it was before being written, to put back when a
+/// transactional run fails, see
+/// [`Interpreter::interpret_transactional`](super::Interpreter::interpret_transactional).
+/// Holding the environment itself rather than a copy keeps closures sharing
+/// it seeing what's put back.
+#[derive(Debug)]
+pub(crate) struct Undo {
+    environment: Rc<RefCell<Environment>>,
+    name: Symbol,
+    binding: Option<Binding>,
 }
 
-impl Default for Environment {
-    fn default() -> Self {
-        Self {
-            values: HashMap::new(),
-            encolosing: None,
-        }
+impl Undo {
+    /// Remembers `name` in `environment` as it is now, undefined included.
+    pub(crate) fn of(environment: Rc<RefCell<Environment>>, name: &str) -> JBreadResult<Self> {
+        let name = Symbol::intern(name);
+        let binding = Environment::reading(&environment, &name)?
+            .values
+            .get(&name)
+            .cloned();
+        Ok(Self {
+            environment,
+            name,
+            binding,
+        })
+    }
+
+    pub(crate) fn revert(self) {
+        let mut environment = self.environment.borrow_mut();
+        match self.binding {
+            Some(binding) => environment.values.insert(self.name, binding),
+            None => environment.values.remove(&self.name),
+        };
     }
 }
 
+/// The variables visible from an environment, see [`Environment::snapshot`].
+/// Each list is sorted by name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnvSnapshot {
+    pub(crate) variables: Vec<(String, Value)>,
+    pub(crate) constants: Vec<(String, Value)>,
+}
+
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<Symbol, Binding>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
 impl Environment {
     pub fn new(enclosing: Rc<RefCell<Environment>>) -> Self {
         Self {
             values: HashMap::new(),
-            encolosing: Some(enclosing),
+            enclosing: Some(enclosing),
         }
     }
 
-    fn error(&self, name: &Token) -> JBreadErrors {
+    /// Borrows `environment` to use `name` in it, failing instead of
+    /// panicking while it's being written to.
+    pub(crate) fn reading<'a>(
+        environment: &'a RefCell<Environment>,
+        name: &str,
+    ) -> JBreadResult<Ref<'a, Environment>> {
+        environment.try_borrow().map_err(|_| Self::busy(name))
+    }
+
+    /// Borrows `environment` to write `name` in it, failing instead of
+    /// panicking while it's in use.
+    pub(crate) fn writing<'a>(
+        environment: &'a RefCell<Environment>,
+        name: &str,
+    ) -> JBreadResult<RefMut<'a, Environment>> {
+        environment.try_borrow_mut().map_err(|_| Self::busy(name))
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn busy(name: &str) -> JBreadErrors {
         JBreadErrors::RunTimeException(Error::new(
-            name.line,
-            name.lexeme.clone(),
+            ErrorKind::EnvironmentBusy,
+            0,
+            0,
+            name.to_string(),
+            format!("The scope of '{}' is already in use", name),
+        ))
+    }
+
+    /// The error for reading or assigning a variable that isn't defined.
+    pub(crate) fn undefined(name: &Token) -> JBreadErrors {
+        JBreadErrors::RunTimeException(Error::at(
+            ErrorKind::UndefinedVariable,
+            name,
+            name.lexeme.to_string(),
             "Undefined variable".to_string(),
         ))
     }
 
-    pub fn define(&mut self, name: &str, value: Option<LiteralEnum>) {
-        self.values.insert(name.to_string(), value);
+    /// [`undefined`](Self::undefined) after looking `name` up through the
+    /// whole chain, suggesting visible names close to it, as typos are the
+    /// usual cause.
+    #[cold]
+    #[inline(never)]
+    fn undefined_here(&self, name: &Token) -> JBreadErrors {
+        match self.did_you_mean(&name.lexeme) {
+            Some(hint) => JBreadErrors::RunTimeException(Error::at(
+                ErrorKind::UndefinedVariable,
+                name,
+                name.lexeme.to_string(),
+                format!("Undefined variable, {}", hint),
+            )),
+            None => Self::undefined(name),
+        }
+    }
+
+    /// The error for assigning `name` when nothing in the chain declares
+    /// it, suggesting either a close name or declaring it.
+    #[cold]
+    #[inline(never)]
+    fn undeclared(&self, name: &Token) -> JBreadErrors {
+        let message = match self.did_you_mean(&name.lexeme) {
+            Some(hint) => format!(
+                "Cannot assign to undeclared variable '{}', {}",
+                name.lexeme, hint
+            ),
+            None => format!(
+                "Cannot assign to undeclared variable '{0}'; declare it with 'var {0} = ...'",
+                name.lexeme
+            ),
+        };
+        JBreadErrors::RunTimeException(Error::at(
+            ErrorKind::UndeclaredAssignment,
+            name,
+            name.lexeme.to_string(),
+            message,
+        ))
+    }
+
+    /// "did you mean 'a', 'b' or 'c'?" for the [`suggestions`](Self::suggestions)
+    /// for `name`, if there are any.
+    fn did_you_mean(&self, name: &str) -> Option<String> {
+        let suggestions = self.suggestions(name);
+        let (last, rest) = suggestions.split_last()?;
+        let quoted: Vec<_> = rest.iter().map(|name| format!("'{}'", name)).collect();
+        Some(match quoted.is_empty() {
+            true => format!("did you mean '{}'?", last),
+            false => format!("did you mean {} or '{}'?", quoted.join(", "), last),
+        })
+    }
+
+    /// Up to three visible names within a couple of edits of `name`, or
+    /// starting with it or it with them, the closest first and those of
+    /// closer scopes first among equally close ones.
+    fn suggestions(&self, name: &str) -> Vec<String> {
+        let max_distance = (name.chars().count() / 3).clamp(1, 2);
+        let mut candidates: Vec<_> = self
+            .visible_names()
+            .into_iter()
+            .filter_map(|candidate| {
+                let distance = edit_distance(name, &candidate);
+                // Otherwise any one-letter name would be close to every other.
+                let shorter = name.chars().count().min(candidate.chars().count());
+                let close = distance <= max_distance && distance < shorter;
+                let prefixed = shorter >= 3
+                    && (candidate.starts_with(name) || name.starts_with(candidate.as_str()));
+                (close || prefixed).then_some((distance, candidate))
+            })
+            .collect();
+        // Stable, keeping the scope order among equal distances.
+        candidates.sort_by_key(|(distance, _)| *distance);
+        candidates
+            .into_iter()
+            .take(3)
+            .map(|(_, candidate)| candidate)
+            .collect()
     }
 
-    pub fn get(&self, token: &Token) -> JBreadResult<Option<LiteralEnum>> {
+    /// The names visible from here, this environment's and then each
+    /// enclosing one's, sorted within a scope. A shadowed name is only
+    /// listed for the innermost scope defining it.
+    pub fn visible_names(&self) -> Vec<String> {
+        self.fold_chain(Vec::new(), |mut names, environment| {
+            let mut scope: Vec<_> = environment
+                .values
+                .keys()
+                .map(|name| name.to_string())
+                .filter(|name| !names.contains(name))
+                .collect();
+            scope.sort();
+            names.extend(scope);
+            names
+        })
+    }
+
+    pub fn define(&mut self, name: impl Into<Symbol>, value: Value) {
+        let binding = Binding {
+            value,
+            mutable: true,
+            initialized: true,
+        };
+        self.values.insert(name.into(), binding);
+    }
+
+    /// Defines a variable without a value, as `var x;` does, which can't be
+    /// read until it's assigned.
+    pub fn declare(&mut self, name: impl Into<Symbol>) {
+        let binding = Binding {
+            value: Value::Nil,
+            mutable: true,
+            initialized: false,
+        };
+        self.values.insert(name.into(), binding);
+    }
+
+    /// Defines a variable that [`assign`](Self::assign) refuses to change.
+    pub fn define_constant(&mut self, name: impl Into<Symbol>, value: Value) {
+        let binding = Binding {
+            value,
+            mutable: false,
+            initialized: true,
+        };
+        self.values.insert(name.into(), binding);
+    }
+
+    /// The error for assigning a constant.
+    fn constant(name: &Token) -> JBreadErrors {
+        JBreadErrors::RunTimeException(Error::at(
+            ErrorKind::ConstantAssignment,
+            name,
+            name.lexeme.to_string(),
+            format!("Cannot assign to constant '{}'", name.lexeme),
+        ))
+    }
+
+    /// Looks `token` up in this environment and then in each enclosing one.
+    pub fn get(&self, token: &Token) -> JBreadResult<Value> {
         if let Some(value) = self.values.get(&token.lexeme) {
-            match value {
-                Some(value) => Ok(Some(value.clone())),
-                None => Err(self.error(token)),
+            return self.read(token, value);
+        }
+        let mut current = self.enclosing.clone();
+        while let Some(environment) = current {
+            let environment = Self::reading(&environment, &token.lexeme)?;
+            if let Some(value) = environment.values.get(&token.lexeme) {
+                return self.read(token, value);
             }
-        } else if let Some(enclosed) = &self.encolosing {
-            Ok(enclosed.to_owned().borrow().get(token)?.to_owned())
-        } else {
-            Err(self.error(&token))
+            current = environment.enclosing.clone();
         }
+        Err(self.undefined_here(token))
     }
 
-    pub fn assign(&mut self, name: &Token, value: Option<LiteralEnum>) -> JBreadResult<()> {
-        if !self.values.contains_key(name.lexeme.as_str()) {
-            if let Some(enclosing) = &mut self.encolosing {
-                return enclosing.borrow_mut().assign(name, value);
+    /// Returns the value `name` held before.
+    pub fn assign(&mut self, name: &Token, value: Value) -> JBreadResult<Value> {
+        if let Some(binding) = self.values.get_mut(&name.lexeme) {
+            return Self::write(name, binding, value);
+        }
+        let mut current = self.enclosing.clone();
+        while let Some(environment) = current {
+            let mut environment = Self::writing(&environment, &name.lexeme)?;
+            if let Some(binding) = environment.values.get_mut(&name.lexeme) {
+                return Self::write(name, binding, value);
+            }
+            current = environment.enclosing.clone();
+        }
+        Err(self.undeclared(name))
+    }
+
+    /// The bindings of this scope, to put back with
+    /// [`roll_back`](Self::roll_back). Values shared with them, like lists,
+    /// aren't copied so changes made through them stay.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.values.clone())
+    }
+
+    /// Puts back the bindings of this scope as they were at `checkpoint`.
+    pub fn roll_back(&mut self, checkpoint: Checkpoint) {
+        self.values = checkpoint.0;
+    }
+
+    /// Every variable visible from here, flattened into one scope: where a
+    /// name is shadowed only the innermost binding is taken. As with
+    /// [`checkpoint`](Self::checkpoint), lists are shared rather than copied.
+    pub fn snapshot(&self) -> EnvSnapshot {
+        let visible = self.fold_chain(HashMap::new(), |mut visible, environment| {
+            for (name, binding) in environment.values.iter() {
+                visible
+                    .entry(name.clone())
+                    .or_insert_with(|| binding.clone());
+            }
+            visible
+        });
+        let mut snapshot = EnvSnapshot::default();
+        for (name, binding) in visible {
+            let entry = (name.to_string(), binding.value);
+            if binding.mutable {
+                snapshot.variables.push(entry);
             } else {
-                return Err(self.error(name));
+                snapshot.constants.push(entry);
             }
         }
-        self.values.insert(name.lexeme.to_string(), value);
-        Ok(())
+        snapshot
+            .variables
+            .sort_by(|left, right| left.0.cmp(&right.0));
+        snapshot
+            .constants
+            .sort_by(|left, right| left.0.cmp(&right.0));
+        snapshot
+    }
+
+    /// Defines everything in `snapshot` in this environment, replacing the
+    /// bindings of the same name it already has.
+    pub fn restore(&mut self, snapshot: EnvSnapshot) {
+        for (name, value) in snapshot.variables {
+            self.define(name, value);
+        }
+        for (name, value) in snapshot.constants {
+            self.define_constant(name, value);
+        }
+    }
+
+    /// The variables of this environment and then of each enclosing one,
+    /// a scope per entry with its names sorted.
+    pub fn scopes(&self) -> Vec<Vec<(String, Value)>> {
+        let mut scope: Vec<_> = self
+            .values
+            .iter()
+            .map(|(name, binding)| (name.to_string(), binding.value.clone()))
+            .collect();
+        scope.sort_by(|left, right| left.0.cmp(&right.0));
+        let mut scopes = vec![scope];
+        if let Some(enclosing) = &self.enclosing {
+            scopes.extend(enclosing.borrow().scopes());
+        }
+        scopes
+    }
+
+    /// The environment in the chain starting at `environment` that defines
+    /// `name`, the one [`assign`](Self::assign) would write to.
+    pub(crate) fn holding(
+        environment: &Rc<RefCell<Environment>>,
+        name: &Token,
+    ) -> JBreadResult<Option<Rc<RefCell<Environment>>>> {
+        let mut current = Some(environment.clone());
+        while let Some(environment) = current {
+            let next = {
+                let scope = Self::reading(&environment, &name.lexeme)?;
+                if scope.values.contains_key(&name.lexeme) {
+                    return Ok(Some(environment.clone()));
+                }
+                scope.enclosing.clone()
+            };
+            current = next;
+        }
+        Ok(None)
+    }
+
+    /// The outermost environment of the chain starting at `environment`,
+    /// where globals live.
+    pub fn globals(environment: &Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        let mut current = environment.clone();
+        loop {
+            let next = current.borrow().enclosing.clone();
+            match next {
+                Some(enclosing) => current = enclosing,
+                None => return current,
+            }
+        }
+    }
+
+    /// Returns the environment `distance` enclosing links away, borrowing
+    /// each link only long enough to step over it. `None` when `distance` is
+    /// zero or the chain is shorter than that.
+    pub fn ancestor(&self, distance: usize) -> Option<Rc<RefCell<Environment>>> {
+        let mut current = self.enclosing.clone()?;
+        for _ in 1..distance {
+            let next = current.borrow().enclosing.clone()?;
+            current = next;
+        }
+        (distance > 0).then_some(current)
+    }
+
+    /// Reads a variable the resolver found `distance` scopes away.
+    pub fn get_at(&self, distance: usize, name: &Token) -> JBreadResult<Value> {
+        if distance == 0 {
+            return match self.values.get(&name.lexeme) {
+                Some(value) => self.read(name, value),
+                None => Err(Self::undefined(name)),
+            };
+        }
+        let ancestor = self
+            .ancestor(distance)
+            .ok_or_else(|| Self::undefined(name))?;
+        let ancestor = Self::reading(&ancestor, &name.lexeme)?;
+        match ancestor.values.get(&name.lexeme) {
+            Some(value) => self.read(name, value),
+            None => Err(Self::undefined(name)),
+        }
+    }
+
+    /// Assigns a variable the resolver found `distance` scopes away,
+    /// returning the value it held before.
+    pub fn assign_at(
+        &mut self,
+        distance: usize,
+        name: &Token,
+        value: Value,
+    ) -> JBreadResult<Value> {
+        if distance == 0 {
+            return match self.values.get_mut(&name.lexeme) {
+                Some(binding) => Self::write(name, binding, value),
+                None => Err(Self::undefined(name)),
+            };
+        }
+        let ancestor = self
+            .ancestor(distance)
+            .ok_or_else(|| Self::undefined(name))?;
+        let mut ancestor = Self::writing(&ancestor, &name.lexeme)?;
+        match ancestor.values.get_mut(&name.lexeme) {
+            Some(binding) => Self::write(name, binding, value),
+            None => Err(Self::undefined(name)),
+        }
+    }
+
+    /// Reads a name defined in this environment only, `None` if undefined.
+    pub(crate) fn get_local(&self, name: &str) -> Option<Value> {
+        self.values
+            .get(&Symbol::intern(name))
+            .map(|binding| binding.value.clone())
+    }
+
+    /// Rough number of bytes a binding takes, inline and on the heap. This is
+    /// the single estimate behind all memory accounting.
+    pub(crate) fn binding_bytes(name: &str, value: &Value) -> usize {
+        let payload = match value {
+            Value::String(string) => string.len(),
+            _ => 0,
+        };
+        std::mem::size_of::<String>() + name.len() + std::mem::size_of::<Binding>() + payload
+    }
+
+    /// Estimated size of this environment alone, enclosing ones excluded.
+    pub(crate) fn estimated_bytes(&self) -> usize {
+        std::mem::size_of::<Environment>()
+            + self
+                .values
+                .iter()
+                .map(|(name, binding)| Self::binding_bytes(name, &binding.value))
+                .sum::<usize>()
+    }
+
+    /// Folds `f` over this environment and every enclosing one, borrowing
+    /// each link only while it's visited.
+    pub(crate) fn fold_chain<T>(&self, init: T, mut f: impl FnMut(T, &Environment) -> T) -> T {
+        let mut acc = f(init, self);
+        let mut current = self.enclosing.clone();
+        while let Some(environment) = current {
+            let environment = environment.borrow();
+            acc = f(acc, &environment);
+            current = environment.enclosing.clone();
+        }
+        acc
+    }
+
+    /// Number of environments in the chain and bindings live across it.
+    pub(crate) fn chain_size(&self) -> (usize, usize) {
+        self.fold_chain((0, 0), |(depth, bindings), environment| {
+            (depth + 1, bindings + environment.values.len())
+        })
+    }
+
+    /// A declared but uninitialized variable can't be read.
+    fn read(&self, name: &Token, binding: &Binding) -> JBreadResult<Value> {
+        match binding.initialized {
+            true => Ok(binding.value.clone()),
+            false => Err(Self::undefined(name)),
+        }
+    }
+
+    fn write(name: &Token, binding: &mut Binding, value: Value) -> JBreadResult<Value> {
+        if !binding.mutable {
+            return Err(Self::constant(name));
+        }
+        binding.initialized = true;
+        Ok(std::mem::replace(&mut binding.value, value))
+    }
+}
+
+/// The Levenshtein distance between `left` and `right`, in characters.
+fn edit_distance(left: &str, right: &str) -> usize {
+    let right: Vec<char> = right.chars().collect();
+    let mut previous: Vec<usize> = (0..=right.len()).collect();
+    for (i, left_chr) in left.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, right_chr) in right.iter().enumerate() {
+            let substitution = previous[j] + usize::from(left_chr != *right_chr);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[right.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TokenTypes;
+
+    fn name(lexeme: &str) -> Token {
+        Token::new(TokenTypes::Identifier, lexeme.to_string(), None, 1)
+    }
+
+    /// Builds `depth` environments nested below `root`, returning the innermost.
+    fn nest(root: &Rc<RefCell<Environment>>, depth: usize) -> Rc<RefCell<Environment>> {
+        (0..depth).fold(root.clone(), |enclosing, _| {
+            Rc::new(RefCell::new(Environment::new(enclosing)))
+        })
+    }
+
+    #[test]
+    fn test_ancestor() {
+        let root = Rc::new(RefCell::new(Environment::default()));
+        let innermost = nest(&root, 3);
+        assert!(Rc::ptr_eq(&innermost.borrow().ancestor(3).unwrap(), &root));
+        assert!(innermost.borrow().ancestor(4).is_none());
+        assert!(innermost.borrow().ancestor(0).is_none());
+    }
+
+    #[test]
+    fn test_scopes() {
+        let root = Rc::new(RefCell::new(Environment::default()));
+        root.borrow_mut().define("b", Value::Nil);
+        root.borrow_mut().define("a", Value::Number(1.0));
+        let inner = nest(&root, 1);
+        inner.borrow_mut().define("a", Value::Bool(true));
+        assert_eq!(
+            inner.borrow().scopes(),
+            vec![
+                vec![("a".to_string(), Value::Bool(true))],
+                vec![
+                    ("a".to_string(), Value::Number(1.0)),
+                    ("b".to_string(), Value::Nil)
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let root = Rc::new(RefCell::new(Environment::default()));
+        root.borrow_mut().define("a", Value::Number(1.0));
+        root.borrow_mut().declare("uninitialized");
+        root.borrow_mut().define("empty", Value::Nil);
+        root.borrow_mut()
+            .define_constant("c", Value::String("c".to_string()));
+        let inner = nest(&root, 2);
+        inner.borrow_mut().define("a", Value::Bool(true));
+        let snapshot = inner.borrow().snapshot();
+        assert_eq!(
+            snapshot.variables,
+            vec![
+                ("a".to_string(), Value::Bool(true)),
+                ("empty".to_string(), Value::Nil),
+                ("uninitialized".to_string(), Value::Nil),
+            ]
+        );
+
+        let mut restored = Environment::default();
+        restored.define("a", Value::Number(5.0));
+        restored.define("kept", Value::Number(6.0));
+        restored.restore(snapshot);
+        assert_eq!(restored.get(&name("a")).unwrap(), Value::Bool(true));
+        assert_eq!(restored.get(&name("kept")).unwrap(), Value::Number(6.0));
+        assert_eq!(
+            restored.get(&name("c")).unwrap(),
+            Value::String("c".to_string())
+        );
+        let error = restored.assign(&name("c"), Value::Number(1.0)).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ConstantAssignment);
+        assert_eq!(restored.get(&name("empty")).unwrap(), Value::Nil);
+        // A snapshot only holds values, so a variable declared without one
+        // comes back holding nil.
+        assert_eq!(restored.get(&name("uninitialized")).unwrap(), Value::Nil);
+    }
+
+    #[test]
+    fn test_assign_at_mutates_root() {
+        let root = Rc::new(RefCell::new(Environment::default()));
+        root.borrow_mut().define("a", Value::Number(1.0));
+        // A shadowing binding halfway up must not be the one written to.
+        let middle = nest(&root, 5);
+        middle.borrow_mut().define("a", Value::Number(5.0));
+        let innermost = nest(&middle, 5);
+
+        innermost
+            .borrow_mut()
+            .assign_at(10, &name("a"), Value::Number(2.0))
+            .unwrap();
+        assert_eq!(
+            root.borrow().get_at(0, &name("a")).unwrap(),
+            Value::Number(2.0)
+        );
+        assert_eq!(
+            innermost.borrow().get_at(10, &name("a")).unwrap(),
+            Value::Number(2.0)
+        );
+        assert_eq!(
+            innermost.borrow().get(&name("a")).unwrap(),
+            Value::Number(5.0)
+        );
+    }
+
+    #[test]
+    fn test_get_at_missing() {
+        let root = Rc::new(RefCell::new(Environment::default()));
+        let innermost = nest(&root, 2);
+        let error = innermost.borrow().get_at(1, &name("a")).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UndefinedVariable);
+        assert!(innermost.borrow().get_at(5, &name("a")).is_err());
+    }
+
+    #[test]
+    fn test_undefined_suggestions() {
+        let root = Rc::new(RefCell::new(Environment::default()));
+        let message = |environment: &Rc<RefCell<Environment>>, missing: &str| {
+            let error = environment.borrow().get(&name(missing)).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::UndefinedVariable);
+            error.error().message().to_string()
+        };
+        assert_eq!(message(&root, "countr"), "Undefined variable");
+
+        root.borrow_mut().define("counter", Value::Number(1.0));
+        root.borrow_mut().define("total", Value::Number(2.0));
+        assert_eq!(
+            message(&root, "countr"),
+            "Undefined variable, did you mean 'counter'?"
+        );
+        assert_eq!(message(&root, "width"), "Undefined variable");
+        let error = root
+            .borrow_mut()
+            .assign(&name("totl"), Value::Nil)
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UndeclaredAssignment);
+        assert_eq!(
+            error.error().message(),
+            "Cannot assign to undeclared variable 'totl', did you mean 'total'?"
+        );
+
+        // Closer scopes come first on ties, and shadowed names once.
+        let inner = nest(&root, 2);
+        inner.borrow_mut().define("countes", Value::Number(3.0));
+        assert_eq!(
+            message(&inner, "countez"),
+            "Undefined variable, did you mean 'countes' or 'counter'?"
+        );
+        inner.borrow_mut().define("counter", Value::Number(4.0));
+        root.borrow_mut().define("counted", Value::Number(5.0));
+        root.borrow_mut().define("count", Value::Number(6.0));
+        assert_eq!(
+            message(&inner, "countre"),
+            "Undefined variable, did you mean 'counter', 'countes' or 'count'?"
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("counter", "counter"), 0);
+        assert_eq!(edit_distance("countr", "counter"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
     }
 }
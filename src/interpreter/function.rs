@@ -0,0 +1,99 @@
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+use crate::{
+    ast,
+    interpreter::{environment::Environment, Instance, Value, INITIALIZER},
+};
+
+/// A function value, closing over the environment it was declared in.
+pub struct Function {
+    pub(crate) declaration: ast::Function,
+    pub(crate) closure: Rc<RefCell<Environment>>,
+    /// Whether this is a class's `init` method, which always returns `this`.
+    pub(crate) is_initializer: bool,
+    /// Made from a lambda, whose declaration is named by its `fun` or `->`.
+    pub(crate) is_anonymous: bool,
+}
+
+impl Function {
+    pub(crate) fn new(declaration: ast::Function, closure: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            declaration,
+            closure,
+            is_initializer: false,
+            is_anonymous: false,
+        }
+    }
+
+    pub(crate) fn lambda(lambda: &ast::Lambda, closure: Rc<RefCell<Environment>>) -> Self {
+        let declaration = ast::Function {
+            name: (*lambda.keyword).clone(),
+            params: lambda.params.clone(),
+            body: lambda.body.0.clone(),
+            span: lambda.span,
+        };
+        Self {
+            is_anonymous: true,
+            ..Self::new(declaration, closure)
+        }
+    }
+
+    pub(crate) fn method(declaration: ast::Function, closure: Rc<RefCell<Environment>>) -> Self {
+        let is_initializer = declaration.name.lexeme == INITIALIZER;
+        Self {
+            declaration,
+            closure,
+            is_initializer,
+            is_anonymous: false,
+        }
+    }
+
+    /// Returns a copy of this method with `this` bound to `instance`.
+    pub fn bind(&self, instance: Rc<Instance>) -> Function {
+        let mut environment = Environment::new(self.closure.clone());
+        environment.define("this", Value::Instance(instance));
+        Self {
+            declaration: self.declaration.clone(),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+            is_anonymous: self.is_anonymous,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        if self.is_anonymous {
+            return "anonymous";
+        }
+        &self.declaration.name.lexeme
+    }
+
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+}
+
+/// The closure usually holds the function itself, so only the name is shown.
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Function({})", self.name())
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.is_anonymous {
+            return write!(f, "<fn {}>", self.name());
+        }
+        match self.arity() {
+            1 => write!(f, "<fn anonymous(1 param)>"),
+            arity => write!(f, "<fn anonymous({} params)>", arity),
+        }
+    }
+}
+
+/// Functions are only equal to themselves.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
@@ -0,0 +1,444 @@
+//! Globals as a JSON object, so a pipeline can feed a run its inputs and pick
+//! up what it computed.
+//!
+//! | jBread            | JSON                                 |
+//! |-------------------|--------------------------------------|
+//! | `nil`             | `null`                               |
+//! | booleans          | booleans                             |
+//! | finite numbers    | numbers                              |
+//! | `NaN`, `inf`      | the strings `"NaN"`, `"inf"`, `"-inf"` |
+//! | strings           | strings                              |
+//! | lists             | arrays                               |
+//!
+//! Non-finite numbers are written as strings like in the serialized AST, so
+//! they come back as strings when imported. Functions, classes, instances and
+//! natives have no JSON form and are left out of the export, or fail it when
+//! held by a list, as does a list holding itself.
+//!
+//! An [`EnvSnapshot`] is written the same way, as
+//! `{"variables": {...}, "constants": {...}}`.
+
+use std::{fmt, rc::Rc};
+
+use serde::{
+    de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor},
+    ser::{self, Serialize, Serializer},
+};
+use serde_json::{Map, Number, Value as Json};
+
+use super::{environment::EnvSnapshot, Interpreter, ListCell, Value};
+use crate::{Error, ErrorKind, JBreadErrors, Scanner, TokenTypes};
+
+impl Interpreter {
+    /// The globals as a JSON object, keyed by name.
+    pub fn export_globals_json(&self) -> Result<String, JBreadErrors> {
+        let globals = self.environment.borrow().scopes().pop().unwrap_or_default();
+        Ok(Json::Object(to_object(&globals)?).to_string())
+    }
+
+    /// Defines a global for each key of the JSON object `json`, replacing
+    /// globals of the same name. Nothing is defined if any of it fails.
+    pub fn import_globals_json(&mut self, json: &str) -> Result<(), JBreadErrors> {
+        let Globals(globals) = serde_json::from_str(json).map_err(json_error)?;
+        let mut environment = self.environment.borrow_mut();
+        for (name, value) in globals {
+            environment.define(name, value);
+        }
+        Ok(())
+    }
+}
+
+impl EnvSnapshot {
+    /// The snapshot as JSON, leaving out the values without a JSON form.
+    pub fn to_json(&self) -> Result<String, JBreadErrors> {
+        Ok(self.to_json_value()?.to_string())
+    }
+
+    /// Reads a snapshot [`to_json`](Self::to_json) wrote.
+    pub fn from_json(json: &str) -> Result<Self, JBreadErrors> {
+        serde_json::from_str(json).map_err(json_error)
+    }
+
+    fn to_json_value(&self) -> Result<Json, JBreadErrors> {
+        let mut object = Map::new();
+        object.insert(
+            "variables".to_string(),
+            Json::Object(to_object(&self.variables)?),
+        );
+        object.insert(
+            "constants".to_string(),
+            Json::Object(to_object(&self.constants)?),
+        );
+        Ok(Json::Object(object))
+    }
+}
+
+impl Serialize for EnvSnapshot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_json_value()
+            .map_err(ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EnvSnapshot {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = SnapshotFields::deserialize(deserializer)?;
+        Ok(EnvSnapshot {
+            variables: fields.variables.0,
+            constants: fields.constants.0,
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SnapshotFields {
+    #[serde(default)]
+    variables: Globals,
+    #[serde(default)]
+    constants: Globals,
+}
+
+fn json_error(err: serde_json::Error) -> JBreadErrors {
+    JBreadErrors::ParseError(Error::new(
+        ErrorKind::InvalidJson,
+        err.line() as u32,
+        err.column() as u32,
+        "JSON".to_string(),
+        err.to_string(),
+    ))
+}
+
+/// `bindings` as a JSON object, skipping what isn't data.
+fn to_object(bindings: &[(String, Value)]) -> Result<Map<String, Json>, JBreadErrors> {
+    let mut object = Map::new();
+    for (name, value) in bindings {
+        if !is_data(value) {
+            continue;
+        }
+        let value =
+            to_json(value, &mut Vec::new()).map_err(|message| export_error(name, message))?;
+        object.insert(name.clone(), value);
+    }
+    Ok(object)
+}
+
+fn is_data(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::Nil
+            | Value::String(_)
+            | Value::Number(_)
+            | Value::Bool(_)
+            | Value::NaN
+            | Value::List(_)
+    )
+}
+
+fn to_json(value: &Value, visited: &mut Vec<*const ListCell>) -> Result<Json, String> {
+    let value = match value {
+        Value::Nil => Json::Null,
+        Value::String(string) => Json::String(string.clone()),
+        Value::Bool(boolean) => Json::Bool(*boolean),
+        Value::Number(number) => match Number::from_f64(*number) {
+            Some(number) => Json::Number(number),
+            None if number.is_nan() => Json::String("NaN".to_string()),
+            None if *number > 0.0 => Json::String("inf".to_string()),
+            None => Json::String("-inf".to_string()),
+        },
+        Value::NaN => Json::String("NaN".to_string()),
+        Value::List(list) => {
+            if visited.contains(&Rc::as_ptr(list)) {
+                return Err("A list holding itself has no JSON form".to_string());
+            }
+            visited.push(Rc::as_ptr(list));
+            let elements = list
+                .borrow()
+                .iter()
+                .map(|element| to_json(element, visited))
+                .collect::<Result<_, _>>()?;
+            visited.pop();
+            Json::Array(elements)
+        }
+        other => return Err(format!("A {} has no JSON form", other.type_name())),
+    };
+    Ok(value)
+}
+
+fn export_error(name: &str, message: String) -> JBreadErrors {
+    JBreadErrors::RunTimeException(Error::new(
+        ErrorKind::Unsupported,
+        0,
+        0,
+        name.to_string(),
+        message,
+    ))
+}
+
+fn is_identifier(name: &str) -> bool {
+    let (tokens, errors) = Scanner::new(name).scan();
+    errors.is_empty()
+        && matches!(tokens.as_slice(), [identifier, _] if identifier.token_type == TokenTypes::Identifier)
+}
+
+/// The globals an imported object holds, in the order given.
+#[derive(Default)]
+struct Globals(Vec<(String, Value)>);
+
+impl<'de> Deserialize<'de> for Globals {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(GlobalsVisitor)
+    }
+}
+
+struct GlobalsVisitor;
+
+impl<'de> Visitor<'de> for GlobalsVisitor {
+    type Value = Globals;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an object of globals")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Globals, A::Error> {
+        let mut globals = Vec::new();
+        while let Some(name) = map.next_key::<String>()? {
+            if !is_identifier(&name) {
+                return Err(de::Error::custom(format!(
+                    "{:?} is not a variable name",
+                    name
+                )));
+            }
+            let Imported(value) = map.next_value()?;
+            globals.push((name, value));
+        }
+        Ok(Globals(globals))
+    }
+}
+
+struct Imported(Value);
+
+impl<'de> Deserialize<'de> for Imported {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ImportedVisitor)
+    }
+}
+
+struct ImportedVisitor;
+
+impl<'de> Visitor<'de> for ImportedVisitor {
+    type Value = Imported;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "null, a boolean, a number, a string or an array")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Imported, E> {
+        Ok(Imported(Value::Nil))
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Imported, E> {
+        Ok(Imported(Value::Bool(value)))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Imported, E> {
+        self.visit_f64(value as f64)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Imported, E> {
+        self.visit_f64(value as f64)
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Imported, E> {
+        Ok(Imported(Value::Number(value)))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Imported, E> {
+        Ok(Imported(Value::String(value.to_string())))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Imported, A::Error> {
+        let mut elements = Vec::new();
+        while let Some(Imported(element)) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(Imported(Value::list(elements)))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, _: A) -> Result<Imported, A::Error> {
+        Err(de::Error::custom(
+            "Objects aren't supported, only null, booleans, numbers, strings and arrays",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::{interpreter::environment::Environment, Token};
+
+    fn lookup(interpreter: &Interpreter, name: &str) -> Value {
+        let token = Token::new(TokenTypes::Identifier, name.to_string(), None, 1);
+        interpreter.environment.borrow().get(&token).unwrap()
+    }
+
+    fn run(source: &str) -> Interpreter {
+        let mut interpreter = Interpreter::default();
+        let tokens = Scanner::new(source).scan().0;
+        let ast = crate::parser::Parser::new(&tokens).parse().unwrap();
+        interpreter.interpret(&ast).unwrap();
+        interpreter
+    }
+
+    #[test]
+    fn test_export_globals() {
+        let interpreter = run(
+            "var s = \"hi\"; var n = 1.5; var b = true; var z; var ratio = 0/0;
+             var up = 1/0; var down = -1/0; var l = [1, [\"a\", nil]];
+             fun f() {} class C {}",
+        );
+        assert_eq!(
+            interpreter.export_globals_json().unwrap(),
+            r#"{"b":true,"down":"-inf","l":[1.0,["a",null]],"n":1.5,"ratio":"NaN","s":"hi","up":"inf","z":null}"#
+        );
+    }
+
+    #[test]
+    fn test_export_globals_unsupported() {
+        let interpreter = run("var l = [1]; l[0] = l;");
+        let error = interpreter.export_globals_json().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Unsupported);
+        assert!(error.to_string().contains("A list holding itself"));
+
+        let interpreter = run("fun f() {} var l = [f];");
+        let error = interpreter.export_globals_json().unwrap_err();
+        assert!(error.to_string().contains("has no JSON form"));
+    }
+
+    #[test]
+    fn test_globals_round_trip() {
+        let exported = run("var s = \"hi\"; var n = -2; var b = false; var z; var ratio = 0/0;")
+            .export_globals_json()
+            .unwrap();
+        let mut interpreter = Interpreter::default();
+        interpreter.import_globals_json(&exported).unwrap();
+        assert_eq!(lookup(&interpreter, "s"), Value::String("hi".to_string()));
+        assert_eq!(lookup(&interpreter, "n"), Value::Number(-2.0));
+        assert_eq!(lookup(&interpreter, "b"), Value::Bool(false));
+        // Non-finite numbers come back as the strings they were written as.
+        assert_eq!(
+            lookup(&interpreter, "ratio"),
+            Value::String("NaN".to_string())
+        );
+        assert_eq!(interpreter.export_globals_json().unwrap(), exported);
+    }
+
+    #[test]
+    fn test_import_globals_overwrites() {
+        let mut interpreter = run("var kept = 1; var replaced = 2; const fixed = 3;");
+        interpreter
+            .import_globals_json(r#"{"replaced": "new", "added": [true], "fixed": 4}"#)
+            .unwrap();
+        assert_eq!(lookup(&interpreter, "kept"), Value::Number(1.0));
+        assert_eq!(
+            lookup(&interpreter, "replaced"),
+            Value::String("new".to_string())
+        );
+        assert_eq!(lookup(&interpreter, "fixed"), Value::Number(4.0));
+        assert_eq!(
+            interpreter.export_globals_json().unwrap(),
+            r#"{"added":[true],"fixed":4.0,"kept":1.0,"replaced":"new"}"#
+        );
+    }
+
+    #[test]
+    fn test_snapshot_json_round_trip() {
+        let interpreter = run(
+            "var a = 1; var l = [\"x\", nil]; var z; const c = 0/0; fun f() {}
+             { var a = 2; var inner = a; }",
+        );
+        let environment = Rc::new(RefCell::new(Environment::new(
+            interpreter.environment.clone(),
+        )));
+        environment.borrow_mut().define("a", Value::Number(2.0));
+        environment.borrow_mut().define("inner", Value::Bool(true));
+
+        let json = environment.borrow().snapshot().to_json().unwrap();
+        assert_eq!(
+            json,
+            r#"{"constants":{"c":"NaN"},"variables":{"a":2.0,"inner":true,"l":["x",null],"z":null}}"#
+        );
+        let snapshot = EnvSnapshot::from_json(&json).unwrap();
+        assert_eq!(serde_json::to_string(&snapshot).unwrap(), json);
+
+        let mut restored = run("var a = 10; var b = 20;");
+        restored.environment.borrow_mut().restore(snapshot);
+        let stmts = crate::parser::Parser::new(
+            &Scanner::new("var sum = a + b; var first = l[0];").scan().0,
+        )
+        .parse()
+        .unwrap();
+        restored.interpret(&stmts).unwrap();
+        assert_eq!(lookup(&restored, "sum"), Value::Number(22.0));
+        assert_eq!(lookup(&restored, "first"), Value::String("x".to_string()));
+        assert!(restored
+            .environment
+            .borrow()
+            .get(&Token::new(
+                TokenTypes::Identifier,
+                "f".to_string(),
+                None,
+                1
+            ))
+            .is_err());
+
+        for json in [r#"{"globals": {}}"#, r#"{"variables": {"var": 1}}"#] {
+            let error = EnvSnapshot::from_json(json).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::InvalidJson, "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_import_globals_errors() {
+        for (json, line, column, message) in [
+            ("{\"a\": 1,\n \"b\": }", 2, 7, "expected value"),
+            ("[1, 2]", 1, 1, "expected an object of globals"),
+            (
+                "{\"a\": 1,\n \"b\": {\"c\": 2}}",
+                2,
+                7,
+                "Objects aren't supported",
+            ),
+            ("{\"a\": [1, {}]}", 1, 12, "Objects aren't supported"),
+            (
+                "{\"not a name\": 1}",
+                1,
+                13,
+                "\"not a name\" is not a variable name",
+            ),
+            ("{\"var\": 1}", 1, 6, "\"var\" is not a variable name"),
+        ] {
+            let mut interpreter = Interpreter::default();
+            let error = interpreter.import_globals_json(json).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::InvalidJson, "{}", json);
+            assert!(matches!(error, JBreadErrors::ParseError(_)), "{}", json);
+            let error = error.to_string();
+            assert!(error.contains(message), "{}: {}", json, error);
+            assert!(
+                error.contains(&format!("at {}:{} in JSON", line, column)),
+                "{}",
+                error
+            );
+        }
+
+        // Nothing before the failure is defined.
+        let mut interpreter = Interpreter::default();
+        assert!(interpreter
+            .import_globals_json(r#"{"a": 1, "b": {}}"#)
+            .is_err());
+        assert_eq!(interpreter.export_globals_json().unwrap(), "{}");
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::interpreter::DEFAULT_SEED;
+
+/// Services provided by the application embedding the interpreter.
+pub trait HostInterface {
+    /// Seed for the random natives when a script hasn't called `set_seed`.
+    fn random_seed(&self) -> u64;
+
+    /// Shows a line printed by the script, stdout unless overridden.
+    fn print(&self, line: &str) {
+        println!("{}", line);
+    }
+
+    /// Shows text printed by the script without ending the line, e.g. a
+    /// prompt, stdout unless overridden.
+    fn write(&self, text: &str) {
+        print!("{}", text);
+        let _ = io::stdout().flush();
+    }
+}
+
+/// Default host, seeding from the system clock.
+#[derive(Debug, Default)]
+pub struct SystemHost;
+
+impl HostInterface for SystemHost {
+    fn random_seed(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(DEFAULT_SEED, |elapsed| elapsed.as_nanos() as u64)
+    }
+}
+
+/// Output kept in memory, e.g. for [`Interpreter::set_output`]. Clones
+/// share the buffer, so one can be read while an interpreter writes to
+/// another.
+///
+/// [`Interpreter::set_output`]: crate::Interpreter::set_output
+#[derive(Debug, Clone, Default)]
+pub struct OutputBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl OutputBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// What was written so far, invalid UTF-8 replaced.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    /// What was written so far, leaving the buffer empty.
+    pub fn take(&self) -> String {
+        String::from_utf8_lossy(&self.0.take()).into_owned()
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
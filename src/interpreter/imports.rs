@@ -0,0 +1,69 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// Which files `import` statements have run and which are still running.
+#[derive(Debug, Default)]
+pub(crate) struct Imports {
+    /// The file being run, whose directory relative paths start from. The
+    /// working directory is used without one, e.g. in the REPL.
+    script: Option<PathBuf>,
+    /// Canonical paths of the imports still running, innermost last.
+    running: Vec<PathBuf>,
+    /// Canonical paths of the imports that ran, importing them again does
+    /// nothing.
+    done: HashSet<PathBuf>,
+}
+
+impl Imports {
+    pub(crate) fn set_script(&mut self, path: &Path) {
+        self.script = Some(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+    }
+
+    /// Where `path` points to from the file importing it.
+    pub(crate) fn resolve(&self, path: &str) -> PathBuf {
+        match self.running.last().or(self.script.as_ref()) {
+            Some(importer) => importer.parent().unwrap_or(Path::new("")).join(path),
+            None => PathBuf::from(path),
+        }
+    }
+
+    /// Starts running the file at the canonical `path`, `Ok(false)` when it
+    /// already ran. Fails with the chain of files leading back to it when it
+    /// is still running.
+    pub(crate) fn begin(&mut self, path: PathBuf) -> Result<bool, String> {
+        if self.done.contains(&path) {
+            return Ok(false);
+        }
+        let chain: Vec<&PathBuf> = self.script.iter().chain(self.running.iter()).collect();
+        if let Some(start) = chain.iter().position(|running| **running == path) {
+            let names: Vec<String> = chain[start..]
+                .iter()
+                .copied()
+                .chain([&path])
+                .map(|path| file_name(path))
+                .collect();
+            return Err(names.join(" -> "));
+        }
+        self.running.push(path);
+        Ok(true)
+    }
+
+    /// Stops running the innermost import, which only counts as done when it
+    /// ran to completion.
+    pub(crate) fn end(&mut self, completed: bool) {
+        if let Some(path) = self.running.pop() {
+            if completed {
+                self.done.insert(path);
+            }
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
@@ -1,530 +1,3093 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{RefCell, RefMut},
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    path::Path,
+    rc::Rc,
+};
 
 use crate::{
-    ast::{Expr, Literal, Stmt, VisitorExpr, VisitorStmt},
-    errors::{self, JBreadErrors, JBreadResult},
-    interpreter::environment::Environment,
-    AstNode, AstStmt, Literal as LiteralEnum, Token, TokenTypes,
+    ast::{Expr, Stmt, VisitorExpr, VisitorStmt},
+    errors::{self, CallFrame, Error, ErrorKind, JBreadErrors, JBreadResult},
+    interpreter::{
+        environment::{Environment, Undo},
+        imports::Imports,
+        native,
+        random::RandomSource,
+        Arity, Class, ExecutionEngine, Function, HostInterface, Instance, InterpreterOptions,
+        InterpreterStats, ListCell, MapKey, SystemHost, TraceControl, Tracer, UndeclaredAssignment,
+        Value, INITIALIZER,
+    },
+    read_source_file,
+    resolver::ExprId,
+    AstNode, AstPrinter, AstStmt, LiteralValue, Parser, Resolver, Scanner, ScannerOptions,
+    SourceRef, Token, TokenTypes,
 };
 
+/// How deeply evaluating expressions and executing statements may nest,
+/// function calls included, before the program fails. The stack grows onto
+/// the heap as deeper levels need it, so this only bounds how much memory
+/// runaway nesting takes.
+pub const MAX_EVALUATION_DEPTH: usize = 4096;
+
+/// How deeply calls may nest before the program fails with a stack
+/// overflow. A call takes at least two levels of [`MAX_EVALUATION_DEPTH`]
+/// and usually more, so this is what simple runaway recursion runs into.
+pub const MAX_CALL_DEPTH: usize = MAX_EVALUATION_DEPTH / 4;
+
 pub struct Interpreter {
     // pub globals: HashMap<String, Value>,
     // pub locals: HashMap<String, Value>,
     pub environment: Rc<RefCell<Environment>>,
+    /// Scope distances from the resolver. Variables without an entry are
+    /// looked up dynamically.
+    locals: Rc<HashMap<ExprId, usize>>,
+    /// Set by a `return` statement, the statements enclosing it stop
+    /// executing until the call it returns from picks the value up.
+    returning: Option<Value>,
+    /// Only collected once enabled.
+    stats: Option<Rc<RefCell<InterpreterStats>>>,
+    random: Rc<RefCell<RandomSource>>,
+    host: Rc<dyn HostInterface>,
+    /// What `input` reads lines from, stdin when not set.
+    input: Option<Rc<RefCell<dyn BufRead>>>,
+    /// What `print` writes to, the host when not set.
+    output: Option<Rc<RefCell<dyn Write>>>,
+    /// What errors and warnings are reported to, stderr when not set.
+    error_output: Option<Rc<RefCell<dyn Write>>>,
+    engine: ExecutionEngine,
+    undeclared_assignment: UndeclaredAssignment,
+    /// How deep evaluation is.
+    depth: usize,
+    max_depth: usize,
+    /// The calls being run, innermost last.
+    frames: Vec<CallFrame>,
+    max_call_depth: usize,
+    /// Statements executed and expressions evaluated in the current run.
+    steps: u64,
+    max_steps: Option<u64>,
+    imports: Imports,
+    tracer: Option<Box<dyn Tracer>>,
+    /// Bindings as they were before each write of a transactional run.
+    journal: Option<Vec<Undo>>,
 }
 
 impl Default for Interpreter {
     fn default() -> Self {
+        Self::with_host(SystemHost)
+    }
+}
+
+impl Interpreter {
+    pub fn with_host(host: impl HostInterface + 'static) -> Self {
+        let host: Rc<dyn HostInterface> = Rc::new(host);
         Self {
             environment: Rc::new(RefCell::new(Environment::default())),
+            locals: Rc::default(),
+            returning: None,
+            stats: None,
+            random: Rc::new(RefCell::new(RandomSource::new(host.clone()))),
+            host,
+            input: None,
+            output: None,
+            error_output: None,
+            engine: ExecutionEngine::default(),
+            undeclared_assignment: UndeclaredAssignment::default(),
+            depth: 0,
+            max_depth: MAX_EVALUATION_DEPTH,
+            frames: Vec::new(),
+            max_call_depth: MAX_CALL_DEPTH,
+            steps: 0,
+            max_steps: None,
+            imports: Imports::default(),
+            tracer: None,
+            journal: None,
         }
     }
-}
 
-impl Interpreter {
-    fn new(environment: Rc<RefCell<Environment>>) -> Self {
-        Self { environment }
+    pub fn with_options(options: InterpreterOptions) -> Self {
+        let mut interpreter = Self::default();
+        interpreter.set_options(options);
+        interpreter
+    }
+
+    /// Applies `options`, e.g. to an interpreter made
+    /// [`with_host`](Self::with_host).
+    pub fn set_options(&mut self, options: InterpreterOptions) {
+        self.engine = options.engine;
+        self.undeclared_assignment = options.undeclared_assignment;
+    }
+
+    /// An interpreter treating assignments to undeclared variables as
+    /// `mode` says, failing on them by default.
+    pub fn with_undeclared_assignment(mut self, mode: UndeclaredAssignment) -> Self {
+        self.undeclared_assignment = mode;
+        self
+    }
+
+    /// In deterministic mode the random natives start from a fixed seed
+    /// instead of asking the host for one.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.random.borrow_mut().set_deterministic(deterministic);
+    }
+
+    /// Fails past `max_depth` levels of evaluation instead of
+    /// [`MAX_EVALUATION_DEPTH`].
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Fails with [`ErrorKind::StackOverflow`] past `max_call_depth` nested
+    /// calls instead of [`MAX_CALL_DEPTH`]. Calls still count towards the
+    /// evaluation depth, which a higher limit may run into first.
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        self.max_call_depth = max_call_depth;
+    }
+
+    /// The calls being run, innermost last, e.g. for a native or a tracer
+    /// to tell where it was called from.
+    pub fn call_stack(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    /// An interpreter failing with [`ErrorKind::BudgetExceeded`] once a run
+    /// takes more than `max_steps` steps. Executing a statement and
+    /// evaluating an expression are a step each, as is running a bytecode
+    /// instruction.
+    pub fn with_step_limit(mut self, max_steps: u64) -> Self {
+        self.set_step_limit(Some(max_steps));
+        self
+    }
+
+    /// Limits the steps of each run, see
+    /// [`with_step_limit`](Self::with_step_limit). `None` lifts the limit.
+    pub fn set_step_limit(&mut self, max_steps: Option<u64>) {
+        self.max_steps = max_steps;
+    }
+
+    /// Calls `tracer` back as programs run, see [`Tracer`]. Only the
+    /// tree-walking engine does.
+    pub fn set_tracer(&mut self, tracer: impl Tracer + 'static) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// Uninstalls the tracer, handing it back.
+    pub fn take_tracer(&mut self) -> Option<Box<dyn Tracer>> {
+        self.tracer.take()
+    }
+
+    /// Reads the lines `input` returns from `input` instead of stdin.
+    pub fn set_input(&mut self, input: impl BufRead + 'static) {
+        self.input = Some(Rc::new(RefCell::new(input)));
+    }
+
+    /// Writes what the program prints to `output` instead of handing it to
+    /// the host.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = Some(Rc::new(RefCell::new(output)));
+    }
+
+    /// An interpreter writing what programs print to `output`, see
+    /// [`set_output`](Self::set_output).
+    pub fn with_output(mut self, output: impl Write + 'static) -> Self {
+        self.set_output(output);
+        self
+    }
+
+    /// Reports errors, warnings and traces to `output` instead of stderr.
+    pub fn set_error_output(&mut self, output: impl Write + 'static) {
+        self.error_output = Some(Rc::new(RefCell::new(output)));
+    }
+
+    /// An interpreter reporting to `output`, see
+    /// [`set_error_output`](Self::set_error_output).
+    pub fn with_error_output(mut self, output: impl Write + 'static) -> Self {
+        self.set_error_output(output);
+        self
+    }
+
+    /// The next line of input without its line ending, `None` at the end.
+    pub(crate) fn read_line(&self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        let read = match &self.input {
+            Some(input) => input.borrow_mut().read_line(&mut line)?,
+            None => io::stdin().lock().read_line(&mut line)?,
+        };
+        if read == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    pub(crate) fn random(&self) -> RefMut<'_, RandomSource> {
+        self.random.borrow_mut()
+    }
+
+    /// Uses the resolved scope distances for the next program interpreted,
+    /// which must be the one `locals` was resolved from.
+    pub fn set_locals(&mut self, locals: HashMap<ExprId, usize>) {
+        self.locals = Rc::new(locals);
+    }
+
+    /// Resolves `import` paths from the directory of `path`, the file being
+    /// run, instead of the working directory.
+    pub fn set_script_path(&mut self, path: impl AsRef<Path>) {
+        self.imports.set_script(path.as_ref());
+    }
+
+    /// Starts collecting [`InterpreterStats`].
+    pub fn enable_stats(&mut self) {
+        self.stats.get_or_insert_with(Rc::default);
+    }
+
+    pub fn stats(&self) -> Option<InterpreterStats> {
+        self.stats.as_ref().map(|stats| stats.borrow().clone())
+    }
+
+    /// Estimated bytes held by the current environment chain, e.g. the
+    /// globals left over between runs.
+    pub fn memory_estimate(&self) -> usize {
+        self.environment
+            .borrow()
+            .fold_chain(0, |bytes, environment| {
+                bytes + environment.estimated_bytes()
+            })
+    }
+
+    /// Drops every global, zeroes the stats and reseeds the random stream, so the interpreter can be
+    /// reused for an unrelated program.
+    pub fn reset(&mut self) {
+        self.environment = Rc::new(RefCell::new(Environment::default()));
+        self.locals = Rc::default();
+        self.returning = None;
+        self.imports = Imports::default();
+        self.random.borrow_mut().reset();
+        if let Some(stats) = &self.stats {
+            *stats.borrow_mut() = InterpreterStats::default();
+        }
+    }
+
+    fn look_up_variable(&self, name: &Token, id: ExprId) -> JBreadResult<Value> {
+        match self.locals.get(&id) {
+            Some(distance) => {
+                Environment::reading(&self.environment, &name.lexeme)?.get_at(*distance, name)
+            }
+            None => self.look_up_global(name),
+        }
+    }
+
+    /// Looks `name` up dynamically through the current environment chain.
+    pub(crate) fn look_up_global(&self, name: &Token) -> JBreadResult<Value> {
+        // Natives only apply once no global of the same name exists.
+        Environment::reading(&self.environment, &name.lexeme)?
+            .get(name)
+            .or_else(|error| native(&name.lexeme).map(Value::Native).ok_or(error))
+    }
+
+    /// Assigns `name`, resolved as the expression `id`. The value is
+    /// evaluated beforehand, so the environment is only borrowed while it's
+    /// written.
+    fn assign_variable(&mut self, name: &Token, id: ExprId, value: Value) -> JBreadResult<()> {
+        if self.undeclared_assignment == UndeclaredAssignment::ImplicitGlobal
+            && !self.locals.contains_key(&id)
+            && self.declare_implicitly(name, &value)?
+        {
+            return Ok(());
+        }
+        if self.journal.is_some() {
+            self.journal_assignment(name, self.locals.get(&id).copied())?;
+        }
+        let new = self.tracer.as_ref().map(|_| value.clone());
+        let old = {
+            let mut environment = Environment::writing(&self.environment, &name.lexeme)?;
+            match self.locals.get(&id) {
+                Some(distance) => environment.assign_at(*distance, name, value)?,
+                None => environment.assign(name, value)?,
+            }
+        };
+        if let (Some(tracer), Some(new)) = (&mut self.tracer, new) {
+            tracer.on_var_write(&name.lexeme, Some(&old), &new);
+        }
+        Ok(())
+    }
+
+    /// Assigns a global the bytecode compiled, which the resolver didn't
+    /// see.
+    #[cfg(feature = "bytecode")]
+    pub(crate) fn assign_global(&mut self, name: &Token, value: Value) -> JBreadResult<()> {
+        if self.undeclared_assignment == UndeclaredAssignment::ImplicitGlobal
+            && self.declare_implicitly(name, &value)?
+        {
+            return Ok(());
+        }
+        if self.journal.is_some() {
+            self.journal_assignment(name, None)?;
+        }
+        Environment::writing(&self.environment, &name.lexeme)?.assign(name, value)?;
+        Ok(())
+    }
+
+    /// Defines `name` as a global holding `value` when nothing visible
+    /// declares it, returning whether it did.
+    #[inline(never)]
+    fn declare_implicitly(&mut self, name: &Token, value: &Value) -> JBreadResult<bool> {
+        if Environment::holding(&self.environment, name)?.is_some() {
+            return Ok(false);
+        }
+        let globals = Environment::globals(&self.environment);
+        self.define(&globals, &name.lexeme, value.clone())?;
+        Ok(true)
+    }
+
+    /// Records the binding `name` is about to assign, `distance` scopes away
+    /// if resolved. Nothing is recorded for an undefined name, which the
+    /// assignment fails on.
+    #[inline(never)]
+    fn journal_assignment(&mut self, name: &Token, distance: Option<usize>) -> JBreadResult<()> {
+        let environment = match distance {
+            Some(0) => Some(self.environment.clone()),
+            Some(distance) => self.environment.borrow().ancestor(distance),
+            None => Environment::holding(&self.environment, name)?,
+        };
+        if let (Some(environment), Some(journal)) = (environment, &mut self.journal) {
+            journal.push(Undo::of(environment, &name.lexeme)?);
+        }
+        Ok(())
+    }
+
+    /// Makes an instance of `class`, running its initializer if it has one.
+    fn construct(&mut self, class: Rc<Class>, arguments: Vec<Value>) -> JBreadResult<Value> {
+        let instance = Rc::new(Instance::new(class.clone()));
+        if let Some(init) = class.find_method(INITIALIZER) {
+            self.call_function(&init.bind(instance.clone()), arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+
+    /// Checks that `callee` can be called with `count` arguments at
+    /// `paren`, then pushes the frame for the call, failing instead past the
+    /// maximum call depth. Natives get no frame, their errors being at the
+    /// call already, which the returned `false` tells. Kept out of
+    /// [`visit_expr_call`](VisitorExpr::visit_expr_call), whose frame every
+    /// level of recursion takes.
+    #[inline(never)]
+    fn enter_call(&mut self, callee: &Value, count: usize, paren: &Token) -> JBreadResult<bool> {
+        let arity = match callee {
+            Value::Function(function) => Arity::exact(function.arity()),
+            Value::Class(class) => Arity::exact(class.arity()),
+            Value::Native(native) => native.arity,
+            _ => {
+                return Err(self.error(
+                    paren,
+                    ErrorKind::NotCallable,
+                    "Can only call functions and classes",
+                ))
+            }
+        };
+        if !arity.accepts(count) {
+            return Err(self.error(
+                paren,
+                ErrorKind::ArityMismatch,
+                &format!("Expected {} arguments but got {}", arity, count),
+            ));
+        }
+
+        let function = match callee {
+            Value::Function(function) => function.name(),
+            Value::Class(class) => class.name(),
+            _ => return Ok(false),
+        };
+        if self.frames.len() >= self.max_call_depth {
+            return Err(self.error(
+                paren,
+                ErrorKind::StackOverflow,
+                "Stack overflow: maximum call depth exceeded",
+            ));
+        }
+        self.frames.push(CallFrame {
+            function: function.to_string(),
+            line: paren.line(),
+        });
+        Ok(true)
+    }
+
+    /// Pops the frame [`enter_call`](Self::enter_call) pushed, if it did,
+    /// for the call that gave `result`. This happens whether the call
+    /// returned or failed, a runtime error getting the frame added to its
+    /// backtrace.
+    #[inline(never)]
+    fn leave_frame(
+        &mut self,
+        pushed: bool,
+        mut result: JBreadResult<Value>,
+    ) -> JBreadResult<Value> {
+        if !pushed {
+            return result;
+        }
+        if let (Some(frame), Err(JBreadErrors::RunTimeException(error))) =
+            (self.frames.pop(), &mut result)
+        {
+            error.push_frame(&frame.function, frame.line);
+        }
+        result
+    }
+
+    fn call_function(&mut self, function: &Function, arguments: Vec<Value>) -> JBreadResult<Value> {
+        let environment = self.call_environment(function, arguments)?;
+        self.execute_block(&function.declaration.body, environment)?;
+        let value = self.returning.take().unwrap_or_default();
+        if function.is_initializer {
+            return Ok(function
+                .closure
+                .borrow()
+                .get_local("this")
+                .unwrap_or_default());
+        }
+        Ok(value)
+    }
+
+    /// The environment a call of `function` runs in, its parameters bound
+    /// to `arguments`. Kept out of [`call_function`](Self::call_function),
+    /// whose frame every level of recursion takes.
+    fn call_environment(
+        &mut self,
+        function: &Function,
+        arguments: Vec<Value>,
+    ) -> JBreadResult<Rc<RefCell<Environment>>> {
+        // The call runs in a child of the environment the function was
+        // declared in, not the caller's.
+        let environment = self.new_environment(function.closure.clone());
+        for (param, argument) in function.declaration.params.iter().zip(arguments) {
+            self.define(&environment, &param.lexeme, argument)?;
+        }
+        Ok(environment)
+    }
+
+    fn instance(&self, value: Value, name: &Token) -> JBreadResult<Rc<Instance>> {
+        match value {
+            Value::Instance(instance) => Ok(instance),
+            other => Err(self.type_mismatch(
+                name,
+                "instance",
+                other.type_name(),
+                "Only instances have properties",
+            )),
+        }
+    }
+
+    /// `expected` describes what could have been indexed instead.
+    fn list(&self, value: Value, bracket: &Token, expected: &str) -> JBreadResult<Rc<ListCell>> {
+        match value {
+            Value::List(list) => Ok(list),
+            other => Err(self.type_mismatch(
+                bracket,
+                expected,
+                other.type_name(),
+                &format!("Only a {} can be indexed here", expected),
+            )),
+        }
+    }
+
+    /// The key `key` stands for in a map, NaN and values of other types
+    /// being no key.
+    fn map_key(&self, key: &Value, bracket: &Token) -> JBreadResult<MapKey> {
+        match MapKey::from_value(key) {
+            Some(key) => Ok(key),
+            None if key.to_number().is_ok() => {
+                Err(self.error(bracket, ErrorKind::InvalidIndex, "NaN cannot be a map key"))
+            }
+            None => Err(self.type_mismatch(
+                bracket,
+                "string, number or boolean",
+                key.type_name(),
+                "Map key must be a string, a number or a boolean",
+            )),
+        }
+    }
+
+    /// Checks that `index` is a whole number within `0..len`, `container`
+    /// naming what's indexed in the error.
+    fn checked_index(
+        &self,
+        index: Value,
+        len: usize,
+        bracket: &Token,
+        container: &str,
+    ) -> JBreadResult<usize> {
+        let index = match index {
+            Value::Number(index) => index,
+            other => {
+                return Err(self.type_mismatch(
+                    bracket,
+                    "number",
+                    other.type_name(),
+                    &format!("{} index must be a number", capitalized(container)),
+                ))
+            }
+        };
+        if index.fract() != 0.0 {
+            return Err(self.error(
+                bracket,
+                ErrorKind::InvalidIndex,
+                &format!("{} index must be a whole number", capitalized(container)),
+            ));
+        }
+        // Negative indices are rejected rather than counted from the end.
+        if index < 0.0 || index >= len as f64 {
+            return Err(self.error(
+                bracket,
+                ErrorKind::IndexOutOfBounds,
+                &format!(
+                    "Index {} out of bounds for {} of length {}",
+                    index, container, len
+                ),
+            ));
+        }
+        Ok(index as usize)
+    }
+
+    /// Operands can't be nil, which `operator` is blamed for, except for
+    /// `==` and `!=`: nil only equals nil.
+    pub(crate) fn operand(
+        &self,
+        value: Value,
+        operator: &Token,
+        message: &str,
+    ) -> JBreadResult<Value> {
+        match value {
+            Value::Nil
+                if !matches!(
+                    operator.token_type,
+                    TokenTypes::EqualEqual | TokenTypes::BangEqual
+                ) =>
+            {
+                Err(self.type_mismatch(operator, "value", "nil", message))
+            }
+            value => Ok(value),
+        }
+    }
+
+    /// Applies a binary `operator` to two evaluated operands. Operands are
+    /// only converted as the operator needs, so the hot arithmetic path
+    /// neither clones them nor builds errors it doesn't return.
+    pub(crate) fn binary(
+        &self,
+        operator: &Token,
+        left: Value,
+        right: Value,
+    ) -> JBreadResult<Value> {
+        let value = match operator.token_type {
+            // For number
+            TokenTypes::Minus => {
+                let (left, right) = self.numbers(operator, &left, &right)?;
+                Value::from_number(left - right)
+            }
+            TokenTypes::Star => {
+                let (left, right) = self.numbers(operator, &left, &right)?;
+                Value::from_number(left * right)
+            }
+            TokenTypes::StarStar => {
+                let (left, right) = self.numbers(operator, &left, &right)?;
+                Value::from_number(left.powf(right))
+            }
+            TokenTypes::Greater
+            | TokenTypes::GreaterEqual
+            | TokenTypes::Less
+            | TokenTypes::LessEqual => self.comparison(operator, &left, &right)?,
+            // For integers
+            TokenTypes::Ampersand => {
+                Self::from_integer(self.integer(&left, operator)? & self.integer(&right, operator)?)
+            }
+            TokenTypes::Pipe => {
+                Self::from_integer(self.integer(&left, operator)? | self.integer(&right, operator)?)
+            }
+            TokenTypes::Caret => {
+                Self::from_integer(self.integer(&left, operator)? ^ self.integer(&right, operator)?)
+            }
+            TokenTypes::LessLess | TokenTypes::GreaterGreater => {
+                let value = self.integer(&left, operator)?;
+                let amount = self.integer(&right, operator)?;
+                if !(0..64).contains(&amount) {
+                    return Err(self.error(
+                        operator,
+                        ErrorKind::InvalidOperand,
+                        &format!("Cannot shift by {}, expected 0 to 63", amount),
+                    ));
+                }
+                Self::from_integer(match operator.token_type {
+                    TokenTypes::LessLess => value << amount,
+                    _ => value >> amount,
+                })
+            }
+            // For all types
+            TokenTypes::BangEqual => Value::Bool(!left.equals(&right)),
+            TokenTypes::EqualEqual => Value::Bool(left.equals(&right)),
+            // Division follows IEEE rather than failing: dividing by zero
+            // gives `inf` or `-inf` and `0 / 0` gives NaN. Other operators
+            // dividing numbers should do the same.
+            TokenTypes::Slash => match (left.to_number(), right.to_number()) {
+                (Ok(left), Ok(right)) => Value::from_number(left / right),
+                _ => {
+                    let found = match left {
+                        Value::Number(_) | Value::NaN => right.type_name(),
+                        _ => left.type_name(),
+                    };
+                    return Err(self.type_mismatch(
+                        operator,
+                        "number",
+                        found,
+                        &format!("Cannot divide non-number: {}", operands(&left, &right)),
+                    ));
+                }
+            },
+            // For addition and string concat
+            TokenTypes::Plus => match (left, right) {
+                (Value::String(mut left), Value::String(right)) => {
+                    left.push_str(&right);
+                    Value::String(left)
+                }
+                (
+                    left @ (Value::Number(_) | Value::NaN),
+                    right @ (Value::Number(_) | Value::NaN),
+                ) => {
+                    let (left, right) = self.numbers(operator, &left, &right)?;
+                    Value::from_number(left + right)
+                }
+                (Value::List(left), Value::List(right)) => {
+                    let mut elements = left.borrow().clone();
+                    elements.extend(right.borrow().iter().cloned());
+                    Value::list(elements)
+                }
+                (left, right) => {
+                    return Err(self.type_mismatch(
+                        operator,
+                        "two numbers, strings or lists",
+                        &format!("{} and {}", left.type_name(), right.type_name()),
+                        &format!("Invalid operands: {}", operands(&left, &right)),
+                    ))
+                }
+            },
+
+            _ => {
+                return Err(self.error(
+                    operator,
+                    ErrorKind::InvalidOperator,
+                    "Invalid operator for binary expression",
+                ))
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Applies a unary `operator` to an evaluated operand.
+    pub(crate) fn unary(&self, operator: &Token, right: Value) -> JBreadResult<Value> {
+        let value = match operator.token_type {
+            TokenTypes::Minus => {
+                Value::from_number(-self.number(&right, operator, "Operand must be a number")?)
+            }
+            TokenTypes::Bang => Value::Bool(!bool::try_from(right)?),
+            TokenTypes::Tilde => Self::from_integer(!self.integer(&right, operator)?),
+            _ => {
+                return Err(self.error(
+                    operator,
+                    ErrorKind::InvalidOperator,
+                    "Invalid operator for unary expression",
+                ))
+            }
+        };
+
+        Ok(value)
+    }
+
+    /// Converts an operand of an arithmetic or comparison `operator` to a
+    /// number, failing at the operator.
+    fn number(&self, value: &Value, operator: &Token, message: &str) -> JBreadResult<f64> {
+        value
+            .to_number()
+            .map_err(|_| self.type_mismatch(operator, "number", value.type_name(), message))
+    }
+
+    /// Compares two numbers or two strings, see [`Value::order`]. NaN
+    /// compares false with anything.
+    fn comparison(&self, operator: &Token, left: &Value, right: &Value) -> JBreadResult<Value> {
+        let ordering = match (left, right) {
+            (Value::String(_), Value::String(_))
+            | (Value::Number(_) | Value::NaN, Value::Number(_) | Value::NaN) => left.order(right),
+            _ => {
+                let (expected, found) = match left {
+                    Value::String(_) => ("string", right.type_name()),
+                    Value::Number(_) | Value::NaN => ("number", right.type_name()),
+                    _ => ("number", left.type_name()),
+                };
+                return Err(self.type_mismatch(
+                    operator,
+                    expected,
+                    found,
+                    &format!(
+                        "Operands must be two numbers or two strings: {}",
+                        operands(left, right)
+                    ),
+                ));
+            }
+        };
+        Ok(Value::Bool(ordering.is_some_and(
+            |ordering| match operator.token_type {
+                TokenTypes::Greater => ordering.is_gt(),
+                TokenTypes::GreaterEqual => ordering.is_ge(),
+                TokenTypes::Less => ordering.is_lt(),
+                _ => ordering.is_le(),
+            },
+        )))
+    }
+
+    /// Converts both operands of an arithmetic `operator` to numbers, the
+    /// error showing what they were.
+    fn numbers(&self, operator: &Token, left: &Value, right: &Value) -> JBreadResult<(f64, f64)> {
+        match (left.to_number(), right.to_number()) {
+            (Ok(left), Ok(right)) => Ok((left, right)),
+            (left_number, _) => {
+                let found = match left_number {
+                    Ok(_) => right.type_name(),
+                    Err(_) => left.type_name(),
+                };
+                Err(self.type_mismatch(
+                    operator,
+                    "number",
+                    found,
+                    &format!("Operands must be numbers: {}", operands(left, right)),
+                ))
+            }
+        }
+    }
+
+    /// Converts an operand of a bitwise `operator` to an integer, failing
+    /// unless it's a whole number that fits in an `i64`.
+    fn integer(&self, value: &Value, operator: &Token) -> JBreadResult<i64> {
+        let number = match value {
+            Value::Number(number) => *number,
+            Value::NaN => f64::NAN,
+            value => {
+                return Err(self.type_mismatch(
+                    operator,
+                    "number",
+                    value.type_name(),
+                    "Bitwise operands must be numbers",
+                ))
+            }
+        };
+        // `i64::MAX as f64` rounds up to 2^63, which doesn't fit.
+        if number.fract() != 0.0 || !(i64::MIN as f64..i64::MAX as f64).contains(&number) {
+            return Err(self.error(
+                operator,
+                ErrorKind::InvalidOperand,
+                &format!(
+                    "Bitwise operand {} is not an integer that fits in 64 bits",
+                    number
+                ),
+            ));
+        }
+        Ok(number as i64)
+    }
+
+    fn from_integer(value: i64) -> Value {
+        Value::Number(value as f64)
+    }
+
+    pub(crate) fn print(&self, value: Value) -> JBreadResult<()> {
+        self.print_line(&value.to_string())
+    }
+
+    /// Prints `values` on one line, separated by spaces, as `print a, b;`
+    /// and `println(a, b)` do.
+    pub(crate) fn print_values(&self, values: &[Value]) -> JBreadResult<()> {
+        let values: Vec<String> = values.iter().map(Value::to_string).collect();
+        self.print_line(&values.join(" "))
+    }
+
+    pub(crate) fn print_line(&self, line: &str) -> JBreadResult<()> {
+        match &self.output {
+            Some(output) => Self::written(writeln!(output.borrow_mut(), "{}", line)),
+            None => {
+                self.host.print(line);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn write(&self, text: &str) -> JBreadResult<()> {
+        match &self.output {
+            Some(output) => {
+                let mut output = output.borrow_mut();
+                Self::written(
+                    output
+                        .write_all(text.as_bytes())
+                        .and_then(|()| output.flush()),
+                )
+            }
+            None => {
+                self.host.write(text);
+                Ok(())
+            }
+        }
+    }
+
+    /// Reports `text` on a line of its own to the error output.
+    pub(crate) fn report(&self, text: &str) {
+        self.write_error(&format!("{}\n", text));
+    }
+
+    /// Writes `text` to the error output as is. There's nowhere left to
+    /// report a failure to, so it's dropped.
+    pub(crate) fn write_error(&self, text: &str) {
+        let _ = match &self.error_output {
+            Some(output) => {
+                let mut output = output.borrow_mut();
+                output
+                    .write_all(text.as_bytes())
+                    .and_then(|()| output.flush())
+            }
+            None => {
+                let mut stderr = io::stderr().lock();
+                stderr
+                    .write_all(text.as_bytes())
+                    .and_then(|()| stderr.flush())
+            }
+        };
+    }
+
+    /// Fails the program when its output couldn't be written.
+    fn written(result: io::Result<()>) -> JBreadResult<()> {
+        result.map_err(|error| {
+            JBreadErrors::RunTimeException(Error::new(
+                ErrorKind::Io,
+                0,
+                0,
+                "output".to_string(),
+                format!("Couldn't write output: {}", error),
+            ))
+        })
+    }
+
+    /// Checks that a repeat count is a whole number that is not negative.
+    pub(crate) fn repeat_count(&self, count: Value, keyword: &Token) -> JBreadResult<f64> {
+        let count = match count {
+            Value::Number(count) => count,
+            Value::NaN => f64::NAN,
+            value => {
+                let found = value.type_name();
+                return Err(self.type_mismatch(
+                    keyword,
+                    "number",
+                    found,
+                    "Repeat count must be a number",
+                ));
+            }
+        };
+        if count < 0.0 || count.fract() != 0.0 {
+            return Err(self.error(
+                keyword,
+                ErrorKind::InvalidRepeatCount,
+                "Repeat count must be a whole number that is not negative",
+            ));
+        }
+        Ok(count)
+    }
+
+    fn new_environment(&self, enclosing: Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+        let environment = Rc::new(RefCell::new(Environment::new(enclosing)));
+        if let Some(stats) = &self.stats {
+            stats.borrow_mut().record_environment(&environment.borrow());
+        }
+        environment
+    }
+
+    pub(crate) fn define(
+        &mut self,
+        environment: &Rc<RefCell<Environment>>,
+        name: &str,
+        value: Value,
+    ) -> JBreadResult<()> {
+        self.bind(environment, name, Some(value), true)
+    }
+
+    /// Defines `name` without a value, see [`Environment::declare`].
+    #[cfg(feature = "bytecode")]
+    pub(crate) fn declare(
+        &mut self,
+        environment: &Rc<RefCell<Environment>>,
+        name: &str,
+    ) -> JBreadResult<()> {
+        self.bind(environment, name, None, true)
+    }
+
+    /// Defines `name`, declaring it without a value when `value` is `None`.
+    fn bind(
+        &mut self,
+        environment: &Rc<RefCell<Environment>>,
+        name: &str,
+        value: Option<Value>,
+        mutable: bool,
+    ) -> JBreadResult<()> {
+        let bytes = self
+            .stats
+            .as_ref()
+            .map(|_| Environment::binding_bytes(name, value.as_ref().unwrap_or(&Value::Nil)));
+        if let Some(tracer) = &mut self.tracer {
+            tracer.on_var_write(name, None, value.as_ref().unwrap_or(&Value::Nil));
+        }
+        if let Some(journal) = &mut self.journal {
+            journal.push(Undo::of(environment.clone(), name)?);
+        }
+        let mut environment = Environment::writing(environment, name)?;
+        match (value, mutable) {
+            (Some(value), true) => environment.define(name, value),
+            (Some(value), false) => environment.define_constant(name, value),
+            (None, _) => environment.declare(name),
+        }
+        if let (Some(stats), Some(bytes)) = (&self.stats, bytes) {
+            stats.borrow_mut().record_binding(&environment, bytes);
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> JBreadResult<Value> {
+        if self.tracer.is_some() {
+            self.trace_expr(expr);
+        }
+        self.nested(|interpreter| expr.accept(interpreter))
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> JBreadResult<()> {
+        self.execute_with(stmt, |interpreter| stmt.accept(interpreter))
+    }
+
+    /// Runs `run` as the execution of `stmt`.
+    fn execute_with<T>(
+        &mut self,
+        stmt: &Stmt,
+        run: impl FnOnce(&mut Self) -> JBreadResult<T>,
+    ) -> JBreadResult<T> {
+        if self.tracer.is_some() {
+            self.trace_before(stmt);
+        }
+        let result = match self.nested(run) {
+            // Placed at the innermost statement that knows its line.
+            Err(error)
+                if error.kind() == &ErrorKind::BudgetExceeded && error.error().line() == 0 =>
+            {
+                Err(self.budget_exceeded(stmt.line().unwrap_or(0)))
+            }
+            result => result,
+        };
+        if self.tracer.is_some() {
+            self.trace_after(stmt, result.as_ref().map(|_| ()));
+        }
+        result
+    }
+
+    // The tracer is called from here rather than from `execute_with` and
+    // `evaluate`, whose frames every level of nesting takes.
+    fn trace_before(&mut self, stmt: &Stmt) {
+        if let Some(tracer) = &mut self.tracer {
+            let line = stmt.line().unwrap_or(0);
+            if tracer.before_stmt(stmt, line) == TraceControl::Pause {
+                tracer.paused(stmt, line);
+            }
+        }
+    }
+
+    fn trace_after(&mut self, stmt: &Stmt, result: Result<(), &JBreadErrors>) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.after_stmt(stmt, result);
+        }
+    }
+
+    fn trace_expr(&mut self, expr: &Expr) {
+        if let Some(tracer) = &mut self.tracer {
+            tracer.before_expr(expr);
+        }
+    }
+
+    /// Counts a step of the run, `false` once the step limit is used up.
+    pub(crate) fn step(&mut self) -> bool {
+        let Some(max_steps) = self.max_steps else {
+            return true;
+        };
+        self.steps += 1;
+        self.steps <= max_steps
+    }
+
+    pub(crate) fn budget_exceeded(&self, line: u32) -> JBreadErrors {
+        JBreadErrors::RunTimeException(Error::new(
+            ErrorKind::BudgetExceeded,
+            line,
+            0,
+            "program".to_string(),
+            format!(
+                "Execution budget exceeded after {} steps",
+                self.max_steps.unwrap_or_default()
+            ),
+        ))
+    }
+
+    /// Runs `run` a level deeper, failing instead past the maximum depth or
+    /// the step limit.
+    fn nested<T>(&mut self, run: impl FnOnce(&mut Self) -> JBreadResult<T>) -> JBreadResult<T> {
+        if !self.step() {
+            return Err(self.budget_exceeded(0));
+        }
+        let depth = self.depth;
+        if depth >= self.max_depth {
+            return Err(JBreadErrors::RunTimeException(Error::new(
+                ErrorKind::NestingTooDeep,
+                0,
+                0,
+                "program".to_string(),
+                format!("Nested deeper than {} levels while running", self.max_depth),
+            )));
+        }
+        self.depth = depth + 1;
+        let result = crate::stack::grow(|| run(self));
+        self.depth = depth;
+        result
+    }
+
+    /// Runs the file at `path` the way the program imports it, with its
+    /// variables looked up dynamically as it was resolved on its own.
+    fn run_import(&mut self, path: &Path) -> JBreadResult<()> {
+        let name = path.display().to_string();
+        let source = read_source_file(&name)?;
+        let mut scanner =
+            Scanner::with_source(SourceRef::new(&name, &source), ScannerOptions::default());
+        scanner.scan_tokens();
+        if let Some(error) = scanner.errors().first() {
+            return Err(error.clone());
+        }
+        let stmts = Parser::new(scanner.scanned()).parse()?;
+        Resolver::default().resolve(&stmts)?;
+        for stmt in stmts.iter() {
+            self.execute(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn error(&self, token: &Token, kind: ErrorKind, message: &str) -> JBreadErrors {
+        JBreadErrors::RunTimeException(errors::Error::at(
+            kind,
+            token,
+            token.lexeme.to_string(),
+            message.to_string(),
+        ))
+    }
+
+    fn type_mismatch(
+        &self,
+        token: &Token,
+        expected: &str,
+        found: &str,
+        message: &str,
+    ) -> JBreadErrors {
+        let kind = ErrorKind::TypeMismatch {
+            expected: expected.to_string(),
+            found: found.to_string(),
+        };
+        self.error(token, kind, message)
+    }
+
+    /// Runs a program, returning the value of its last statement when that
+    /// is an expression statement, e.g. `1 + 1;`. A block's statements
+    /// don't count, so a program ending in one has no value.
+    pub fn interpret(&mut self, stmts: &[Stmt]) -> JBreadResult<Option<Value>> {
+        self.steps = 0;
+        match self.engine {
+            ExecutionEngine::TreeWalk => {}
+            #[cfg(feature = "bytecode")]
+            ExecutionEngine::Bytecode => {
+                let chunk = crate::bytecode::Compiler::default().compile(stmts)?;
+                return crate::bytecode::Vm::new(self).run(&chunk);
+            }
+        }
+        for (index, stmt) in stmts.iter().enumerate() {
+            match stmt {
+                Stmt::Expression(statement) if index + 1 == stmts.len() => {
+                    let value = self.execute_with(stmt, |interpreter| {
+                        interpreter.evaluate(&statement.expression)
+                    })?;
+                    return Ok(Some(value));
+                }
+                stmt => self.execute(stmt)?,
+            }
+            // Only reachable when the program wasn't resolved.
+            if self.returning.take().is_some() {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs a program like [`interpret`](Self::interpret), except that if it
+    /// fails every variable it defined or assigned is put back as it was,
+    /// in whichever scope it lives, closures' included. `interpret` keeps
+    /// what ran before the failure, as the REPL wants.
+    ///
+    /// An exit isn't a failure, so what ran before it stays. Changes made
+    /// inside values, like to fields or list elements, always stay. Every
+    /// write is recorded until the run ends, so a long run takes memory for
+    /// each.
+    pub fn interpret_transactional(&mut self, stmts: &[Stmt]) -> JBreadResult<Option<Value>> {
+        let enclosing = self.journal.replace(Vec::new());
+        let result = self.interpret(stmts);
+        let journal = std::mem::replace(&mut self.journal, enclosing).unwrap_or_default();
+        match (&result, &mut self.journal) {
+            (Err(error), _) if !matches!(error, JBreadErrors::Exit(_)) => {
+                journal.into_iter().rev().for_each(Undo::revert)
+            }
+            // Kept for the enclosing run, which may still fail.
+            (_, Some(enclosing)) => enclosing.extend(journal),
+            (_, None) => {}
+        }
+        result
+    }
+
+    fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Rc<RefCell<Environment>>,
+    ) -> Result<(), JBreadErrors> {
+        // Runs in `environment` until the block is done, failed or returned,
+        // the enclosing one being back in place either way.
+        let enclosing = std::mem::replace(&mut self.environment, environment);
+        let result = statements.iter().try_for_each(|stmt| {
+            if self.returning.is_some() {
+                return Ok(());
+            }
+            self.execute(stmt)
+        });
+        self.environment = enclosing;
+        result
+    }
+}
+
+/// Both operands of a binary operator as they appear in its errors.
+fn operands(left: &Value, right: &Value) -> String {
+    format!("{} and {}", left.repr(), right.repr())
+}
+
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl VisitorExpr for Interpreter {
+    type Result = JBreadResult<Value>;
+
+    fn visit_expr_binary(&mut self, expr: &crate::ast::Binary) -> Self::Result {
+        let left = self.evaluate(&expr.left)?;
+        let left = self.operand(left, &expr.operator, "Left value is not a literal")?;
+        let right = self.evaluate(&expr.right)?;
+        let right = self.operand(right, &expr.operator, "Right value is not a literal")?;
+        self.binary(&expr.operator, left, right)
+    }
+
+    fn visit_expr_grouping(&mut self, expr: &crate::ast::Grouping) -> Self::Result {
+        self.evaluate(&expr.expression)
+    }
+
+    fn visit_expr_literal(&mut self, expr: &crate::ast::Literal) -> Self::Result {
+        Ok(expr.value.as_ref().map_or(Value::Nil, Value::from))
+    }
+
+    fn visit_expr_unary(&mut self, expr: &crate::ast::Unary) -> Self::Result {
+        let right = self.evaluate(&expr.right)?;
+        let right = self.operand(right, &expr.operator, "Right value is not a literal")?;
+        self.unary(&expr.operator, right)
+    }
+
+    fn visit_expr_variable(&mut self, expr: &crate::ast::Variable) -> Self::Result {
+        self.look_up_variable(&expr.name, ExprId::of(expr))
+    }
+
+    fn visit_expr_assign(&mut self, expr: &crate::ast::Assign) -> Self::Result {
+        let evaluated = self.evaluate(&expr.value)?;
+        self.assign_variable(&expr.name, ExprId::of(expr), evaluated.clone())?;
+        Ok(evaluated)
+    }
+
+    fn visit_expr_call(&mut self, expr: &crate::ast::Call) -> Self::Result {
+        let callee = self.evaluate(&expr.callee)?;
+        let mut arguments = Vec::with_capacity(expr.arguments.len());
+        for argument in expr.arguments.iter() {
+            arguments.push(self.evaluate(argument)?);
+        }
+
+        let pushed = self.enter_call(&callee, arguments.len(), &expr.paren)?;
+        // Not returning early, so the frame is always left.
+        let result = match callee {
+            Value::Function(function) => self.call_function(&function, arguments),
+            Value::Class(class) => self.construct(class, arguments),
+            Value::Native(native) => native.call(self, &expr.paren, arguments),
+            _ => unreachable!("callee was checked to be callable"),
+        };
+        self.leave_frame(pushed, result)
+    }
+
+    fn visit_expr_get(&mut self, expr: &crate::ast::Get) -> Self::Result {
+        let object = self.evaluate(&expr.object)?;
+        let instance = self.instance(object, &expr.name)?;
+        match instance.get(&expr.name.lexeme) {
+            Some(value) => Ok(value),
+            None => Err(self.error(
+                &expr.name,
+                ErrorKind::UndefinedProperty,
+                &format!("Undefined property '{}'", expr.name.lexeme),
+            )),
+        }
+    }
+
+    fn visit_expr_set(&mut self, expr: &crate::ast::Set) -> Self::Result {
+        let object = self.evaluate(&expr.object)?;
+        let instance = self.instance(object, &expr.name)?;
+        let value = self.evaluate(&expr.value)?;
+        instance.set(&expr.name.lexeme, value.clone());
+        Ok(value)
+    }
+
+    fn visit_expr_list_literal(&mut self, expr: &crate::ast::ListLiteral) -> Self::Result {
+        let mut elements = Vec::with_capacity(expr.elements.len());
+        for element in expr.elements.iter() {
+            elements.push(self.evaluate(element)?);
+        }
+        Ok(Value::list(elements))
+    }
+
+    /// A later entry with the same key replaces an earlier one.
+    fn visit_expr_map_literal(&mut self, expr: &crate::ast::MapLiteral) -> Self::Result {
+        let mut entries = HashMap::with_capacity(expr.entries.len());
+        for entry in expr.entries.iter() {
+            let key = self.evaluate(&entry.key)?;
+            let key = self.map_key(&key, &expr.brace)?;
+            entries.insert(key, self.evaluate(&entry.value)?);
+        }
+        Ok(Value::map(entries))
+    }
+
+    fn visit_expr_index(&mut self, expr: &crate::ast::Index) -> Self::Result {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.evaluate(&expr.index)?;
+        // Strings are indexed by character, not byte.
+        if let Value::String(string) = &object {
+            let len = string.chars().count();
+            let index = self.checked_index(index, len, &expr.bracket, "string")?;
+            let chr = string.chars().nth(index).unwrap();
+            return Ok(Value::String(chr.to_string()));
+        }
+        // A key a map doesn't hold reads as nil.
+        if let Value::Map(map) = &object {
+            let key = self.map_key(&index, &expr.bracket)?;
+            return Ok(map.borrow().get(&key).cloned().unwrap_or_default());
+        }
+        let list = self.list(object, &expr.bracket, "list, map or string")?;
+        let list = list.borrow();
+        let index = self.checked_index(index, list.len(), &expr.bracket, "list")?;
+        Ok(list[index].clone())
+    }
+
+    fn visit_expr_index_assign(&mut self, expr: &crate::ast::IndexAssign) -> Self::Result {
+        let object = self.evaluate(&expr.object)?;
+        let index = self.evaluate(&expr.index)?;
+        let value = self.evaluate(&expr.value)?;
+        if let Value::Map(map) = &object {
+            let key = self.map_key(&index, &expr.bracket)?;
+            map.borrow_mut().insert(key, value.clone());
+            return Ok(value);
+        }
+        let list = self.list(object, &expr.bracket, "list or map")?;
+        let mut list = list.borrow_mut();
+        let index = self.checked_index(index, list.len(), &expr.bracket, "list")?;
+        list[index] = value.clone();
+        Ok(value)
+    }
+
+    fn visit_expr_this(&mut self, expr: &crate::ast::This) -> Self::Result {
+        self.look_up_variable(&expr.keyword, ExprId::of(expr))
+    }
+
+    fn visit_expr_interpolation(&mut self, expr: &crate::ast::Interpolation) -> Self::Result {
+        let mut result = String::new();
+        for part in expr.parts.iter() {
+            result.push_str(&self.evaluate(part)?.to_string());
+        }
+        Ok(Value::String(result))
+    }
+
+    fn visit_expr_lambda(&mut self, expr: &crate::ast::Lambda) -> Self::Result {
+        let function = Function::lambda(expr, self.environment.clone());
+        Ok(Value::Function(Rc::new(function)))
+    }
+}
+
+impl VisitorStmt for Interpreter {
+    type Result = JBreadResult<()>;
+
+    fn visit_stmt_expression(&mut self, expr: &crate::ast::Expression) -> Self::Result {
+        self.evaluate(&expr.expression)?;
+        Ok(())
+    }
+
+    fn visit_stmt_print(&mut self, expr: &crate::ast::Print) -> Self::Result {
+        let mut values = Vec::with_capacity(expr.expressions.len());
+        for expression in expr.expressions.iter() {
+            values.push(self.evaluate(expression)?);
+        }
+        self.print_values(&values)
+    }
+
+    /// The message is only evaluated once the assertion failed.
+    fn visit_stmt_assert(&mut self, stmt: &crate::ast::Assert) -> Self::Result {
+        if self.evaluate(&stmt.condition)?.is_truthy() {
+            return Ok(());
+        }
+        let message = match &stmt.message {
+            Some(message) => self.evaluate(message)?.to_string(),
+            None => stmt.condition.accept(&mut AstPrinter::default()),
+        };
+        Err(JBreadErrors::RunTimeException(Error::at(
+            ErrorKind::AssertionFailed,
+            &stmt.keyword,
+            "assert".to_string(),
+            format!("Assertion failed: {}", message),
+        )))
+    }
+
+    fn visit_stmt_var(&mut self, stmt: &crate::ast::Var) -> Self::Result {
+        let value = match &stmt.initializer {
+            Some(expr) => Some(self.evaluate(expr)?),
+            None => None,
+        };
+
+        self.bind(&self.environment.clone(), &stmt.name.lexeme, value, true)?;
+        Ok(())
+    }
+
+    fn visit_stmt_const(&mut self, stmt: &crate::ast::Const) -> Self::Result {
+        let value = self.evaluate(&stmt.initializer)?;
+        self.bind(
+            &self.environment.clone(),
+            &stmt.name.lexeme,
+            Some(value),
+            false,
+        )?;
+        Ok(())
+    }
+
+    /// Runs the imported file in the global environment, imports only being
+    /// allowed at the top level. Errors running it are reported where they
+    /// happened, those loading it at the import.
+    fn visit_stmt_import(&mut self, stmt: &crate::ast::Import) -> Self::Result {
+        let path = match &stmt.path.literal {
+            Some(LiteralValue::String(path)) => path.to_string(),
+            _ => stmt.path.lexeme.trim_matches('"').to_string(),
+        };
+        let resolved = self.imports.resolve(&path);
+        let failed = |error: JBreadErrors| {
+            JBreadErrors::RunTimeException(Error::at(
+                ErrorKind::ImportFailed,
+                &stmt.keyword,
+                "import".to_string(),
+                format!("Couldn't import {}: {}", path, error),
+            ))
+        };
+        let canonical = resolved
+            .canonicalize()
+            .map_err(|error| failed(JBreadErrors::io(&resolved.display().to_string(), &error)))?;
+        let begun = self.imports.begin(canonical.clone());
+        match begun {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(cycle) => {
+                return Err(JBreadErrors::RunTimeException(Error::at(
+                    ErrorKind::ImportCycle,
+                    &stmt.keyword,
+                    "import".to_string(),
+                    format!("Import cycle: {}", cycle),
+                )))
+            }
+        }
+        let result = self.run_import(&canonical).map_err(|error| match error {
+            JBreadErrors::Io(_) | JBreadErrors::ParseError(_) | JBreadErrors::ResolveError(_) => {
+                failed(error)
+            }
+            error => error,
+        });
+        self.imports.end(result.is_ok());
+        result
+    }
+
+    fn visit_stmt_multi_var(&mut self, stmt: &crate::ast::MultiVar) -> Self::Result {
+        // Every initializer is evaluated before any name is defined.
+        let mut values = Vec::with_capacity(stmt.names.len());
+        for initializer in stmt.initializers.iter() {
+            values.push(Some(self.evaluate(initializer)?));
+        }
+        // Without initializers the names are declared without a value.
+        values.resize(stmt.names.len(), None);
+        for (name, value) in stmt.names.iter().zip(values) {
+            self.bind(&self.environment.clone(), &name.lexeme, value, true)?;
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_multi_assign(&mut self, stmt: &crate::ast::MultiAssign) -> Self::Result {
+        // Every value is evaluated before any target is assigned, so
+        // `a, b = b, a;` swaps.
+        let mut values = Vec::with_capacity(stmt.values.len());
+        for value in stmt.values.iter() {
+            values.push(self.evaluate(value)?);
+        }
+        for (target, value) in stmt.targets.iter().zip(values) {
+            self.assign_variable(&target.name, ExprId::of(target), value)?;
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_block(&mut self, expr: &crate::ast::Block) -> Self::Result {
+        let environment = self.new_environment(self.environment.clone());
+        self.execute_block(&expr.statements, environment)
+    }
+
+    /// Case values are evaluated in order until one equals the subject, like
+    /// `==`. Only that arm runs, there's no falling through to the next.
+    fn visit_stmt_switch(&mut self, stmt: &crate::ast::Switch) -> Self::Result {
+        let subject = self.evaluate(&stmt.subject)?;
+        let mut matched = None;
+        for arm in stmt.arms.iter() {
+            if let Some(value) = &arm.value {
+                if self.evaluate(value)?.equals(&subject) {
+                    matched = Some(arm);
+                    break;
+                }
+            }
+        }
+        let matched = matched.or_else(|| stmt.arms.iter().find(|arm| arm.value.is_none()));
+        match matched {
+            Some(arm) => {
+                let environment = self.new_environment(self.environment.clone());
+                self.execute_block(&arm.body, environment)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn visit_stmt_if(&mut self, stmt: &crate::ast::If) -> Self::Result {
+        if self.evaluate(&stmt.condition)?.is_truthy() {
+            self.execute(&stmt.then_branch)
+        } else if let Some(else_branch) = &stmt.else_branch {
+            self.execute(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_stmt_repeat(&mut self, stmt: &crate::ast::Repeat) -> Self::Result {
+        let count = self.evaluate(&stmt.count)?;
+        let count = self.repeat_count(count, &stmt.keyword)?;
+
+        let mut remaining = count;
+        while remaining > 0.0 && self.returning.is_none() {
+            self.execute(&stmt.body)?;
+            remaining -= 1.0;
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_function(&mut self, stmt: &crate::ast::Function) -> Self::Result {
+        let function = Function::new(stmt.clone(), self.environment.clone());
+        self.define(
+            &self.environment.clone(),
+            &stmt.name.lexeme,
+            Value::Function(Rc::new(function)),
+        )?;
+        Ok(())
+    }
+
+    fn visit_stmt_class(&mut self, stmt: &crate::ast::Class) -> Self::Result {
+        let methods = stmt
+            .methods
+            .iter()
+            .map(|method| {
+                let function = Function::method(method.clone(), self.environment.clone());
+                (method.name.lexeme.clone(), Rc::new(function))
+            })
+            .collect();
+        let class = Class::new(stmt.name.lexeme.clone(), methods);
+        self.define(
+            &self.environment.clone(),
+            &stmt.name.lexeme,
+            Value::Class(Rc::new(class)),
+        )?;
+        Ok(())
+    }
+
+    fn visit_stmt_return(&mut self, stmt: &crate::ast::Return) -> Self::Result {
+        let value = match &stmt.value {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Nil,
+        };
+        self.returning = Some(value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Interpreter, UndeclaredAssignment, Value};
+    use crate::{
+        ast::{Expr, Stmt},
+        errors::{CallFrame, ErrorKind, JBreadResult, SourceMap},
+        parser::Parser,
+        AstNode, AstStmt, LiteralValue, OutputBuffer, Scanner, Token, TokenTypes,
+    };
+
+    #[test]
+    fn test_binary_str_concat() {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::String("Hello".to_string())),
+            Token::new(TokenTypes::Plus, "+".to_string(), None, 1),
+            Expr::literal(LiteralValue::String(" World!".to_string())),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_binary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_binary_expr.is_ok());
+        assert_eq!(
+            parsed_binary_expr.unwrap(),
+            Value::String("Hello World!".to_string())
+        );
+    }
+
+    #[test]
+    fn test_number_literal_edges() {
+        assert_eq!(run_printed("print .5 + 1; print 1_000 * 2;"), "1.5\n2000\n");
+    }
+
+    #[test]
+    fn test_binary_num_add() {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::Number(1.0)),
+            Token::new(TokenTypes::Plus, "+".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(2.0)),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_binary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_binary_expr.is_ok());
+        assert_eq!(parsed_binary_expr.unwrap(), Value::Number(3.0));
+    }
+
+    #[test]
+    fn test_0_0_division() {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::Number(0.0)),
+            Token::new(TokenTypes::Slash, "/".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(0.0)),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_binary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_binary_expr.is_ok());
+        assert_eq!(parsed_binary_expr.unwrap(), Value::NaN);
+    }
+
+    fn evaluate_numbers(left: f64, operator: TokenTypes, lexeme: &str, right: f64) -> Value {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::Number(left)),
+            Token::new(operator, lexeme.to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(right)),
+        );
+        expr.accept(&mut Interpreter::default()).unwrap()
+    }
+
+    #[test]
+    fn test_infinity_arithmetic() {
+        let inf = f64::INFINITY;
+        assert_eq!(
+            evaluate_numbers(1.0, TokenTypes::Slash, "/", 0.0),
+            Value::Number(inf)
+        );
+        assert_eq!(
+            evaluate_numbers(-1.0, TokenTypes::Slash, "/", 0.0),
+            Value::Number(-inf)
+        );
+        assert_eq!(
+            evaluate_numbers(inf, TokenTypes::Plus, "+", 1.0),
+            Value::Number(inf)
+        );
+        assert_eq!(
+            evaluate_numbers(inf, TokenTypes::Minus, "-", inf),
+            Value::NaN
+        );
+        assert_eq!(
+            evaluate_numbers(inf, TokenTypes::Star, "*", 0.0),
+            Value::NaN
+        );
+        assert_eq!(
+            evaluate_numbers(1e308, TokenTypes::Star, "*", 10.0),
+            Value::Number(inf)
+        );
+    }
+
+    #[test]
+    fn test_infinity_comparison_and_equality() {
+        let inf = f64::INFINITY;
+        assert_eq!(
+            evaluate_numbers(inf, TokenTypes::Greater, ">", f64::MAX),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            evaluate_numbers(-inf, TokenTypes::Less, "<", f64::MIN),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            evaluate_numbers(inf, TokenTypes::EqualEqual, "==", inf),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            evaluate_numbers(inf, TokenTypes::EqualEqual, "==", -inf),
+            Value::Bool(false)
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let number = |value| Value::Number(value);
+        let inf = f64::INFINITY;
+        for (expression, expected) in [
+            ("1 / 0", number(inf)),
+            ("-1 / 0", number(-inf)),
+            ("1 / -0", number(-inf)),
+            ("0 / 0", Value::NaN),
+            ("1 / 0 + 1", number(inf)),
+            ("(1 / 0) * -2", number(-inf)),
+            ("1 / (1 / 0)", number(0.0)),
+            ("1 / 0 - 1 / 0", Value::NaN),
+            ("(1 / 0) / (1 / 0)", Value::NaN),
+            ("1 / 0 == inf", Value::Bool(true)),
+            ("-1 / 0 < -1e308", Value::Bool(true)),
+        ] {
+            let (interpreter, result) = run_source(&format!("var result = {};", expression));
+            assert!(result.is_ok(), "{}", expression);
+            assert_eq!(lookup(&interpreter, "result"), expected, "{}", expression);
+        }
+
+        assert_eq!(
+            run_printed("print 1 / 0; print -1 / 0; print 0 / 0;"),
+            "inf\n-inf\nNaN\n"
+        );
+    }
+
+    #[test]
+    fn test_infinity_display() {
+        assert_eq!(LiteralValue::Number(f64::INFINITY).to_string(), "inf");
+        assert_eq!(LiteralValue::Number(f64::NEG_INFINITY).to_string(), "-inf");
+    }
+
+    #[test]
+    fn test_binary_multiplication() {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::Number(2.0)),
+            Token::new(TokenTypes::Star, "*".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(2.0)),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_binary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_binary_expr.is_ok());
+        assert_eq!(parsed_binary_expr.unwrap(), Value::Number(4.0));
+    }
+
+    #[test]
+    fn test_binary_division() {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::Number(4.0)),
+            Token::new(TokenTypes::Slash, "/".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(2.0)),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_binary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_binary_expr.is_ok());
+        assert_eq!(parsed_binary_expr.unwrap(), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_binary_subtraction() {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::Number(4.0)),
+            Token::new(TokenTypes::Minus, "-".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(2.0)),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_binary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_binary_expr.is_ok());
+        assert_eq!(parsed_binary_expr.unwrap(), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_binary_greater() {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::Number(4.0)),
+            Token::new(TokenTypes::Greater, ">".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(2.0)),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_binary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_binary_expr.is_ok());
+        assert_eq!(parsed_binary_expr.unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_binary_greater_equal() {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::Number(4.0)),
+            Token::new(TokenTypes::GreaterEqual, ">=".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(2.0)),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_binary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_binary_expr.is_ok());
+        assert_eq!(parsed_binary_expr.unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_binary_less() {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::Number(4.0)),
+            Token::new(TokenTypes::Less, "<".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(2.0)),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_binary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_binary_expr.is_ok());
+        assert_eq!(parsed_binary_expr.unwrap(), Value::Bool(false));
+    }
+
+    #[test]
+    fn test_unary_negation() {
+        let expr = Expr::unary(
+            Token::new(TokenTypes::Minus, "-".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(2.0)),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_unary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_unary_expr.is_ok());
+        assert_eq!(parsed_unary_expr.unwrap(), Value::Number(-2.0));
+    }
+
+    #[test]
+    fn test_grouping() {
+        let expr = Expr::grouping(Expr::literal(LiteralValue::Number(2.0)));
+        let mut interpreter = Interpreter::default();
+
+        let parsed_grouping_expr = expr.accept(&mut interpreter);
+        assert!(parsed_grouping_expr.is_ok());
+        assert_eq!(parsed_grouping_expr.unwrap(), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_string_and_int_addition() {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::String("Hello".to_string())),
+            Token::new(TokenTypes::Plus, "+".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(2.0)),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_binary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_binary_expr.is_err());
+        assert_eq!(
+            parsed_binary_expr.unwrap_err().kind(),
+            &ErrorKind::TypeMismatch {
+                expected: "two numbers, strings or lists".to_string(),
+                found: "string and number".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_string_minus_number() {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::String("Hello".to_string())),
+            Token::new(TokenTypes::Minus, "-".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(2.0)),
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_binary_expr = expr.accept(&mut interpreter);
+        assert!(parsed_binary_expr.is_err());
+        assert_eq!(
+            parsed_binary_expr.unwrap_err().kind(),
+            &ErrorKind::TypeMismatch {
+                expected: "number".to_string(),
+                found: "string".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_var_fetching_without_initialization() {
+        let expr = Expr::variable(Token::new(TokenTypes::Identifier, "a".to_string(), None, 1));
+        let mut interpreter = Interpreter::default();
+
+        let parsed_var_expr = expr.accept(&mut interpreter);
+        assert!(parsed_var_expr.is_err());
+        assert_eq!(
+            parsed_var_expr.unwrap_err().kind(),
+            &ErrorKind::UndefinedVariable
+        );
+    }
+
+    #[test]
+    fn test_error_composes_with_std_error() {
+        fn run() -> Result<(), Box<dyn std::error::Error>> {
+            let expr = Expr::variable(Token::new(TokenTypes::Identifier, "a".to_string(), None, 1));
+            expr.accept(&mut Interpreter::default())?;
+            Ok(())
+        }
+
+        let error = run().unwrap_err();
+        assert_eq!(error.to_string(), "\"Undefined variable\" at line: 1 in a");
+    }
+
+    #[test]
+    fn test_var_assignment_with_value() {
+        let expr = Expr::variable(Token::new(TokenTypes::Identifier, "a".to_string(), None, 1));
+        let mut interpreter = Interpreter::default();
+        interpreter
+            .environment
+            .borrow_mut()
+            .define("a", Value::Number(2.0));
+
+        let parsed_var_expr = expr.accept(&mut interpreter);
+        assert!(parsed_var_expr.is_ok());
+        assert_eq!(parsed_var_expr.unwrap(), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_var_assignment_with_value_and_assignment() {
+        let expr = Expr::variable(Token::new(TokenTypes::Identifier, "a".to_string(), None, 1));
+        let mut interpreter = Interpreter::default();
+        interpreter
+            .environment
+            .borrow_mut()
+            .define("a", Value::Number(2.0));
+
+        let parsed_var_expr = expr.accept(&mut interpreter);
+        assert!(parsed_var_expr.is_ok());
+        assert_eq!(parsed_var_expr.unwrap(), Value::Number(2.0));
+
+        let assignment_expr = Expr::assign(
+            Token::new(TokenTypes::Identifier, "a".to_string(), None, 1),
+            Expr::literal(LiteralValue::Number(3.0)),
+        );
+        let parsed_assignment_expr = assignment_expr.accept(&mut interpreter);
+        assert!(parsed_assignment_expr.is_ok());
+        assert_eq!(parsed_assignment_expr.unwrap(), Value::Number(3.0));
+    }
+
+    #[test]
+    fn test_print_statement() {
+        let stmt = Stmt::print(vec![Expr::literal(LiteralValue::Number(2.0))]);
+        let output = OutputBuffer::new();
+        let mut interpreter = Interpreter::default().with_output(output.clone());
+
+        let parsed_print_stmt = stmt.accept(&mut interpreter);
+        assert!(parsed_print_stmt.is_ok());
+        assert_eq!(output.take(), "2\n");
+    }
+
+    #[test]
+    fn test_var_statement() {
+        let stmt = Stmt::var(
+            Token::new(TokenTypes::Identifier, "a".to_string(), None, 1),
+            None,
+        );
+        let mut interpreter = Interpreter::default();
+
+        let parsed_var_stmt = stmt.accept(&mut interpreter);
+        assert!(parsed_var_stmt.is_ok());
+    }
+
+    fn run_source(source: &str) -> (Interpreter, JBreadResult<()>) {
+        #[cfg(feature = "bytecode")]
+        crate::bytecode::assert_engines_agree(source, false);
+        let mut scanner = Scanner::new(source);
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut interpreter = Interpreter::default();
+        let result = interpreter.interpret(&stmts).map(drop);
+        (interpreter, result)
+    }
+
+    /// Runs `source`, which must succeed, returning what it printed.
+    fn run_printed(source: &str) -> String {
+        let output = OutputBuffer::new();
+        let mut scanner = Scanner::new(source);
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut interpreter = Interpreter::default().with_output(output.clone());
+        interpreter.interpret(&stmts).unwrap();
+        output.take()
+    }
+
+    fn lookup(interpreter: &Interpreter, name: &str) -> Value {
+        let token = Token::new(TokenTypes::Identifier, name.to_string(), None, 1);
+        interpreter.environment.borrow().get(&token).unwrap()
+    }
+
+    #[test]
+    fn test_repeat_counts() {
+        let (interpreter, result) = run_source("var n = 0; repeat (5) { n = n + 1; }");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "n"), Value::Number(5.0));
+
+        let (interpreter, result) = run_source("var n = 0; repeat (0) { n = n + 1; }");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "n"), Value::Number(0.0));
+    }
+
+    #[test]
+    fn test_repeat_invalid_counts() {
+        for count in ["1.5", "-1", "\"three\"", "nil"] {
+            let (_, result) = run_source(&format!("repeat ({}) print 1;", count));
+            assert!(result.is_err(), "repeat ({}) should fail", count);
+        }
+        for count in ["-2", "0/0", "nan"] {
+            let (_, result) = run_source(&format!("repeat ({}) print 1;", count));
+            assert_eq!(
+                result.unwrap_err().kind(),
+                &ErrorKind::InvalidRepeatCount,
+                "repeat ({})",
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn test_repeat_count_evaluated_once() {
+        let (interpreter, result) =
+            run_source("var c = 0; var n = 0; repeat (c = c + 3) { n = n + 1; }");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "c"), Value::Number(3.0));
+        assert_eq!(lookup(&interpreter, "n"), Value::Number(3.0));
+    }
+
+    #[test]
+    fn test_repeat_nested() {
+        let (interpreter, result) =
+            run_source("var n = 0; repeat (3) { var i = 0; repeat (2) { n = n + 1; i = i + 1; } }");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "n"), Value::Number(6.0));
+    }
+
+    #[test]
+    fn test_if_runs_one_branch() {
+        let source = |a: &str| {
+            format!(
+                "var a = {}; var ran = \"\"; \
+                 if (a == 1) ran = ran + \"first\"; \
+                 else if (a == 2) ran = ran + \"second\"; \
+                 elif (a == 3) {{ ran = ran + \"third\"; }} \
+                 else ran = ran + \"last\";",
+                a
+            )
+        };
+        for (a, expected) in [
+            ("1", "first"),
+            ("2", "second"),
+            ("3", "third"),
+            ("4", "last"),
+        ] {
+            for (interpreter, result) in [run_source(&source(a)), run_resolved(&source(a))] {
+                assert!(result.is_ok(), "{:?}", result);
+                assert_eq!(
+                    lookup(&interpreter, "ran"),
+                    Value::String(expected.into()),
+                    "{}",
+                    a
+                );
+            }
+        }
+
+        // Conditions are truthy like `assert`'s.
+        let (interpreter, result) = run_source(
+            "var n = 0; if (nil) n = 1; if (false) n = 2; if (0) n = n + 10; if (\"\") n = n + 100;",
+        );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "n"), Value::Number(110.0));
+    }
+
+    #[test]
+    fn test_switch() {
+        let source = |subject: &str| {
+            format!(
+                "var n; switch ({}) {{ case 1: n = \"one\"; case \"two\": n = 2; \
+                 default: n = \"other\"; }}",
+                subject
+            )
+        };
+        for (subject, expected) in [
+            ("1", Value::String("one".into())),
+            ("\"two\"", Value::Number(2.0)),
+            ("3", Value::String("other".into())),
+        ] {
+            let (interpreter, result) = run_source(&source(subject));
+            assert!(result.is_ok());
+            assert_eq!(lookup(&interpreter, "n"), expected, "{}", subject);
+        }
+
+        // No arm matching and no default runs nothing.
+        let (interpreter, result) = run_source("var n = 0; switch (2) { case 1: n = 1; }");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "n"), Value::Number(0.0));
+    }
+
+    #[test]
+    fn test_switch_does_not_fall_through() {
+        let (interpreter, result) = run_source(
+            "var calls = 0; fun next() { calls = calls + 1; return calls; } var n = 0; \
+             switch (next()) { case 0: n = n + 1; case 1: n = n + 10; case 1: n = n + 100; \
+             default: n = n + 1000; }",
+        );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "n"), Value::Number(10.0));
+        // The subject is evaluated once.
+        assert_eq!(lookup(&interpreter, "calls"), Value::Number(1.0));
+    }
+
+    fn run_resolved(source: &str) -> (Interpreter, JBreadResult<()>) {
+        #[cfg(feature = "bytecode")]
+        crate::bytecode::assert_engines_agree(source, true);
+        let mut scanner = Scanner::new(source);
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let resolution = crate::Resolver::default().resolve(&stmts).unwrap();
+        let mut interpreter = Interpreter::default();
+        interpreter.set_locals(resolution.locals);
+        let result = interpreter.interpret(&stmts).map(drop);
+        (interpreter, result)
+    }
+
+    #[test]
+    fn test_resolved_read_through_deep_nesting() {
+        let source = format!(
+            "var out; {{ var a = 7; {} out = a; {} }}",
+            "{".repeat(50),
+            "}".repeat(50)
+        );
+        let (interpreter, result) = run_resolved(&source);
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "out"), Value::Number(7.0));
+    }
+
+    #[test]
+    fn test_resolved_assignment_skips_shadowed_scopes() {
+        let source =
+            "var out; { var a = 1; { var b = 0; { a = a + 1; { var a = 10; } } } out = a; }";
+        let (interpreter, result) = run_resolved(source);
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "out"), Value::Number(2.0));
+    }
+
+    #[test]
+    fn test_block_writes_through_to_outer_scopes() {
+        let source = "var a = 1; var out; { a = a + 1; { var b = a; { a = b * 10; } } out = a; }";
+        for (interpreter, result) in [run_source(source), run_resolved(source)] {
+            assert!(result.is_ok());
+            assert_eq!(lookup(&interpreter, "a"), Value::Number(20.0));
+            assert_eq!(lookup(&interpreter, "out"), Value::Number(20.0));
+        }
+    }
+
+    #[test]
+    fn test_block_error_leaves_global_scope() {
+        for source in [
+            "var a = 1; { var a = 2; { var b = a; b(); } }",
+            "var a = 1; fun f() { var a = 3; { return a(); } } { var c = f(); }",
+            "var a = 1; switch (a) { case 1: { var a = 2; a(); } }",
+        ] {
+            for (mut interpreter, result) in [run_source(source), run_resolved(source)] {
+                assert_eq!(result.unwrap_err().kind(), &ErrorKind::NotCallable);
+                // Back in the global scope, so the next program defines
+                // globals again.
+                assert_eq!(lookup(&interpreter, "a"), Value::Number(1.0));
+                let mut scanner = Scanner::new("var after = a + 1;");
+                let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+                interpreter.set_locals(Default::default());
+                interpreter.interpret(&stmts).unwrap();
+                assert_eq!(lookup(&interpreter, "after"), Value::Number(2.0));
+                assert!(interpreter.environment.borrow().ancestor(1).is_none());
+            }
+        }
     }
 
-    fn evalute(&mut self, expr: &Expr) -> JBreadResult<Literal> {
-        expr.accept(self)
+    fn defined(interpreter: &Interpreter, name: &str) -> bool {
+        let token = Token::new(TokenTypes::Identifier, name.to_string(), None, 1);
+        interpreter.environment.borrow().get(&token).is_ok()
     }
 
-    fn execute(&mut self, stmt: &Stmt) -> JBreadResult<()> {
-        stmt.accept(self)
-    }
+    #[test]
+    fn test_transactional_run_rolls_back() {
+        let mut scanner = Scanner::new("var a = 1;\nvar b = 2;\nmissing();");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
 
-    fn error(&self, token: &Token, message: &str) -> JBreadErrors {
-        JBreadErrors::RunTimeException(errors::Error::new(
-            token.line,
-            token.lexeme.clone(),
-            message.to_string(),
-        ))
+        let mut interpreter = Interpreter::default();
+        assert!(interpreter.interpret_transactional(&stmts).is_err());
+        assert!(!defined(&interpreter, "a") && !defined(&interpreter, "b"));
+
+        let mut interpreter = Interpreter::default();
+        assert!(interpreter.interpret(&stmts).is_err());
+        assert!(defined(&interpreter, "a") && defined(&interpreter, "b"));
     }
 
-    pub fn interpret(&mut self, stmts: &[Stmt]) -> JBreadResult<()> {
-        for stmt in stmts.iter() {
-            self.execute(stmt)?;
+    #[test]
+    fn test_transactional_run_rolls_back_outer_scopes() {
+        let setup = "var x = 1; var shadowed = \"global\";
+            fun counter() { var count = 0; fun next() { count = count + 1; return count; } return next; }
+            var next = counter();";
+        let failing = "{ x = 2; var shadowed = 0; { x = x + 1; shadowed = 5; } }
+            next(); next(); var y = next(); x = y; missing();";
+        for resolve in [false, true] {
+            let mut interpreter = Interpreter::default();
+            let mut scanner = Scanner::new(&format!("{}\n{}", setup, failing));
+            let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+            if resolve {
+                let resolution = crate::Resolver::default().resolve(&stmts).unwrap();
+                interpreter.set_locals(resolution.locals);
+            }
+            let (setup, failing) = stmts.split_at(4);
+            interpreter.interpret(setup).unwrap();
+            assert!(interpreter.interpret_transactional(failing).is_err());
+
+            assert_eq!(lookup(&interpreter, "x"), Value::Number(1.0));
+            assert_eq!(
+                lookup(&interpreter, "shadowed"),
+                Value::String("global".into())
+            );
+            assert!(!defined(&interpreter, "y"));
+            // The closure's own scope was put back too.
+            let mut scanner = Scanner::new("next();");
+            let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+            interpreter.set_locals(Default::default());
+            assert_eq!(
+                interpreter.interpret(&stmts).unwrap(),
+                Some(Value::Number(1.0))
+            );
         }
-        Ok(())
-    }
 
-    fn execute_block(
-        &self,
-        statements: &[Stmt],
-        environment: Rc<RefCell<Environment>>,
-    ) -> Result<(), JBreadErrors> {
-        let mut interpreter = Interpreter::new(environment);
-        statements
-            .iter()
-            .try_for_each(|stmt| interpreter.execute(stmt))?;
-        Ok(())
+        // What succeeds stays.
+        let mut interpreter = Interpreter::default();
+        let mut scanner = Scanner::new("var a = 1; { a = 2; }");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        interpreter.interpret_transactional(&stmts).unwrap();
+        assert_eq!(lookup(&interpreter, "a"), Value::Number(2.0));
     }
-}
-
-impl VisitorExpr for Interpreter {
-    type Result = JBreadResult<Literal>;
 
-    fn visit_expr_binary(&mut self, expr: &crate::ast::Binary) -> Self::Result {
-        let left = self
-            .evalute(&expr.left)?
-            .value
-            .ok_or(self.error(&expr.operator, "Left value is not a literal"))?;
-        let right = self
-            .evalute(&expr.right)?
-            .value
-            .ok_or(self.error(&expr.operator, "Right value is not a literal"))?;
-
-        let left_num: JBreadResult<f64> = left.clone().try_into();
-        let right_num: JBreadResult<f64> = right.clone().try_into();
-
-        let expr = match expr.operator.token_type {
-            // For number
-            TokenTypes::Minus => LiteralEnum::Number(left_num? - right_num?),
-            TokenTypes::Star => LiteralEnum::Number(left_num? * right_num?),
-            TokenTypes::Greater => LiteralEnum::Boolean(left_num? > right_num?),
-            TokenTypes::GreaterEqual => LiteralEnum::Boolean(left_num? >= right_num?),
-            TokenTypes::Less => LiteralEnum::Boolean(left_num? < right_num?),
-            TokenTypes::LessEqual => LiteralEnum::Boolean(left_num? <= right_num?),
-            // For all types
-            TokenTypes::BangEqual => LiteralEnum::Boolean(left != right),
-            TokenTypes::EqualEqual => LiteralEnum::Boolean(left == right),
-            // For 0/0 division
-            TokenTypes::Slash => match (left_num, right_num) {
-                (Ok(left), Ok(right)) => {
-                    if right == 0.0 && left == 0.0 {
-                        LiteralEnum::NaN
-                    } else {
-                        LiteralEnum::Number(left / right)
-                    }
-                }
-                _ => return Err(self.error(&expr.operator, "Cannot divide non-number")),
-            },
-            // For addition and string concat
-            TokenTypes::Plus => match (left.clone(), right.clone()) {
-                (LiteralEnum::String(_), LiteralEnum::String(_)) => {
-                    let left_str: String = left.try_into()?;
-                    let right_str: String = right.try_into()?;
-                    LiteralEnum::String(left_str + &right_str)
-                }
-                (LiteralEnum::Number(_), LiteralEnum::Number(_)) => {
-                    LiteralEnum::Number(left_num? + right_num?)
+    #[test]
+    fn test_const_reassignment_fails() {
+        for (source, line, original) in [
+            ("const PI = 2.5;\nPI = 3;", 2, 2.5),
+            ("const PI = 2.5;\n{\n  {\n    PI = PI + 1; } }", 4, 2.5),
+            ("const PI = 3;\nvar a = 1;\na, PI = 2, 4;", 3, 3.0),
+            ("{ const PI = 3;\n  fun f() { PI = 4; }\n  f(); }", 2, 3.0),
+        ] {
+            for (interpreter, result) in [run_source(source), run_resolved(source)] {
+                let error = result.unwrap_err();
+                assert_eq!(error.kind(), &ErrorKind::ConstantAssignment, "{}", source);
+                assert!(
+                    error
+                        .to_string()
+                        .starts_with(&format!("\"Cannot assign to constant 'PI'\" at {}:", line)),
+                    "{}",
+                    error
+                );
+                // The constant is unchanged wherever it's still in scope.
+                let name = Token::new(TokenTypes::Identifier, "PI".to_string(), None, 1);
+                if let Ok(value) = interpreter.environment.borrow().get(&name) {
+                    assert_eq!(value, Value::Number(original));
                 }
-                _ => return Err(self.error(&expr.operator, "Invalid operands")),
-            },
-
-            _ => return Err(self.error(&expr.operator, "Invalid operator for binary expression")),
-        };
+            }
+        }
+    }
 
-        Ok(Literal { value: Some(expr) })
+    #[test]
+    fn test_const_shadowing() {
+        let source = "const a = 1; var inner; var outer;
+            { var a = 2; a = 3; inner = a; { const a = 4; outer = a; } }
+            { const a = 5; }
+            var b = a;";
+        for (interpreter, result) in [run_source(source), run_resolved(source)] {
+            assert!(result.is_ok(), "{:?}", result);
+            assert_eq!(lookup(&interpreter, "inner"), Value::Number(3.0));
+            assert_eq!(lookup(&interpreter, "outer"), Value::Number(4.0));
+            assert_eq!(lookup(&interpreter, "b"), Value::Number(1.0));
+        }
     }
 
-    fn visit_expr_grouping(&mut self, expr: &crate::ast::Grouping) -> Self::Result {
-        self.evalute(&expr.expression)
+    #[test]
+    fn test_closure_counters_are_independent() {
+        let (interpreter, result) = run_resolved(
+            "fun makeCounter() {
+                var i = 0;
+                fun count() { i = i + 1; return i; }
+                return count;
+            }
+            var a = makeCounter();
+            var b = makeCounter();
+            a(); a();
+            var x = a();
+            var y = b();",
+        );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "x"), Value::Number(3.0));
+        assert_eq!(lookup(&interpreter, "y"), Value::Number(1.0));
     }
 
-    fn visit_expr_literal(&mut self, expr: &crate::ast::Literal) -> Self::Result {
-        Ok(expr.to_owned())
+    #[test]
+    fn test_closure_outlives_block() {
+        let (interpreter, result) = run_resolved(
+            "var get;
+            { var captured = \"kept\"; fun f() { return captured; } get = f; }
+            var captured = \"global\";
+            var out = get();",
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            lookup(&interpreter, "out"),
+            Value::String("kept".to_string())
+        );
     }
 
-    fn visit_expr_unary(&mut self, expr: &crate::ast::Unary) -> Self::Result {
-        let right_value = self
-            .evalute(&expr.right)?
-            .value
-            .ok_or(self.error(&expr.operator, "Right value is not a literal"))?;
-
-        let expr = match expr.operator.token_type {
-            TokenTypes::Minus => LiteralEnum::Number(-right_value.try_into()?),
-            TokenTypes::Bang => LiteralEnum::Boolean(!right_value.try_into()?),
-            _ => return Err(self.error(&expr.operator, "Invalid operator for unary expression")),
-        };
+    #[test]
+    fn test_function_sees_own_name() {
+        let (interpreter, result) =
+            run_resolved("var same; { fun f() { return f; } var g = f(); same = g == f; }");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "same"), Value::Bool(true));
+    }
 
-        Ok(Literal { value: Some(expr) })
+    #[test]
+    fn test_return_stops_loops_and_blocks() {
+        let (interpreter, result) = run_resolved(
+            "var n = 0; fun f() { repeat (10) { n = n + 1; { return n; } } } var out = f();",
+        );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "n"), Value::Number(1.0));
+        assert_eq!(lookup(&interpreter, "out"), Value::Number(1.0));
     }
 
-    fn visit_expr_variable(&mut self, expr: &crate::ast::Variable) -> Self::Result {
-        match self.environment.borrow().get(&expr.name) {
-            Ok(value) => Ok(Literal {
-                value: value.to_owned(),
-            }),
-            Err(err) => Err(err),
-        }
+    #[test]
+    fn test_call_errors() {
+        let (_, result) = run_resolved("var a = 1; a();");
+        assert_eq!(result.unwrap_err().kind(), &ErrorKind::NotCallable);
+
+        let (_, result) = run_resolved("fun f(a) {} f(1, 2);");
+        assert_eq!(result.unwrap_err().kind(), &ErrorKind::ArityMismatch);
     }
 
-    fn visit_expr_assign(&mut self, expr: &crate::ast::Assign) -> Self::Result {
-        let evaluated = self.evalute(&expr.value)?;
-        self.environment
-            .borrow_mut()
-            .assign(&expr.name, evaluated.value.clone())?;
-        Ok(evaluated)
+    #[test]
+    fn test_class_fields_and_methods() {
+        let (interpreter, result) = run_resolved(
+            "class Counter {
+                init(start) { this.count = start; }
+                bump() { this.count = this.count + 1; return this; }
+            }
+            var counter = Counter(10);
+            counter.bump().bump();
+            counter.label = \"clicks\";
+            var count = counter.count;
+            var label = counter.label;",
+        );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "count"), Value::Number(12.0));
+        assert_eq!(
+            lookup(&interpreter, "label"),
+            Value::String("clicks".to_string())
+        );
     }
-}
 
-impl VisitorStmt for Interpreter {
-    type Result = JBreadResult<()>;
+    #[test]
+    fn test_bound_method_keeps_this() {
+        let (interpreter, result) = run_resolved(
+            "class Box { get() { return this.value; } }
+            var box = Box();
+            box.value = 1;
+            var get = box.get;
+            box.value = 2;
+            var out = get();",
+        );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "out"), Value::Number(2.0));
+    }
 
-    fn visit_stmt_expression(&mut self, expr: &crate::ast::Expression) -> Self::Result {
-        self.evalute(&expr.expression)?;
-        Ok(())
+    #[test]
+    fn test_undefined_method() {
+        let (_, result) = run_resolved("class A {}\nvar a = A();\na.missing();");
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UndefinedProperty);
+        assert_eq!(
+            error.to_string(),
+            "\"Undefined property 'missing'\" at 3:3 in missing"
+        );
     }
 
-    fn visit_stmt_print(&mut self, expr: &crate::ast::Print) -> Self::Result {
-        let value = self.evalute(&expr.expression)?;
-        println!("{:?}", value);
-        Ok(())
+    #[test]
+    fn test_error_lines() {
+        let error = |source: &str| run_resolved(source).1.unwrap_err().to_string();
+        assert_eq!(
+            error("var a = 1;\nvar b = 2;\nprint a + c;\nprint a;\nprint b;"),
+            "\"Undefined variable\" at 3:11 in c"
+        );
+        assert_eq!(
+            error("var a = 1;\nprint (a * (2 + -\"x\"));"),
+            "\"Operand must be a number\" at 2:17 in -"
+        );
+        // An operand on a later line than its operator still blames the
+        // operator's line.
+        assert_eq!(
+            error("var a = 1;\nprint a +\n  nil;"),
+            "\"Right value is not a literal\" at 2:9 in +"
+        );
     }
 
-    fn visit_stmt_var(&mut self, stmt: &crate::ast::Var) -> Self::Result {
-        let expr = match &stmt.initializer {
-            Some(expr) => self.evalute(expr)?,
-            None => Literal { value: None },
-        };
+    #[test]
+    fn test_property_on_non_instance() {
+        let (_, result) = run_resolved("var a = 1; a.b = 2;");
+        assert_eq!(
+            result.unwrap_err().kind(),
+            &ErrorKind::TypeMismatch {
+                expected: "instance".to_string(),
+                found: "number".to_string()
+            }
+        );
 
-        self.environment
-            .borrow_mut()
-            .define(&stmt.name.lexeme, expr.value);
-        Ok(())
+        let (_, result) = run_resolved("var a = \"s\";\nprint a.b.c;");
+        let error = result.unwrap_err().to_string();
+        assert!(
+            error.contains("Only instances have properties"),
+            "{}",
+            error
+        );
+        assert!(error.contains(" at 2:"), "{}", error);
     }
 
-    fn visit_stmt_block(&mut self, expr: &crate::ast::Block) -> Self::Result {
-        self.execute_block(
-            &expr.statements,
-            Rc::new(RefCell::new(Environment::new(self.environment.clone()))),
-        )
+    fn list(values: &[f64]) -> Value {
+        let elements = values.iter().map(|value| Value::Number(*value)).collect();
+        Value::list(elements)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::{Interpreter, VisitorExpr, VisitorStmt};
-    use crate::{
-        ast::{Assign, Binary, Expr, Grouping, Literal, Print, Unary, Var, Variable},
-        Literal as LiteralEnum, Token, TokenTypes,
-    };
 
     #[test]
-    fn test_binary_str_concat() {
-        let expr = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::String("Hello".to_string())),
-            })),
-            operator: Token::new(TokenTypes::Plus, "+".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::String(" World!".to_string())),
-            })),
-        };
-        let mut interpreter = Interpreter::default();
+    fn test_nan_semantics() {
+        let nan = Value::NaN;
+        let boolean = |value| Value::Bool(value);
+        for (expression, expected) in [
+            ("NaN + 1", nan.clone()),
+            ("1 + NaN", nan.clone()),
+            ("NaN - 1", nan.clone()),
+            ("NaN * 0", nan.clone()),
+            ("NaN / 1", nan.clone()),
+            ("1 / NaN", nan.clone()),
+            ("NaN ** 2", nan.clone()),
+            ("2 ** NaN", nan.clone()),
+            ("-NaN", nan.clone()),
+            ("-(0 / 0)", nan.clone()),
+            ("(0 / 0) + inf", nan.clone()),
+            ("NaN > 1", boolean(false)),
+            ("NaN >= NaN", boolean(false)),
+            ("1 < NaN", boolean(false)),
+            ("NaN <= inf", boolean(false)),
+            ("NaN == NaN", boolean(false)),
+            ("NaN != NaN", boolean(true)),
+            ("NaN == 1", boolean(false)),
+            ("1 != NaN", boolean(true)),
+            ("0 / 0 == 0 / 0", boolean(false)),
+            ("[NaN] == [NaN]", boolean(false)),
+        ] {
+            let (interpreter, result) = run_source(&format!("var result = {};", expression));
+            assert!(result.is_ok(), "{}", expression);
+            assert_eq!(lookup(&interpreter, "result"), expected, "{}", expression);
+        }
 
-        let parsed_binary_expr = interpreter.visit_expr_binary(&expr);
-        assert!(parsed_binary_expr.is_ok());
         assert_eq!(
-            parsed_binary_expr.unwrap().value,
-            Some(LiteralEnum::String("Hello World!".to_string()))
+            run_printed("print NaN; print -NaN; print NaN + 1; print [0 / 0];"),
+            "NaN\nNaN\nNaN\n[NaN]\n"
         );
+
+        for (source, message) in [
+            (
+                "NaN + \"a\";",
+                "\"Invalid operands: NaN and \"a\"\" at 1:5 in +",
+            ),
+            (
+                "NaN / \"a\";",
+                "\"Cannot divide non-number: NaN and \"a\"\" at 1:5 in /",
+            ),
+            (
+                "\"a\\b\" < 2;",
+                "\"Operands must be two numbers or two strings: \"a\\\\b\" and 2.0\" at 1:7 in <",
+            ),
+            (
+                "NaN | 1;",
+                "\"Bitwise operand NaN is not an integer that fits in 64 bits\" at 1:5 in |",
+            ),
+        ] {
+            let (_, result) = run_source(source);
+            assert_eq!(result.unwrap_err().to_string(), message, "{}", source);
+        }
     }
 
     #[test]
-    fn test_binary_num_add() {
-        let expr = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(1.0)),
-            })),
-            operator: Token::new(TokenTypes::Plus, "+".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
-        };
-        let mut interpreter = Interpreter::default();
+    fn test_power() {
+        let (interpreter, result) = run_source(
+            "var a = 2 ** 10; var b = -2 ** 2; var c = 2 ** 3 ** 2; var d = 0 ** 0; var e = 4 ** -0.5;",
+        );
+        assert!(result.is_ok());
+        for (name, value) in [
+            ("a", 1024.0),
+            ("b", -4.0),
+            ("c", 512.0),
+            ("d", 1.0),
+            ("e", 0.5),
+        ] {
+            assert_eq!(lookup(&interpreter, name), Value::Number(value));
+        }
 
-        let parsed_binary_expr = interpreter.visit_expr_binary(&expr);
-        assert!(parsed_binary_expr.is_ok());
-        assert_eq!(
-            parsed_binary_expr.unwrap().value,
-            Some(LiteralEnum::Number(3.0))
+        let (interpreter, result) = run_source("var a = (-8) ** (1 / 3);");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "a"), Value::NaN);
+    }
+
+    #[test]
+    fn test_bitwise_operators() {
+        let (interpreter, result) = run_source(
+            "var masked = 12 & 10; var joined = 12 | 3; var flipped = 12 ^ 10; var inverted = ~5; \
+             var shl = 1 << 62; var shr = -16 >> 2; var mixed = 1 | 2 & 3; var big = 2 ** 62 | 1;",
         );
+        assert!(result.is_ok());
+        for (name, value) in [
+            ("masked", 8.0),
+            ("joined", 15.0),
+            ("flipped", 6.0),
+            ("inverted", -6.0),
+            ("shl", 4611686018427387904.0),
+            ("shr", -4.0),
+            ("mixed", 3.0),
+            ("big", 4611686018427387905.0),
+        ] {
+            assert_eq!(lookup(&interpreter, name), Value::Number(value));
+        }
     }
 
     #[test]
-    fn test_0_0_division() {
-        let expr = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(0.0)),
-            })),
-            operator: Token::new(TokenTypes::Slash, "/".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(0.0)),
-            })),
-        };
-        let mut interpreter = Interpreter::default();
+    fn test_bitwise_errors() {
+        for (source, kind, message) in [
+            (
+                "1.5 & 1;",
+                ErrorKind::InvalidOperand,
+                "\"Bitwise operand 1.5 is not an integer that fits in 64 bits\" at 1:5 in &",
+            ),
+            (
+                "~inf;",
+                ErrorKind::InvalidOperand,
+                "\"Bitwise operand inf is not an integer that fits in 64 bits\" at 1:1 in ~",
+            ),
+            (
+                "1 | 2 ** 63;",
+                ErrorKind::InvalidOperand,
+                "\"Bitwise operand 9223372036854776000 is not an integer that fits in 64 bits\" at 1:3 in |",
+            ),
+            (
+                "1 << 64;",
+                ErrorKind::InvalidOperand,
+                "\"Cannot shift by 64, expected 0 to 63\" at 1:3 in <<",
+            ),
+            (
+                "1 >> -1;",
+                ErrorKind::InvalidOperand,
+                "\"Cannot shift by -1, expected 0 to 63\" at 1:3 in >>",
+            ),
+            (
+                "\"a\" ^ 1;",
+                ErrorKind::TypeMismatch {
+                    expected: "number".to_string(),
+                    found: "string".to_string(),
+                },
+                "\"Bitwise operands must be numbers\" at 1:5 in ^",
+            ),
+        ] {
+            let (_, result) = run_source(source);
+            let error = result.unwrap_err();
+            assert_eq!(error.kind(), &kind, "{}", source);
+            assert_eq!(error.to_string(), message);
+        }
+    }
 
-        let parsed_binary_expr = interpreter.visit_expr_binary(&expr);
-        assert!(parsed_binary_expr.is_ok());
-        assert_eq!(parsed_binary_expr.unwrap().value, Some(LiteralEnum::NaN));
+    #[test]
+    fn test_chained_assignment() {
+        let (interpreter, result) = run_source("var a; var b; var c = a = b = 3;");
+        assert!(result.is_ok());
+        for name in ["a", "b", "c"] {
+            assert_eq!(lookup(&interpreter, name), Value::Number(3.0));
+        }
     }
 
     #[test]
-    fn test_binary_multipication() {
-        let expr = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
-            operator: Token::new(TokenTypes::Star, "*".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
-        };
-        let mut interpreter = Interpreter::default();
+    fn test_multiple_assignment_swaps() {
+        let (interpreter, result) = run_source("var a, b = 1, 2; a, b = b, a;");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "a"), Value::Number(2.0));
+        assert_eq!(lookup(&interpreter, "b"), Value::Number(1.0));
+
+        let (interpreter, result) =
+            run_resolved("var a = 0; { var x, y = 1, 2; x, y = y, x; a = x * 10 + y; }");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "a"), Value::Number(21.0));
+    }
 
-        let parsed_binary_expr = interpreter.visit_expr_binary(&expr);
-        assert!(parsed_binary_expr.is_ok());
-        assert_eq!(
-            parsed_binary_expr.unwrap().value,
-            Some(LiteralEnum::Number(4.0))
+    #[test]
+    fn test_list_literals() {
+        let (interpreter, result) =
+            run_source("var empty = []; var xs = [1, 2 + 1, [4]]; var inner = xs[2][0];");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "empty"), list(&[]));
+        assert_eq!(lookup(&interpreter, "inner"), Value::Number(4.0));
+        assert_eq!(lookup(&interpreter, "xs").to_string(), "[1, 3, [4]]");
+    }
+
+    #[test]
+    fn test_list_index_assignment_and_aliasing() {
+        let (interpreter, result) = run_source(
+            "var xs = [1, 2, 3]; var ys = xs; ys[0] = 5; xs[2] = xs[0] + ys[1]; var zs = xs + [nil];",
         );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "xs"), list(&[5.0, 2.0, 7.0]));
+        assert_eq!(lookup(&interpreter, "ys"), list(&[5.0, 2.0, 7.0]));
+        assert_eq!(lookup(&interpreter, "zs").to_string(), "[5, 2, 7, nil]");
     }
 
     #[test]
-    fn test_binary_division() {
-        let expr = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(4.0)),
-            })),
-            operator: Token::new(TokenTypes::Slash, "/".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
-        };
-        let mut interpreter = Interpreter::default();
+    fn test_concatenation_copies() {
+        let (interpreter, result) =
+            run_source("var a = [1]; var b = a + [2]; b[0] = 9; var n = len(b);");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "a"), list(&[1.0]));
+        assert_eq!(lookup(&interpreter, "b"), list(&[9.0, 2.0]));
+        assert_eq!(lookup(&interpreter, "n"), Value::Number(2.0));
+    }
 
-        let parsed_binary_expr = interpreter.visit_expr_binary(&expr);
-        assert!(parsed_binary_expr.is_ok());
+    #[test]
+    fn test_map_literals() {
+        let (interpreter, result) = run_source(
+            "var empty = {}; var m = {\"b\": 2, \"a\": 1 + 0, 1: \"one\", true: [1],}; \
+             var a = m[\"a\"]; var missing = type(m[\"c\"]); var n = len(m); var ks = keys(m);",
+        );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "empty").to_string(), "{}");
+        assert_eq!(lookup(&interpreter, "a"), Value::Number(1.0));
+        assert_eq!(lookup(&interpreter, "missing").to_string(), "nil");
+        assert_eq!(lookup(&interpreter, "n"), Value::Number(4.0));
+        assert_eq!(
+            lookup(&interpreter, "m").to_string(),
+            "{true: [1], 1: one, a: 1, b: 2}"
+        );
         assert_eq!(
-            parsed_binary_expr.unwrap().value,
-            Some(LiteralEnum::Number(2.0))
+            lookup(&interpreter, "ks").repr(),
+            "[true, 1.0, \"a\", \"b\"]"
         );
     }
 
     #[test]
-    fn test_binary_subtraction() {
-        let expr = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(4.0)),
-            })),
-            operator: Token::new(TokenTypes::Minus, "-".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
-        };
-        let mut interpreter = Interpreter::default();
+    fn test_map_index_assignment_and_aliasing() {
+        let (interpreter, result) = run_source(
+            "var m = {\"a\": 1}; var alias = m; alias[\"c\"] = 3; m[\"a\"] = m[\"c\"] + 1; \
+             m[1] = \"number\"; m[\"1\"] = \"string\"; m[-0] = \"zero\"; var zero = m[0];",
+        );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "m"), lookup(&interpreter, "alias"));
+        assert_eq!(
+            lookup(&interpreter, "m").to_string(),
+            "{0: zero, 1: number, 1: string, a: 4, c: 3}"
+        );
+        assert_eq!(lookup(&interpreter, "zero").to_string(), "zero");
 
-        let parsed_binary_expr = interpreter.visit_expr_binary(&expr);
-        assert!(parsed_binary_expr.is_ok());
+        let (_, result) = run_source("var m = {}; m[NaN] = 1;");
+        assert_eq!(result.unwrap_err().kind(), &ErrorKind::InvalidIndex);
+        let (_, result) = run_source("var m = {[1]: 1};");
         assert_eq!(
-            parsed_binary_expr.unwrap().value,
-            Some(LiteralEnum::Number(2.0))
+            result.unwrap_err().kind(),
+            &ErrorKind::TypeMismatch {
+                expected: "string, number or boolean".to_string(),
+                found: "list".to_string()
+            }
         );
     }
 
     #[test]
-    fn test_binary_greater() {
-        let expr = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(4.0)),
-            })),
-            operator: Token::new(TokenTypes::Greater, ">".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
-        };
-        let mut interpreter = Interpreter::default();
+    fn test_list_index_errors() {
+        let (_, result) = run_source("var xs = [1, 2];\nxs[2];");
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::IndexOutOfBounds);
+        assert_eq!(
+            error.to_string(),
+            "\"Index 2 out of bounds for list of length 2\" at 2:3 in ["
+        );
 
-        let parsed_binary_expr = interpreter.visit_expr_binary(&expr);
-        assert!(parsed_binary_expr.is_ok());
+        let (_, result) = run_source("var xs = [1, 2]; xs[-1] = 0;");
+        assert_eq!(result.unwrap_err().kind(), &ErrorKind::IndexOutOfBounds);
+
+        let (_, result) = run_source("var xs = [1, 2]; xs[0.5];");
+        assert_eq!(result.unwrap_err().kind(), &ErrorKind::InvalidIndex);
+
+        let (_, result) = run_source("var n = 1; n[0];");
         assert_eq!(
-            parsed_binary_expr.unwrap().value,
-            Some(LiteralEnum::Boolean(true))
+            result.unwrap_err().kind(),
+            &ErrorKind::TypeMismatch {
+                expected: "list, map or string".to_string(),
+                found: "number".to_string()
+            }
         );
     }
 
     #[test]
-    fn test_binary_greater_equal() {
-        let expr = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(4.0)),
-            })),
-            operator: Token::new(TokenTypes::GreaterEqual, ">=".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
-        };
-        let mut interpreter = Interpreter::default();
-
-        let parsed_binary_expr = interpreter.visit_expr_binary(&expr);
-        assert!(parsed_binary_expr.is_ok());
+    fn test_assert() {
+        let (interpreter, result) =
+            run_source("var a = 1; assert a == 1; assert \"yes\", \"unused\"; a = 2;");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "a"), Value::Number(2.0));
+
+        let (_, result) = run_source("var a = 1;\nassert a == 2;");
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::AssertionFailed);
         assert_eq!(
-            parsed_binary_expr.unwrap().value,
-            Some(LiteralEnum::Boolean(true))
+            error.to_string(),
+            "\"Assertion failed: (== a 2)\" at 2:1 in assert"
         );
+
+        let (_, result) = run_source("var a = 1; assert nil, \"a is \" + \"wrong\";");
+        let error = result.unwrap_err();
+        assert!(error.to_string().contains("Assertion failed: a is wrong"));
     }
 
     #[test]
-    fn test_binary_less() {
-        let expr = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(4.0)),
-            })),
-            operator: Token::new(TokenTypes::Less, "<".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
-        };
-        let mut interpreter = Interpreter::default();
+    fn test_interpolation() {
+        assert_eq!(
+            run_printed(
+                "var a = 1; var b = 2; fun f(x, y) { return x * y; }\n\
+                 print \"sum is ${a + b}\";\n\
+                 print \"${a + (b * 2)} and ${f(3, 4)}${\"!\"}\";\n\
+                 print \"${nil} ${NaN} ${1 / 0} ${[1, \"a\"]} ${\"in ${b}\"}\";\n\
+                 print \"$5 {plain}\";"
+            ),
+            "sum is 3\n5 and 12!\nnil NaN inf [1, a] in 2\n$5 {plain}\n"
+        );
 
-        let parsed_binary_expr = interpreter.visit_expr_binary(&expr);
-        assert!(parsed_binary_expr.is_ok());
+        // Errors in the code point into the string.
+        let (_, result) = run_source("var a = 1;\nprint \"a is ${a + -\"x\"}\";");
         assert_eq!(
-            parsed_binary_expr.unwrap().value,
-            Some(LiteralEnum::Boolean(false))
+            result.unwrap_err().to_string(),
+            "\"Operand must be a number\" at 2:19 in -"
         );
+        let mut scanner = Scanner::new("print \"${}\";");
+        let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnexpectedEof);
     }
 
     #[test]
-    fn test_unary_negation() {
-        let expr = Unary {
-            operator: Token::new(TokenTypes::Minus, "-".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
+    fn test_step_limit() {
+        let parse = |source: &str| {
+            let mut scanner = Scanner::new(source);
+            Parser::new(scanner.scan_tokens()).parse().unwrap()
         };
-        let mut interpreter = Interpreter::default();
-
-        let parsed_unary_expr = interpreter.visit_expr_unary(&expr);
-        assert!(parsed_unary_expr.is_ok());
+        let endless = parse("var n = 0;\nrepeat (1e12) {\n    n = n + 1;\n}");
+        let error = Interpreter::default()
+            .with_step_limit(10_000)
+            .interpret(&endless)
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::BudgetExceeded);
         assert_eq!(
-            parsed_unary_expr.unwrap().value,
-            Some(LiteralEnum::Number(-2.0))
+            error.to_string(),
+            "\"Execution budget exceeded after 10000 steps\" at line: 3 in program"
         );
+
+        // Each run gets the whole budget.
+        let stmts = parse("var a = 1; repeat (10) a = a * 2; print a;");
+        let mut interpreter = Interpreter::default()
+            .with_step_limit(100)
+            .with_output(OutputBuffer::new());
+        interpreter.interpret(&stmts).unwrap();
+        interpreter.interpret(&stmts).unwrap();
+
+        // Running out of stack is still told apart.
+        let deep = parse("fun f(n) { return f(n + 1); } f(0);");
+        let error = Interpreter::default()
+            .with_step_limit(u64::MAX)
+            .interpret(&deep)
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::StackOverflow);
     }
 
     #[test]
-    fn test_grouping() {
-        let expr = Grouping {
-            expression: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
-        };
-        let mut interpreter = Interpreter::default();
+    fn test_assert_message_is_lazy() {
+        let (interpreter, result) = run_source(
+            "var calls = 0; fun note() { calls = calls + 1; return \"m\"; }
+            assert true, note(); assert 1 < 2, note();",
+        );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "calls"), Value::Number(0.0));
 
-        let parsed_grouping_expr = interpreter.visit_expr_grouping(&expr);
-        assert!(parsed_grouping_expr.is_ok());
-        assert_eq!(
-            parsed_grouping_expr.unwrap().value,
-            Some(LiteralEnum::Number(2.0))
+        let (interpreter, result) = run_source(
+            "var calls = 0; fun note() { calls = calls + 1; return \"m\"; } assert false, note();",
         );
+        assert!(result.is_err());
+        assert_eq!(lookup(&interpreter, "calls"), Value::Number(1.0));
     }
 
-    #[test]
-    fn test_string_and_int_addition() {
-        let expr = Binary {
-            left: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::String("Hello".to_string())),
-            })),
-            operator: Token::new(TokenTypes::Plus, "+".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
-        };
-        let mut interpreter = Interpreter::default();
+    fn string(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
 
-        let parsed_binary_expr = interpreter.visit_expr_binary(&expr);
-        assert!(parsed_binary_expr.is_err());
+    #[test]
+    fn test_string_indexing_by_character() {
+        let (interpreter, result) =
+            run_source("var s = \"héllo\"; var first = s[0]; var second = s[1]; var last = s[4];");
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "first"), string("h"));
+        assert_eq!(lookup(&interpreter, "second"), string("é"));
+        assert_eq!(lookup(&interpreter, "last"), string("o"));
     }
 
     #[test]
-    fn test_var_fetching_without_initalization() {
-        let expr = Variable {
-            name: Token::new(TokenTypes::Identifier, "a".to_string(), None, 1),
-        };
-        let mut interpreter = Interpreter::default();
+    fn test_string_index_errors() {
+        let (_, result) = run_source("var s = \"héllo\";\ns[5];");
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::IndexOutOfBounds);
+        assert_eq!(
+            error.to_string(),
+            "\"Index 5 out of bounds for string of length 5\" at 2:2 in ["
+        );
 
-        let parsed_var_expr = interpreter.visit_expr_variable(&expr);
-        assert!(parsed_var_expr.is_err());
+        let (_, result) = run_source("\"\"[0];");
+        assert_eq!(result.unwrap_err().kind(), &ErrorKind::IndexOutOfBounds);
+
+        let (_, result) = run_source("\"abc\"[1.5];");
+        assert_eq!(result.unwrap_err().kind(), &ErrorKind::InvalidIndex);
+
+        // Strings can't be changed in place.
+        let (_, result) = run_source("var s = \"abc\"; s[0] = \"x\";");
+        assert_eq!(
+            result.unwrap_err().kind(),
+            &ErrorKind::TypeMismatch {
+                expected: "list or map".to_string(),
+                found: "string".to_string()
+            }
+        );
     }
 
     #[test]
-    fn test_var_assignment_with_value() {
-        let expr = Variable {
-            name: Token::new(TokenTypes::Identifier, "a".to_string(), None, 1),
-        };
-        let mut interpreter = Interpreter::default();
-        interpreter
-            .environment
-            .borrow_mut()
-            .define("a", Some(LiteralEnum::Number(2.0)));
+    fn test_string_natives() {
+        let (interpreter, result) = run_source(
+            "var s = \"Héllo\";
+            var middle = substr(s, 1, 3); var all = substr(s, 0, 5);
+            var none = substr(s, 5, 0); var empty = substr(\"\", 0, 0);
+            var up = upper(s); var down = lower(s); var blank = upper(\"\");
+            var at = index_of(s, \"llo\"); var missing = index_of(s, \"x\");
+            var start = index_of(s, \"\"); var in_empty = index_of(\"\", \"a\");",
+        );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "middle"), string("éll"));
+        assert_eq!(lookup(&interpreter, "all"), string("Héllo"));
+        assert_eq!(lookup(&interpreter, "none"), string(""));
+        assert_eq!(lookup(&interpreter, "empty"), string(""));
+        assert_eq!(lookup(&interpreter, "up"), string("HÉLLO"));
+        assert_eq!(lookup(&interpreter, "down"), string("héllo"));
+        assert_eq!(lookup(&interpreter, "blank"), string(""));
+        assert_eq!(lookup(&interpreter, "at"), Value::Number(2.0));
+        assert_eq!(lookup(&interpreter, "missing"), Value::Number(-1.0));
+        assert_eq!(lookup(&interpreter, "start"), Value::Number(0.0));
+        assert_eq!(lookup(&interpreter, "in_empty"), Value::Number(-1.0));
+    }
 
-        let parsed_var_expr = interpreter.visit_expr_variable(&expr);
-        assert!(parsed_var_expr.is_ok());
-        assert_eq!(
-            parsed_var_expr.unwrap().value,
-            Some(LiteralEnum::Number(2.0))
+    #[test]
+    fn test_equality_natives() {
+        let (interpreter, result) = run_source(
+            "var exact = 0.1 + 0.2 == 0.3; var same = equals(0.1 + 0.2, 0.3); \
+             var close = approx_equals(0.1 + 0.2, 0.3); var loose = approx_equals(1, 1.1); \
+             var wide = approx_equals(1, 1.1, 0.2); var infinite = approx_equals(inf, inf); \
+             var nans = equals(NaN, NaN); var approx_nans = approx_equals(NaN, NaN); \
+             var nan_number = approx_equals(NaN, 1, 1e300); var nils = equals(nil, nil); \
+             var lists = equals([1, [2, nil]], [1, [2, nil]]); var lengths = equals([1], [1, 2]);",
         );
+        assert!(result.is_ok());
+        for (name, value) in [
+            ("exact", false),
+            ("same", false),
+            ("close", true),
+            ("loose", false),
+            ("wide", true),
+            ("infinite", true),
+            ("nans", false),
+            ("approx_nans", false),
+            ("nan_number", false),
+            ("nils", true),
+            ("lists", true),
+            ("lengths", false),
+        ] {
+            assert_eq!(lookup(&interpreter, name), Value::Bool(value), "{}", name);
+        }
     }
 
     #[test]
-    fn test_var_assignment_with_value_and_assignment() {
-        let expr = Variable {
-            name: Token::new(TokenTypes::Identifier, "a".to_string(), None, 1),
-        };
-        let mut interpreter = Interpreter::default();
-        interpreter
-            .environment
-            .borrow_mut()
-            .define("a", Some(LiteralEnum::Number(2.0)));
+    fn test_nil_equality() {
+        let (interpreter, result) = run_source(
+            "var both = nil == nil; var zero = nil == 0; var empty = nil == \"\"; \
+             var text = 1 == \"1\"; var boolean = true == 1; var neither = nil != nil; \
+             var other = nil != 3; var native = equals(nil, 0);",
+        );
+        assert!(result.is_ok(), "{:?}", result);
+        for (name, value) in [
+            ("both", true),
+            ("zero", false),
+            ("empty", false),
+            ("text", false),
+            ("boolean", false),
+            ("neither", false),
+            ("other", true),
+            ("native", false),
+        ] {
+            assert_eq!(lookup(&interpreter, name), Value::Bool(value), "{}", name);
+        }
 
-        let parsed_var_expr = interpreter.visit_expr_variable(&expr);
-        assert!(parsed_var_expr.is_ok());
-        assert_eq!(
-            parsed_var_expr.unwrap().value,
-            Some(LiteralEnum::Number(2.0))
+        // Other operators still refuse it.
+        let (_, result) = run_source("var a = nil < 1;");
+        assert!(result.is_err());
+
+        // A variable holding nil reads as nil, while one declared without a
+        // value still can't be read.
+        let (interpreter, result) = run_source(
+            "var x = nil; var stored = x == nil; var n = num(\"abc\"); var parsed = n != nil; \
+             fun f(a) { return a == nil; } var argument = f(nil); var y; y = nil; var assigned = y == nil;",
         );
+        assert!(result.is_ok(), "{:?}", result);
+        for (name, value) in [
+            ("stored", true),
+            ("parsed", false),
+            ("argument", true),
+            ("assigned", true),
+        ] {
+            assert_eq!(lookup(&interpreter, name), Value::Bool(value), "{}", name);
+        }
+        let (_, result) = run_source("var x; var y = x == nil;");
+        assert_eq!(result.unwrap_err().kind(), &ErrorKind::UndefinedVariable);
+        let (_, result) = run_source("var a, b; a = 1; var c = b == nil;");
+        assert_eq!(result.unwrap_err().kind(), &ErrorKind::UndefinedVariable);
+    }
 
-        let assignment_expr = Assign {
-            name: Token::new(TokenTypes::Identifier, "a".to_string(), None, 1),
-            value: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(3.0)),
-            })),
-        };
-        let parsed_assignment_expr = interpreter.visit_expr_assign(&assignment_expr);
-        assert!(parsed_assignment_expr.is_ok());
-        assert_eq!(
-            parsed_assignment_expr.unwrap().value,
-            Some(LiteralEnum::Number(3.0))
+    #[test]
+    fn test_cyclic_lists() {
+        let (interpreter, result) = run_source(
+            "var xs = [1, 2]; xs[0] = xs; var ys = [1, 2]; ys[0] = ys; var zs = [1, 3]; zs[0] = zs; \
+             var same = xs == ys; var deep = equals(xs, ys); var different = xs == zs;",
         );
+        assert!(result.is_ok());
+        assert_eq!(lookup(&interpreter, "same"), Value::Bool(true));
+        assert_eq!(lookup(&interpreter, "deep"), Value::Bool(true));
+        assert_eq!(lookup(&interpreter, "different"), Value::Bool(false));
+        assert_eq!(lookup(&interpreter, "xs").to_string(), "[[...], 2]");
     }
 
     #[test]
-    fn test_print_statement() {
-        let expr = Expr::Literal(Literal {
-            value: Some(LiteralEnum::Number(2.0)),
-        });
-        let stmt = Print {
-            expression: Box::new(expr),
-        };
-        let mut interpreter = Interpreter::default();
+    fn test_native_arity_ranges() {
+        for (source, kind, message) in [
+            (
+                "approx_equals(1);",
+                ErrorKind::ArityMismatch,
+                "\"Expected 2 to 3 arguments but got 1\" at 1:16 in )",
+            ),
+            (
+                "approx_equals(1, 2, 3, 4);",
+                ErrorKind::ArityMismatch,
+                "\"Expected 2 to 3 arguments but got 4\" at 1:25 in )",
+            ),
+            (
+                "equals(1);",
+                ErrorKind::ArityMismatch,
+                "\"Expected 2 arguments but got 1\" at 1:9 in )",
+            ),
+            (
+                "approx_equals(\"a\", 1);",
+                ErrorKind::InvalidArgument,
+                "\"approx_equals: argument 'a' must be a number\" at 1:21 in approx_equals",
+            ),
+            (
+                "approx_equals(1, nil);",
+                ErrorKind::InvalidArgument,
+                "\"approx_equals: argument 'b' must be a number\" at 1:21 in approx_equals",
+            ),
+            (
+                "approx_equals(1, 1, -1);",
+                ErrorKind::InvalidArgument,
+                "\"approx_equals: argument 'epsilon' must not be negative\" at 1:23 in approx_equals",
+            ),
+        ] {
+            let (_, result) = run_source(source);
+            let error = result.unwrap_err();
+            assert_eq!(error.kind(), &kind, "{}", source);
+            assert_eq!(error.to_string(), message);
+        }
+    }
 
-        let parsed_print_stmt = interpreter.visit_stmt_print(&stmt);
-        assert!(parsed_print_stmt.is_ok());
+    #[test]
+    fn test_string_native_errors() {
+        for (source, message) in [
+            (
+                "substr(\"abc\", 2, 2);",
+                "\"substr: range 2..4 out of bounds for string of length 3\" at 1:19 in substr",
+            ),
+            (
+                "substr(\"\", 0, 1);",
+                "\"substr: range 0..1 out of bounds for string of length 0\" at 1:16 in substr",
+            ),
+            (
+                "substr(\"abc\", -1, 1);",
+                "\"substr: range -1..0 out of bounds for string of length 3\" at 1:20 in substr",
+            ),
+            (
+                "substr(\"abc\", 0.5, 1);",
+                "\"substr: argument 'start' must be a whole number\" at 1:21 in substr",
+            ),
+            (
+                "upper(1);",
+                "\"upper: argument 's' must be a string\" at 1:8 in upper",
+            ),
+            (
+                "index_of(\"abc\", nil);",
+                "\"index_of: argument 'needle' must be a string\" at 1:20 in index_of",
+            ),
+        ] {
+            let (_, result) = run_source(source);
+            let error = result.unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::InvalidArgument);
+            assert_eq!(error.to_string(), message);
+        }
     }
 
     #[test]
-    fn test_var_statement() {
-        let stmt = Var {
-            name: Token::new(TokenTypes::Identifier, "a".to_string(), None, 1),
-            initializer: None,
-        };
+    fn test_runaway_recursion_overflows() {
+        let (interpreter, result) = run_source("fun f() {\n  f();\n}\nf();");
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::StackOverflow);
+        assert_eq!(
+            error.to_string(),
+            "\"Stack overflow: maximum call depth exceeded\" at 2:5 in )"
+        );
+        assert_eq!(error.error().trace().len(), super::MAX_CALL_DEPTH);
+        assert!(interpreter.call_stack().is_empty());
+
+        let mut scanner = Scanner::new("fun f(n) { if (n > 0) f(n - 1); } f(10);");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
         let mut interpreter = Interpreter::default();
+        interpreter.set_max_call_depth(11);
+        interpreter.interpret(&stmts).unwrap();
+        interpreter.set_max_call_depth(10);
+        let error = interpreter.interpret(&stmts).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::StackOverflow);
+    }
 
-        let parsed_var_stmt = interpreter.visit_stmt_var(&stmt);
-        assert!(parsed_var_stmt.is_ok());
+    #[test]
+    fn test_error_backtrace() {
+        let source = "fun c(x) {\n  return x + 1;\n}\nfun b(x) {\n  return c(x);\n}\n\
+            fun a(x) {\n  return b(x);\n}\nprint a(true);";
+        let (mut interpreter, result) = run_source(source);
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.error().trace(),
+            [
+                CallFrame {
+                    function: "c".to_string(),
+                    line: 5
+                },
+                CallFrame {
+                    function: "b".to_string(),
+                    line: 8
+                },
+                CallFrame {
+                    function: "a".to_string(),
+                    line: 10
+                },
+            ]
+        );
+        assert!(error.render(&SourceMap::new(source)).ends_with(
+            "Backtrace, most recent call first:\n  \
+             in c() called at line 5\n  \
+             in b() called at line 8\n  \
+             in a() called at line 10"
+        ));
+
+        // Nothing is left over for the next run, as in the REPL.
+        assert!(interpreter.call_stack().is_empty());
+        let mut scanner = Scanner::new("print b(1);\nprint c(nil);");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        assert!(interpreter.interpret(&stmts[..1]).is_ok());
+        let error = interpreter.interpret(&stmts[1..]).unwrap_err();
+        assert_eq!(error.error().trace().len(), 1);
+        assert!(interpreter.call_stack().is_empty());
+    }
+
+    #[test]
+    fn test_undeclared_assignment() {
+        let (_, result) = run_source("x = 5;");
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UndeclaredAssignment);
+        assert_eq!(
+            error.to_string(),
+            "\"Cannot assign to undeclared variable 'x'; declare it with 'var x = ...'\" at 1:1 in x"
+        );
+
+        let source = "x = 5;\n{\n  var local = 1;\n  { y = x + local; }\n}\nx = x + 1;";
+        for resolve in [false, true] {
+            let mut scanner = Scanner::new(source);
+            let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+            let mut interpreter = Interpreter::default()
+                .with_undeclared_assignment(UndeclaredAssignment::ImplicitGlobal);
+            if resolve {
+                let resolution = crate::Resolver::default().resolve(&stmts).unwrap();
+                interpreter.set_locals(resolution.locals);
+            }
+            interpreter.interpret(&stmts).unwrap();
+            let environment = interpreter.environment.borrow();
+            assert_eq!(environment.get_local("x"), Some(Value::Number(6.0)));
+            assert_eq!(environment.get_local("y"), Some(Value::Number(6.0)));
+        }
+
+        // Reading is as strict as ever.
+        let mut scanner = Scanner::new("print z;\nz = z + 1;");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut interpreter =
+            Interpreter::default().with_undeclared_assignment(UndeclaredAssignment::ImplicitGlobal);
+        for stmt in stmts.chunks(1) {
+            let error = interpreter.interpret(stmt).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::UndefinedVariable);
+        }
     }
 }
@@ -1,4 +1,26 @@
-mod environment;
+mod class;
+pub(crate) mod environment;
+mod function;
+#[cfg(feature = "serde")]
+mod globals;
+mod host;
+mod imports;
 mod interpret;
+mod native;
+mod options;
+mod random;
+mod stats;
+mod trace;
+mod value;
 
+pub use class::*;
+pub use environment::EnvSnapshot;
+pub use function::*;
+pub use host::*;
 pub use interpret::*;
+pub use native::*;
+pub use options::*;
+pub use random::*;
+pub use stats::*;
+pub use trace::*;
+pub use value::*;
@@ -0,0 +1,1190 @@
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+};
+
+use crate::{
+    errors::{Error, ErrorKind, JBreadErrors, JBreadResult},
+    interpreter::{Interpreter, Value},
+    Token,
+};
+
+/// Smallest differences `approx_equals` ignores when not given an epsilon.
+const DEFAULT_EPSILON: f64 = 1e-9;
+
+type NativeFn = fn(&mut Interpreter, &Token, Vec<Value>) -> JBreadResult<Value>;
+
+/// How many arguments a callable accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arity {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Arity {
+    pub const fn exact(count: usize) -> Self {
+        Self {
+            min: count,
+            max: count,
+        }
+    }
+
+    /// Between `min` and `max` arguments, the trailing ones being optional.
+    pub const fn range(min: usize, max: usize) -> Self {
+        Self { min, max }
+    }
+
+    /// `min` arguments or more, the last parameter naming the rest.
+    pub const fn at_least(min: usize) -> Self {
+        Self {
+            min,
+            max: usize::MAX,
+        }
+    }
+
+    pub fn is_variadic(&self) -> bool {
+        self.max == usize::MAX
+    }
+
+    pub fn accepts(&self, count: usize) -> bool {
+        (self.min..=self.max).contains(&count)
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.min)
+        } else if self.is_variadic() {
+            match self.min {
+                0 => write!(f, "any number"),
+                min => write!(f, "at least {}", min),
+            }
+        } else {
+            write!(f, "{} to {}", self.min, self.max)
+        }
+    }
+}
+
+/// A function implemented in Rust, available to every script unless
+/// shadowed by a global of the same name. Optional arguments that weren't
+/// passed are missing from the arguments the function gets.
+#[derive(Clone, Copy)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: Arity,
+    /// Names of the parameters, the optional ones last.
+    pub params: &'static [&'static str],
+    /// One line for the language reference.
+    pub description: &'static str,
+    function: NativeFn,
+}
+
+impl NativeFunction {
+    /// Runs the native, `paren` being the call's closing parenthesis.
+    pub fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> JBreadResult<Value> {
+        (self.function)(interpreter, paren, arguments)
+    }
+}
+
+impl Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "NativeFunction({})", self.name)
+    }
+}
+
+impl Display for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+impl PartialEq for NativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+const NATIVES: &[NativeFunction] = &[
+    NativeFunction {
+        name: "random",
+        arity: Arity::exact(0),
+        params: &[],
+        description: "A random number from 0 up to but excluding 1.",
+        function: random,
+    },
+    NativeFunction {
+        name: "random_range",
+        arity: Arity::exact(2),
+        params: &["lo", "hi"],
+        description: "A random number from `lo` up to but excluding `hi`.",
+        function: random_range,
+    },
+    NativeFunction {
+        name: "random_int",
+        arity: Arity::exact(2),
+        params: &["lo", "hi"],
+        description: "A random whole number from `lo` to `hi`, both included.",
+        function: random_int,
+    },
+    NativeFunction {
+        name: "random_seed",
+        arity: Arity::exact(1),
+        params: &["n"],
+        description: "Seeds the random numbers drawn after it, making them the same on every run.",
+        function: random_seed,
+    },
+    NativeFunction {
+        name: "set_seed",
+        arity: Arity::exact(1),
+        params: &["n"],
+        description: "The older name of `random_seed`.",
+        function: set_seed,
+    },
+    NativeFunction {
+        name: "len",
+        arity: Arity::exact(1),
+        params: &["value"],
+        description: "The length of a list or map, or the number of characters in a string.",
+        function: len,
+    },
+    NativeFunction {
+        name: "keys",
+        arity: Arity::exact(1),
+        params: &["map"],
+        description: "A list of the keys of `map`, in the order it prints them.",
+        function: keys,
+    },
+    NativeFunction {
+        name: "substr",
+        arity: Arity::exact(3),
+        params: &["s", "start", "len"],
+        description: "The `len` characters of `s` starting at character `start`.",
+        function: substr,
+    },
+    NativeFunction {
+        name: "upper",
+        arity: Arity::exact(1),
+        params: &["s"],
+        description: "`s` in upper case.",
+        function: upper,
+    },
+    NativeFunction {
+        name: "lower",
+        arity: Arity::exact(1),
+        params: &["s"],
+        description: "`s` in lower case.",
+        function: lower,
+    },
+    NativeFunction {
+        name: "index_of",
+        arity: Arity::exact(2),
+        params: &["s", "needle"],
+        description: "The character index of the first `needle` in `s`, -1 if there's none.",
+        function: index_of,
+    },
+    NativeFunction {
+        name: "equals",
+        arity: Arity::exact(2),
+        params: &["a", "b"],
+        description: "Whether `a` and `b` are equal, the same as `==`.",
+        function: equals,
+    },
+    NativeFunction {
+        name: "approx_equals",
+        arity: Arity::range(2, 3),
+        params: &["a", "b", "epsilon"],
+        description: "Whether numbers `a` and `b` are at most `epsilon` apart, 1e-9 if not given.",
+        function: approx_equals,
+    },
+    NativeFunction {
+        name: "abs",
+        arity: Arity::exact(1),
+        params: &["x"],
+        description: "`x` without its sign.",
+        function: abs,
+    },
+    NativeFunction {
+        name: "floor",
+        arity: Arity::exact(1),
+        params: &["x"],
+        description: "The largest whole number not above `x`.",
+        function: floor,
+    },
+    NativeFunction {
+        name: "ceil",
+        arity: Arity::exact(1),
+        params: &["x"],
+        description: "The smallest whole number not below `x`.",
+        function: ceil,
+    },
+    NativeFunction {
+        name: "round",
+        arity: Arity::exact(1),
+        params: &["x"],
+        description: "The whole number nearest to `x`, halves rounded away from zero.",
+        function: round,
+    },
+    NativeFunction {
+        name: "sqrt",
+        arity: Arity::exact(1),
+        params: &["x"],
+        description: "The square root of `x`, NaN when `x` is negative.",
+        function: sqrt,
+    },
+    NativeFunction {
+        name: "min",
+        arity: Arity::at_least(1),
+        params: &["a", "rest..."],
+        description:
+            "The least of its arguments, all numbers or all strings. NaN if any of them is NaN.",
+        function: min,
+    },
+    NativeFunction {
+        name: "max",
+        arity: Arity::at_least(1),
+        params: &["a", "rest..."],
+        description:
+            "The greatest of its arguments, all numbers or all strings. NaN if any of them is NaN.",
+        function: max,
+    },
+    NativeFunction {
+        name: "input",
+        arity: Arity::range(0, 1),
+        params: &["prompt"],
+        description:
+            "Prints `prompt` on the same line and reads a line of input, nil at the end of input.",
+        function: input,
+    },
+    NativeFunction {
+        name: "num",
+        arity: Arity::exact(1),
+        params: &["s"],
+        description: "`s` read as a number, nil if it isn't one.",
+        function: num,
+    },
+    NativeFunction {
+        name: "exit",
+        arity: Arity::range(0, 1),
+        params: &["code"],
+        description: "Stops the program with exit status `code`, from 0 to 255, 0 if not given.",
+        function: exit,
+    },
+    NativeFunction {
+        name: "type",
+        arity: Arity::exact(1),
+        params: &["value"],
+        description: "The type of `value`, e.g. \"number\", \"string\" or \"nan\".",
+        function: type_of,
+    },
+    NativeFunction {
+        name: "debug",
+        arity: Arity::exact(1),
+        params: &["value"],
+        description: "Prints `value` unambiguously, e.g. strings quoted and `2` as `2.0`.",
+        function: debug,
+    },
+    NativeFunction {
+        name: "println",
+        arity: Arity::at_least(0),
+        params: &["values..."],
+        description:
+            "Prints `values` on one line separated by spaces, like `print`, returning nil.",
+        function: println,
+    },
+];
+
+/// Every native, in the order they're documented.
+pub fn natives() -> &'static [NativeFunction] {
+    NATIVES
+}
+
+/// Finds the native called `name`.
+pub fn native(name: &str) -> Option<&'static NativeFunction> {
+    NATIVES.iter().find(|native| native.name == name)
+}
+
+fn argument_error(paren: &Token, function: &str, message: &str) -> JBreadErrors {
+    JBreadErrors::RunTimeException(Error::at(
+        ErrorKind::InvalidArgument,
+        paren,
+        function.to_string(),
+        format!("{}: {}", function, message),
+    ))
+}
+
+fn number(paren: &Token, function: &str, argument: &str, value: &Value) -> JBreadResult<f64> {
+    match value {
+        Value::Number(number) if number.is_finite() => Ok(*number),
+        _ => Err(argument_error(
+            paren,
+            function,
+            &format!("argument '{}' must be a finite number", argument),
+        )),
+    }
+}
+
+/// Any number, NaN and infinities included.
+fn numeric(paren: &Token, function: &str, argument: &str, value: &Value) -> JBreadResult<f64> {
+    match value {
+        Value::Number(number) => Ok(*number),
+        Value::NaN => Ok(f64::NAN),
+        _ => Err(argument_error(
+            paren,
+            function,
+            &format!("argument '{}' must be a number", argument),
+        )),
+    }
+}
+
+fn whole_number(paren: &Token, function: &str, argument: &str, value: &Value) -> JBreadResult<f64> {
+    let number = number(paren, function, argument, value)?;
+    if number.fract() != 0.0 {
+        return Err(argument_error(
+            paren,
+            function,
+            &format!("argument '{}' must be a whole number", argument),
+        ));
+    }
+    Ok(number)
+}
+
+fn string<'a>(
+    paren: &Token,
+    function: &str,
+    argument: &str,
+    value: &'a Value,
+) -> JBreadResult<&'a str> {
+    match value {
+        Value::String(string) => Ok(string),
+        _ => Err(argument_error(
+            paren,
+            function,
+            &format!("argument '{}' must be a string", argument),
+        )),
+    }
+}
+
+fn len(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let len = match &arguments[0] {
+        Value::List(list) => list.borrow().len(),
+        Value::Map(map) => map.borrow().len(),
+        Value::String(string) => string.chars().count(),
+        _ => {
+            return Err(argument_error(
+                paren,
+                "len",
+                "argument 'value' must be a list, a map or a string",
+            ))
+        }
+    };
+    Ok(Value::Number(len as f64))
+}
+
+fn keys(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let Value::Map(map) = &arguments[0] else {
+        return Err(argument_error(
+            paren,
+            "keys",
+            "argument 'map' must be a map",
+        ));
+    };
+    let mut keys: Vec<_> = map.borrow().keys().cloned().collect();
+    keys.sort();
+    Ok(Value::list(keys.iter().map(Value::from).collect()))
+}
+
+/// `len` characters of `s` starting at character `start`.
+fn substr(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let string = string(paren, "substr", "s", &arguments[0])?;
+    let start = whole_number(paren, "substr", "start", &arguments[1])?;
+    let len = whole_number(paren, "substr", "len", &arguments[2])?;
+    let count = string.chars().count();
+    if start < 0.0 || len < 0.0 || start + len > count as f64 {
+        return Err(argument_error(
+            paren,
+            "substr",
+            &format!(
+                "range {}..{} out of bounds for string of length {}",
+                start,
+                start + len,
+                count
+            ),
+        ));
+    }
+    let substring = string.chars().skip(start as usize).take(len as usize);
+    Ok(Value::String(substring.collect()))
+}
+
+fn upper(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let string = string(paren, "upper", "s", &arguments[0])?;
+    Ok(Value::String(string.to_uppercase()))
+}
+
+fn lower(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let string = string(paren, "lower", "s", &arguments[0])?;
+    Ok(Value::String(string.to_lowercase()))
+}
+
+/// Character index of the first `needle` in `s`, -1 when there's none.
+fn index_of(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let haystack = string(paren, "index_of", "s", &arguments[0])?;
+    let needle = string(paren, "index_of", "needle", &arguments[1])?;
+    let index = match haystack.find(needle) {
+        Some(offset) => haystack[..offset].chars().count() as f64,
+        None => -1.0,
+    };
+    Ok(Value::Number(index))
+}
+
+/// The same comparison as `==`, lists being compared element by element and
+/// nil only equal to nil.
+fn equals(
+    _interpreter: &mut Interpreter,
+    _paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    Ok(Value::Bool(arguments[0].equals(&arguments[1])))
+}
+
+/// Whether numbers `a` and `b` are at most `epsilon` apart. Like with `==`,
+/// NaN isn't close to anything.
+fn approx_equals(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let a = numeric(paren, "approx_equals", "a", &arguments[0])?;
+    let b = numeric(paren, "approx_equals", "b", &arguments[1])?;
+    let epsilon = match arguments.get(2) {
+        Some(epsilon) => number(paren, "approx_equals", "epsilon", epsilon)?,
+        None => DEFAULT_EPSILON,
+    };
+    if epsilon < 0.0 {
+        return Err(argument_error(
+            paren,
+            "approx_equals",
+            "argument 'epsilon' must not be negative",
+        ));
+    }
+    let equal = a == b || (a - b).abs() <= epsilon;
+    Ok(Value::Bool(equal))
+}
+
+/// A line of input without its line ending, read after printing `prompt`.
+/// `operation` of the number `x`, following IEEE like the operators do.
+fn math(
+    paren: &Token,
+    function: &str,
+    x: &Value,
+    operation: fn(f64) -> f64,
+) -> JBreadResult<Value> {
+    Ok(Value::from_number(operation(numeric(
+        paren, function, "x", x,
+    )?)))
+}
+
+fn abs(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    math(paren, "abs", &arguments[0], f64::abs)
+}
+
+fn floor(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    math(paren, "floor", &arguments[0], f64::floor)
+}
+
+fn ceil(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    math(paren, "ceil", &arguments[0], f64::ceil)
+}
+
+fn round(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    math(paren, "round", &arguments[0], f64::round)
+}
+
+fn sqrt(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    math(paren, "sqrt", &arguments[0], f64::sqrt)
+}
+
+/// The argument the others order `wanted` against, the first of equal
+/// ones. They must all be numbers or all strings, as `<` compares, and NaN
+/// among numbers makes the result NaN rather than being skipped.
+fn extremum(
+    paren: &Token,
+    function: &str,
+    arguments: Vec<Value>,
+    wanted: Ordering,
+) -> JBreadResult<Value> {
+    let kind = |value: &Value| match value {
+        Value::Number(_) | Value::NaN => Some("number"),
+        Value::String(_) => Some("string"),
+        _ => None,
+    };
+    let Some(expected) = kind(&arguments[0]) else {
+        return Err(argument_error(
+            paren,
+            function,
+            "argument 1 must be a number or a string",
+        ));
+    };
+    for (index, argument) in arguments.iter().enumerate().skip(1) {
+        if kind(argument) != Some(expected) {
+            return Err(argument_error(
+                paren,
+                function,
+                &format!(
+                    "argument {} must be a {} like argument 1, not a {}",
+                    index + 1,
+                    expected,
+                    argument.type_name()
+                ),
+            ));
+        }
+    }
+    if arguments
+        .iter()
+        .any(|argument| matches!(argument, Value::NaN))
+    {
+        return Ok(Value::NaN);
+    }
+    Ok(arguments
+        .into_iter()
+        .reduce(|best, argument| match argument.order(&best) {
+            Some(ordering) if ordering == wanted => argument,
+            _ => best,
+        })
+        .expect("min and max take at least one argument"))
+}
+
+fn min(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    extremum(paren, "min", arguments, Ordering::Less)
+}
+
+fn max(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    extremum(paren, "max", arguments, Ordering::Greater)
+}
+
+fn input(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    if let Some(prompt) = arguments.first() {
+        interpreter.write(string(paren, "input", "prompt", prompt)?)?;
+    }
+    match interpreter.read_line() {
+        Ok(Some(line)) => Ok(Value::String(line)),
+        Ok(None) => Ok(Value::Nil),
+        Err(error) => Err(JBreadErrors::RunTimeException(Error::at(
+            ErrorKind::Io,
+            paren,
+            "input".to_string(),
+            format!("input: couldn't read a line: {}", error),
+        ))),
+    }
+}
+
+/// `s` as a finite number. Anything else, whitespace around one included,
+/// gives nil rather than an error so scripts can check what was typed.
+fn num(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let string = string(paren, "num", "s", &arguments[0])?;
+    match string.parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(Value::Number(number)),
+        _ => Ok(Value::Nil),
+    }
+}
+
+/// Stops the program, through [`JBreadErrors::Exit`] rather than ending the
+/// process, which may be hosting more than the script.
+fn exit(
+    _interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let code = match arguments.first() {
+        Some(code) => whole_number(paren, "exit", "code", code)?,
+        None => 0.0,
+    };
+    if !(0.0..=255.0).contains(&code) {
+        return Err(argument_error(
+            paren,
+            "exit",
+            "argument 'code' must be from 0 to 255",
+        ));
+    }
+    let code = code as i32;
+    Err(JBreadErrors::Exit(Error::at(
+        ErrorKind::Exit(code),
+        paren,
+        "exit".to_string(),
+        format!("exited with code {}", code),
+    )))
+}
+
+fn type_of(
+    _interpreter: &mut Interpreter,
+    _paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    Ok(Value::String(arguments[0].type_name().to_lowercase()))
+}
+
+fn debug(
+    interpreter: &mut Interpreter,
+    _paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    interpreter.print_line(&arguments[0].repr())?;
+    Ok(Value::Nil)
+}
+
+fn println(
+    interpreter: &mut Interpreter,
+    _paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    interpreter.print_values(&arguments)?;
+    Ok(Value::Nil)
+}
+
+fn random(
+    interpreter: &mut Interpreter,
+    _paren: &Token,
+    _arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let value = interpreter.random().rng().next_f64();
+    Ok(Value::Number(value))
+}
+
+fn random_range(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let lo = number(paren, "random_range", "lo", &arguments[0])?;
+    let hi = number(paren, "random_range", "hi", &arguments[1])?;
+    if lo >= hi {
+        return Err(argument_error(
+            paren,
+            "random_range",
+            "argument 'lo' must be less than 'hi'",
+        ));
+    }
+    let value = lo + (hi - lo) * interpreter.random().rng().next_f64();
+    // Rounding can land exactly on `hi` for very wide ranges.
+    Ok(Value::Number(if value < hi { value } else { lo }))
+}
+
+fn random_int(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    let lo = whole_number(paren, "random_int", "lo", &arguments[0])?;
+    let hi = whole_number(paren, "random_int", "hi", &arguments[1])?;
+    if lo > hi {
+        return Err(argument_error(
+            paren,
+            "random_int",
+            "argument 'lo' must not be greater than 'hi'",
+        ));
+    }
+    let span = (hi - lo) as u64 + 1;
+    let offset = interpreter.random().rng().next_u64() % span;
+    Ok(Value::Number(lo + offset as f64))
+}
+
+fn random_seed(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    seed(interpreter, paren, "random_seed", &arguments[0])
+}
+
+fn set_seed(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    arguments: Vec<Value>,
+) -> JBreadResult<Value> {
+    seed(interpreter, paren, "set_seed", &arguments[0])
+}
+
+/// Reseeds the interpreter's stream, `function` being the native called.
+fn seed(
+    interpreter: &mut Interpreter,
+    paren: &Token,
+    function: &str,
+    n: &Value,
+) -> JBreadResult<Value> {
+    let seed = whole_number(paren, function, "n", n)?;
+    interpreter.random().seed(seed as i64 as u64);
+    Ok(Value::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Cursor, rc::Rc};
+
+    use super::*;
+    use crate::{interpreter::HostInterface, parser::Parser, Scanner, TokenTypes};
+
+    struct FixedHost(u64);
+
+    impl HostInterface for FixedHost {
+        fn random_seed(&self) -> u64 {
+            self.0
+        }
+    }
+
+    /// Keeps what's written without a newline.
+    struct PromptHost(Rc<RefCell<String>>);
+
+    impl HostInterface for PromptHost {
+        fn random_seed(&self) -> u64 {
+            0
+        }
+
+        fn write(&self, text: &str) {
+            self.0.borrow_mut().push_str(text);
+        }
+    }
+
+    fn run(interpreter: &mut Interpreter, source: &str) -> JBreadResult<()> {
+        let mut scanner = Scanner::new(source);
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        interpreter.interpret(&stmts).map(drop)
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Value {
+        let token = Token::new(TokenTypes::Identifier, name.to_string(), None, 1);
+        interpreter
+            .environment
+            .borrow()
+            .get(&token)
+            .unwrap_or(Value::Nil)
+    }
+
+    fn paren() -> Token {
+        Token::new(TokenTypes::RightParen, ")".to_string(), None, 1)
+    }
+
+    fn call(interpreter: &mut Interpreter, name: &str, arguments: &[f64]) -> JBreadResult<Value> {
+        let arguments = arguments
+            .iter()
+            .map(|number| Value::Number(*number))
+            .collect();
+        native(name).unwrap().call(interpreter, &paren(), arguments)
+    }
+
+    fn draw(interpreter: &mut Interpreter, count: usize) -> Vec<Value> {
+        (0..count)
+            .map(|_| call(interpreter, "random", &[]).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let source = "set_seed(42); var a = random(); var b = random_range(5, 10); var c = random_int(1, 100);";
+        let globals = |interpreter: &Interpreter| {
+            ["a", "b", "c"].map(|name| {
+                let token = Token::new(TokenTypes::Identifier, name.to_string(), None, 1);
+                interpreter.environment.borrow().get(&token).unwrap()
+            })
+        };
+        let mut scanner = Scanner::new(source);
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+
+        let mut first = Interpreter::default();
+        let mut second = Interpreter::default();
+        first.interpret(&stmts).unwrap();
+        second.interpret(&stmts).unwrap();
+        assert_eq!(globals(&first), globals(&second));
+
+        let before = globals(&first);
+        first.interpret(&stmts).unwrap();
+        assert_eq!(globals(&first), before);
+    }
+
+    #[test]
+    fn test_seeded_sequence() {
+        let mut interpreter = Interpreter::default();
+        call(&mut interpreter, "random_seed", &[1.0]).unwrap();
+        let first = draw(&mut interpreter, 3);
+        let ints: Vec<Value> = (0..5)
+            .map(|_| call(&mut interpreter, "random_int", &[1.0, 6.0]).unwrap())
+            .collect();
+        let numbers = |numbers: &[f64]| -> Vec<Value> {
+            numbers.iter().copied().map(Value::Number).collect()
+        };
+        assert_eq!(
+            first,
+            numbers(&[0.7029218331588505, 0.5204366199388569, 0.5741057000197225])
+        );
+        assert_eq!(ints, numbers(&[6.0, 6.0, 5.0, 3.0, 4.0]));
+
+        // Reseeding starts the same sequence over, `set_seed` being the
+        // same native under its older name.
+        call(&mut interpreter, "set_seed", &[1.0]).unwrap();
+        assert_eq!(draw(&mut interpreter, 3), first);
+    }
+
+    #[test]
+    fn test_range_bounds() {
+        let mut interpreter = Interpreter::default();
+        for _ in 0..1000 {
+            match call(&mut interpreter, "random_range", &[-2.5, 3.0]).unwrap() {
+                Value::Number(value) => assert!((-2.5..3.0).contains(&value)),
+                other => panic!("Expected a number, got {:?}", other),
+            }
+            match call(&mut interpreter, "random", &[]).unwrap() {
+                Value::Number(value) => assert!((0.0..1.0).contains(&value)),
+                other => panic!("Expected a number, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_int_inclusive() {
+        let mut interpreter = Interpreter::default();
+        let mut seen = [false; 3];
+        for _ in 0..1000 {
+            match call(&mut interpreter, "random_int", &[1.0, 3.0]).unwrap() {
+                Value::Number(value) => {
+                    assert!((1.0..=3.0).contains(&value) && value.fract() == 0.0);
+                    seen[value as usize - 1] = true;
+                }
+                other => panic!("Expected a number, got {:?}", other),
+            }
+        }
+        assert_eq!(seen, [true; 3]);
+        assert_eq!(
+            call(&mut interpreter, "random_int", &[7.0, 7.0]).unwrap(),
+            Value::Number(7.0)
+        );
+    }
+
+    #[test]
+    fn test_argument_validation() {
+        let mut interpreter = Interpreter::default();
+        let error = call(&mut interpreter, "random_range", &[2.0, 1.0]).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidArgument);
+        assert_eq!(
+            error.to_string(),
+            "\"random_range: argument 'lo' must be less than 'hi'\" at line: 1 in random_range"
+        );
+
+        let error = native("random_range")
+            .unwrap()
+            .call(
+                &mut interpreter,
+                &paren(),
+                vec![Value::Number(1.0), Value::Nil],
+            )
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("random_range: argument 'hi' must be a finite number"));
+
+        let error = call(&mut interpreter, "random_int", &[3.0, 2.0]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "\"random_int: argument 'lo' must not be greater than 'hi'\" at line: 1 in random_int"
+        );
+        let error = call(&mut interpreter, "random_int", &[0.5, 2.0]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("random_int: argument 'lo' must be a whole number"));
+        let error = call(&mut interpreter, "random_seed", &[f64::INFINITY]).unwrap_err();
+        assert!(error.to_string().contains("random_seed: argument 'n'"));
+        let error = call(&mut interpreter, "set_seed", &[f64::INFINITY]).unwrap_err();
+        assert!(error.to_string().contains("set_seed: argument 'n'"));
+    }
+
+    #[test]
+    fn test_seeding_sources() {
+        let mut deterministic = Interpreter::with_host(FixedHost(1));
+        deterministic.set_deterministic(true);
+        let mut seeded = Interpreter::default();
+        call(
+            &mut seeded,
+            "set_seed",
+            &[crate::interpreter::DEFAULT_SEED as f64],
+        )
+        .unwrap();
+        assert_eq!(draw(&mut deterministic, 5), draw(&mut seeded, 5));
+
+        // The host's seed is used outside deterministic mode.
+        let mut hosted = Interpreter::with_host(FixedHost(7));
+        let mut seeded = Interpreter::default();
+        call(&mut seeded, "set_seed", &[7.0]).unwrap();
+        assert_eq!(draw(&mut hosted, 5), draw(&mut seeded, 5));
+    }
+
+    #[test]
+    fn test_independent_streams() {
+        let mut first = Interpreter::with_host(FixedHost(3));
+        let mut second = Interpreter::with_host(FixedHost(3));
+        let drawn = draw(&mut first, 2);
+        // Drawing from the first stream didn't advance the second one.
+        assert_eq!(draw(&mut second, 2), drawn);
+    }
+
+    #[test]
+    fn test_globals_shadow_natives() {
+        let mut scanner = Scanner::new("fun random() { return 4; } var a = random();");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut interpreter = Interpreter::default();
+        interpreter.interpret(&stmts).unwrap();
+        let token = Token::new(TokenTypes::Identifier, "a".to_string(), None, 1);
+        assert_eq!(
+            interpreter.environment.borrow().get(&token).unwrap(),
+            Value::Number(4.0)
+        );
+    }
+
+    #[test]
+    fn test_input() {
+        let written = Rc::new(RefCell::new(String::new()));
+        let mut interpreter = Interpreter::with_host(PromptHost(written.clone()));
+        interpreter.set_input(Cursor::new("3.14\nabc\r\n"));
+        run(
+            &mut interpreter,
+            "var a = input(\"> \"); var b = input(); var c = input(\"? \");",
+        )
+        .unwrap();
+        assert_eq!(*written.borrow(), "> ? ");
+        assert_eq!(global(&interpreter, "a"), Value::String("3.14".to_string()));
+        assert_eq!(global(&interpreter, "b"), Value::String("abc".to_string()));
+        assert_eq!(global(&interpreter, "c"), Value::Nil);
+
+        let error = run(&mut interpreter, "input(1);").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("input: argument 'prompt' must be a string"));
+    }
+
+    #[test]
+    fn test_num() {
+        let mut interpreter = Interpreter::default();
+        run(
+            &mut interpreter,
+            "var decimal = num(\"2.75\"); var negative = num(\"-2e3\"); var word = num(\"abc\");
+            var empty = num(\"\"); var padded = num(\" 1 \"); var infinite = num(\"inf\");",
+        )
+        .unwrap();
+        assert_eq!(global(&interpreter, "decimal"), Value::Number(2.75));
+        assert_eq!(global(&interpreter, "negative"), Value::Number(-2000.0));
+        for name in ["word", "empty", "padded", "infinite"] {
+            assert_eq!(global(&interpreter, name), Value::Nil, "{}", name);
+        }
+
+        let error = run(&mut interpreter, "num(1);").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidArgument);
+    }
+
+    #[test]
+    fn test_type() {
+        let mut interpreter = Interpreter::default();
+        run(
+            &mut interpreter,
+            "var n = type(1); var s = type(\"a\"); var b = type(false); var z = type(nil);
+            var x = type(NaN); var l = type([]); var f = type(type);",
+        )
+        .unwrap();
+        let types = ["n", "s", "b", "z", "x", "l", "f"].map(|name| global(&interpreter, name));
+        assert_eq!(
+            types.map(|value| value.to_string()),
+            ["number", "string", "boolean", "nil", "nan", "list", "function"]
+        );
+    }
+
+    #[test]
+    fn test_debug() {
+        let output = crate::OutputBuffer::new();
+        let mut interpreter = Interpreter::default().with_output(output.clone());
+        run(
+            &mut interpreter,
+            "print \"a b\"; debug(\"a b\"); print 2; debug(2); debug([1.5, nil, \"x\"]);",
+        )
+        .unwrap();
+        assert_eq!(output.take(), "a b\n\"a b\"\n2\n2.0\n[1.5, nil, \"x\"]\n");
+        run(&mut interpreter, "var d = debug(1);").unwrap();
+        assert_eq!(global(&interpreter, "d"), Value::Nil);
+    }
+
+    #[test]
+    fn test_println() {
+        let output = crate::OutputBuffer::new();
+        let mut interpreter = Interpreter::default().with_output(output.clone());
+        run(
+            &mut interpreter,
+            "print 1, \"a b\", nil; println(1, \"a b\", nil); println();\n\
+             var p = println(\"inside\") == nil; print p;\n\
+             var apply = (f, x) -> f(x); apply(println, [2]);",
+        )
+        .unwrap();
+        assert_eq!(output.take(), "1 a b nil\n1 a b nil\n\ninside\ntrue\n[2]\n");
+        assert_eq!(native("println").unwrap().arity.to_string(), "any number");
+        assert_eq!(Arity::at_least(1).to_string(), "at least 1");
+    }
+
+    #[test]
+    fn test_math() {
+        let mut interpreter = Interpreter::default();
+        for (name, x, expected) in [
+            ("abs", -2.5, 2.5),
+            ("abs", f64::NEG_INFINITY, f64::INFINITY),
+            ("floor", -1.5, -2.0),
+            ("ceil", -1.5, -1.0),
+            ("round", 2.5, 3.0),
+            ("round", -2.5, -3.0),
+            ("sqrt", 9.0, 3.0),
+            ("sqrt", f64::INFINITY, f64::INFINITY),
+        ] {
+            let value = call(&mut interpreter, name, &[x]).unwrap();
+            assert_eq!(value, Value::Number(expected), "{}({})", name, x);
+        }
+        for name in ["abs", "floor", "ceil", "round", "sqrt"] {
+            assert_eq!(
+                call(&mut interpreter, name, &[f64::NAN]).unwrap(),
+                Value::NaN
+            );
+        }
+        assert_eq!(call(&mut interpreter, "sqrt", &[-1.0]).unwrap(), Value::NaN);
+
+        let error = run(&mut interpreter, "floor(\"1\");").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidArgument);
+        assert_eq!(
+            error.error().message(),
+            "floor: argument 'x' must be a number"
+        );
+    }
+
+    #[test]
+    fn test_min_max() {
+        let output = crate::OutputBuffer::new();
+        let mut interpreter = Interpreter::default().with_output(output.clone());
+        run(
+            &mut interpreter,
+            "print min(3, 1, 2), max(3, 1, 2), min(-0.5), max(-inf, 7);\n\
+             print min(\"pear\", \"apple\", \"fig\"), max(\"pear\", \"apple\", \"fig\");\n\
+             print min(1, NaN, 0), max(NaN);",
+        )
+        .unwrap();
+        assert_eq!(output.take(), "1 3 -0.5 7\napple pear\nNaN NaN\n");
+
+        for (source, kind, message) in [
+            (
+                "min();",
+                ErrorKind::ArityMismatch,
+                "Expected at least 1 arguments but got 0",
+            ),
+            (
+                "max(1, 2, \"3\");",
+                ErrorKind::InvalidArgument,
+                "max: argument 3 must be a number like argument 1, not a string",
+            ),
+            (
+                "min(\"a\", NaN);",
+                ErrorKind::InvalidArgument,
+                "min: argument 2 must be a string like argument 1, not a NaN",
+            ),
+            (
+                "min(true, false);",
+                ErrorKind::InvalidArgument,
+                "min: argument 1 must be a number or a string",
+            ),
+        ] {
+            let error = run(&mut interpreter, source).unwrap_err();
+            assert_eq!(error.kind(), &kind, "{}", source);
+            assert_eq!(error.error().message(), message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_exit() {
+        let output = crate::OutputBuffer::new();
+        let mut interpreter = Interpreter::default().with_output(output.clone());
+        let error = run(
+            &mut interpreter,
+            "var a = 1; print a;
+            fun stop() { { a = 2; exit(3); } a = 3; }
+            repeat (2) { stop(); print \"again\"; }
+            a = 4; print a;",
+        )
+        .unwrap_err();
+        assert!(matches!(error, JBreadErrors::Exit(_)));
+        assert_eq!(error.kind(), &ErrorKind::Exit(3));
+        assert_eq!(error.exit_code(), 3);
+        // Nothing after the call ran, however far out it was.
+        assert_eq!(output.take(), "1\n");
+        assert_eq!(global(&interpreter, "a"), Value::Number(2.0));
+
+        let error = run(&mut interpreter, "exit();").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Exit(0));
+        for code in ["1.5", "-1", "256", "\"1\"", "inf"] {
+            let error = run(&mut interpreter, &format!("exit({});", code)).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::InvalidArgument, "{}", code);
+        }
+    }
+}
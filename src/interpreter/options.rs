@@ -0,0 +1,31 @@
+/// What [`Interpreter::interpret`](super::Interpreter::interpret) runs a
+/// program on.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ExecutionEngine {
+    /// Walks the AST directly.
+    #[default]
+    TreeWalk,
+    /// Compiles the program to bytecode and runs it on a stack machine.
+    /// Only expressions, variables, blocks, `print` and `repeat` compile so
+    /// far, anything else is rejected before the program starts.
+    #[cfg(feature = "bytecode")]
+    Bytecode,
+}
+
+/// What assigning a variable that was never declared does.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum UndeclaredAssignment {
+    /// Fails with [`ErrorKind::UndeclaredAssignment`](crate::ErrorKind::UndeclaredAssignment),
+    /// as Lox does.
+    #[default]
+    Strict,
+    /// Declares it as a global, wherever the assignment is, for embedders
+    /// using the language for configuration. Reading it before still fails.
+    ImplicitGlobal,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InterpreterOptions {
+    pub engine: ExecutionEngine,
+    pub undeclared_assignment: UndeclaredAssignment,
+}
@@ -0,0 +1,89 @@
+use std::rc::Rc;
+
+use crate::interpreter::HostInterface;
+
+/// Seed used in deterministic mode, or when the host can't provide one.
+pub const DEFAULT_SEED: u64 = 0x6a62_7265_6164;
+
+/// xoshiro256** generator. Small, fast and reproducible, not meant for
+/// anything security related.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn from_seed(seed: u64) -> Self {
+        // SplitMix64 expands the seed so that close seeds give unrelated
+        // streams and the state is never all zeros.
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        Self {
+            state: [next(), next(), next(), next()],
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+        result
+    }
+
+    /// Uniform float in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The random stream of one interpreter, seeded on first use.
+pub(crate) struct RandomSource {
+    host: Rc<dyn HostInterface>,
+    deterministic: bool,
+    rng: Option<Rng>,
+}
+
+impl RandomSource {
+    pub(crate) fn new(host: Rc<dyn HostInterface>) -> Self {
+        Self {
+            host,
+            deterministic: false,
+            rng: None,
+        }
+    }
+
+    pub(crate) fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    pub(crate) fn seed(&mut self, seed: u64) {
+        self.rng = Some(Rng::from_seed(seed));
+    }
+
+    /// Forgets the stream, the next draw seeds it again.
+    pub(crate) fn reset(&mut self) {
+        self.rng = None;
+    }
+
+    pub(crate) fn rng(&mut self) -> &mut Rng {
+        if self.rng.is_none() {
+            let seed = match self.deterministic {
+                true => DEFAULT_SEED,
+                false => self.host.random_seed(),
+            };
+            self.seed(seed);
+        }
+        self.rng.as_mut().unwrap()
+    }
+}
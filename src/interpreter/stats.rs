@@ -0,0 +1,138 @@
+use crate::interpreter::environment::Environment;
+
+#[cfg(test)]
+thread_local! {
+    /// Times the accounting path ran on this thread.
+    static ACCOUNTED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Counters for sizing memory when running many interpreters, collected
+/// once enabled with [`Interpreter::enable_stats`](super::Interpreter::enable_stats).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InterpreterStats {
+    /// Most environments ever chained together, the global one included.
+    pub peak_depth: usize,
+    /// Most bindings ever live across a single environment chain.
+    pub peak_bindings: usize,
+    /// Environments created for blocks and calls.
+    pub environments_created: usize,
+    /// Running total of the estimated size of every environment created and
+    /// binding defined.
+    pub estimated_bytes: usize,
+}
+
+impl InterpreterStats {
+    pub(crate) fn record_environment(&mut self, environment: &Environment) {
+        self.environments_created += 1;
+        self.estimated_bytes += environment.estimated_bytes();
+        self.observe(environment);
+    }
+
+    pub(crate) fn record_binding(&mut self, environment: &Environment, bytes: usize) {
+        self.estimated_bytes += bytes;
+        self.observe(environment);
+    }
+
+    /// Updates the peaks from the chain `environment` is the innermost of.
+    pub(crate) fn observe(&mut self, environment: &Environment) {
+        #[cfg(test)]
+        ACCOUNTED.with(|accounted| accounted.set(accounted.get() + 1));
+
+        let (depth, bindings) = environment.chain_size();
+        self.peak_depth = self.peak_depth.max(depth);
+        self.peak_bindings = self.peak_bindings.max(bindings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpreter::Interpreter, parser::Parser, Scanner};
+
+    fn run(interpreter: &mut Interpreter, source: &str) {
+        let mut scanner = Scanner::new(source);
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        interpreter.interpret(&stmts).unwrap();
+    }
+
+    fn with_stats() -> Interpreter {
+        let mut interpreter = Interpreter::default();
+        interpreter.enable_stats();
+        interpreter
+    }
+
+    #[test]
+    fn test_peak_depth_and_bindings() {
+        let mut interpreter = with_stats();
+        run(
+            &mut interpreter,
+            "var a = 1; { var b = 2; var c = 3; { var d = 4; } } { var e = 5; }",
+        );
+        let stats = interpreter.stats().unwrap();
+        assert_eq!(stats.peak_depth, 3);
+        assert_eq!(stats.peak_bindings, 4);
+        assert_eq!(stats.environments_created, 3);
+        assert!(stats.estimated_bytes > 0);
+    }
+
+    #[test]
+    fn test_calls_counted() {
+        let mut interpreter = with_stats();
+        run(
+            &mut interpreter,
+            "fun f(x) { var y = x; return y; } f(1); f(2);",
+        );
+        let stats = interpreter.stats().unwrap();
+        assert_eq!(stats.environments_created, 2);
+        assert_eq!(stats.peak_depth, 2);
+        assert_eq!(stats.peak_bindings, 3);
+    }
+
+    #[test]
+    fn test_estimate_grows_with_strings() {
+        let estimates: Vec<usize> = [1, 10, 100, 1000]
+            .iter()
+            .map(|len| {
+                let mut interpreter = Interpreter::default();
+                run(
+                    &mut interpreter,
+                    &format!("var s = \"{}\";", "x".repeat(*len)),
+                );
+                interpreter.memory_estimate()
+            })
+            .collect();
+        assert!(estimates.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_disabled_stats_skip_accounting() {
+        let before = ACCOUNTED.with(|accounted| accounted.get());
+        let mut interpreter = Interpreter::default();
+        run(&mut interpreter, "var a = 1; { var b = a; }");
+        assert!(interpreter.stats().is_none());
+        assert_eq!(ACCOUNTED.with(|accounted| accounted.get()), before);
+
+        run(&mut with_stats(), "var a = 1;");
+        assert!(ACCOUNTED.with(|accounted| accounted.get()) > before);
+    }
+
+    #[test]
+    fn test_reset_between_runs() {
+        let program = "var a = \"pooled\"; { var b = a + a; }";
+        let mut interpreter = with_stats();
+        let empty = interpreter.memory_estimate();
+
+        run(&mut interpreter, program);
+        let estimate = interpreter.memory_estimate();
+        let stats = interpreter.stats().unwrap();
+        assert!(estimate > empty);
+
+        interpreter.reset();
+        assert_eq!(interpreter.memory_estimate(), empty);
+        assert_eq!(interpreter.stats(), Some(InterpreterStats::default()));
+
+        run(&mut interpreter, program);
+        assert_eq!(interpreter.memory_estimate(), estimate);
+        assert_eq!(interpreter.stats(), Some(stats));
+    }
+}
@@ -0,0 +1,153 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    ast::{Expr, Stmt},
+    interpreter::Value,
+    JBreadErrors,
+};
+
+/// What the interpreter does after [`Tracer::before_stmt`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TraceControl {
+    #[default]
+    Continue,
+    /// Calls [`Tracer::paused`] before running the statement.
+    Pause,
+}
+
+/// Callbacks following a program as the tree-walking interpreter runs it,
+/// installed with [`Interpreter::set_tracer`](super::Interpreter::set_tracer).
+/// Each one is called on the thread running the program, which waits for it,
+/// so a debugger can block in them until its user wants to go on.
+pub trait Tracer {
+    /// Before `stmt`, found at `line`, runs. A block's line is that of its
+    /// first statement, and `line` is 0 for statements holding no token,
+    /// like `print 1;`.
+    fn before_stmt(&mut self, _stmt: &Stmt, _line: u32) -> TraceControl {
+        TraceControl::Continue
+    }
+
+    /// After `before_stmt` asked to pause, returning once `stmt` should run.
+    fn paused(&mut self, _stmt: &Stmt, _line: u32) {}
+
+    fn after_stmt(&mut self, _stmt: &Stmt, _result: Result<(), &JBreadErrors>) {}
+
+    fn before_expr(&mut self, _expr: &Expr) {}
+
+    /// A variable was declared, `old` being `None`, or assigned.
+    fn on_var_write(&mut self, _name: &str, _old: Option<&Value>, _new: &Value) {}
+}
+
+/// Something a [`CollectingTracer`] saw.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceEvent {
+    /// A statement started at this line.
+    Stmt(u32),
+    Write {
+        name: String,
+        old: Option<Value>,
+        new: Value,
+    },
+}
+
+/// Records the statements run and the variables written. Clones share what
+/// was recorded, so one can be kept to read it back after installing another.
+#[derive(Debug, Clone, Default)]
+pub struct CollectingTracer {
+    events: Rc<RefCell<Vec<TraceEvent>>>,
+}
+
+impl CollectingTracer {
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.events.borrow().clone()
+    }
+}
+
+impl Tracer for CollectingTracer {
+    fn before_stmt(&mut self, _stmt: &Stmt, line: u32) -> TraceControl {
+        self.events.borrow_mut().push(TraceEvent::Stmt(line));
+        TraceControl::Continue
+    }
+
+    fn on_var_write(&mut self, name: &str, old: Option<&Value>, new: &Value) {
+        self.events.borrow_mut().push(TraceEvent::Write {
+            name: name.to_string(),
+            old: old.cloned(),
+            new: new.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpreter::Interpreter, parser::Parser, OutputBuffer, Scanner};
+
+    fn write(name: &str, old: Option<f64>, new: f64) -> TraceEvent {
+        TraceEvent::Write {
+            name: name.to_string(),
+            old: old.map(Value::Number),
+            new: Value::Number(new),
+        }
+    }
+
+    #[test]
+    fn test_collecting_tracer() {
+        let mut scanner = Scanner::new("var a = 1;\n{\n  var b = 2;\n  a = a + b;\n}\nprint a;");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let tracer = CollectingTracer::default();
+        let output = OutputBuffer::default();
+        let mut interpreter = Interpreter::default().with_output(output.clone());
+        interpreter.set_tracer(tracer.clone());
+        interpreter.interpret(&stmts).unwrap();
+
+        assert_eq!(
+            tracer.events(),
+            [
+                TraceEvent::Stmt(1),
+                write("a", None, 1.0),
+                TraceEvent::Stmt(3),
+                TraceEvent::Stmt(3),
+                write("b", None, 2.0),
+                TraceEvent::Stmt(4),
+                write("a", Some(1.0), 3.0),
+                TraceEvent::Stmt(6),
+            ]
+        );
+        assert_eq!(output.contents(), "3\n");
+    }
+
+    /// Pauses before every `print`, counting the pauses.
+    #[derive(Default)]
+    struct PausingTracer {
+        pauses: Rc<RefCell<Vec<u32>>>,
+    }
+
+    impl Tracer for PausingTracer {
+        fn before_stmt(&mut self, stmt: &Stmt, _line: u32) -> TraceControl {
+            match stmt {
+                Stmt::Print(_) => TraceControl::Pause,
+                _ => TraceControl::Continue,
+            }
+        }
+
+        fn paused(&mut self, _stmt: &Stmt, line: u32) {
+            self.pauses.borrow_mut().push(line);
+        }
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut scanner = Scanner::new("var a = 2;\nprint a;\nprint a + 1;");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let tracer = PausingTracer::default();
+        let pauses = tracer.pauses.clone();
+        let mut interpreter = Interpreter::default().with_output(OutputBuffer::default());
+        interpreter.set_tracer(tracer);
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(*pauses.borrow(), [2, 3]);
+
+        assert!(interpreter.take_tracer().is_some());
+        assert!(interpreter.take_tracer().is_none());
+    }
+}
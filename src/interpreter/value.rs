@@ -0,0 +1,614 @@
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::Display,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+use crate::{
+    errors::{Error, ErrorKind, JBreadErrors, JBreadResult},
+    interpreter::{Class, Function, Instance, NativeFunction},
+    LiteralValue,
+};
+
+/// The elements of a list.
+pub(crate) type ListCell = RefCell<Vec<Value>>;
+
+/// The entries of a map.
+pub(crate) type MapCell = RefCell<HashMap<MapKey, Value>>;
+
+/// What a map can be keyed by: a string, a number or a boolean. Numbers are
+/// the same key when they're `==`, so `-0` and `0` are one key, and NaN is
+/// none. The number `1` and the string `"1"` are different keys.
+#[derive(Debug, Clone)]
+pub enum MapKey {
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+impl MapKey {
+    /// The key `value` stands for, `None` for NaN and values of other types.
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(boolean) => Some(MapKey::Bool(*boolean)),
+            Value::Number(number) if number.is_nan() => None,
+            // Adding zero turns `-0` into `0`.
+            Value::Number(number) => Some(MapKey::Number(number + 0.0)),
+            Value::String(string) => Some(MapKey::String(string.clone())),
+            _ => None,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            MapKey::Bool(_) => 0,
+            MapKey::Number(_) => 1,
+            MapKey::String(_) => 2,
+        }
+    }
+}
+
+impl From<&MapKey> for Value {
+    fn from(key: &MapKey) -> Self {
+        match key {
+            MapKey::Bool(boolean) => Value::Bool(*boolean),
+            MapKey::Number(number) => Value::Number(*number),
+            MapKey::String(string) => Value::String(string.clone()),
+        }
+    }
+}
+
+impl PartialEq for MapKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MapKey {}
+
+impl Hash for MapKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            MapKey::Bool(boolean) => boolean.hash(state),
+            MapKey::Number(number) => number.to_bits().hash(state),
+            MapKey::String(string) => string.hash(state),
+        }
+    }
+}
+
+/// Booleans first, then numbers, then strings, the order maps print in.
+impl Ord for MapKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (MapKey::Bool(left), MapKey::Bool(right)) => left.cmp(right),
+            (MapKey::Number(left), MapKey::Number(right)) => left.total_cmp(right),
+            (MapKey::String(left), MapKey::String(right)) => left.cmp(right),
+            (left, right) => left.rank().cmp(&right.rank()),
+        }
+    }
+}
+
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A value a running program works with. Literals in the source become one
+/// through [`From<&LiteralValue>`], the rest only exist at runtime.
+#[derive(Debug, Clone, Default)]
+pub enum Value {
+    #[default]
+    Nil,
+    Bool(bool),
+    Number(f64),
+    NaN,
+    String(String),
+    /// Shared between every variable holding it.
+    List(Rc<ListCell>),
+    /// Shared like a list.
+    Map(Rc<MapCell>),
+    Function(Rc<Function>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
+    Native(&'static NativeFunction),
+}
+
+impl Value {
+    /// A new list holding `elements`.
+    pub fn list(elements: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(elements)))
+    }
+
+    /// A new map holding `entries`.
+    pub fn map(entries: HashMap<MapKey, Value>) -> Self {
+        Value::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::NaN => "NaN",
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Function(_) | Value::Native(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+        }
+    }
+
+    /// Only nil and false are falsy.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
+    /// The language's `==`. As with IEEE floats NaN equals nothing, not even
+    /// itself, lists are compared element by element and maps entry by entry.
+    pub fn equals(&self, other: &Self) -> bool {
+        self.compare(other, false, &mut Vec::new())
+    }
+
+    /// `visited` holds the pairs of lists or maps being compared further up,
+    /// meeting one again means they're cyclic and equal so far.
+    fn compare(
+        &self,
+        other: &Self,
+        nan_equal: bool,
+        visited: &mut Vec<(*const (), *const ())>,
+    ) -> bool {
+        match (self, other) {
+            (Value::List(left), Value::List(right)) => {
+                let pair = (Rc::as_ptr(left).cast(), Rc::as_ptr(right).cast());
+                if visited.contains(&pair) {
+                    return true;
+                }
+                visited.push(pair);
+                let (left, right) = (left.borrow(), right.borrow());
+                let equal = left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right.iter())
+                        .all(|(left, right)| left.compare(right, nan_equal, visited));
+                visited.pop();
+                equal
+            }
+            (Value::Map(left), Value::Map(right)) => {
+                let pair = (Rc::as_ptr(left).cast(), Rc::as_ptr(right).cast());
+                if visited.contains(&pair) {
+                    return true;
+                }
+                visited.push(pair);
+                let (left, right) = (left.borrow(), right.borrow());
+                let equal = left.len() == right.len()
+                    && left.iter().all(|(key, left)| {
+                        right
+                            .get(key)
+                            .is_some_and(|right| left.compare(right, nan_equal, visited))
+                    });
+                visited.pop();
+                equal
+            }
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Number(_) | Value::NaN, Value::Number(_) | Value::NaN) => {
+                let number = |value: &Value| value.to_number().unwrap_or(f64::NAN);
+                let (left, right) = (number(self), number(other));
+                Self::numbers_equal(left, right) || (nan_equal && left.is_nan() && right.is_nan())
+            }
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Function(left), Value::Function(right)) => left == right,
+            (Value::Class(left), Value::Class(right)) => left == right,
+            (Value::Instance(left), Value::Instance(right)) => left == right,
+            (Value::Native(left), Value::Native(right)) => left == right,
+            _ => false,
+        }
+    }
+
+    /// The language's `==` on numbers, IEEE equality: `-0 == 0`, `inf ==
+    /// inf`, and NaN equals nothing. Integers past 2^53 have already been
+    /// rounded to the nearest float, so `9007199254740993 ==
+    /// 9007199254740992`.
+    pub fn numbers_equal(left: f64, right: f64) -> bool {
+        left == right
+    }
+
+    /// How a number prints: integers without a fraction (`3`), the rest in
+    /// the shortest form that reads back as the same number, never in
+    /// exponent notation. `-0` prints as `0` since the two are equal, the
+    /// infinities as `inf` and `-inf`, and NaN as `NaN`.
+    pub fn format_number(number: f64) -> String {
+        if number == 0.0 {
+            "0".to_string()
+        } else if number.is_nan() {
+            "NaN".to_string()
+        } else {
+            number.to_string()
+        }
+    }
+
+    /// An unambiguous form of the value for debugging, unlike printing it:
+    /// strings quoted with their special characters escaped, numbers always
+    /// with a decimal point, e.g. `"a\nb"` and `2.0`, and lists of those.
+    pub fn repr(&self) -> String {
+        struct Repr<'a>(&'a Value);
+
+        impl Display for Repr<'_> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.0.write(f, &mut Vec::new(), true)
+            }
+        }
+
+        Repr(self).to_string()
+    }
+
+    fn write_repr(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(number) if number.is_finite() && number.fract() == 0.0 => {
+                write!(f, "{}.0", Self::format_number(*number))
+            }
+            Value::String(string) => {
+                write!(f, "\"")?;
+                for chr in string.chars() {
+                    match chr {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\r' => write!(f, "\\r")?,
+                        '\t' => write!(f, "\\t")?,
+                        chr if chr.is_control() => write!(f, "\\u{{{:x}}}", chr as u32)?,
+                        chr => write!(f, "{}", chr)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            value => write!(f, "{}", value),
+        }
+    }
+
+    /// Writes the value, or its [`repr`](Self::repr), showing a list that
+    /// contains itself as `[...]` where it repeats, and a map as `{...}`.
+    /// Map entries are written in key order.
+    fn write(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        visited: &mut Vec<*const ()>,
+        repr: bool,
+    ) -> std::fmt::Result {
+        match self {
+            Value::List(list) => {
+                if visited.contains(&Rc::as_ptr(list).cast()) {
+                    return write!(f, "[...]");
+                }
+                visited.push(Rc::as_ptr(list).cast());
+                write!(f, "[")?;
+                for (index, element) in list.borrow().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.write(f, visited, repr)?;
+                }
+                visited.pop();
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                if visited.contains(&Rc::as_ptr(map).cast()) {
+                    return write!(f, "{{...}}");
+                }
+                visited.push(Rc::as_ptr(map).cast());
+                let map = map.borrow();
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_by_key(|(key, _)| *key);
+                write!(f, "{{")?;
+                for (index, (key, value)) in entries.into_iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    Value::from(key).write(f, visited, repr)?;
+                    write!(f, ": ")?;
+                    value.write(f, visited, repr)?;
+                }
+                visited.pop();
+                write!(f, "}}")
+            }
+            _ if repr => self.write_repr(f),
+            _ => write!(f, "{}", self),
+        }
+    }
+
+    /// Like converting with `try_into`, without giving up the value.
+    pub fn to_number(&self) -> JBreadResult<f64> {
+        match self {
+            Value::Number(number) => Ok(*number),
+            // Arithmetic and comparisons then follow IEEE semantics.
+            Value::NaN => Ok(f64::NAN),
+            other => Err(conversion_error(other, "number", "Number")),
+        }
+    }
+
+    /// How `self` orders against `other` for `<` and the like and for `min`
+    /// and `max`: numbers by value, strings by their characters' code
+    /// points. `None` for other pairs, and when either is NaN.
+    pub fn order(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+            _ => self.to_number().ok()?.partial_cmp(&other.to_number().ok()?),
+        }
+    }
+
+    /// Wraps the result of a numeric operation, folding IEEE NaN (e.g. from
+    /// `inf - inf` or `inf * 0`) into the dedicated `NaN` value.
+    pub fn from_number(number: f64) -> Self {
+        if number.is_nan() {
+            Value::NaN
+        } else {
+            Value::Number(number)
+        }
+    }
+}
+
+/// The error for converting `value` to the `expected` type, `name` being the
+/// type as Rust calls it.
+fn conversion_error(value: &Value, expected: &str, name: &str) -> JBreadErrors {
+    JBreadErrors::RunTimeException(Error::new(
+        ErrorKind::TypeMismatch {
+            expected: expected.to_string(),
+            found: value.type_name().to_string(),
+        },
+        0,
+        0,
+        name.to_string(),
+        format!("Cannot convert non-{} to {}", expected, expected),
+    ))
+}
+
+impl From<&LiteralValue> for Value {
+    fn from(literal: &LiteralValue) -> Self {
+        match literal {
+            LiteralValue::String(string) => Value::String(string.clone()),
+            LiteralValue::Number(number) => Value::Number(*number),
+            LiteralValue::Boolean(boolean) => Value::Bool(*boolean),
+            LiteralValue::NaN => Value::NaN,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(boolean) => write!(f, "{}", boolean),
+            Value::Number(number) => write!(f, "{}", Self::format_number(*number)),
+            Value::NaN => write!(f, "NaN"),
+            Value::String(string) => write!(f, "{}", string),
+            Value::List(_) | Value::Map(_) => self.write(f, &mut Vec::new(), false),
+            Value::Function(function) => write!(f, "{}", function),
+            Value::Class(class) => write!(f, "{}", class),
+            Value::Instance(instance) => write!(f, "{}", instance),
+            Value::Native(native) => write!(f, "{}", native),
+        }
+    }
+}
+
+/// Unlike [`Value::equals`], NaN equals NaN here so values holding it can
+/// still be compared.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.compare(other, true, &mut Vec::new())
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = JBreadErrors;
+
+    fn try_from(value: Value) -> Result<f64, Self::Error> {
+        value.to_number()
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = JBreadErrors;
+
+    fn try_from(value: Value) -> Result<bool, Self::Error> {
+        match value {
+            Value::Bool(boolean) => Ok(boolean),
+            other => Err(conversion_error(&other, "boolean", "Boolean")),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = JBreadErrors;
+
+    fn try_from(value: Value) -> Result<String, Self::Error> {
+        match value {
+            Value::String(string) => Ok(string),
+            other => Err(conversion_error(&other, "string", "String")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(f64::try_from(Value::Number(2.0)).unwrap(), 2.0);
+        assert!(f64::try_from(Value::NaN).unwrap().is_nan());
+        assert!(bool::try_from(Value::Bool(true)).unwrap());
+        assert_eq!(
+            String::try_from(Value::String("a".to_string())).unwrap(),
+            "a"
+        );
+
+        let error = bool::try_from(Value::Nil).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::TypeMismatch {
+                expected: "boolean".to_string(),
+                found: "nil".to_string(),
+            }
+        );
+        let error = String::try_from(Value::Number(1.0)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "\"Cannot convert non-string to string\" at line: 0 in String"
+        );
+
+        assert_eq!(
+            Value::from(&LiteralValue::Boolean(false)),
+            Value::Bool(false)
+        );
+        assert_eq!(Value::from(&LiteralValue::NaN), Value::NaN);
+    }
+
+    #[test]
+    fn test_repr() {
+        let string = |string: &str| Value::String(string.to_string());
+        for (value, repr) in [
+            (Value::Nil, "nil"),
+            (Value::Bool(true), "true"),
+            (Value::Number(2.0), "2.0"),
+            (Value::Number(-0.0), "0.0"),
+            (Value::Number(1.5), "1.5"),
+            (Value::Number(f64::INFINITY), "inf"),
+            (Value::Number(f64::NEG_INFINITY), "-inf"),
+            (Value::NaN, "NaN"),
+            (string("a\nb"), "\"a\\nb\""),
+            (string("say \"hi\"\t\\"), "\"say \\\"hi\\\"\\t\\\\\""),
+            (string("\u{7}"), "\"\\u{7}\""),
+            (
+                Value::list(vec![Value::Number(1.0), string("x"), Value::list(vec![])]),
+                "[1.0, \"x\", []]",
+            ),
+            (
+                Value::Native(crate::interpreter::native("len").unwrap()),
+                "<native fn len>",
+            ),
+        ] {
+            assert_eq!(value.repr(), repr, "{:?}", value);
+        }
+
+        let list = Value::list(vec![string("a")]);
+        if let Value::List(cell) = &list {
+            cell.borrow_mut().push(list.clone());
+        }
+        assert_eq!(list.repr(), "[\"a\", [...]]");
+        assert_eq!(list.to_string(), "[a, [...]]");
+    }
+
+    #[test]
+    fn test_maps() {
+        let key = |value: Value| MapKey::from_value(&value);
+        assert_eq!(key(Value::Number(-0.0)), key(Value::Number(0.0)));
+        assert_ne!(key(Value::Number(1.0)), key(Value::String("1".to_string())));
+        assert_eq!(key(Value::NaN), None);
+        assert_eq!(key(Value::Number(f64::NAN)), None);
+        assert_eq!(key(Value::list(vec![])), None);
+
+        let map = Value::map(HashMap::from([
+            (MapKey::String("b".to_string()), Value::Number(2.0)),
+            (MapKey::Number(1.0), Value::String("a".to_string())),
+        ]));
+        assert_eq!(map.type_name(), "map");
+        assert_eq!(map.to_string(), "{1: a, b: 2}");
+        assert_eq!(map.repr(), "{1.0: \"a\", \"b\": 2.0}");
+        let copy = Value::map(HashMap::from([
+            (MapKey::Number(1.0), Value::String("a".to_string())),
+            (MapKey::String("b".to_string()), Value::Number(2.0)),
+        ]));
+        assert!(map.equals(&copy));
+        assert!(!map.equals(&Value::map(HashMap::new())));
+
+        if let Value::Map(cell) = &map {
+            cell.borrow_mut().insert(MapKey::Bool(true), map.clone());
+        }
+        assert_eq!(map.to_string(), "{true: {...}, 1: a, b: 2}");
+        assert_eq!(map, map.clone());
+    }
+
+    #[test]
+    fn test_truthiness_and_display() {
+        assert!(!Value::Nil.is_truthy());
+        assert!(!Value::Bool(false).is_truthy());
+        for value in [
+            Value::Bool(true),
+            Value::Number(0.0),
+            Value::NaN,
+            Value::String(String::new()),
+            Value::list(vec![]),
+        ] {
+            assert!(value.is_truthy(), "{:?}", value);
+        }
+
+        let list = Value::list(vec![Value::Nil, Value::Number(1.5), Value::NaN]);
+        assert_eq!(list.to_string(), "[nil, 1.5, NaN]");
+        if let Value::List(cell) = &list {
+            cell.borrow_mut().push(list.clone());
+        }
+        assert_eq!(list.to_string(), "[nil, 1.5, NaN, [...]]");
+        assert_eq!(list, list.clone());
+        // The NaN in it isn't equal to itself.
+        assert!(!list.equals(&list.clone()));
+        assert!(!Value::NaN.equals(&Value::NaN));
+        assert!(Value::Nil.equals(&Value::Nil));
+    }
+
+    #[test]
+    fn test_number_display() {
+        let display = |number: f64| Value::from_number(number).to_string();
+        // Integers print without a fraction, the rest as short as they can
+        // while reading back the same.
+        assert_eq!(display(3.0), "3");
+        assert_eq!(display(-12.0), "-12");
+        assert_eq!(display(1.5), "1.5");
+        assert_eq!(display(0.1 + 0.2), "0.30000000000000004");
+        // Negative zero is zero.
+        assert_eq!(display(-0.0), "0");
+        assert_eq!(display(0.0), "0");
+        // The infinities are spelled like their literal.
+        assert_eq!(display(f64::INFINITY), "inf");
+        assert_eq!(display(f64::NEG_INFINITY), "-inf");
+        assert_eq!(display(f64::NAN), "NaN");
+        // A NaN that skipped `from_number` prints the same.
+        assert_eq!(Value::Number(f64::NAN).to_string(), "NaN");
+        // Large numbers never switch to exponent notation, and integers past
+        // 2^53 print as the float they were rounded to.
+        assert_eq!(display(1e21), "1000000000000000000000");
+        assert_eq!(display(9007199254740993.0), "9007199254740992");
+        assert_eq!(display(1e-7), "0.0000001");
+    }
+
+    #[test]
+    fn test_number_equality() {
+        let number = Value::Number;
+        // Zero's sign doesn't matter.
+        assert!(number(-0.0).equals(&number(0.0)));
+        // Each infinity equals itself only.
+        assert!(number(f64::INFINITY).equals(&number(f64::INFINITY)));
+        assert!(!number(f64::INFINITY).equals(&number(f64::NEG_INFINITY)));
+        // NaN equals nothing, however it's held.
+        for nan in [Value::NaN, number(f64::NAN)] {
+            assert!(!nan.equals(&Value::NaN));
+            assert!(!nan.equals(&number(f64::NAN)));
+            assert!(!nan.equals(&number(0.0)));
+        }
+        // Except to Rust's `==`, so values holding it can be compared.
+        assert_eq!(number(f64::NAN), Value::NaN);
+        // No epsilon, sums that round differently differ.
+        assert!(!number(0.1 + 0.2).equals(&number(0.3)));
+        // Integers past 2^53 round to the nearest float first.
+        assert!(number(9007199254740993.0).equals(&number(9007199254740992.0)));
+        assert!(!number(9007199254740995.0).equals(&number(9007199254740992.0)));
+        // Numbers never equal other types.
+        assert!(!number(0.0).equals(&Value::Bool(false)));
+        assert!(!number(1.0).equals(&Value::String("1".to_string())));
+    }
+}
@@ -1,101 +1,904 @@
-#![feature(const_mut_refs)]
-
-#[macro_use]
-
-mod ast;
-mod errors;
+mod analysis;
+pub mod ast;
+#[cfg(test)]
+mod budgets;
+#[cfg(feature = "bytecode")]
+mod bytecode;
+mod cli;
+pub mod errors;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "interpreter")]
 mod interpreter;
+mod lint;
+mod optimizer;
 mod parser;
+mod pipeline;
+mod profile;
+mod repl;
+mod resolver;
+#[cfg(test)]
+mod robustness;
 mod scanner;
+#[cfg(feature = "interpreter")]
+mod session;
+mod source;
+mod stack;
+#[cfg(test)]
+mod stress;
+mod symbol;
 mod token;
 mod tool;
+pub mod walk;
 
-use errors::JBreadErrors;
-use parser::Parser;
+pub use analysis::*;
+pub use cli::*;
+pub use errors::*;
+#[cfg(feature = "ffi")]
+pub use ffi::*;
+#[cfg(feature = "interpreter")]
+pub use interpreter::{
+    CollectingTracer, EnvSnapshot, ExecutionEngine, Interpreter, InterpreterOptions, OutputBuffer,
+    TraceControl, TraceEvent, Tracer, UndeclaredAssignment, Value,
+};
+pub use lint::*;
+pub use optimizer::*;
+pub use parser::Parser;
+pub use pipeline::*;
+pub use profile::*;
+pub use repl::*;
+pub use resolver::*;
 pub use scanner::*;
+#[cfg(feature = "interpreter")]
+pub use session::*;
+pub use source::*;
+pub use symbol::*;
 pub use token::*;
 pub use tool::*;
 
-use scanner::Scanner;
-use std::{
-    fs::File,
-    io::{self, Read},
-    sync::Mutex,
-};
-
-use crate::interpreter::Interpreter;
+#[cfg(feature = "interpreter")]
+use std::{io, path::PathBuf, rc::Rc, sync::Arc};
 
+// Only the runner reads the options, which needs the interpreter.
+#[cfg_attr(not(feature = "interpreter"), allow(dead_code))]
 pub struct JuniorBread {
-    has_error: bool,
+    options: Options,
+    #[cfg(feature = "interpreter")]
+    interpreter_options: InterpreterOptions,
+    #[cfg(feature = "interpreter")]
+    history_file: Option<PathBuf>,
+    #[cfg(feature = "interpreter")]
+    trace_options: TraceOptions,
+    #[cfg(feature = "interpreter")]
+    profile: LanguageProfile,
+    #[cfg(feature = "interpreter")]
+    fold_constants: bool,
+    #[cfg(all(feature = "interpreter", feature = "serde"))]
+    globals_export: Option<String>,
 }
 
-impl JuniorBread {
-    const HAS_ERROR: Mutex<bool> = Mutex::new(false);
+impl Default for JuniorBread {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
+impl JuniorBread {
     pub fn new() -> Self {
-        Self { has_error: false }
+        Self::with_options(Options::default())
     }
 
-    pub fn set_error() {
-        *Self::HAS_ERROR.lock().unwrap() = true;
+    pub fn with_options(options: Options) -> Self {
+        Self {
+            options,
+            #[cfg(feature = "interpreter")]
+            interpreter_options: InterpreterOptions::default(),
+            #[cfg(feature = "interpreter")]
+            history_file: None,
+            #[cfg(feature = "interpreter")]
+            trace_options: TraceOptions::default(),
+            #[cfg(feature = "interpreter")]
+            profile: LanguageProfile::default(),
+            #[cfg(feature = "interpreter")]
+            fold_constants: false,
+            #[cfg(all(feature = "interpreter", feature = "serde"))]
+            globals_export: None,
+        }
     }
+}
 
-    pub fn remove_error() {
-        *Self::HAS_ERROR.lock().unwrap() = false;
+#[cfg(feature = "interpreter")]
+impl JuniorBread {
+    /// Options for the interpreters [`run_file`](Self::run_file) and
+    /// [`run_prompt`](Self::run_prompt) create.
+    pub fn with_interpreter_options(mut self, options: InterpreterOptions) -> Self {
+        self.interpreter_options = options;
+        self
     }
 
-    pub fn run_file(&self, path: &str) {
-        let mut file = File::open(path).unwrap();
-        let mut contents = String::new();
-        let mut interpreter = Interpreter::default();
+    /// Where [`run_prompt`](Self::run_prompt) keeps its history between
+    /// sessions, without one it only lasts the session.
+    pub fn with_history_file(mut self, path: PathBuf) -> Self {
+        self.history_file = Some(path);
+        self
+    }
 
-        file.read_to_string(&mut contents).unwrap();
-        self.run(&contents, &mut interpreter);
+    /// What to print about each program before running it.
+    pub fn with_trace_options(mut self, options: TraceOptions) -> Self {
+        self.trace_options = options;
+        self
+    }
+
+    /// Limits on the source [`run`](Self::run) is handed, the same as
+    /// setting them on the [profile](Self::with_profile).
+    pub fn with_scanner_options(mut self, options: ScannerOptions) -> Self {
+        self.profile.max_lines = options.max_lines;
+        self
+    }
 
-        if self.has_error {
-            std::process::exit(65);
+    /// The keywords programs may use and the limits they run under. Its step
+    /// limit applies to the interpreters [`run_file`](Self::run_file),
+    /// [`run_source`](Self::run_source) and [`run_prompt`](Self::run_prompt)
+    /// create, not to those handed to [`run`](Self::run).
+    pub fn with_profile(mut self, profile: LanguageProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Whether to [fold constants](fold_constants) in each program before
+    /// running it.
+    pub fn with_constant_folding(mut self, fold: bool) -> Self {
+        self.fold_constants = fold;
+        self
+    }
+
+    /// Where [`run_source`](Self::run_source) writes the globals as JSON once
+    /// a program ran successfully, see
+    /// [`export_globals_json`](Interpreter::export_globals_json).
+    #[cfg(feature = "serde")]
+    pub fn with_globals_export(mut self, path: String) -> Self {
+        self.globals_export = Some(path);
+        self
+    }
+
+    /// Runs the file at `path`, failing with [`JBreadErrors::Io`] if it
+    /// can't be read or isn't text, see [`read_source`].
+    pub fn run_file(&self, path: &str) -> JBreadResult<()> {
+        let mut interpreter = self.interpreter();
+        interpreter.set_script_path(path);
+        let contents =
+            read_source_file(path).inspect_err(|error| interpreter.report(&error.to_string()))?;
+        self.run_program(SourceRef::new(path, &contents), &mut interpreter)
+    }
+
+    /// Runs a whole program in a fresh interpreter.
+    pub fn run_source(&self, source: &str) -> JBreadResult<()> {
+        let mut interpreter = self.interpreter();
+        self.run_program(SourceRef::new(UNNAMED_SOURCE, source), &mut interpreter)
+    }
+
+    /// An interpreter with the options and the step limit it was given.
+    fn interpreter(&self) -> Interpreter {
+        let mut interpreter = Interpreter::with_options(self.interpreter_options);
+        interpreter.set_step_limit(self.profile.max_steps);
+        interpreter
+    }
+
+    fn run_program(
+        &self,
+        source: Arc<SourceRef>,
+        interpreter: &mut Interpreter,
+    ) -> JBreadResult<()> {
+        self.execute(source, interpreter, false)?;
+        #[cfg(feature = "serde")]
+        if let Some(path) = &self.globals_export {
+            export_globals(path, interpreter)
+                .inspect_err(|error| interpreter.report(&error.to_string()))?;
         }
+        Ok(())
     }
 
-    pub fn run_prompt(&self) {
-        let mut interpreter = Interpreter::default();
-        loop {
-            let mut input = String::new();
-            io::stdin().read_line(&mut input).unwrap();
-            self.run(&input, &mut interpreter);
+    /// Runs a session reading entries from stdin until end of input or
+    /// `:quit`, failing with [`JBreadErrors::Io`] if stdin can't be read.
+    pub fn run_prompt(&self) -> JBreadResult<()> {
+        let mut interpreter = self.interpreter();
+        let mut history = match &self.history_file {
+            Some(path) => History::persistent(path.clone()),
+            None => History::default(),
+        };
+        let mut input = io::stdin().lock();
+        // Ends on end of input, which is Ctrl-D at a terminal.
+        while let Some(entry) = read_entry(&mut input, &mut io::stdout())
+            .map_err(|error| JBreadErrors::io("<stdin>", &error))
+            .inspect_err(|error| interpreter.report(&error.to_string()))?
+        {
+            if let Err(error) = history.push(&entry) {
+                interpreter.report(&error.to_string());
+            }
+            match parse_command(&entry) {
+                Some(Command::Quit) => break,
+                Some(command) => self.run_command(command, &mut interpreter),
+                // Errors were reported and the session goes on.
+                None => {
+                    let _ = self.run_entry(&entry, &mut interpreter);
+                }
+            }
         }
+        Ok(())
     }
 
-    pub fn run(&self, source: &str, interpreter: &mut Interpreter) {
-        let mut scanner = Scanner::new(source);
-        let mut parser = Parser::new(scanner.scan_tokens());
+    /// Carries out a REPL meta-command other than `:quit`, which is up to
+    /// the loop running the session.
+    pub fn run_command(&self, command: Command, interpreter: &mut Interpreter) {
+        match command {
+            // Pasted lines are read by `read_entry` and run as an entry.
+            Command::Quit | Command::Paste => {}
+            Command::Env => {
+                let scopes = format_scopes(&interpreter.environment.borrow().scopes());
+                // Nowhere to report a failed write but the same output.
+                let _ = interpreter.write(&scopes);
+            }
+            Command::Reset => interpreter.environment = Rc::default(),
+            Command::Load(path) => match read_source_file(&path) {
+                Ok(source) => {
+                    let _ = self.execute(SourceRef::new(&path, &source), interpreter, false);
+                }
+                Err(error) => interpreter.report(&error.to_string()),
+            },
+            Command::Ast(source) => {
+                let scanner =
+                    Scanner::with_profile(SourceRef::new(UNNAMED_SOURCE, &source), &self.profile);
+                let parsed = match scanner.scan() {
+                    (_, errors) if !errors.is_empty() => Err(errors[0].clone()),
+                    (tokens, _) => Parser::new(&tokens)
+                        .with_profile(&self.profile)
+                        .parse_expression(),
+                };
+                match parsed {
+                    Ok(expr) => {
+                        let _ =
+                            interpreter.write(&format!("{}\n", AstPrinter::default().print(expr)));
+                    }
+                    Err(error) => interpreter.report(&error.render(&SourceMap::new(&source))),
+                }
+            }
+            #[cfg(feature = "serde")]
+            Command::Save(path) => {
+                if let Err(error) = save_session(&path, interpreter) {
+                    interpreter.report(&error.to_string());
+                }
+            }
+            #[cfg(feature = "serde")]
+            Command::Restore(path) => {
+                if let Err(error) = restore_session(&path, interpreter) {
+                    interpreter.report(&error.to_string());
+                }
+            }
+            #[cfg(not(feature = "serde"))]
+            Command::Save(_) | Command::Restore(_) => {
+                interpreter.report("Saving and restoring a session needs the serde feature")
+            }
+            Command::Invalid(message) => interpreter.report(&message),
+        }
+    }
+
+    /// Runs a REPL entry, which may hold several statements, as a unit: a
+    /// trailing expression statement prints its value and if any statement
+    /// fails the globals are put back as they were before the entry.
+    pub fn run_entry(&self, entry: &str, interpreter: &mut Interpreter) -> JBreadResult<()> {
+        let checkpoint = interpreter.environment.borrow().checkpoint();
+        let result = self
+            .execute(SourceRef::new(UNNAMED_SOURCE, entry), interpreter, true)
+            .map(drop);
+        match &result {
+            // What ran before the exit stays, and the session goes on.
+            Err(exit @ JBreadErrors::Exit(_)) => {
+                interpreter.report(&format!("exited with code {}", exit.exit_code()))
+            }
+            Err(_) => interpreter.environment.borrow_mut().roll_back(checkpoint),
+            Ok(()) => {}
+        }
+        result
+    }
+
+    /// Runs `source` in `interpreter`, reporting errors as they happen and
+    /// returning the one that stopped it. Otherwise returns the value of the
+    /// program's last statement when that's an expression statement, see
+    /// [`Interpreter::interpret`].
+    pub fn run(&self, source: &str, interpreter: &mut Interpreter) -> JBreadResult<Option<Value>> {
+        self.execute(SourceRef::new(UNNAMED_SOURCE, source), interpreter, false)
+    }
+
+    fn execute(
+        &self,
+        source: Arc<SourceRef>,
+        interpreter: &mut Interpreter,
+        echo: bool,
+    ) -> JBreadResult<Option<Value>> {
+        let mut scanner = Scanner::with_profile(source.clone(), &self.profile);
+        scanner.scan_tokens();
+        if let Some(error) = scanner.errors().first() {
+            interpreter.report(&error.render(source.map()));
+            return Err(error.clone());
+        }
+        let tokens = scanner.scanned();
+        if self.trace_options.tokens {
+            interpreter.write_error(&explain_tokens(tokens));
+        }
+        let mut parser = Parser::new(tokens).with_profile(&self.profile);
         let ast = parser.parse();
 
-        if let Err(error) = &ast {
-            error.report();
-            Self::set_error();
-            return;
+        if let Err(error) = ast {
+            interpreter.report(&error.render(source.map()));
+            return Err(error);
         };
 
-        let ast = ast.unwrap();
-        dbg!(scanner.scan_tokens());
-        dbg!(&ast);
+        let mut ast = ast.unwrap();
+        let mut warnings = parser.take_diagnostics();
+        if echo {
+            // The echoed expression is printed, so it has an effect after all.
+            if let Some(ast::Stmt::Expression(statement)) = ast.last() {
+                if parser::has_no_effect(&statement.expression) {
+                    warnings.pop();
+                }
+            }
+        }
+        if self.fold_constants {
+            ast = fold_constants(ast);
+        }
+        if self.trace_options.ast {
+            interpreter.write_error(&explain_ast(&ast));
+        }
+        if self.trace_options.dot {
+            return interpreter
+                .write(&AstDotPrinter::print_program(&ast))
+                .map(|()| None);
+        }
+        let mut diagnostics = warnings.into_lint();
+        match Resolver::default().resolve(&ast) {
+            Ok(resolution) => {
+                interpreter.set_locals(resolution.locals);
+                diagnostics.extend(resolution.warnings);
+            }
+            Err(error) => {
+                interpreter.report(&error.render(source.map()));
+                return Err(error);
+            }
+        }
 
+        if self.options.validate_on_run {
+            diagnostics.extend(self.options.validate(tokens));
+        }
+        let mut suppressions =
+            Suppressions::new(scanner.directives(), tokens, &self.options.known_codes());
+        let diagnostics = suppressions.apply(diagnostics);
+        if !self.options.silence_warnings {
+            for diagnostic in diagnostics {
+                interpreter.report(&diagnostic.to_string());
+            }
+        }
         let result = interpreter.interpret(&ast);
 
-        if let Err(err) = &result {
-            err.report();
-            Self::set_error();
-            return;
+        match &result {
+            Ok(Some(value)) if echo => interpreter.print(value.clone())?,
+            // Stopping on purpose, the caller decides what it means.
+            Err(JBreadErrors::Exit(_)) | Ok(_) => {}
+            Err(err) => {
+                // Errors in an imported file point into that file.
+                let source = err.error().source().unwrap_or(&source);
+                interpreter.report(&err.render(source.map()));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(all(feature = "interpreter", feature = "serde"))]
+fn export_globals(path: &str, interpreter: &Interpreter) -> JBreadResult<()> {
+    write_json(path, interpreter.export_globals_json()?)
+}
+
+/// Writes what's visible from the session's environment to `path`, see
+/// [`Environment::snapshot`](interpreter::environment::Environment::snapshot).
+#[cfg(all(feature = "interpreter", feature = "serde"))]
+fn save_session(path: &str, interpreter: &Interpreter) -> JBreadResult<()> {
+    write_json(path, interpreter.environment.borrow().snapshot().to_json()?)
+}
+
+/// Defines what [`save_session`] wrote to `path`, replacing variables of the
+/// same name.
+#[cfg(all(feature = "interpreter", feature = "serde"))]
+fn restore_session(path: &str, interpreter: &Interpreter) -> JBreadResult<()> {
+    let json = std::fs::read_to_string(path).map_err(|error| JBreadErrors::io(path, &error))?;
+    let snapshot = EnvSnapshot::from_json(&json)?;
+    interpreter.environment.borrow_mut().restore(snapshot);
+    Ok(())
+}
+
+#[cfg(all(feature = "interpreter", feature = "serde"))]
+fn write_json(path: &str, json: String) -> JBreadResult<()> {
+    std::fs::write(path, json).map_err(|error| {
+        JBreadErrors::Io(Error::new(
+            ErrorKind::Io,
+            0,
+            0,
+            path.to_string(),
+            format!("Couldn't write {}: {}", path, error),
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    /// Misspellings that were renamed away and shouldn't come back. This
+    /// file is skipped so the list itself doesn't trip the test.
+    const MISSPELLINGS: &[&str] = &[
+        "encolosing",
+        "decleration",
+        "evalute",
+        "multipication",
+        "initalization",
+    ];
+
+    fn misspellings_in(dir: &Path, found: &mut Vec<String>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                misspellings_in(&path, found);
+                continue;
+            }
+            if path.extension().is_none_or(|extension| extension != "rs") || path.ends_with(file!())
+            {
+                continue;
+            }
+            let contents = fs::read_to_string(&path).unwrap();
+            for (line, text) in contents.lines().enumerate() {
+                let lowercase = text.to_lowercase();
+                for word in MISSPELLINGS.iter().filter(|word| lowercase.contains(*word)) {
+                    found.push(format!("{}:{}: {}", path.display(), line + 1, word));
+                }
+            }
+        }
+    }
+
+    fn feature_gates_in(dir: &Path, found: &mut Vec<String>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                feature_gates_in(&path, found);
+            } else if path.extension().is_some_and(|extension| extension == "rs") {
+                let contents = fs::read_to_string(&path).unwrap();
+                for (line, text) in contents.lines().enumerate() {
+                    if text.trim_start().starts_with("#![feature") {
+                        found.push(format!("{}:{}: {}", path.display(), line + 1, text));
+                    }
+                }
+            }
         }
     }
 
-    pub fn error(err: JBreadErrors) {
-        Self::report(err);
+    #[cfg(feature = "interpreter")]
+    fn lookup(
+        interpreter: &crate::interpreter::Interpreter,
+        name: &str,
+    ) -> crate::JBreadResult<crate::Value> {
+        let token = crate::Token::new(crate::TokenTypes::Identifier, name.to_string(), None, 1);
+        interpreter.environment.borrow().get(&token)
     }
 
-    pub fn report(error: JBreadErrors) {
-        eprintln!("{:?}\n{}", error, error.to_string());
-        Self::set_error();
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_run_file_errors() {
+        use crate::{ErrorKind, JBreadErrors, JuniorBread};
+
+        let bread = JuniorBread::new();
+        let path = std::env::temp_dir().join(format!("jbread_missing_{}.jbr", std::process::id()));
+        let path = path.display().to_string();
+        let error = bread.run_file(&path).unwrap_err();
+        assert!(matches!(error, JBreadErrors::Io(_)));
+        assert_eq!(error.kind(), &ErrorKind::Io);
+        assert!(error.to_string().contains(&path));
+        assert_eq!(error.exit_code(), 74);
+
+        assert_eq!(bread.run_source("print ;").unwrap_err().exit_code(), 65);
+        assert_eq!(
+            bread.run_source("print -\"a\";").unwrap_err().exit_code(),
+            70
+        );
+        assert!(bread.run_source("print 1;").is_ok());
+
+        let binary = format!("{}.bin", path);
+        fs::write(&binary, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let error = bread.run_file(&binary).unwrap_err();
+        fs::remove_file(&binary).unwrap();
+        assert_eq!(error.kind(), &ErrorKind::BinaryContent);
+        assert!(error.to_string().starts_with(&binary));
+
+        let bread = bread.with_scanner_options(crate::ScannerOptions { max_lines: Some(1) });
+        let error = bread.run_source("print 1;\nprint 2;").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::TooManyLines);
+        assert_eq!(error.exit_code(), 65);
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_run_with_profile() {
+        use crate::{ErrorKind, JuniorBread, LanguageProfile, TokenTypes};
+
+        let profile = LanguageProfile {
+            max_steps: Some(100),
+            ..LanguageProfile::default().without(TokenTypes::Fun)
+        };
+        let bread = JuniorBread::new().with_profile(profile);
+        let error = bread.run_source("fun f() {}").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::DisabledKeyword);
+        assert_eq!(error.exit_code(), 65);
+        let error = bread.run_source("var f = fun () {};").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::DisabledKeyword);
+
+        let error = bread
+            .run_source("var n = 0; repeat (1000) n = n + 1;")
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::BudgetExceeded);
+        assert!(bread
+            .run_source("var n = 0; repeat (10) n = n + 1;")
+            .is_ok());
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_run_returns_last_value() {
+        use crate::{interpreter::Interpreter, ErrorKind, JuniorBread, OutputBuffer, Value};
+
+        let bread = JuniorBread::new();
+        let run = |source: &str| {
+            let mut interpreter = Interpreter::default().with_error_output(OutputBuffer::new());
+            bread.run(source, &mut interpreter)
+        };
+        assert_eq!(run("1 + 1;").unwrap(), Some(Value::Number(2.0)));
+        assert_eq!(
+            run("var a = \"a\"; a + \"b\";").unwrap(),
+            Some(Value::String("ab".into()))
+        );
+        assert_eq!(run("").unwrap(), None);
+        assert_eq!(run("1; var a = 1;").unwrap(), None);
+        assert_eq!(run("print 1;").unwrap(), None);
+        // Only the program's own statements count, not a block's.
+        assert_eq!(run("{ 1; }").unwrap(), None);
+
+        let error = run("1; -\"a\"; 2;").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::TypeMismatch { .. }));
+
+        #[cfg(feature = "bytecode")]
+        crate::bytecode::assert_engines_agree("var a = 2; a * 3;", true);
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_import() {
+        use crate::{interpreter::Interpreter, ErrorKind, JuniorBread, OutputBuffer};
+
+        let dir = std::env::temp_dir().join(format!("jbread_import_{}", std::process::id()));
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        let write = |name: &str, source: &str| fs::write(dir.join(name), source).unwrap();
+        write(
+            "main.jbr",
+            "import \"lib/helpers.jbr\";\nprint twice(count);",
+        );
+        write(
+            "lib/helpers.jbr",
+            "import \"counter.jbr\";\nfun twice(n) { return n * 2; }",
+        );
+        write("lib/counter.jbr", "var count = 1;\nprint \"counting\";");
+        write("missing.jbr", "import \"nowhere.jbr\";");
+        write("broken.jbr", "print 1;\nimport \"lib/bad.jbr\";");
+        write("lib/bad.jbr", "print ;");
+        write("a.jbr", "import \"b.jbr\";");
+        write("b.jbr", "print \"b\";\nimport \"a.jbr\";");
+        let bread = JuniorBread::new();
+        let run = |name: &str, entry: &str| {
+            let output = OutputBuffer::new();
+            let mut interpreter = Interpreter::default()
+                .with_output(output.clone())
+                .with_error_output(OutputBuffer::new());
+            interpreter.set_script_path(dir.join(name));
+            let result = bread.run_entry(entry, &mut interpreter);
+            (result, output.take())
+        };
+
+        // Paths are relative to the importing file, a second import is skipped.
+        let main = fs::read_to_string(dir.join("main.jbr")).unwrap();
+        let (result, output) = run(
+            "main.jbr",
+            &format!("{}\nimport \"lib/counter.jbr\";", main),
+        );
+        assert!(result.is_ok());
+        assert_eq!(output, "counting\n2\n");
+
+        let (result, _) = run("missing.jbr", "import \"nowhere.jbr\";");
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ImportFailed);
+        assert!(error
+            .to_string()
+            .contains("Couldn't import nowhere.jbr: Couldn't read"));
+
+        let (result, output) = run("broken.jbr", "print 1;\nimport \"lib/bad.jbr\";");
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ImportFailed);
+        assert!(error.to_string().contains(" at 2:1 in import"));
+        assert!(error.to_string().contains("\"Expected Expression\" at 1:7"));
+        assert_eq!(output, "1\n");
+
+        let (result, output) = run("a.jbr", "import \"b.jbr\";");
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ImportCycle);
+        assert!(error
+            .to_string()
+            .contains("Import cycle: a.jbr -> b.jbr -> a.jbr"));
+        assert_eq!(output, "b\n");
+
+        let (result, _) = run("main.jbr", "{ import \"a.jbr\"; }");
+        assert_eq!(
+            result.unwrap_err().kind(),
+            &ErrorKind::ImportOutsideTopLevel
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_exit() {
+        use crate::{interpreter::Interpreter, JBreadErrors, JuniorBread, OutputBuffer, Value};
+
+        let bread = JuniorBread::new();
+        let error = bread.run_source("print 1; exit(3); print 2;").unwrap_err();
+        assert!(matches!(error, JBreadErrors::Exit(_)));
+        assert_eq!(error.exit_code(), 3);
+
+        // An entry that exits keeps what it did, and the session goes on.
+        let output = OutputBuffer::new();
+        let errors = OutputBuffer::new();
+        let mut interpreter = Interpreter::default()
+            .with_output(output.clone())
+            .with_error_output(errors.clone());
+        let result = bread.run_entry("var a = 1; exit(2); a = 3;", &mut interpreter);
+        assert_eq!(result.unwrap_err().exit_code(), 2);
+        assert_eq!(errors.take(), "exited with code 2\n");
+        assert!(bread.run_entry("print a;", &mut interpreter).is_ok());
+        assert_eq!(output.take(), "1\n");
+        assert_eq!(lookup(&interpreter, "a").unwrap(), Value::Number(1.0));
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_scan_errors_are_rendered() {
+        use crate::{interpreter::Interpreter, ErrorKind, JuniorBread, OutputBuffer};
+
+        let errors = OutputBuffer::new();
+        let mut interpreter = Interpreter::default().with_error_output(errors.clone());
+        let error = JuniorBread::new()
+            .run_entry("print 1;\nprint \"open;", &mut interpreter)
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnterminatedString);
+        assert_eq!(
+            errors.take(),
+            "\"Unterminated string.\" at 2:7 in \nline 2: print \"open;\n              ^~~~~~\n"
+        );
+    }
+
+    #[cfg(all(feature = "interpreter", feature = "serde"))]
+    #[test]
+    fn test_globals_export() {
+        use crate::JuniorBread;
+
+        let path = std::env::temp_dir().join(format!("jbread_globals_{}.json", std::process::id()));
+        let bread = JuniorBread::new().with_globals_export(path.display().to_string());
+
+        assert!(bread.run_source("var a = 1; var b = a + 1;").is_ok());
+        assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"a":1.0,"b":2.0}"#);
+        fs::remove_file(&path).unwrap();
+
+        // A failed run leaves no file behind.
+        assert!(bread.run_source("var a = 1; print -\"a\";").is_err());
+        assert!(!path.exists());
+        assert!(bread.run_source("var a = ;").is_err());
+        assert!(!path.exists());
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_run_entry_statement_lists() {
+        use crate::{interpreter::Interpreter, JBreadErrors, JuniorBread, OutputBuffer, Value};
+
+        let bread = JuniorBread::new();
+        let output = OutputBuffer::new();
+        let errors = OutputBuffer::new();
+        let mut interpreter = Interpreter::default()
+            .with_output(output.clone())
+            .with_error_output(errors.clone());
+        let mut run = |entry: &str| {
+            let result = bread.run_entry(entry, &mut interpreter);
+            (result, output.take())
+        };
+
+        // Every statement runs, only a trailing expression echoes.
+        let (result, printed) = run("var a = 1; var b = 2; print a + b;");
+        assert!(result.is_ok());
+        assert_eq!(printed, "3\n");
+        let (_, printed) = run("a + b; a * 10;");
+        assert_eq!(printed, "10\n");
+        let (_, printed) = run("var c = a + b; c * 2;");
+        assert_eq!(printed, "6\n");
+
+        // A failure puts back every global the entry touched, and its column
+        // tells which statement on the line failed.
+        let (result, printed) = run("a = 5; var d = -\"x\"; var e = 1; e;");
+        let Err(JBreadErrors::RunTimeException(error)) = result else {
+            panic!("{:?}", result);
+        };
+        assert!(error.to_string().contains(" at 1:16 "), "{}", error);
+        assert!(printed.is_empty());
+        assert!(errors.take().contains("1:16"));
+        assert_eq!(lookup(&interpreter, "a").unwrap(), Value::Number(1.0));
+        assert!(lookup(&interpreter, "d").is_err());
+        assert!(lookup(&interpreter, "e").is_err());
+        assert_eq!(lookup(&interpreter, "c").unwrap(), Value::Number(3.0));
+
+        // Running a program doesn't echo.
+        bread.run("a;", &mut interpreter).unwrap();
+        assert!(output.take().is_empty());
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_run_pasted_entries() {
+        use crate::{interpreter::Interpreter, read_entry, JuniorBread, OutputBuffer};
+
+        let bread = JuniorBread::new();
+        let output = OutputBuffer::new();
+        let mut interpreter = Interpreter::default()
+            .with_output(output.clone())
+            .with_error_output(OutputBuffer::new());
+        let mut input = std::io::Cursor::new(
+            "var a = 1;\n{\n  print a;\n}\n\
+             :paste\nprint \"pasted\";\nprint (;\nprint 3;\n.\n\
+             :paste\nvar s = \"two\nlines\";\nfun f() {\n  print s;\n}\n\nf();\nprint \"end\";\n.\n",
+        );
+        let mut results = Vec::new();
+        while let Some(entry) = read_entry(&mut input, &mut Vec::new()).unwrap() {
+            let result = bread.run_entry(&entry, &mut interpreter);
+            results.push((
+                result.err().map(|error| error.error().line()),
+                output.take(),
+            ));
+        }
+        assert_eq!(
+            results,
+            [
+                (None, String::new()),
+                (None, "1\n".to_string()),
+                // Nothing of a paste runs when any of it doesn't parse, and
+                // lines count from its start.
+                (Some(2), String::new()),
+                (None, "two\nlines\nend\n".to_string()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_warnings_do_not_change_execution() {
+        use crate::{Interpreter, JuniorBread, Options, OutputBuffer};
+
+        let source = "var a = 1; a; repeat (a = 2) print a; \"done\"; print a;";
+        let run = |bread: JuniorBread| {
+            let (output, errors) = (OutputBuffer::new(), OutputBuffer::new());
+            let mut interpreter = Interpreter::default()
+                .with_output(output.clone())
+                .with_error_output(errors.clone());
+            bread.run(source, &mut interpreter).unwrap();
+            (output.take(), errors.take())
+        };
+        let mut options = Options::default();
+        options.silence_warnings = true;
+        let silenced = JuniorBread::with_options(options);
+        let (printed, warnings) = run(JuniorBread::new());
+        assert_eq!(printed, "2\n2\n2\n");
+        assert_eq!(warnings.matches("warning[").count(), 3, "{}", warnings);
+        assert_eq!(run(silenced), (printed, String::new()));
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_load_and_reset_commands() {
+        use crate::{interpreter::Interpreter, Command, JuniorBread, Value};
+
+        let path = std::env::temp_dir().join(format!("jbread_load_{}.jbr", std::process::id()));
+        fs::write(
+            &path,
+            "fun double(x) { return x * 2; }\nvar loaded = double(21);\n",
+        )
+        .unwrap();
+
+        let bread = JuniorBread::new();
+        let mut interpreter = Interpreter::default();
+        bread.run("var before = 1;", &mut interpreter).unwrap();
+        bread.run_command(Command::Load(path.display().to_string()), &mut interpreter);
+        fs::remove_file(&path).unwrap();
+
+        // What the file defined is usable from entries after it.
+        bread
+            .run("var again = double(before);", &mut interpreter)
+            .unwrap();
+        assert_eq!(lookup(&interpreter, "loaded").unwrap(), Value::Number(42.0));
+        assert_eq!(lookup(&interpreter, "again").unwrap(), Value::Number(2.0));
+
+        bread.run_command(Command::Reset, &mut interpreter);
+        for name in ["before", "double", "loaded", "again"] {
+            assert!(lookup(&interpreter, name).is_err(), "{}", name);
+        }
+    }
+
+    #[cfg(all(feature = "interpreter", feature = "serde"))]
+    #[test]
+    fn test_save_and_restore_commands() {
+        use crate::{interpreter::Interpreter, Command, JuniorBread, Value};
+
+        let path = std::env::temp_dir().join(format!("jbread_session_{}.json", std::process::id()));
+        let path = path.display().to_string();
+        let bread = JuniorBread::new();
+        let mut interpreter = Interpreter::default();
+        bread
+            .run(
+                "var a = 1; const b = [a, \"two\"]; fun f() {}",
+                &mut interpreter,
+            )
+            .unwrap();
+        bread.run_command(Command::Save(path.clone()), &mut interpreter);
+
+        let mut restored = Interpreter::default();
+        bread.run("var a = 5; var c = 3;", &mut restored).unwrap();
+        bread.run_command(Command::Restore(path.clone()), &mut restored);
+        fs::remove_file(&path).unwrap();
+
+        bread.run("var d = a + c + b[0];", &mut restored).unwrap();
+        assert_eq!(lookup(&restored, "d").unwrap(), Value::Number(5.0));
+        assert!(bread.run("b = nil;", &mut restored).is_err());
+        assert!(lookup(&restored, "f").is_err());
+    }
+
+    #[test]
+    fn test_no_known_misspellings() {
+        let mut found = Vec::new();
+        misspellings_in(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"),
+            &mut found,
+        );
+        assert!(
+            found.is_empty(),
+            "known misspellings found:\n{}",
+            found.join("\n")
+        );
+    }
+
+    /// Users build on stable, so nothing here may need a nightly feature
+    /// gate and the toolchain file pins stable for the crate's own builds.
+    #[test]
+    fn test_builds_on_stable() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut found = Vec::new();
+        for dir in ["src", "benches", "examples", "tests"] {
+            feature_gates_in(&root.join(dir), &mut found);
+        }
+        assert!(
+            found.is_empty(),
+            "feature gates found:\n{}",
+            found.join("\n")
+        );
+
+        let toolchain = fs::read_to_string(root.join("rust-toolchain.toml")).unwrap();
+        assert!(toolchain.contains("channel = \"stable\""));
     }
 }
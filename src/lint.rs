@@ -0,0 +1,473 @@
+use std::{
+    fmt::Display,
+    io,
+    panic::{self, AssertUnwindSafe},
+};
+
+use crate::{
+    errors::{JBreadResult, ASSIGNMENT_IN_CONDITION, NO_EFFECT},
+    parser::Parser,
+    LiteralValue, Pipeline, Token, TokenTypes, UNUSED_VARIABLE,
+};
+
+/// Code attached to diagnostics produced when a validator panics.
+pub const INTERNAL_LINT_ERROR: &str = "internal-lint-error";
+/// Code for `jbread-allow` directives that didn't suppress anything.
+pub const UNUSED_SUPPRESSION: &str = "unused-suppression";
+/// Code for codes in a `jbread-allow` directive that aren't known.
+pub const UNKNOWN_LINT_CODE: &str = "unknown-lint-code";
+
+/// Prefix of a comment suppressing diagnostics in the statement right after
+/// it, e.g. `// jbread-allow: unused-variable, bad-code`.
+pub const ALLOW_DIRECTIVE: &str = "jbread-allow:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Position {
+    pub fn new(line: u32, column: u32) -> Self {
+        Self { line, column }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintDiagnostic {
+    pub code: String,
+    pub message: String,
+    pub position: Position,
+}
+
+impl LintDiagnostic {
+    pub fn new(code: &str, message: &str, position: Position) -> Self {
+        Self {
+            code: code.to_string(),
+            message: message.to_string(),
+            position,
+        }
+    }
+
+    pub fn report(&self, out: &mut dyn io::Write) -> io::Result<()> {
+        writeln!(out, "{}", self)
+    }
+}
+
+impl Display for LintDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "warning[{}]: \"{}\" at {}:{}",
+            self.code, self.message, self.position.line, self.position.column
+        )
+    }
+}
+
+/// A `jbread-allow` comment found by the scanner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AllowDirective {
+    pub codes: Vec<String>,
+    pub position: Position,
+    /// Offset in the source right after the comment.
+    pub end: usize,
+}
+
+struct Suppression {
+    position: Position,
+    codes: Vec<String>,
+    /// First and last position covered, `None` when no statement follows.
+    range: Option<(Position, Position)>,
+    used: bool,
+}
+
+/// Suppresses diagnostics according to the `jbread-allow` directives of a
+/// program. Each directive covers the statement starting right after it,
+/// children included.
+pub struct Suppressions {
+    suppressions: Vec<Suppression>,
+    diagnostics: Vec<LintDiagnostic>,
+}
+
+impl Suppressions {
+    pub fn new(directives: &[AllowDirective], tokens: &[Token], known_codes: &[&str]) -> Self {
+        let mut diagnostics = Vec::new();
+        let suppressions = directives
+            .iter()
+            .map(|directive| {
+                let mut codes = Vec::new();
+                for code in directive.codes.iter() {
+                    if known_codes.contains(&code.as_str()) {
+                        codes.push(code.clone());
+                    } else {
+                        diagnostics.push(LintDiagnostic::new(
+                            UNKNOWN_LINT_CODE,
+                            &format!(
+                                "Unknown lint code '{}', expected one of: {}",
+                                code,
+                                known_codes.join(", ")
+                            ),
+                            directive.position,
+                        ));
+                    }
+                }
+                Suppression {
+                    position: directive.position,
+                    codes,
+                    range: statement_range(tokens, directive.end),
+                    used: false,
+                }
+            })
+            .collect();
+        Self {
+            suppressions,
+            diagnostics,
+        }
+    }
+
+    /// Drops the suppressed diagnostics.
+    pub fn apply(&mut self, diagnostics: Vec<LintDiagnostic>) -> Vec<LintDiagnostic> {
+        diagnostics
+            .into_iter()
+            .filter(|diagnostic| {
+                let mut suppressed = false;
+                for suppression in self.suppressions.iter_mut() {
+                    let Some((start, end)) = suppression.range else {
+                        continue;
+                    };
+                    if suppression.codes.contains(&diagnostic.code)
+                        && (start..=end).contains(&diagnostic.position)
+                    {
+                        suppression.used = true;
+                        suppressed = true;
+                    }
+                }
+                !suppressed
+            })
+            .collect()
+    }
+
+    /// Returns the diagnostics about the directives themselves: unknown
+    /// codes and directives that suppressed nothing.
+    pub fn finish(self) -> Vec<LintDiagnostic> {
+        let mut diagnostics = self.diagnostics;
+        for suppression in self.suppressions {
+            if !suppression.used && !suppression.codes.is_empty() {
+                diagnostics.push(LintDiagnostic::new(
+                    UNUSED_SUPPRESSION,
+                    &format!(
+                        "Suppression of '{}' has no effect",
+                        suppression.codes.join(", ")
+                    ),
+                    suppression.position,
+                ));
+            }
+        }
+        diagnostics
+    }
+}
+
+/// Positions of the first and last character of the statement starting at
+/// the first token past `offset`.
+fn statement_range(tokens: &[Token], offset: usize) -> Option<(Position, Position)> {
+    let start = tokens.iter().position(|token| token.span.start >= offset)?;
+    let end = Parser::statement_end(tokens, start)?;
+    let (first, last) = (&tokens[start], &tokens[end - 1]);
+    let width = last.lexeme.as_str().chars().count();
+    Some((
+        Position::new(first.line(), first.column()),
+        Position::new(last.line(), last.column() + width.saturating_sub(1) as u32),
+    ))
+}
+
+pub type StringLiteralValidator = Box<dyn Fn(&str, Position) -> Option<LintDiagnostic>>;
+
+/// Host configuration for the lint pass run by [`check`].
+#[derive(Default)]
+pub struct Options {
+    string_literal_validators: Vec<StringLiteralValidator>,
+    lint_codes: Vec<String>,
+    /// Also run the validators before executing a program with `JuniorBread::run`.
+    pub validate_on_run: bool,
+    /// Don't print warnings before running a program with `JuniorBread::run`.
+    pub silence_warnings: bool,
+}
+
+impl Options {
+    /// Registers a validator invoked for every string literal of a checked
+    /// program. Validators run in registration order.
+    pub fn register_string_literal_validator(&mut self, validator: StringLiteralValidator) {
+        self.string_literal_validators.push(validator);
+    }
+
+    /// Registers a code the validators may report, so `jbread-allow`
+    /// directives accept it.
+    pub fn register_lint_code(&mut self, code: &str) {
+        self.lint_codes.push(code.to_string());
+    }
+
+    /// Every code a `jbread-allow` directive may name.
+    pub fn known_codes(&self) -> Vec<&str> {
+        let mut codes = vec![
+            UNUSED_VARIABLE,
+            NO_EFFECT,
+            ASSIGNMENT_IN_CONDITION,
+            INTERNAL_LINT_ERROR,
+        ];
+        codes.extend(self.lint_codes.iter().map(String::as_str));
+        codes
+    }
+
+    /// Runs the registered validators over the string literals in `tokens`.
+    /// Diagnostics are returned in source order; a panicking validator is
+    /// turned into an [`INTERNAL_LINT_ERROR`] diagnostic.
+    pub fn validate(&self, tokens: &[Token]) -> Vec<LintDiagnostic> {
+        let mut diagnostics = Vec::new();
+        for token in tokens {
+            let value = match (&token.token_type, &token.literal) {
+                (TokenTypes::String, Some(LiteralValue::String(value))) => value,
+                _ => continue,
+            };
+            let position = Position::new(token.line(), token.column());
+            for validator in self.string_literal_validators.iter() {
+                match panic::catch_unwind(AssertUnwindSafe(|| validator(value, position))) {
+                    Ok(Some(diagnostic)) => diagnostics.push(diagnostic),
+                    Ok(None) => (),
+                    Err(_) => diagnostics.push(LintDiagnostic::new(
+                        INTERNAL_LINT_ERROR,
+                        "String literal validator panicked",
+                        position,
+                    )),
+                }
+            }
+        }
+        diagnostics
+    }
+}
+
+/// Parses and resolves `source` without executing it and returns the lint
+/// diagnostics in source order, minus those suppressed by `jbread-allow`
+/// directives.
+pub fn check(source: &str, options: &Options) -> JBreadResult<Vec<LintDiagnostic>> {
+    let analysis = Pipeline::new(source).analyze()?;
+    let tokens = &analysis.tokens;
+
+    let mut diagnostics = analysis.diagnostics.into_lint();
+    diagnostics.extend(analysis.resolution.warnings);
+    diagnostics.extend(options.validate(tokens));
+    let mut suppressions = Suppressions::new(&analysis.directives, tokens, &options.known_codes());
+    let mut diagnostics = suppressions.apply(diagnostics);
+    diagnostics.extend(suppressions.finish());
+    // Stable, so diagnostics at the same position keep their order.
+    diagnostics.sort_by_key(|diagnostic| diagnostic.position);
+    Ok(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "interpreter")]
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    #[cfg(feature = "interpreter")]
+    use crate::{interpreter::Interpreter, JuniorBread, Scanner};
+
+    fn code_validator(value: &str, position: Position) -> Option<LintDiagnostic> {
+        let (prefix, suffix) = value.split_once('-')?;
+        let valid = prefix.len() == 3
+            && prefix.chars().all(|chr| chr.is_ascii_uppercase())
+            && suffix.len() == 4
+            && suffix.chars().all(|chr| chr.is_ascii_digit());
+        if valid {
+            None
+        } else {
+            Some(LintDiagnostic::new("bad-code", value, position))
+        }
+    }
+
+    #[test]
+    fn test_validator_nested_positions() {
+        let mut options = Options::default();
+        options.register_string_literal_validator(Box::new(|value, position| {
+            if value.contains('-') {
+                code_validator(value, position)
+            } else {
+                Some(LintDiagnostic::new("bad-code", value, position))
+            }
+        }));
+
+        let source = "var a = \"ABC-1234\";\n{\n  var b = (\"ab-12\" + \"x\");\n  print b;\n}\nprint \"XYZ-0001\";";
+        let diagnostics = check(source, &options).unwrap();
+        assert_eq!(
+            diagnostics,
+            vec![
+                LintDiagnostic::new("bad-code", "ab-12", Position::new(3, 12)),
+                LintDiagnostic::new("bad-code", "x", Position::new(3, 22)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validators_interleave_in_source_order() {
+        let mut options = Options::default();
+        options.register_string_literal_validator(Box::new(|value, position| {
+            Some(LintDiagnostic::new("first", value, position))
+        }));
+        options.register_string_literal_validator(Box::new(|value, position| {
+            Some(LintDiagnostic::new("second", value, position))
+        }));
+
+        let diagnostics = check("var a = \"a\";\nvar b = \"b\";", &options).unwrap();
+        let codes: Vec<(&str, u32)> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.code.as_str(), diagnostic.position.line))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![("first", 1), ("second", 1), ("first", 2), ("second", 2)]
+        );
+    }
+
+    #[test]
+    fn test_validator_panic_is_contained() {
+        let mut options = Options::default();
+        options.register_string_literal_validator(Box::new(|_, _| panic!("validator bug")));
+        options.register_string_literal_validator(Box::new(|value, position| {
+            Some(LintDiagnostic::new("after", value, position))
+        }));
+
+        let diagnostics = check("print \"a\";", &options).unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code, INTERNAL_LINT_ERROR);
+        assert_eq!(diagnostics[1].code, "after");
+    }
+
+    #[test]
+    fn test_check_parse_error() {
+        assert!(check("var = ;", &Options::default()).is_err());
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_run_does_not_invoke_validators() {
+        let called = Rc::new(Cell::new(false));
+        let mut options = Options::default();
+        let flag = called.clone();
+        options.register_string_literal_validator(Box::new(move |_, _| {
+            flag.set(true);
+            None
+        }));
+
+        let bread = JuniorBread::with_options(options);
+        bread
+            .run("var a = \"a\";", &mut Interpreter::default())
+            .unwrap();
+        assert!(!called.get());
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_run_invokes_validators_when_requested() {
+        let called = Rc::new(Cell::new(false));
+        let mut options = Options {
+            validate_on_run: true,
+            ..Default::default()
+        };
+        let flag = called.clone();
+        options.register_string_literal_validator(Box::new(move |_, _| {
+            flag.set(true);
+            None
+        }));
+
+        let bread = JuniorBread::with_options(options);
+        bread
+            .run("var a = \"a\";", &mut Interpreter::default())
+            .unwrap();
+        assert!(called.get());
+    }
+
+    fn codes(diagnostics: &[LintDiagnostic]) -> Vec<(&str, u32)> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.code.as_str(), diagnostic.position.line))
+            .collect()
+    }
+
+    #[test]
+    fn test_suppression_covers_one_statement() {
+        let source = "{\n  // jbread-allow: unused-variable\n  var a = 1;\n  var b = 2;\n}";
+        let diagnostics = check(source, &Options::default()).unwrap();
+        assert_eq!(codes(&diagnostics), vec![(UNUSED_VARIABLE, 4)]);
+    }
+
+    #[test]
+    fn test_suppression_covers_children() {
+        let mut options = Options::default();
+        options.register_lint_code("bad-code");
+        options.register_string_literal_validator(Box::new(|value, position| {
+            code_validator(value, position)
+        }));
+
+        let source = "// jbread-allow: bad-code, unused-variable\n{\n  var a = (\"x-1\" + \"y-2\");\n}\nprint \"z-3\";";
+        let diagnostics = check(source, &options).unwrap();
+        assert_eq!(codes(&diagnostics), vec![("bad-code", 5)]);
+    }
+
+    #[test]
+    fn test_unused_suppression() {
+        let source = "var a = 1;\n// jbread-allow: unused-variable\nprint a;\n// jbread-allow: unused-variable";
+        let diagnostics = check(source, &Options::default()).unwrap();
+        assert_eq!(
+            codes(&diagnostics),
+            vec![(UNUSED_SUPPRESSION, 2), (UNUSED_SUPPRESSION, 4)]
+        );
+        assert_eq!(diagnostics[0].position, Position::new(2, 1));
+    }
+
+    #[test]
+    fn test_unknown_code() {
+        let source = "{\n  // jbread-allow: unused-variable, no-such-code\n  var a = 1;\n}";
+        let diagnostics = check(source, &Options::default()).unwrap();
+        assert_eq!(codes(&diagnostics), vec![(UNKNOWN_LINT_CODE, 2)]);
+        assert_eq!(
+            diagnostics[0].message,
+            "Unknown lint code 'no-such-code', expected one of: unused-variable, no-effect, assignment-in-condition, internal-lint-error"
+        );
+    }
+
+    #[test]
+    fn test_parse_warnings() {
+        let source = "var a = 1;\na;\n\"text\";\n(nil);\na + 1;\nrepeat (a = 2) print a;\nrepeat ((a = 2)) print a;\n// jbread-allow: no-effect\ntrue;";
+        let diagnostics = check(source, &Options::default()).unwrap();
+        assert_eq!(
+            diagnostics,
+            vec![
+                LintDiagnostic::new(NO_EFFECT, "Statement has no effect", Position::new(2, 1)),
+                LintDiagnostic::new(NO_EFFECT, "Statement has no effect", Position::new(3, 1)),
+                LintDiagnostic::new(NO_EFFECT, "Statement has no effect", Position::new(4, 1)),
+                LintDiagnostic::new(
+                    ASSIGNMENT_IN_CONDITION,
+                    "Assignment used as a repeat count",
+                    Position::new(6, 9)
+                ),
+            ]
+        );
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_directives_do_not_change_execution() {
+        let plain = "var a = 1; { var b = a + 1; a = b; }";
+        let annotated = "// jbread-allow: unused-variable\nvar a = 1; { // jbread-allow: bad\n var b = a + 1; a = b; }";
+        let parse = |source: &str| {
+            let mut scanner = Scanner::new(source);
+            let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+            let mut interpreter = Interpreter::default();
+            interpreter.interpret(&stmts).unwrap();
+            let token = Token::new(TokenTypes::Identifier, "a".to_string(), None, 1);
+            let a = interpreter.environment.borrow().get(&token).unwrap();
+            (crate::explain_ast(&stmts), a)
+        };
+        assert_eq!(parse(plain), parse(annotated));
+    }
+}
@@ -1,6 +1,98 @@
-use j_bread::JuniorBread;
+use std::{fs, io, process};
+
+use j_bread::{
+    format_source, generate_reference, parse_args, read_source, read_source_file, History,
+    JBreadErrors, JuniorBread, Mode, Pipeline, USAGE,
+};
 
 fn main() {
-    let bread = JuniorBread::new();
-    bread.run_prompt();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args = parse_args(&args).unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(64);
+    });
+
+    let mut bread = JuniorBread::new()
+        .with_trace_options(args.trace)
+        .with_constant_folding(args.optimize);
+    if let Some(path) = args.export_globals {
+        #[cfg(feature = "serde")]
+        {
+            bread = bread.with_globals_export(path);
+        }
+        #[cfg(not(feature = "serde"))]
+        {
+            eprintln!(
+                "Can't export {}, globals export needs the serde feature",
+                path
+            );
+            process::exit(64);
+        }
+    }
+    let result = match args.mode {
+        Mode::GenerateReference => {
+            print!("{}", generate_reference());
+            Ok(())
+        }
+        Mode::Explain { sections, path } => read_source_file(&path)
+            .map(|source| {
+                print!(
+                    "{}",
+                    Pipeline::new(&source)
+                        .executing()
+                        .with_sections(&sections)
+                        .explain()
+                )
+            })
+            .map_err(report),
+        Mode::Format { path, check } => format(&path, check),
+        Mode::File(path) => bread.run_file(&path),
+        Mode::Stdin => read_source("<stdin>", io::stdin())
+            .map_err(report)
+            .and_then(|source| bread.run_source(&source)),
+        Mode::Eval(source) => bread.run_source(&source),
+        Mode::Repl => {
+            if args.history {
+                if let Some(path) = History::default_path() {
+                    bread = bread.with_history_file(path);
+                }
+            }
+            bread.run_prompt()
+        }
+    };
+
+    if let Err(error) = result {
+        process::exit(error.exit_code());
+    }
+}
+
+/// Formats the file at `path`, or stdin for `-`, exiting with status 1 when
+/// checking finds it isn't formatted.
+fn format(path: &str, check: bool) -> Result<(), JBreadErrors> {
+    let source = match path {
+        "-" => read_source("<stdin>", io::stdin()),
+        path => read_source_file(path),
+    }
+    .map_err(report)?;
+    let formatted = format_source(&source).map_err(report)?;
+    if check {
+        if formatted != source {
+            eprintln!("{} would be reformatted", path);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+    match path {
+        "-" => print!("{}", formatted),
+        path if formatted != source => {
+            fs::write(path, formatted).map_err(|error| report(JBreadErrors::io(path, &error)))?
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn report(error: JBreadErrors) -> JBreadErrors {
+    eprintln!("{}", error);
+    error
 }
@@ -0,0 +1,824 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+#[cfg(feature = "interpreter")]
+use crate::interpreter::{Interpreter, Value};
+use crate::{
+    ast::{
+        Assert, Assign, Binary, Block, Call, Class, Const, Expr, Expression, Function, Get,
+        Grouping, If, Import, Index, IndexAssign, Interpolation, Lambda, ListLiteral, Literal,
+        MapEntry, MapLiteral, MultiAssign, MultiVar, Print, Repeat, Return, Set, Stmt, Switch,
+        SwitchArm, This, Unary, Var, Variable, VisitorExpr, VisitorStmt,
+    },
+    AstNode, AstStmt, LiteralValue, Token,
+};
+
+struct Binding {
+    value: Option<LiteralValue>,
+    assigned: bool,
+    global: bool,
+}
+
+/// Replaces reads of variables that are declared with a literal initializer
+/// and never assigned afterwards with the literal itself.
+///
+/// Bindings are resolved lexically, so a read only sees the declaration that
+/// is in scope at that point and shadowed names are kept apart. `nil`, `NaN`
+/// and infinite initializers are never propagated. A program that imports
+/// another file keeps all of its reads, as that file may assign any global.
+///
+/// Globals are looked up when a function runs rather than where it's
+/// declared, so they're never propagated into function and lambda bodies,
+/// and a global declared twice or assigned from a function declared before
+/// it is left alone everywhere.
+#[derive(Default)]
+pub struct ConstantPropagator {
+    scopes: Vec<HashMap<String, usize>>,
+    bindings: Vec<Binding>,
+    declared: usize,
+    rewriting: bool,
+    imports: bool,
+    /// How many function and lambda bodies the walk is in.
+    functions: usize,
+    /// The bindings of each global name, in declaration order.
+    globals: HashMap<String, Vec<usize>>,
+    /// Names assigned where no declaration is in scope, which only a global
+    /// declared later can be.
+    unresolved: HashSet<String>,
+}
+
+impl ConstantPropagator {
+    pub fn propagate(&mut self, stmts: &[Stmt]) -> Vec<Stmt> {
+        self.bindings.clear();
+        self.imports = false;
+        self.globals.clear();
+        self.unresolved.clear();
+
+        // The first walk finds every binding and whether it's ever assigned,
+        // the second one replays the same declarations and rewrites reads.
+        self.rewriting = false;
+        self.walk(stmts);
+        if self.imports {
+            self.bindings
+                .iter_mut()
+                .for_each(|binding| binding.assigned = true);
+        }
+        for (name, ids) in self.globals.iter() {
+            if ids.len() > 1 || self.unresolved.contains(name) {
+                for id in ids {
+                    self.bindings[*id].assigned = true;
+                }
+            }
+        }
+        self.rewriting = true;
+        self.walk(stmts)
+    }
+
+    fn walk(&mut self, stmts: &[Stmt]) -> Vec<Stmt> {
+        self.scopes = vec![HashMap::new()];
+        self.declared = 0;
+        self.functions = 0;
+        stmts.iter().map(|stmt| stmt.accept(self)).collect()
+    }
+
+    fn resolve(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+    }
+
+    /// Records that `name` is assigned.
+    fn assign(&mut self, name: &str) {
+        match self.resolve(name) {
+            Some(id) => self.bindings[id].assigned = true,
+            None => {
+                self.unresolved.insert(name.to_string());
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &str, initializer: Option<&Expr>) {
+        let id = self.declared;
+        self.declared += 1;
+        let global = self.scopes.len() == 1;
+        if !self.rewriting {
+            let value = match initializer {
+                Some(Expr::Literal(Literal { value, .. })) => value.clone().filter(is_propagatable),
+                _ => None,
+            };
+            self.bindings.push(Binding {
+                value,
+                assigned: false,
+                global,
+            });
+            if global {
+                self.globals.entry(name.to_string()).or_default().push(id);
+            }
+        }
+        self.scopes.last_mut().unwrap().insert(name.to_string(), id);
+    }
+
+    fn function(&mut self, function: &Function) -> Function {
+        Function {
+            name: function.name.clone(),
+            params: function.params.clone(),
+            body: Rc::new(self.body(&function.params, &function.body)),
+            span: function.span,
+        }
+    }
+
+    /// The statements of a function or lambda, in a scope of its parameters.
+    fn body(&mut self, params: &[Token], body: &[Stmt]) -> Vec<Stmt> {
+        self.scopes.push(HashMap::new());
+        self.functions += 1;
+        for param in params.iter() {
+            self.declare(&param.lexeme, None);
+        }
+        let body = body.iter().map(|stmt| stmt.accept(self)).collect();
+        self.functions -= 1;
+        self.scopes.pop();
+        body
+    }
+}
+
+fn is_propagatable(value: &LiteralValue) -> bool {
+    match value {
+        LiteralValue::Number(number) => number.is_finite(),
+        LiteralValue::NaN => false,
+        _ => true,
+    }
+}
+
+impl VisitorExpr for ConstantPropagator {
+    type Result = Expr;
+
+    fn visit_expr_binary(&mut self, expr: &Binary) -> Self::Result {
+        Expr::Binary(Binary {
+            left: Box::new(expr.left.accept(self)),
+            operator: expr.operator.clone(),
+            right: Box::new(expr.right.accept(self)),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_grouping(&mut self, expr: &Grouping) -> Self::Result {
+        Expr::Grouping(Grouping {
+            expression: Box::new(expr.expression.accept(self)),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_literal(&mut self, expr: &Literal) -> Self::Result {
+        Expr::Literal(expr.clone())
+    }
+
+    fn visit_expr_unary(&mut self, expr: &Unary) -> Self::Result {
+        Expr::Unary(Unary {
+            operator: expr.operator.clone(),
+            right: Box::new(expr.right.accept(self)),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_variable(&mut self, expr: &Variable) -> Self::Result {
+        if self.rewriting {
+            if let Some(binding) = self.resolve(&expr.name.lexeme).map(|id| &self.bindings[id]) {
+                let late_bound = binding.global && self.functions > 0;
+                if let (false, false, Some(value)) = (binding.assigned, late_bound, &binding.value)
+                {
+                    return Expr::Literal(Literal {
+                        value: Some(value.clone()),
+                        span: expr.span,
+                    });
+                }
+            }
+        }
+        Expr::Variable(expr.clone())
+    }
+
+    fn visit_expr_assign(&mut self, expr: &Assign) -> Self::Result {
+        let value = expr.value.accept(self);
+        self.assign(&expr.name.lexeme);
+        Expr::Assign(Assign {
+            name: expr.name.clone(),
+            value: Box::new(value),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_get(&mut self, expr: &Get) -> Self::Result {
+        Expr::Get(Get {
+            object: Box::new(expr.object.accept(self)),
+            name: expr.name.clone(),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_set(&mut self, expr: &Set) -> Self::Result {
+        Expr::Set(Set {
+            object: Box::new(expr.object.accept(self)),
+            name: expr.name.clone(),
+            value: Box::new(expr.value.accept(self)),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_list_literal(&mut self, expr: &ListLiteral) -> Self::Result {
+        Expr::ListLiteral(ListLiteral {
+            elements: expr
+                .elements
+                .iter()
+                .map(|element| element.accept(self))
+                .collect(),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_map_literal(&mut self, expr: &MapLiteral) -> Self::Result {
+        Expr::MapLiteral(MapLiteral {
+            brace: expr.brace.clone(),
+            entries: expr
+                .entries
+                .iter()
+                .map(|entry| MapEntry {
+                    key: entry.key.accept(self),
+                    value: entry.value.accept(self),
+                })
+                .collect(),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_index(&mut self, expr: &Index) -> Self::Result {
+        Expr::Index(Index {
+            object: Box::new(expr.object.accept(self)),
+            bracket: expr.bracket.clone(),
+            index: Box::new(expr.index.accept(self)),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_index_assign(&mut self, expr: &IndexAssign) -> Self::Result {
+        Expr::IndexAssign(IndexAssign {
+            object: Box::new(expr.object.accept(self)),
+            bracket: expr.bracket.clone(),
+            index: Box::new(expr.index.accept(self)),
+            value: Box::new(expr.value.accept(self)),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_this(&mut self, expr: &This) -> Self::Result {
+        Expr::This(expr.clone())
+    }
+
+    fn visit_expr_interpolation(&mut self, expr: &Interpolation) -> Self::Result {
+        Expr::Interpolation(Interpolation {
+            parts: expr.parts.iter().map(|part| part.accept(self)).collect(),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_lambda(&mut self, expr: &Lambda) -> Self::Result {
+        Expr::Lambda(Lambda {
+            keyword: expr.keyword.clone(),
+            params: expr.params.clone(),
+            body: self.body(&expr.params, &expr.body.0).into(),
+            span: expr.span,
+        })
+    }
+
+    fn visit_expr_call(&mut self, expr: &Call) -> Self::Result {
+        Expr::Call(Call {
+            callee: Box::new(expr.callee.accept(self)),
+            paren: expr.paren.clone(),
+            arguments: expr
+                .arguments
+                .iter()
+                .map(|argument| argument.accept(self))
+                .collect(),
+            span: expr.span,
+        })
+    }
+}
+
+impl VisitorStmt for ConstantPropagator {
+    type Result = Stmt;
+
+    fn visit_stmt_expression(&mut self, expr: &Expression) -> Self::Result {
+        Stmt::Expression(Expression {
+            expression: Box::new(expr.expression.accept(self)),
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_print(&mut self, expr: &Print) -> Self::Result {
+        Stmt::Print(Print {
+            expressions: expr
+                .expressions
+                .iter()
+                .map(|expression| expression.accept(self))
+                .collect(),
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_assert(&mut self, expr: &Assert) -> Self::Result {
+        Stmt::Assert(Assert {
+            keyword: expr.keyword.clone(),
+            condition: Box::new(expr.condition.accept(self)),
+            message: expr
+                .message
+                .as_ref()
+                .map(|message| Box::new(message.accept(self))),
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_var(&mut self, expr: &Var) -> Self::Result {
+        let initializer = expr
+            .initializer
+            .as_ref()
+            .map(|initializer| Box::new(initializer.accept(self)));
+        self.declare(&expr.name.lexeme, expr.initializer.as_deref());
+        Stmt::Var(Var {
+            name: expr.name.clone(),
+            initializer,
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_const(&mut self, expr: &Const) -> Self::Result {
+        let initializer = Box::new(expr.initializer.accept(self));
+        self.declare(&expr.name.lexeme, Some(&expr.initializer));
+        Stmt::Const(Const {
+            name: expr.name.clone(),
+            initializer,
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_import(&mut self, expr: &Import) -> Self::Result {
+        self.imports = true;
+        Stmt::Import(expr.clone())
+    }
+
+    fn visit_stmt_multi_var(&mut self, expr: &MultiVar) -> Self::Result {
+        let initializers = expr
+            .initializers
+            .iter()
+            .map(|initializer| initializer.accept(self))
+            .collect();
+        for (index, name) in expr.names.iter().enumerate() {
+            self.declare(&name.lexeme, expr.initializers.get(index));
+        }
+        Stmt::MultiVar(MultiVar {
+            names: expr.names.clone(),
+            initializers,
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_multi_assign(&mut self, expr: &MultiAssign) -> Self::Result {
+        let values = expr.values.iter().map(|value| value.accept(self)).collect();
+        for target in expr.targets.iter() {
+            self.assign(&target.name.lexeme);
+        }
+        Stmt::MultiAssign(MultiAssign {
+            targets: expr.targets.clone(),
+            values,
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_block(&mut self, expr: &Block) -> Self::Result {
+        self.scopes.push(HashMap::new());
+        let statements = expr
+            .statements
+            .iter()
+            .map(|stmt| stmt.accept(self))
+            .collect();
+        self.scopes.pop();
+        Stmt::Block(Block {
+            statements,
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_switch(&mut self, expr: &Switch) -> Self::Result {
+        let subject = Box::new(expr.subject.accept(self));
+        let arms = expr
+            .arms
+            .iter()
+            .map(|arm| {
+                let value = arm.value.as_ref().map(|value| value.accept(self));
+                self.scopes.push(HashMap::new());
+                let body = arm.body.iter().map(|stmt| stmt.accept(self)).collect();
+                self.scopes.pop();
+                SwitchArm { value, body }
+            })
+            .collect();
+        Stmt::Switch(Switch {
+            keyword: expr.keyword.clone(),
+            subject,
+            arms,
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_if(&mut self, expr: &If) -> Self::Result {
+        Stmt::If(If {
+            keyword: expr.keyword.clone(),
+            condition: Box::new(expr.condition.accept(self)),
+            then_branch: Box::new(expr.then_branch.accept(self)),
+            else_branch: expr
+                .else_branch
+                .as_ref()
+                .map(|branch| Box::new(branch.accept(self))),
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_repeat(&mut self, expr: &Repeat) -> Self::Result {
+        Stmt::Repeat(Repeat {
+            keyword: expr.keyword.clone(),
+            count: Box::new(expr.count.accept(self)),
+            body: Box::new(expr.body.accept(self)),
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_function(&mut self, expr: &Function) -> Self::Result {
+        self.declare(&expr.name.lexeme, None);
+        Stmt::Function(self.function(expr))
+    }
+
+    fn visit_stmt_class(&mut self, expr: &Class) -> Self::Result {
+        self.declare(&expr.name.lexeme, None);
+        Stmt::Class(Class {
+            name: expr.name.clone(),
+            methods: expr
+                .methods
+                .iter()
+                .map(|method| self.function(method))
+                .collect(),
+            span: expr.span,
+        })
+    }
+
+    fn visit_stmt_return(&mut self, expr: &Return) -> Self::Result {
+        Stmt::Return(Return {
+            keyword: expr.keyword.clone(),
+            value: expr
+                .value
+                .as_ref()
+                .map(|value| Box::new(value.accept(self))),
+            span: expr.span,
+        })
+    }
+}
+
+/// Replaces operators applied to literals, and groupings around them, with
+/// the literal they evaluate to, e.g. `1 + 2 * 3` with `7`. The interpreter
+/// computes each one, so folding gives what running would have: `0 / 0`
+/// folds to NaN and `"a" + "b"` to `"ab"`. What would fail at runtime, e.g.
+/// `-"a"`, is left as is so it fails there, on its line.
+///
+/// Folded literals have no token to give their line, so run it before
+/// resolving, which keeps the nodes it's handed.
+#[cfg(feature = "interpreter")]
+pub fn fold_constants(mut stmts: Vec<Stmt>) -> Vec<Stmt> {
+    let folder = ConstantFolder(Interpreter::default());
+    for stmt in stmts.iter_mut() {
+        folder.stmt(stmt);
+    }
+    stmts
+}
+
+/// Evaluates the folded operators with its interpreter.
+#[cfg(feature = "interpreter")]
+struct ConstantFolder(Interpreter);
+
+#[cfg(feature = "interpreter")]
+impl ConstantFolder {
+    fn stmt(&self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Expression(Expression { expression, .. }) => self.expr(expression),
+            Stmt::Print(Print { expressions, .. }) => self.exprs(expressions),
+            Stmt::Assert(stmt) => {
+                self.expr(&mut stmt.condition);
+                if let Some(message) = &mut stmt.message {
+                    self.expr(message);
+                }
+            }
+            Stmt::Var(Var { initializer, .. })
+            | Stmt::Return(Return {
+                value: initializer, ..
+            }) => {
+                if let Some(initializer) = initializer {
+                    self.expr(initializer);
+                }
+            }
+            Stmt::Const(stmt) => self.expr(&mut stmt.initializer),
+            Stmt::Import(_) => {}
+            Stmt::MultiVar(MultiVar { initializers, .. }) => self.exprs(initializers),
+            Stmt::MultiAssign(stmt) => self.exprs(&mut stmt.values),
+            Stmt::Block(stmt) => stmt.statements.iter_mut().for_each(|stmt| self.stmt(stmt)),
+            Stmt::If(stmt) => {
+                self.expr(&mut stmt.condition);
+                self.stmt(&mut stmt.then_branch);
+                if let Some(else_branch) = &mut stmt.else_branch {
+                    self.stmt(else_branch);
+                }
+            }
+            Stmt::Repeat(stmt) => {
+                self.expr(&mut stmt.count);
+                self.stmt(&mut stmt.body);
+            }
+            Stmt::Switch(stmt) => {
+                self.expr(&mut stmt.subject);
+                for arm in stmt.arms.iter_mut() {
+                    if let Some(value) = &mut arm.value {
+                        self.expr(value);
+                    }
+                    arm.body.iter_mut().for_each(|stmt| self.stmt(stmt));
+                }
+            }
+            Stmt::Function(function) => self.body(&mut function.body),
+            Stmt::Class(class) => class
+                .methods
+                .iter_mut()
+                .for_each(|method| self.body(&mut method.body)),
+        }
+    }
+
+    fn body(&self, body: &mut Rc<Vec<Stmt>>) {
+        for stmt in Rc::make_mut(body) {
+            self.stmt(stmt);
+        }
+    }
+
+    fn exprs(&self, exprs: &mut [Expr]) {
+        exprs.iter_mut().for_each(|expr| self.expr(expr))
+    }
+
+    fn expr(&self, expr: &mut Expr) {
+        match expr {
+            Expr::Binary(binary) => {
+                self.expr(&mut binary.left);
+                self.expr(&mut binary.right);
+                let folded = self.binary(binary);
+                fold(expr, folded);
+            }
+            Expr::Unary(unary) => {
+                self.expr(&mut unary.right);
+                let folded = self.unary(unary);
+                fold(expr, folded);
+            }
+            Expr::Grouping(grouping) => {
+                self.expr(&mut grouping.expression);
+                if let Expr::Literal(literal) = &*grouping.expression {
+                    *expr = Expr::Literal(literal.clone()).with_span(grouping.span);
+                }
+            }
+            Expr::Literal(_) | Expr::Variable(_) | Expr::This(_) => {}
+            Expr::Assign(assign) => self.expr(&mut assign.value),
+            Expr::Call(call) => {
+                self.expr(&mut call.callee);
+                self.exprs(&mut call.arguments);
+            }
+            Expr::Get(get) => self.expr(&mut get.object),
+            Expr::Set(set) => {
+                self.expr(&mut set.object);
+                self.expr(&mut set.value);
+            }
+            Expr::ListLiteral(list) => self.exprs(&mut list.elements),
+            Expr::MapLiteral(map) => map.entries.iter_mut().for_each(|entry| {
+                self.expr(&mut entry.key);
+                self.expr(&mut entry.value);
+            }),
+            Expr::Index(index) => {
+                self.expr(&mut index.object);
+                self.expr(&mut index.index);
+            }
+            Expr::IndexAssign(index) => {
+                self.expr(&mut index.object);
+                self.expr(&mut index.index);
+                self.expr(&mut index.value);
+            }
+            Expr::Interpolation(interpolation) => self.exprs(&mut interpolation.parts),
+            Expr::Lambda(lambda) => self.body(&mut lambda.body.0),
+        }
+    }
+
+    fn binary(&self, binary: &Binary) -> Option<Value> {
+        let interpreter = &self.0;
+        let left = interpreter
+            .operand(value(&binary.left)?, &binary.operator, "")
+            .ok()?;
+        let right = interpreter
+            .operand(value(&binary.right)?, &binary.operator, "")
+            .ok()?;
+        interpreter.binary(&binary.operator, left, right).ok()
+    }
+
+    fn unary(&self, unary: &Unary) -> Option<Value> {
+        let interpreter = &self.0;
+        let right = interpreter
+            .operand(value(&unary.right)?, &unary.operator, "")
+            .ok()?;
+        interpreter.unary(&unary.operator, right).ok()
+    }
+}
+
+/// The value of a literal expression.
+#[cfg(feature = "interpreter")]
+fn value(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Literal(literal) => Some(literal.value.as_ref().map_or(Value::Nil, Value::from)),
+        _ => None,
+    }
+}
+
+/// Replaces `expr` with `value` when it has a literal to write it as.
+#[cfg(feature = "interpreter")]
+fn fold(expr: &mut Expr, value: Option<Value>) {
+    let value = match value {
+        Some(Value::Nil) => None,
+        Some(Value::Bool(boolean)) => Some(LiteralValue::Boolean(boolean)),
+        Some(Value::Number(number)) => Some(LiteralValue::Number(number)),
+        Some(Value::NaN) => Some(LiteralValue::NaN),
+        Some(Value::String(string)) => Some(LiteralValue::String(string)),
+        _ => return,
+    };
+    *expr = Expr::Literal(Literal {
+        value,
+        span: expr.span(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "interpreter")]
+    use crate::{
+        interpreter::{Interpreter, Value},
+        Token, TokenTypes,
+    };
+    use crate::{parser::Parser, Scanner};
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let mut scanner = Scanner::new(source);
+        let mut parser = Parser::new(scanner.scan_tokens());
+        parser.parse().unwrap()
+    }
+
+    /// The statements on one line, the way the AST is explained.
+    fn explain(stmts: &[Stmt]) -> String {
+        crate::pipeline::explain_ast(stmts)
+            .lines()
+            .skip(1)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn propagate(source: &str) -> String {
+        explain(&ConstantPropagator::default().propagate(&parse(source)))
+    }
+
+    #[cfg(feature = "interpreter")]
+    fn fold(source: &str) -> String {
+        explain(&fold_constants(parse(source)))
+    }
+
+    #[cfg(feature = "interpreter")]
+    fn lookup(interpreter: &Interpreter, name: &str) -> Value {
+        let token = Token::new(TokenTypes::Identifier, name.to_string(), None, 1);
+        interpreter.environment.borrow().get(&token).unwrap()
+    }
+
+    #[test]
+    fn test_propagate_never_assigned() {
+        assert_eq!(
+            propagate("var rate = 0.2; var total = 10 * rate; print total;"),
+            "(var rate 0.2) (var total (* 10 0.2)) (print total)"
+        );
+    }
+
+    #[test]
+    fn test_reassigned_left_untouched() {
+        assert_eq!(
+            propagate("var a = 1; print a; { a = 2; } print a;"),
+            "(var a 1) (print a) (block (expr (= a 2))) (print a)"
+        );
+    }
+
+    #[test]
+    fn test_shadowing_respected() {
+        assert_eq!(
+            propagate("var a = 1; { print a; var a = 2; print a; a = 3; } print a;"),
+            "(var a 1) (block (print 1) (var a 2) (print a) (expr (= a 3))) (print 1)"
+        );
+    }
+
+    #[test]
+    fn test_special_values_not_propagated() {
+        assert_eq!(
+            propagate("var a = nil; var b = NaN; var c = inf; print a; print b; print c;"),
+            "(var a nil) (var b NaN) (var c inf) (print a) (print b) (print c)"
+        );
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_fold_constants() {
+        assert_eq!(fold("print 1 + 2 * 3;"), "(print 7)");
+        assert_eq!(fold("print \"a\" + \"b\";"), "(print ab)");
+        assert_eq!(fold("print (1 + 2) * -(3);"), "(print -9)");
+        assert_eq!(fold("print 0 / 0; print 1 / 0;"), "(print NaN) (print inf)");
+        assert_eq!(fold("print !(1 == 2);"), "(print true)");
+        // Only literal operands fold, the literal parts of the rest do.
+        assert_eq!(fold("print 1 + x;"), "(print (+ 1 x))");
+        assert_eq!(fold("print x * (2 + 3);"), "(print (* x 5))");
+        // Down into every statement and expression.
+        assert_eq!(
+            fold("var a = 2 ** 3; { fun f() { return [1 - 1, g(2 + 2)]; } }"),
+            "(var a 8) (block (fun f () (return (list 0 (call g 4)))))"
+        );
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_failures_are_left_to_run() {
+        // These fail when run, which folding mustn't do, or do silently.
+        for source in [
+            "print -\"a\";",
+            "print 1 + nil;",
+            "print !1;",
+            "print 1 << 64;",
+        ] {
+            assert_eq!(fold(source), explain(&parse(source)), "{}", source);
+        }
+        let stmts = fold_constants(parse("var a = 1;\nprint a + -\"b\";"));
+        let error = Interpreter::default().interpret(&stmts).unwrap_err();
+        assert_eq!(error.error().line(), 2);
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_propagation_preserves_behavior() {
+        let source = "var a = 2; var b = a; { var a = 3; b = a + b; } var c = a * b;";
+        let stmts = parse(source);
+        let propagated = ConstantPropagator::default().propagate(&stmts);
+        assert_ne!(stmts, propagated);
+
+        let mut original = Interpreter::default();
+        let mut optimized = Interpreter::default();
+        original.interpret(&stmts).unwrap();
+        optimized.interpret(&propagated).unwrap();
+        for name in ["a", "b", "c"] {
+            assert_eq!(lookup(&original, name), lookup(&optimized, name));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "interpreter")]
+    fn test_propagation_preserves_globals_in_functions() {
+        for (source, name, expected) in [
+            // A function assigning a global declared after it.
+            ("fun g() { a = 5; } var a = 1; g(); var b = a;", "b", 5.0),
+            (
+                "fun g() { a, c = 5, 6; } var a = 1; var c; g(); var b = a;",
+                "b",
+                5.0,
+            ),
+            // A function reading a global redeclared after it.
+            (
+                "var a = 1; fun f() { b = a; } var a = 2; var b; f();",
+                "b",
+                2.0,
+            ),
+            (
+                "var a = 1; var f = () -> a; var a = 2; var b = f();",
+                "b",
+                2.0,
+            ),
+        ] {
+            let stmts = parse(source);
+            let propagated = ConstantPropagator::default().propagate(&stmts);
+            for stmts in [stmts, propagated] {
+                let mut interpreter = Interpreter::default();
+                interpreter.interpret(&stmts).unwrap();
+                let value = lookup(&interpreter, name);
+                assert_eq!(value, Value::Number(expected), "{source}");
+            }
+        }
+
+        // Locals of an enclosing function are still propagated.
+        assert!(propagate("fun f() { var a = 1; fun g() { print a; } }").contains("print 1"));
+        assert!(propagate("var a = 1; fun f() { print a; }").contains("print a"));
+    }
+}
@@ -1,77 +1,278 @@
+use std::rc::Rc;
+
 use crate::{
-    ast::{
-        Assign, Binary, Block, Expr, Expression, Grouping, Literal, Print, Stmt, Unary, Var,
-        Variable,
+    ast::{Expr, Function, Get, Index, MapEntry, Stmt, SwitchArm, Variable},
+    errors::{
+        Diagnostics, Error, ErrorKind, JBreadErrors, JBreadResult, JBreadWarning, WarningKind,
     },
-    errors::{Error, JBreadErrors, JBreadResult},
-    Literal as LiteralEnum, Token, TokenTypes,
+    scanner::{template_parts, TemplatePart, KEYWORDS},
+    LanguageProfile, LiteralValue, Position, Scanner, SourceRef, Span, Token, TokenTypes,
+    UNNAMED_SOURCE,
 };
 
-pub trait ParseTrait {
-    // Expressions parsing
+pub trait ParseExpr {
     fn expression(&mut self) -> JBreadResult<Expr>;
     fn assignment(&mut self) -> JBreadResult<Expr>;
-    fn equality(&mut self) -> JBreadResult<Expr>;
-    fn comparison(&mut self) -> JBreadResult<Expr>;
-    fn term(&mut self) -> JBreadResult<Expr>;
-    fn factor(&mut self) -> JBreadResult<Expr>;
     fn unary(&mut self) -> JBreadResult<Expr>;
+    fn power(&mut self) -> JBreadResult<Expr>;
+    fn call(&mut self) -> JBreadResult<Expr>;
     fn primary(&mut self) -> JBreadResult<Expr>;
-    // Statement parsing
+}
+
+pub trait ParseStmt {
     fn expression_statement(&mut self) -> JBreadResult<Stmt>;
     fn print_statement(&mut self) -> JBreadResult<Stmt>;
+    fn assert_statement(&mut self) -> JBreadResult<Stmt>;
     fn block_statement(&mut self) -> JBreadResult<Stmt>;
+    fn repeat_statement(&mut self) -> JBreadResult<Stmt>;
+    fn if_statement(&mut self) -> JBreadResult<Stmt>;
+    fn return_statement(&mut self) -> JBreadResult<Stmt>;
+    fn import_statement(&mut self) -> JBreadResult<Stmt>;
+    fn switch_statement(&mut self) -> JBreadResult<Stmt>;
     fn statement(&mut self) -> JBreadResult<Stmt>;
+    fn declaration(&mut self) -> JBreadResult<Stmt>;
+}
 
-    // Actual parsing
-    fn parse(&mut self) -> JBreadResult<Vec<Stmt>>;
+/// Whether `expr` only reads a literal or a variable, which does nothing as
+/// a statement of its own.
+pub(crate) fn has_no_effect(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(_) | Expr::Variable(_) => true,
+        Expr::Grouping(grouping) => has_no_effect(&grouping.expression),
+        _ => false,
+    }
 }
 
-pub trait ParseExpr {
-    fn expression(&mut self) -> JBreadResult<Expr>;
-    fn assignment(&mut self) -> JBreadResult<Expr>;
-    fn equality(&mut self) -> JBreadResult<Expr>;
-    fn comparison(&mut self) -> JBreadResult<Expr>;
-    fn term(&mut self) -> JBreadResult<Expr>;
-    fn factor(&mut self) -> JBreadResult<Expr>;
-    fn unary(&mut self) -> JBreadResult<Expr>;
-    fn primary(&mut self) -> JBreadResult<Expr>;
+fn is_comparison(token_type: &TokenTypes) -> bool {
+    matches!(
+        token_type,
+        TokenTypes::Greater | TokenTypes::GreaterEqual | TokenTypes::Less | TokenTypes::LessEqual
+    )
 }
 
-pub trait ParseStmt {
-    fn expression_statement(&mut self) -> JBreadResult<Stmt>;
-    fn print_statement(&mut self) -> JBreadResult<Stmt>;
-    fn block_statement(&mut self) -> JBreadResult<Stmt>;
-    fn statement(&mut self) -> JBreadResult<Stmt>;
+/// Keywords only starting statements, named in the error for finding one
+/// where an expression should be.
+const STATEMENT_KEYWORDS: &[TokenTypes] = &[
+    TokenTypes::Print,
+    TokenTypes::Var,
+    TokenTypes::Class,
+    TokenTypes::Return,
+    TokenTypes::Assert,
+    TokenTypes::If,
+    TokenTypes::Repeat,
+];
+
+/// Keywords standing for values, named as such when used as a name.
+const LITERAL_KEYWORDS: &[TokenTypes] = &[
+    TokenTypes::True,
+    TokenTypes::False,
+    TokenTypes::Nil,
+    TokenTypes::Infinity,
+    TokenTypes::NaN,
+];
+
+/// The grammar, which the parser's documentation and the language reference
+/// are both generated from.
+pub const GRAMMAR: &str = include_str!("grammar.txt");
+
+/// How operators of one precedence level group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Associativity {
+    Left,
+    Right,
+    Prefix,
+    Postfix,
+}
+
+pub struct OperatorLevel {
+    pub name: &'static str,
+    pub operators: &'static [TokenTypes],
+    pub associativity: Associativity,
 }
 
-/// This parser implements the following CFG:
-///
-/// STATEMENTS:
-/// program     → declaration* EOF ;
-/// declaration → varDecl | statement ;
-/// varDecl     → "var" IDENTIFIER ( "=" expression )? ";" ;
-/// statement   → exprStmt | printStmt | block ;
-/// exprStmt    → expression ";" ;
-/// printStmt   → "print" expression ";" ;
-/// block       → "{" declaration* "}" ;
-///
-/// EXPRESSIONS:
-/// expression  → equality ;
-/// equality    → comparison ( ( "!=" | "==" ) comparison )\* ;
-/// comparison  → term ( ( ">" | ">=" | "<" | "<=" ) term )\* ;
-/// term        → factor ( ( "-" | "+" ) factor )\* ;
-/// factor      → unary ( ( "/" | "*" ) unary )\* ;
-/// unary       → ( "!" | "-" ) unary | primary ;
-/// primary     → NUMBER | STRING | IDENTIFIER | "true" | "false" | "nil" | "(" expression ")" ;
+/// Operators from the lowest precedence to the highest. The left-associative
+/// binary levels are parsed straight from this table.
+pub const OPERATOR_LEVELS: &[OperatorLevel] = &[
+    OperatorLevel {
+        name: "assignment",
+        operators: &[TokenTypes::Equal],
+        associativity: Associativity::Right,
+    },
+    OperatorLevel {
+        name: "equality",
+        operators: &[TokenTypes::BangEqual, TokenTypes::EqualEqual],
+        associativity: Associativity::Left,
+    },
+    OperatorLevel {
+        name: "bitwise or",
+        operators: &[TokenTypes::Pipe],
+        associativity: Associativity::Left,
+    },
+    OperatorLevel {
+        name: "bitwise xor",
+        operators: &[TokenTypes::Caret],
+        associativity: Associativity::Left,
+    },
+    OperatorLevel {
+        name: "bitwise and",
+        operators: &[TokenTypes::Ampersand],
+        associativity: Associativity::Left,
+    },
+    OperatorLevel {
+        name: "comparison",
+        operators: &[
+            TokenTypes::Greater,
+            TokenTypes::GreaterEqual,
+            TokenTypes::Less,
+            TokenTypes::LessEqual,
+        ],
+        associativity: Associativity::Left,
+    },
+    OperatorLevel {
+        name: "shift",
+        operators: &[TokenTypes::LessLess, TokenTypes::GreaterGreater],
+        associativity: Associativity::Left,
+    },
+    OperatorLevel {
+        name: "term",
+        operators: &[TokenTypes::Minus, TokenTypes::Plus],
+        associativity: Associativity::Left,
+    },
+    OperatorLevel {
+        name: "factor",
+        operators: &[TokenTypes::Slash, TokenTypes::Star],
+        associativity: Associativity::Left,
+    },
+    OperatorLevel {
+        name: "unary",
+        operators: &[TokenTypes::Bang, TokenTypes::Minus, TokenTypes::Tilde],
+        associativity: Associativity::Prefix,
+    },
+    OperatorLevel {
+        name: "power",
+        operators: &[TokenTypes::StarStar],
+        associativity: Associativity::Right,
+    },
+    OperatorLevel {
+        name: "call",
+        operators: &[
+            TokenTypes::LeftParen,
+            TokenTypes::Dot,
+            TokenTypes::LeftBracket,
+        ],
+        associativity: Associativity::Postfix,
+    },
+];
+
+#[doc = concat!(
+    "This parser implements the following CFG:\n\n```text\n",
+    include_str!("grammar.txt"),
+    "```"
+)]
 pub struct Parser<'a> {
-    tokens: &'a Vec<Token>,
+    tokens: &'a [Token],
     current: usize,
+    errors: Vec<JBreadErrors>,
+    diagnostics: Diagnostics,
+    /// How many times the parser recursed, see [`MAX_NESTING_DEPTH`].
+    nesting: usize,
+    max_nesting: usize,
+    /// How deep the tree being built is, see [`MAX_EXPRESSION_DEPTH`].
+    depth: usize,
+    /// Read past the last token, for tokens not ending with an `Eof`.
+    eof: Token,
+    profile: LanguageProfile,
+}
+
+/// The longest identifier accepted, in characters.
+pub const MAX_IDENTIFIER_LENGTH: usize = 255;
+
+/// Keywords added once programs could already use them as names, so they're
+/// only keywords where a name can't be and are names everywhere else. A new
+/// keyword goes here unless the programs it breaks are recorded under
+/// `tests/compat`.
+pub const CONTEXTUAL_KEYWORDS: &[TokenTypes] = &[
+    TokenTypes::Const,
+    TokenTypes::Import,
+    TokenTypes::Switch,
+    TokenTypes::Case,
+    TokenTypes::Default,
+    TokenTypes::Elif,
+];
+
+/// How deeply statements and expressions may nest inside parentheses,
+/// brackets, blocks and the right of prefix, `**` and `=` operators, each of
+/// which the parser recurses into. Well past what written programs need,
+/// and within how deeply the interpreter evaluates.
+pub const MAX_NESTING_DEPTH: usize = 512;
+
+/// How deep a statement's tree may get. Links of a chain like `a[0][1]` or
+/// `1 + 2 + 3` are parsed in a loop but still nest the tree a level deeper
+/// each, which everything walking the tree recurses into.
+pub const MAX_EXPRESSION_DEPTH: usize = 1024;
+
+/// Everything a recovering parse produced. Statements that failed to parse
+/// are left out whole rather than half-built, so tooling can work with the
+/// rest, while anything that runs the program must treat a non-empty
+/// `errors` as failure.
+#[derive(Debug)]
+pub struct ParseOutcome {
+    pub statements: Vec<Stmt>,
+    pub errors: Vec<JBreadErrors>,
+}
+
+impl ParseOutcome {
+    /// The statements if nothing failed, otherwise the first error.
+    pub fn into_result(mut self) -> JBreadResult<Vec<Stmt>> {
+        if self.errors.is_empty() {
+            Ok(self.statements)
+        } else {
+            Err(self.errors.remove(0))
+        }
+    }
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(tokens: &'a Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+    /// Parses `tokens`, borrowed from a `Vec`, a slice or anything else
+    /// holding them, which should end with an [`Eof`](TokenTypes::Eof).
+    /// Tokens that don't are parsed as if they did.
+    pub fn new(tokens: &'a (impl AsRef<[Token]> + ?Sized)) -> Self {
+        let tokens = tokens.as_ref();
+        let line = tokens.last().map_or(1, Token::line);
+        Self {
+            tokens,
+            current: 0,
+            errors: Vec::new(),
+            diagnostics: Diagnostics::default(),
+            nesting: 0,
+            max_nesting: MAX_NESTING_DEPTH,
+            depth: 0,
+            eof: Token::new(TokenTypes::Eof, String::new(), None, line),
+            profile: LanguageProfile::default(),
+        }
+    }
+
+    /// Rejects the keywords `profile` disables and nests no deeper than it
+    /// allows. The tokens should come from a scanner following it too.
+    pub fn with_profile(mut self, profile: &LanguageProfile) -> Self {
+        if let Some(limit) = profile.max_nesting_depth {
+            self.max_nesting = limit;
+        }
+        self.profile = profile.clone();
+        self
+    }
+
+    /// Fails past `limit` levels of nesting instead of
+    /// [`MAX_NESTING_DEPTH`]. The stack grows as parsing and walking the
+    /// tree need it, so raising it mostly costs memory.
+    pub fn with_max_nesting_depth(mut self, limit: usize) -> Self {
+        self.max_nesting = limit;
+        self
+    }
+
+    /// The warnings found so far, leaving none behind.
+    pub fn take_diagnostics(&mut self) -> Diagnostics {
+        std::mem::take(&mut self.diagnostics)
     }
 
     fn match_token(&mut self, token_types: &[TokenTypes]) -> bool {
@@ -84,15 +285,97 @@ impl<'a> Parser<'a> {
         false
     }
 
+    /// The token last consumed, or the first one before any is.
     fn previous(&self) -> &Token {
-        self.tokens.get(self.current - 1).unwrap()
+        self.tokens
+            .get(self.current.saturating_sub(1))
+            .unwrap_or(&self.eof)
+    }
+
+    /// Where the next token starts, and with it the node parsed from there.
+    fn start(&self) -> usize {
+        self.peek().span.start
+    }
+
+    /// From `start` to the end of the last token consumed, the span of the
+    /// node just parsed.
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(start, self.previous().span.end)
     }
 
+    /// Whether the current token is a `token_type`, a contextual keyword
+    /// counting as an identifier.
     fn check(&self, token_type: &TokenTypes) -> bool {
         if self.is_at_end() {
             return false;
         }
-        self.peek().token_type == *token_type
+        Self::is(self.peek(), token_type)
+    }
+
+    fn check_next(&self, token_type: &TokenTypes) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(next) if !self.is_at_end() => Self::is(next, token_type),
+            _ => false,
+        }
+    }
+
+    /// Whether `keyword (...) {` starts here, e.g. a `switch` rather than a
+    /// call of a function named `switch`, which can't be followed by a `{`.
+    fn at_keyword_block(&self, keyword: &TokenTypes) -> bool {
+        self.after_parenthesized(keyword)
+            .is_some_and(|token| token.token_type == TokenTypes::LeftBrace)
+    }
+
+    /// Whether an `elif (...)` branch starts here rather than a call of a
+    /// function named `elif`, which the parentheses can't tell apart: the
+    /// statement after them can't start with what continues an expression.
+    fn at_elif(&self) -> bool {
+        self.after_parenthesized(&TokenTypes::Elif)
+            .is_some_and(|token| {
+                let continues = matches!(
+                    token.token_type,
+                    TokenTypes::Semicolon
+                        | TokenTypes::Dot
+                        | TokenTypes::LeftParen
+                        | TokenTypes::LeftBracket
+                        | TokenTypes::RightParen
+                        | TokenTypes::RightBracket
+                        | TokenTypes::RightBrace
+                        | TokenTypes::Comma
+                        | TokenTypes::Colon
+                        | TokenTypes::Arrow
+                        | TokenTypes::Eof
+                ) || OPERATOR_LEVELS.iter().any(|level| {
+                    level.associativity != Associativity::Prefix
+                        && level.operators.contains(&token.token_type)
+                });
+                !continues
+            })
+    }
+
+    /// The token after the parentheses following `keyword`, when one starts
+    /// here and its parentheses close.
+    fn after_parenthesized(&self, keyword: &TokenTypes) -> Option<&Token> {
+        if !self.check(keyword) || !self.check_next(&TokenTypes::LeftParen) {
+            return None;
+        }
+        let mut depth = 0;
+        let mut tokens = self.tokens[self.current + 1..].iter();
+        for token in tokens.by_ref() {
+            match token.token_type {
+                TokenTypes::LeftParen => depth += 1,
+                TokenTypes::RightParen if depth == 1 => return tokens.next(),
+                TokenTypes::RightParen => depth -= 1,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn is(token: &Token, token_type: &TokenTypes) -> bool {
+        token.token_type == *token_type
+            || (*token_type == TokenTypes::Identifier
+                && CONTEXTUAL_KEYWORDS.contains(&token.token_type))
     }
 
     fn advance(&mut self) -> &Token {
@@ -107,217 +390,508 @@ impl<'a> Parser<'a> {
     }
 
     fn peek(&self) -> &Token {
-        self.tokens.get(self.current).unwrap()
+        self.tokens.get(self.current).unwrap_or(&self.eof)
     }
 
     fn consume(&mut self, token_type: TokenTypes, arg: &str) -> JBreadResult<&Token> {
+        if token_type == TokenTypes::Identifier {
+            self.check_identifier_length()?;
+        }
         if self.check(&token_type) {
-            Ok(self.advance())
+            return Ok(self.advance());
+        }
+        let (peek, previous) = (self.peek(), self.previous());
+        let kind = match peek.token_type {
+            TokenTypes::Eof => ErrorKind::UnexpectedEof,
+            _ => ErrorKind::UnexpectedToken,
+        };
+        // A token missing at the end of a line, like a `;`, is blamed on the
+        // line it should have ended rather than on the next one.
+        if self.current > 0 && previous.line() < peek.line() {
+            Err(self.error(previous, kind, arg))
         } else {
-            Err(self.error(self.peek(), arg))
+            Err(self.error(peek, kind, arg))
         }
     }
 
-    fn error(&self, peek: &Token, arg: &str) -> JBreadErrors {
-        JBreadErrors::ParseError(Error::new(peek.line, peek.lexeme.clone(), arg.to_string()))
+    /// Consumes the name of a `role`, like "variable name", telling a
+    /// keyword found in its place apart from any other token.
+    fn consume_identifier(&mut self, role: &str) -> JBreadResult<&Token> {
+        if let Some(error) = self.keyword_as_name(self.peek(), role) {
+            return Err(error);
+        }
+        self.consume(TokenTypes::Identifier, &format!("Expected a {}", role))
     }
 
-    fn var_decleration(&mut self) -> JBreadResult<Stmt> {
-        let name = self
-            .consume(TokenTypes::Identifier, "Expected a variable name")?
-            .to_owned();
+    /// The error for `token` used as a `role` if it is a keyword other than
+    /// a contextual one, which can be a name.
+    fn keyword_as_name(&self, token: &Token, role: &str) -> Option<JBreadErrors> {
+        let message = if LITERAL_KEYWORDS.contains(&token.token_type) {
+            format!(
+                "'{}' is a literal value and cannot be used as a {}",
+                token.lexeme, role
+            )
+        } else if KEYWORDS
+            .iter()
+            .any(|keyword| keyword.token_type == token.token_type)
+            && !CONTEXTUAL_KEYWORDS.contains(&token.token_type)
+        {
+            format!(
+                "'{}' is a reserved word and cannot be used as a {}",
+                token.lexeme, role
+            )
+        } else {
+            return None;
+        };
+        Some(self.error(token, ErrorKind::ReservedWord, &message))
+    }
+
+    fn check_identifier_length(&self) -> JBreadResult<()> {
+        let token = self.peek();
+        if token.token_type != TokenTypes::Identifier
+            || token.lexeme.chars().count() <= MAX_IDENTIFIER_LENGTH
+        {
+            return Ok(());
+        }
+        Err(self.error(
+            token,
+            ErrorKind::IdentifierTooLong,
+            &format!(
+                "Identifier is longer than {} characters",
+                MAX_IDENTIFIER_LENGTH
+            ),
+        ))
+    }
+
+    /// Runs `parse` a level deeper, back at the current depth afterwards
+    /// whether or not it failed.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> JBreadResult<T>) -> JBreadResult<T> {
+        let (nesting, depth) = (self.nesting, self.depth);
+        self.nesting += 1;
+        let result = if self.nesting > self.max_nesting {
+            Err(self.error(
+                self.peek(),
+                ErrorKind::NestingTooDeep,
+                &format!("Nested deeper than {} levels", self.max_nesting),
+            ))
+        } else {
+            self.link().and_then(|_| crate::stack::grow(|| parse(self)))
+        };
+        (self.nesting, self.depth) = (nesting, depth);
+        result
+    }
+
+    /// Counts the tree one level deeper, failing past
+    /// [`MAX_EXPRESSION_DEPTH`]. Loops building chains reset it when done.
+    fn link(&mut self) -> JBreadResult<()> {
+        self.depth += 1;
+        if self.depth <= MAX_EXPRESSION_DEPTH {
+            return Ok(());
+        }
+        Err(self.error(
+            self.peek(),
+            ErrorKind::NestingTooDeep,
+            &format!("Expression deeper than {} levels", MAX_EXPRESSION_DEPTH),
+        ))
+    }
+
+    fn invalid_target(&self, equals: &Token) -> JBreadErrors {
+        self.error(
+            equals,
+            ErrorKind::InvalidAssignmentTarget,
+            "Invalid assignment target",
+        )
+    }
+
+    fn error(&self, peek: &Token, kind: ErrorKind, arg: &str) -> JBreadErrors {
+        // Kept apart so the REPL can ask for more input instead of failing.
+        let kind = match (kind, &peek.token_type) {
+            (ErrorKind::UnexpectedToken, TokenTypes::Eof) => ErrorKind::UnexpectedEof,
+            (kind, _) => kind,
+        };
+        JBreadErrors::ParseError(Error::at(
+            kind,
+            peek,
+            peek.lexeme.to_string(),
+            arg.to_string(),
+        ))
+    }
+
+    fn var_declaration(&mut self) -> JBreadResult<Stmt> {
+        let mut names = vec![self.consume_identifier("variable name")?.to_owned()];
+        while self.match_token(&[TokenTypes::Comma]) {
+            names.push(self.consume_identifier("variable name")?.to_owned());
+        }
 
-        let mut initializer = None;
+        let mut initializers = Vec::new();
         if self.match_token(&[TokenTypes::Equal]) {
-            initializer = Some(Box::new(self.expression()?));
+            let equals = self.previous().to_owned();
+            initializers = self.expression_list()?;
+            self.check_counts(&equals, names.len(), initializers.len())?;
         }
         self.consume(
             TokenTypes::Semicolon,
             "Expected ';' after variable declaration",
         )?;
-        Ok(Stmt::Var(Var { name, initializer }))
+        if names.len() > 1 {
+            return Ok(Stmt::multi_var(names, initializers));
+        }
+        Ok(Stmt::var(names.remove(0), initializers.pop().map(Box::new)))
     }
 
-    pub fn parse(&mut self) -> JBreadResult<Vec<Stmt>> {
-        let mut statements = Vec::new();
-        while !self.is_at_end() {
-            statements.push(self.statement()?);
+    fn const_declaration(&mut self) -> JBreadResult<Stmt> {
+        let name = self.consume_identifier("constant name")?.to_owned();
+        self.consume(TokenTypes::Equal, "Expected '=' after constant name")?;
+        let initializer = self.expression()?;
+        self.consume(
+            TokenTypes::Semicolon,
+            "Expected ';' after constant declaration",
+        )?;
+        Ok(Stmt::constant(name, initializer))
+    }
+
+    /// Parses `expression ( "," expression )*`.
+    fn expression_list(&mut self) -> JBreadResult<Vec<Expr>> {
+        let mut exprs = vec![self.expression()?];
+        while self.match_token(&[TokenTypes::Comma]) {
+            exprs.push(self.expression()?);
         }
-        Ok(statements)
+        Ok(exprs)
     }
 
-    // TODO: Implement error handling while parsing
-}
+    /// Every name on the left of `equals` needs exactly one value.
+    fn check_counts(&self, equals: &Token, names: usize, values: usize) -> JBreadResult<()> {
+        if names == values {
+            return Ok(());
+        }
+        Err(self.error(
+            equals,
+            ErrorKind::ArityMismatch,
+            &format!("Expected {} values but got {}", names, values),
+        ))
+    }
 
-impl<'a> ParseExpr for Parser<'a> {
-    fn expression(&mut self) -> JBreadResult<Expr> {
-        self.assignment()
+    /// Parses the rest of `a, b = b, a;` once the first target is parsed.
+    fn multi_assignment(&mut self, first: Expr) -> JBreadResult<Stmt> {
+        let mut targets = vec![first];
+        while self.match_token(&[TokenTypes::Comma]) {
+            targets.push(self.call()?);
+        }
+        let equals = self
+            .consume(TokenTypes::Equal, "Expected '=' after assignment targets")?
+            .to_owned();
+        let targets = targets
+            .into_iter()
+            .map(|target| match target {
+                Expr::Variable(variable) => Ok(variable),
+                _ => Err(self.error(
+                    &equals,
+                    ErrorKind::InvalidAssignmentTarget,
+                    "Only variables can be assigned together",
+                )),
+            })
+            .collect::<JBreadResult<Vec<_>>>()?;
+        let values = self.expression_list()?;
+        self.check_counts(&equals, targets.len(), values.len())?;
+        self.consume(TokenTypes::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::multi_assign(targets, values))
     }
 
-    fn assignment(&mut self) -> Result<Expr, JBreadErrors> {
-        let expr = self.equality()?;
+    fn class_declaration(&mut self) -> JBreadResult<Stmt> {
+        let name = self.consume_identifier("class name")?.to_owned();
+        self.consume(TokenTypes::LeftBrace, "Expect '{' before class body.")?;
+        let mut methods = Vec::new();
+        while !self.check(&TokenTypes::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+        self.consume(TokenTypes::RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::class(name, methods))
+    }
 
-        if self.match_token(&[TokenTypes::Equal]) {
-            let equals = self.previous().to_owned();
-            let value = self.assignment()?;
+    /// Parses the name, parameters and body shared by functions and methods.
+    fn function(&mut self, kind: &str) -> JBreadResult<Function> {
+        let start = self.start();
+        let name = self
+            .consume_identifier(&format!("{} name", kind))?
+            .to_owned();
+        self.consume(
+            TokenTypes::LeftParen,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+        let params = self.parameters()?;
+        let body = self.function_body(kind)?;
+        Ok(Function {
+            name,
+            params,
+            body: Rc::new(body),
+            span: self.span_from(start),
+        })
+    }
 
-            match expr {
-                Expr::Variable(Variable { name }) => {
-                    return Ok(Expr::Assign(Assign {
-                        name,
-                        value: Box::new(value),
-                    }));
-                }
-                _ => {
-                    return Err(self.error(&equals, "Invalid assignment target"));
+    /// Parses the parameters after a matched `(`, and the `)` closing them.
+    fn parameters(&mut self) -> JBreadResult<Vec<Token>> {
+        let mut params = Vec::new();
+        if !self.check(&TokenTypes::RightParen) {
+            loop {
+                params.push(self.consume_identifier("parameter name")?.to_owned());
+                if !self.match_token(&[TokenTypes::Comma]) {
+                    break;
                 }
             }
         }
+        self.consume(TokenTypes::RightParen, "Expect ')' after parameters.")?;
+        Ok(params)
+    }
 
-        Ok(expr)
+    fn function_body(&mut self, kind: &str) -> JBreadResult<Vec<Stmt>> {
+        self.consume(
+            TokenTypes::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        match self.block_statement()? {
+            Stmt::Block(block) => Ok(block.statements),
+            _ => unreachable!("block_statement always returns a block"),
+        }
     }
 
-    fn equality(&mut self) -> JBreadResult<Expr> {
-        let mut expr = self.comparison()?;
+    /// Parses the parameters and body of a `fun (...) { ... }` whose `fun`
+    /// was just matched.
+    fn lambda(&mut self) -> JBreadResult<Expr> {
+        let keyword = self.previous().to_owned();
+        self.consume(TokenTypes::LeftParen, "Expect '(' after 'fun'.")?;
+        let params = self.parameters()?;
+        let body = self.function_body("function")?;
+        Ok(Expr::lambda(keyword, params, body))
+    }
 
-        while self.match_token(&[TokenTypes::BangEqual, TokenTypes::EqualEqual]) {
-            let operator = self.previous().to_owned();
-            let right = self.comparison()?;
-            expr = Expr::Binary(Binary {
-                left: Box::new(expr),
-                right: Box::new(right),
-                operator,
-            })
+    /// Whether `(` starts the parameters of an arrow lambda, i.e. only names
+    /// separated by commas up to a `)` followed by `->`.
+    fn at_arrow(&self) -> bool {
+        if !self.check(&TokenTypes::LeftParen) {
+            return false;
         }
-
-        Ok(expr)
+        let mut tokens = self.tokens[self.current + 1..].iter();
+        let mut name = true;
+        for token in tokens.by_ref() {
+            match token.token_type {
+                TokenTypes::RightParen => break,
+                TokenTypes::Comma if !name => name = true,
+                _ if name && Self::is(token, &TokenTypes::Identifier) => name = false,
+                _ => return false,
+            }
+        }
+        tokens
+            .next()
+            .is_some_and(|token| token.token_type == TokenTypes::Arrow)
     }
 
-    fn comparison(&mut self) -> JBreadResult<Expr> {
-        let mut expr = self.term()?;
+    /// Parses `(params) -> body` where [`at_arrow`](Self::at_arrow) said
+    /// so. An expression body is returned as the lambda's value.
+    fn arrow_lambda(&mut self) -> JBreadResult<Expr> {
+        self.advance();
+        let params = self.parameters()?;
+        let arrow = self
+            .consume(TokenTypes::Arrow, "Expect '->' after parameters.")?
+            .to_owned();
+        let body = if self.check(&TokenTypes::LeftBrace) {
+            self.function_body("function")?
+        } else {
+            let start = self.start();
+            let value = self.expression()?;
+            vec![Stmt::return_stmt(arrow.clone(), Some(Box::new(value)))
+                .with_span(self.span_from(start))]
+        };
+        Ok(Expr::lambda(arrow, params, body))
+    }
 
-        while self.match_token(&[
-            TokenTypes::Greater,
-            TokenTypes::GreaterEqual,
-            TokenTypes::Less,
-            TokenTypes::LessEqual,
-        ]) {
-            let operator = self.previous().to_owned();
-            let right = self.term()?;
-            expr = Expr::Binary(Binary {
-                left: Box::new(expr),
-                right: Box::new(right),
-                operator,
-            })
+    fn finish_call(&mut self, callee: Expr) -> JBreadResult<Expr> {
+        let mut arguments = Vec::new();
+        if !self.check(&TokenTypes::RightParen) {
+            loop {
+                arguments.push(self.expression()?);
+                if !self.match_token(&[TokenTypes::Comma]) {
+                    break;
+                }
+            }
         }
+        let paren = self
+            .consume(TokenTypes::RightParen, "Expect ')' after arguments.")?
+            .to_owned();
+        Ok(Expr::call(callee, paren, arguments))
+    }
 
-        Ok(expr)
+    /// Parses the single statement starting at token `start` and returns the
+    /// index of the token following it, `None` if no statement starts there.
+    pub fn statement_end(tokens: &'a [Token], start: usize) -> Option<usize> {
+        let mut parser = Self {
+            current: start,
+            ..Self::new(tokens)
+        };
+        if start >= tokens.len() || parser.is_at_end() {
+            return None;
+        }
+        parser.declaration().ok()?;
+        parser.errors.is_empty().then_some(parser.current)
     }
 
-    fn term(&mut self) -> JBreadResult<Expr> {
-        let mut expr = self.factor()?;
+    /// Parses the entries of the map literal whose `{` was just matched, a
+    /// trailing comma allowed.
+    fn map_literal(&mut self) -> JBreadResult<Expr> {
+        let brace = self.previous().to_owned();
+        let mut entries = Vec::new();
+        while !self.check(&TokenTypes::RightBrace) {
+            let key = self.expression()?;
+            self.consume(TokenTypes::Colon, "Expect ':' after map key.")?;
+            let value = self.expression()?;
+            entries.push(MapEntry { key, value });
+            if !self.match_token(&[TokenTypes::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenTypes::RightBrace, "Expect '}' after map entries.")?;
+        Ok(Expr::map_literal(brace, entries))
+    }
 
-        while self.match_token(&[TokenTypes::Minus, TokenTypes::Plus]) {
-            let operator = self.previous().to_owned();
-            let right = self.factor()?;
-            expr = Expr::Binary(Binary {
-                left: Box::new(expr),
-                right: Box::new(right),
-                operator,
-            })
+    /// Parses the template string just matched, its text and the code of
+    /// each interpolation in turn.
+    fn interpolation(&mut self) -> JBreadResult<Expr> {
+        let token = self.previous();
+        // A template made by hand is scanned on its own.
+        let (source, start) = match token.source() {
+            Some(source) => (source.clone(), token.span.start),
+            None => (SourceRef::new(UNNAMED_SOURCE, token.lexeme.as_str()), 0),
+        };
+        let mut parts = Vec::new();
+        for part in template_parts(token.lexeme.as_str(), start) {
+            match part {
+                TemplatePart::Text(text, span) => {
+                    parts.push(Expr::literal(LiteralValue::String(text)).with_span(span))
+                }
+                TemplatePart::Code(span) => parts.push(self.embedded(&source, span)?),
+            }
         }
+        Ok(Expr::interpolation(parts))
+    }
 
-        Ok(expr)
+    /// Parses the expression in `span` of `source`, as deep into the tree as
+    /// the template it's in.
+    fn embedded(&mut self, source: &std::sync::Arc<SourceRef>, span: Span) -> JBreadResult<Expr> {
+        let (tokens, errors) = Scanner::with_span(source.clone(), span)
+            .renaming(&self.profile)
+            .scan();
+        if let Some(error) = errors.into_iter().next() {
+            return Err(error);
+        }
+        self.nested(|outer| {
+            Parser {
+                nesting: outer.nesting,
+                max_nesting: outer.max_nesting,
+                depth: outer.depth,
+                profile: outer.profile.clone(),
+                ..Parser::new(&tokens)
+            }
+            .parse_expression()
+        })
     }
 
-    fn factor(&mut self) -> JBreadResult<Expr> {
-        let mut expr = self.unary()?;
+    pub fn parse(&mut self) -> JBreadResult<Vec<Stmt>> {
+        self.parse_recovering().into_result()
+    }
 
-        while self.match_token(&[TokenTypes::Slash, TokenTypes::Star]) {
-            let operator = self.previous().to_owned();
-            let right = self.unary()?;
-            expr = Expr::Binary(Binary {
-                left: Box::new(expr),
-                right: Box::new(right),
-                operator,
-            })
+    /// Parses a lone expression, which must make up all of the tokens.
+    pub fn parse_expression(&mut self) -> JBreadResult<Expr> {
+        let expr = self.expression()?;
+        if !self.is_at_end() {
+            return Err(self.error(
+                self.peek(),
+                ErrorKind::UnexpectedToken,
+                "Expect end of expression.",
+            ));
         }
-
         Ok(expr)
     }
+}
 
-    fn unary(&mut self) -> JBreadResult<Expr> {
-        if self.match_token(&[TokenTypes::Bang, TokenTypes::Minus]) {
-            let operator = self.previous().to_owned();
-            let right = self.unary()?;
-            return Ok(Expr::Unary(Unary {
-                right: Box::new(right),
-                operator,
-            }));
+impl<'a> Parser<'a> {
+    /// Warns about an assignment used where a condition is expected, most
+    /// likely meant as `==`. Extra parentheses silence it.
+    fn warn_assignment(&mut self, condition: &Expr, start: Position, message: &str) {
+        if let Expr::Assign(_) | Expr::Set(_) | Expr::IndexAssign(_) = condition {
+            self.diagnostics.push(
+                JBreadWarning::new(WarningKind::AssignmentInCondition, message, start)
+                    .with_span(condition.span()),
+            );
         }
-
-        self.primary()
     }
 
-    fn primary(&mut self) -> JBreadResult<Expr> {
-        if self.match_token(&[TokenTypes::False]) {
-            Ok(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Boolean(false)),
-            }))
-        } else if self.match_token(&[TokenTypes::True]) {
-            Ok(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Boolean(true)),
-            }))
-        } else if self.match_token(&[TokenTypes::Nil]) {
-            Ok(Expr::Literal(Literal { value: None }))
-        } else if self.match_token(&[TokenTypes::NaN]) {
-            Ok(Expr::Literal(Literal {
-                value: Some(LiteralEnum::NaN),
-            }))
-        } else if self.match_token(&[TokenTypes::String, TokenTypes::Number]) {
-            Ok(Expr::Literal(Literal {
-                value: self.previous().literal.to_owned(),
-            }))
-        } else if self.match_token(&[TokenTypes::Identifier]) {
-            Ok(Expr::Variable(Variable {
-                name: self.previous().to_owned(),
-            }))
-        } else if self.match_token(&[TokenTypes::LeftParen]) {
-            let expr = self.expression()?;
-            match self.consume(TokenTypes::RightParen, "Expect ')' after expression.") {
-                Ok(_) => Ok(Expr::Grouping(Grouping {
-                    expression: Box::new(expr),
-                })),
-                Err(_) => panic!("Error"),
-            }
-        } else {
-            Err(self.error(self.previous(), "Expected Expression"))
-        }
+    /// Whether a declaration starts here, `const` and `import` only being
+    /// keywords when a name or a path follows them, and `fun` only declaring
+    /// a function when a name follows it.
+    fn at_declaration(&self) -> bool {
+        self.check(&TokenTypes::Var)
+            || (self.check(&TokenTypes::Fun) && self.check_next(&TokenTypes::Identifier))
+            || self.check(&TokenTypes::Class)
+            || (self.check(&TokenTypes::Const) && self.check_next(&TokenTypes::Identifier))
+            || (self.check(&TokenTypes::Import) && self.check_next(&TokenTypes::String))
     }
-}
 
-impl<'a> ParseStmt for Parser<'a> {
-    fn expression_statement(&mut self) -> JBreadResult<Stmt> {
-        let expr = self.expression()?;
-        self.consume(TokenTypes::Semicolon, "Expect ';' after expression.")?;
-        Ok(Stmt::Expression(Expression {
-            expression: Box::new(expr),
-        }))
+    /// Fails past the keyword if a statement starts here with one the
+    /// profile rejects, so recovery skips the statement rather than stop
+    /// right at it. Contextual keywords only count where they aren't names,
+    /// and keywords starting an expression are left to
+    /// [`reject_disabled`](Self::reject_disabled).
+    fn reject_disabled_statement(&mut self) -> JBreadResult<()> {
+        let token_type = &self.peek().token_type;
+        if !self.profile.rejects(token_type) {
+            return Ok(());
+        }
+        let starts_statement = self.at_declaration()
+            || if CONTEXTUAL_KEYWORDS.contains(token_type) {
+                self.at_keyword_block(&TokenTypes::Switch) || self.at_elif()
+            } else {
+                !LITERAL_KEYWORDS.contains(token_type)
+                    && !matches!(token_type, TokenTypes::This | TokenTypes::Fun)
+            };
+        if !starts_statement {
+            return Ok(());
+        }
+        let error = self.reject_disabled("statement");
+        self.advance();
+        error
     }
 
-    fn print_statement(&mut self) -> JBreadResult<Stmt> {
-        let expr = self.expression()?;
-        self.consume(TokenTypes::Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::Print(Print {
-            expression: Box::new(expr),
-        }))
+    /// Fails if the next token is a keyword the profile rejects, calling
+    /// what it starts a `role`.
+    fn reject_disabled(&self, role: &str) -> JBreadResult<()> {
+        let token = self.peek();
+        if !self.profile.rejects(&token.token_type) {
+            return Ok(());
+        }
+        let message = format!(
+            "The '{}' {} is disabled in this environment.",
+            token.lexeme, role
+        );
+        Err(self.error(token, ErrorKind::DisabledKeyword, &message))
     }
 
-    fn statement(&mut self) -> JBreadResult<Stmt> {
-        if self.match_token(&[TokenTypes::Var]) {
-            self.var_decleration()
-        } else if self.match_token(&[TokenTypes::Print]) {
+    /// The statements left once declarations are ruled out.
+    fn plain_statement(&mut self) -> JBreadResult<Stmt> {
+        if self.match_token(&[TokenTypes::Print]) {
             self.print_statement()
+        } else if self.match_token(&[TokenTypes::Assert]) {
+            self.assert_statement()
+        } else if self.match_token(&[TokenTypes::Repeat]) {
+            self.repeat_statement()
+        } else if self.at_keyword_block(&TokenTypes::Switch) {
+            self.advance();
+            self.switch_statement()
+        } else if self.match_token(&[TokenTypes::If]) {
+            self.if_statement()
+        } else if self.check(&TokenTypes::Else) || self.at_elif() {
+            let message = format!("'{}' without a preceding 'if'.", self.peek().lexeme);
+            Err(self.error(self.peek(), ErrorKind::UnexpectedToken, &message))
+        } else if self.match_token(&[TokenTypes::Return]) {
+            self.return_statement()
         } else if self.match_token(&[TokenTypes::LeftBrace]) {
             self.block_statement()
         } else {
@@ -325,72 +899,558 @@ impl<'a> ParseStmt for Parser<'a> {
         }
     }
 
-    fn block_statement(&mut self) -> JBreadResult<Stmt> {
+    /// Parses the whole program, skipping past each statement that fails so
+    /// the ones before, between and after are still parsed.
+    pub fn parse_recovering(&mut self) -> ParseOutcome {
         let mut statements = Vec::new();
-        while !self.check(&TokenTypes::RightBrace) && !self.is_at_end() {
-            statements.push(self.statement()?);
+        while !self.is_at_end() {
+            let start = self.current;
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(error) => {
+                    self.recover(error);
+                    // A stray '}' stops synchronizing without being consumed.
+                    if self.current == start {
+                        self.advance();
+                    }
+                }
+            }
+        }
+        ParseOutcome {
+            statements,
+            errors: std::mem::take(&mut self.errors),
         }
-        self.consume(TokenTypes::RightBrace, "Expect '}' after block.")?;
-        Ok(Stmt::Block(Block { statements }))
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn test_literal_bool() {
-        let tokens = vec![
-            Token::new(
-                TokenTypes::True,
-                "true".to_string(),
-                Some(LiteralEnum::Boolean(true)),
-                1,
-            ),
-            Token::new(TokenTypes::Eof, "".to_string(), None, 1),
-        ];
-        let mut parser = Parser::new(&tokens);
+    /// Parses the left-associative binary operators of `OPERATOR_LEVELS`
+    /// from `level` on by precedence climbing, which only recurses for an
+    /// operand followed by a tighter operator rather than once per level.
+    fn binary(&mut self, level: usize) -> JBreadResult<Expr> {
+        let start = self.start();
+        let mut expr = self.unary()?;
 
-        let parsed_literal_true = parser.expression();
-        assert!(parsed_literal_true.is_ok(), "Failed to parse true literal");
-        assert_eq!(
-            parsed_literal_true.unwrap(),
-            Expr::Literal(crate::ast::Literal {
-                value: Some(LiteralEnum::Boolean(true))
-            }),
-            "Parsed literal bool is not equal to expected literal true"
-        );
+        let depth = self.depth;
+        while let Some(found) = self.binary_level(level) {
+            self.link()?;
+            let operator = self.advance().to_owned();
+            self.check_chained(&expr, &operator)?;
+            let right = self.binary(found + 1)?;
+            expr = Expr::binary(expr, operator, right).with_span(self.span_from(start));
+        }
+        self.depth = depth;
 
-        let tokens = vec![
-            Token::new(
-                TokenTypes::False,
-                "false".to_string(),
-                Some(LiteralEnum::Boolean(false)),
-                1,
-            ),
-            Token::new(TokenTypes::Eof, "".to_string(), None, 1),
-        ];
-        let mut parser = Parser::new(&tokens);
+        Ok(expr)
+    }
 
-        let parsed_literal_false = parser.expression();
-        assert!(
-            parsed_literal_false.is_ok(),
-            "Failed to parse false literal"
-        );
-        assert_eq!(
-            parsed_literal_false.unwrap(),
-            Expr::Literal(Literal {
-                value: Some(LiteralEnum::Boolean(false))
-            }),
-            "Parsed literal bool is not equal to expected literal false"
+    /// Fails for a comparison of a comparison, e.g. `0 < x < 10`, which
+    /// would compare a boolean with a number rather than `x` with both.
+    /// Parentheses around the first comparison make it deliberate.
+    fn check_chained(&self, left: &Expr, operator: &Token) -> JBreadResult<()> {
+        let Expr::Binary(left) = left else {
+            return Ok(());
+        };
+        if !is_comparison(&left.operator.token_type) || !is_comparison(&operator.token_type) {
+            return Ok(());
+        }
+        let message = format!(
+            "Chained comparisons are not supported, write 'a {} b' and 'b {} c' as separate conditions.",
+            left.operator.lexeme, operator.lexeme
         );
+        Err(self.error(operator, ErrorKind::ChainedComparison, &message))
     }
 
-    #[test]
-    fn test_literal_nil() {
-        let tokens = vec![
-            Token::new(TokenTypes::Nil, "nil".to_string(), None, 1),
+    /// The level from `level` on that the next token is a binary operator of.
+    fn binary_level(&self, level: usize) -> Option<usize> {
+        OPERATOR_LEVELS
+            .iter()
+            .enumerate()
+            .skip(level)
+            .take_while(|(_, found)| found.associativity == Associativity::Left)
+            .find(|(_, found)| found.operators.contains(&self.peek().token_type))
+            .map(|(found, _)| found)
+    }
+
+    /// Records `error` and skips to where the next statement likely starts:
+    /// past a ';', before a statement keyword or before the '}' closing the
+    /// enclosing block. Braces opened while skipping are skipped as a whole.
+    fn recover(&mut self, error: JBreadErrors) {
+        self.errors.push(error);
+        let mut depth = 0;
+        while !self.is_at_end() {
+            match self.peek().token_type {
+                TokenTypes::LeftBrace => depth += 1,
+                TokenTypes::RightBrace if depth == 0 => return,
+                TokenTypes::RightBrace => depth -= 1,
+                TokenTypes::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenTypes::Class
+                | TokenTypes::Fun
+                | TokenTypes::Var
+                | TokenTypes::Const
+                | TokenTypes::Import
+                | TokenTypes::Switch
+                | TokenTypes::Print
+                | TokenTypes::Assert
+                | TokenTypes::If
+                | TokenTypes::Repeat
+                | TokenTypes::Return
+                    if depth == 0 =>
+                {
+                    return
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+}
+
+impl<'a> ParseExpr for Parser<'a> {
+    fn expression(&mut self) -> JBreadResult<Expr> {
+        self.nested(Self::assignment)
+    }
+
+    fn assignment(&mut self) -> Result<Expr, JBreadErrors> {
+        // Assignment itself is the first level.
+        let start = self.start();
+        let expr = self.binary(1)?;
+        let end = self.current;
+
+        if self.match_token(&[TokenTypes::Equal]) {
+            let equals = self.previous().to_owned();
+            let value = self.nested(Self::assignment)?;
+            let span = self.span_from(start);
+
+            match expr {
+                Expr::Variable(Variable { name, .. }) => {
+                    return Ok(Expr::assign(name, value).with_span(span));
+                }
+                Expr::Get(Get { object, name, .. }) => {
+                    return Ok(Expr::set(object, name, value).with_span(span));
+                }
+                Expr::Index(Index {
+                    object,
+                    bracket,
+                    index,
+                    ..
+                }) => {
+                    return Ok(Expr::index_assign(object, bracket, index, value).with_span(span));
+                }
+                Expr::Literal(_) | Expr::This(_) => {
+                    // A keyword right before the `=`, like `x = true = 1`.
+                    let target = &self.tokens[end - 1];
+                    return Err(self
+                        .keyword_as_name(target, "variable name")
+                        .unwrap_or_else(|| self.invalid_target(&equals)));
+                }
+                _ => return Err(self.invalid_target(&equals)),
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> JBreadResult<Expr> {
+        let start = self.start();
+        if self.match_token(&[TokenTypes::Bang, TokenTypes::Minus, TokenTypes::Tilde]) {
+            let operator = self.previous().to_owned();
+            let right = self.nested(Self::unary)?;
+            return Ok(Expr::unary(operator, right).with_span(self.span_from(start)));
+        }
+
+        self.power()
+    }
+
+    fn power(&mut self) -> JBreadResult<Expr> {
+        let start = self.start();
+        let expr = self.call()?;
+
+        // The right operand goes back through `unary`, which makes `**`
+        // right-associative and lets it take a negated exponent.
+        if self.match_token(&[TokenTypes::StarStar]) {
+            let operator = self.previous().to_owned();
+            let right = self.nested(Self::unary)?;
+            return Ok(Expr::binary(expr, operator, right).with_span(self.span_from(start)));
+        }
+
+        Ok(expr)
+    }
+
+    fn call(&mut self) -> JBreadResult<Expr> {
+        let start = self.start();
+        let mut expr = self.primary()?;
+        let depth = self.depth;
+        loop {
+            if self.check(&TokenTypes::LeftParen)
+                || self.check(&TokenTypes::LeftBracket)
+                || self.check(&TokenTypes::Dot)
+            {
+                self.link()?;
+            }
+            if self.match_token(&[TokenTypes::LeftParen]) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_token(&[TokenTypes::LeftBracket]) {
+                let bracket = self.previous().to_owned();
+                let index = self.expression()?;
+                self.consume(TokenTypes::RightBracket, "Expect ']' after index.")?;
+                expr = Expr::index(expr, bracket, index);
+            } else if self.match_token(&[TokenTypes::Dot]) {
+                let name = self
+                    .consume(TokenTypes::Identifier, "Expect property name after '.'.")?
+                    .to_owned();
+                expr = Expr::get(expr, name);
+            } else {
+                break;
+            }
+            expr = expr.with_span(self.span_from(start));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> JBreadResult<Expr> {
+        let start = self.start();
+        let expr = self.operand()?;
+        Ok(expr.with_span(self.span_from(start)))
+    }
+}
+
+impl<'a> Parser<'a> {
+    /// The expression `primary` parses, without its span.
+    fn operand(&mut self) -> JBreadResult<Expr> {
+        // Contextual keywords are names here.
+        if !CONTEXTUAL_KEYWORDS.contains(&self.peek().token_type) {
+            self.reject_disabled("keyword")?;
+        }
+        if self.match_token(&[TokenTypes::False]) {
+            Ok(Expr::literal(LiteralValue::Boolean(false)))
+        } else if self.match_token(&[TokenTypes::True]) {
+            Ok(Expr::literal(LiteralValue::Boolean(true)))
+        } else if self.match_token(&[TokenTypes::Nil]) {
+            Ok(Expr::literal(None))
+        } else if self.match_token(&[TokenTypes::NaN]) {
+            Ok(Expr::literal(LiteralValue::NaN))
+        } else if self.match_token(&[TokenTypes::Infinity]) {
+            Ok(Expr::literal(LiteralValue::Number(f64::INFINITY)))
+        } else if self.match_token(&[TokenTypes::String, TokenTypes::Number]) {
+            Ok(Expr::literal(self.previous().literal.to_owned()))
+        } else if self.match_token(&[TokenTypes::TemplateString]) {
+            self.interpolation()
+        } else if self.match_token(&[TokenTypes::This]) {
+            Ok(Expr::this(self.previous().to_owned()))
+        } else if self.check(&TokenTypes::Identifier) {
+            self.check_identifier_length()?;
+            self.advance();
+            Ok(Expr::variable(self.previous().to_owned()))
+        } else if self.match_token(&[TokenTypes::LeftBracket]) {
+            let mut elements = Vec::new();
+            if !self.check(&TokenTypes::RightBracket) {
+                loop {
+                    elements.push(self.expression()?);
+                    if !self.match_token(&[TokenTypes::Comma]) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenTypes::RightBracket, "Expect ']' after list elements.")?;
+            Ok(Expr::list_literal(elements))
+        } else if self.match_token(&[TokenTypes::LeftBrace]) {
+            // A statement starting with `{` is a block, so this is a map.
+            self.map_literal()
+        } else if self.match_token(&[TokenTypes::Fun]) {
+            self.lambda()
+        } else if self.at_arrow() {
+            self.arrow_lambda()
+        } else if self.match_token(&[TokenTypes::LeftParen]) {
+            let expr = self.expression()?;
+            self.consume(TokenTypes::RightParen, "Expect ')' after expression.")?;
+            Ok(Expr::grouping(expr))
+        } else if self.check(&TokenTypes::RightParen)
+            || self.check(&TokenTypes::RightBracket)
+            || self.check(&TokenTypes::RightBrace)
+        {
+            // A closing delimiter nothing opened.
+            let message = format!("Unexpected '{}'", self.peek().lexeme);
+            Err(self.error(self.peek(), ErrorKind::UnexpectedToken, &message))
+        } else if STATEMENT_KEYWORDS.contains(&self.peek().token_type) {
+            let message = format!(
+                "'{}' is a statement and cannot be used as an expression",
+                self.peek().lexeme
+            );
+            Err(self.error(self.peek(), ErrorKind::UnexpectedToken, &message))
+        } else if self.check(&TokenTypes::Equal) {
+            Err(self.error(
+                self.peek(),
+                ErrorKind::UnexpectedToken,
+                "Unexpected '=': did you mean '=='?",
+            ))
+        } else {
+            Err(self.error(
+                self.peek(),
+                ErrorKind::UnexpectedToken,
+                "Expected Expression",
+            ))
+        }
+    }
+}
+
+impl<'a> ParseStmt for Parser<'a> {
+    fn expression_statement(&mut self) -> JBreadResult<Stmt> {
+        let start = Position::new(self.peek().line(), self.peek().column());
+        let expr = self.expression()?;
+        if self.check(&TokenTypes::Comma) {
+            return self.multi_assignment(expr);
+        }
+        self.consume(TokenTypes::Semicolon, "Expect ';' after expression.")?;
+        if has_no_effect(&expr) {
+            self.diagnostics.push(
+                JBreadWarning::new(WarningKind::NoEffect, "Statement has no effect", start)
+                    .with_span(expr.span()),
+            );
+        }
+        Ok(Stmt::expression(expr))
+    }
+
+    /// `print;` is an error rather than an empty line, which `println()`
+    /// prints.
+    fn print_statement(&mut self) -> JBreadResult<Stmt> {
+        let exprs = self.expression_list()?;
+        self.consume(TokenTypes::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::print(exprs))
+    }
+
+    fn assert_statement(&mut self) -> JBreadResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        let condition = self.expression()?;
+        let message = if self.match_token(&[TokenTypes::Comma]) {
+            Some(Box::new(self.expression()?))
+        } else {
+            None
+        };
+        self.consume(TokenTypes::Semicolon, "Expect ';' after assertion.")?;
+        Ok(Stmt::assert(keyword, condition, message))
+    }
+
+    /// Any statement but a declaration, as the body of a `repeat`: a
+    /// variable declared there would be gone right after it.
+    fn statement(&mut self) -> JBreadResult<Stmt> {
+        let start = self.start();
+        let stmt = self.nested(|parser| {
+            parser.reject_disabled_statement()?;
+            if parser.at_declaration() {
+                let message = format!(
+                    "Expect a statement, a '{}' declaration needs a block here.",
+                    parser.peek().lexeme
+                );
+                return Err(parser.error(parser.peek(), ErrorKind::UnexpectedToken, &message));
+            }
+            parser.plain_statement()
+        })?;
+        Ok(stmt.with_span(self.span_from(start)))
+    }
+
+    fn declaration(&mut self) -> JBreadResult<Stmt> {
+        let start = self.start();
+        let stmt = self.nested(|parser| {
+            parser.reject_disabled_statement()?;
+            // Assigning a keyword, like `class = 3;`, would otherwise start
+            // the statement the keyword does. `var = 1;` is missing a name.
+            if parser.check_next(&TokenTypes::Equal) && !parser.check(&TokenTypes::Var) {
+                if let Some(error) = parser.keyword_as_name(parser.peek(), "variable name") {
+                    // Past the keyword, so recovery doesn't stop right at it.
+                    parser.advance();
+                    return Err(error);
+                }
+            }
+            if parser.match_token(&[TokenTypes::Var]) {
+                parser.var_declaration()
+            } else if parser.check(&TokenTypes::Const) && parser.check_next(&TokenTypes::Identifier)
+            {
+                parser.advance();
+                parser.const_declaration()
+            } else if parser.check(&TokenTypes::Import) && parser.check_next(&TokenTypes::String) {
+                parser.advance();
+                parser.import_statement()
+            } else if parser.check(&TokenTypes::Fun) && parser.check_next(&TokenTypes::Identifier) {
+                parser.advance();
+                Ok(parser.function("function")?.into())
+            } else if parser.match_token(&[TokenTypes::Class]) {
+                parser.class_declaration()
+            } else {
+                parser.plain_statement()
+            }
+        })?;
+        Ok(stmt.with_span(self.span_from(start)))
+    }
+
+    fn block_statement(&mut self) -> JBreadResult<Stmt> {
+        let mut statements = Vec::new();
+        while !self.check(&TokenTypes::RightBrace) && !self.is_at_end() {
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(error) => self.recover(error),
+            }
+        }
+        self.consume(TokenTypes::RightBrace, "Expect '}' after block.")?;
+        Ok(Stmt::block(statements))
+    }
+
+    fn import_statement(&mut self) -> JBreadResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        let path = self
+            .consume(TokenTypes::String, "Expect a file path after 'import'.")?
+            .to_owned();
+        self.consume(TokenTypes::Semicolon, "Expect ';' after import.")?;
+        Ok(Stmt::import(keyword, path))
+    }
+
+    fn switch_statement(&mut self) -> JBreadResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        self.consume(TokenTypes::LeftParen, "Expect '(' after 'switch'.")?;
+        let subject = self.expression()?;
+        self.consume(TokenTypes::RightParen, "Expect ')' after switch subject.")?;
+        self.consume(TokenTypes::LeftBrace, "Expect '{' before switch arms.")?;
+        let mut arms = Vec::new();
+        let mut has_default = false;
+        while !self.check(&TokenTypes::RightBrace) && !self.is_at_end() {
+            self.reject_disabled("keyword")?;
+            let value = if self.match_token(&[TokenTypes::Case]) {
+                let value = self.expression()?;
+                self.consume(TokenTypes::Colon, "Expect ':' after case value.")?;
+                Some(value)
+            } else if self.match_token(&[TokenTypes::Default]) {
+                if has_default {
+                    return Err(self.error(
+                        self.previous(),
+                        ErrorKind::DuplicateDefault,
+                        "A switch can only have one 'default' arm.",
+                    ));
+                }
+                has_default = true;
+                self.consume(TokenTypes::Colon, "Expect ':' after 'default'.")?;
+                None
+            } else {
+                return Err(self.error(
+                    self.peek(),
+                    ErrorKind::UnexpectedToken,
+                    "Expect 'case' or 'default' in switch.",
+                ));
+            };
+            let mut body = Vec::new();
+            while !self.check(&TokenTypes::Case)
+                && !self.check(&TokenTypes::Default)
+                && !self.check(&TokenTypes::RightBrace)
+                && !self.is_at_end()
+            {
+                body.push(self.declaration()?);
+            }
+            arms.push(SwitchArm { value, body });
+        }
+        self.consume(TokenTypes::RightBrace, "Expect '}' after switch arms.")?;
+        Ok(Stmt::switch(keyword, subject, arms))
+    }
+
+    fn repeat_statement(&mut self) -> JBreadResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        self.consume(TokenTypes::LeftParen, "Expect '(' after 'repeat'.")?;
+        let start = Position::new(self.peek().line(), self.peek().column());
+        let count = self.expression()?;
+        self.consume(TokenTypes::RightParen, "Expect ')' after repeat count.")?;
+        self.warn_assignment(&count, start, "Assignment used as a repeat count");
+        let body = self.statement()?;
+        Ok(Stmt::repeat(keyword, count, body))
+    }
+
+    /// Also parses what follows an `elif`, which is short for `else if`.
+    fn if_statement(&mut self) -> JBreadResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        let message = format!("Expect '(' after '{}'.", keyword.lexeme);
+        self.consume(TokenTypes::LeftParen, &message)?;
+        let start = Position::new(self.peek().line(), self.peek().column());
+        let condition = self.expression()?;
+        self.consume(TokenTypes::RightParen, "Expect ')' after if condition.")?;
+        self.warn_assignment(&condition, start, "Assignment used as a condition");
+        let then_branch = self.statement()?;
+        if self.check(&TokenTypes::Else) || self.at_elif() {
+            self.reject_disabled("keyword")?;
+        }
+        let else_branch = if self.match_token(&[TokenTypes::Else]) {
+            Some(Box::new(self.statement()?))
+        } else if self.at_elif() {
+            let start = self.advance().span.start;
+            let elif = self.nested(Self::if_statement)?;
+            Some(Box::new(elif.with_span(self.span_from(start))))
+        } else {
+            None
+        };
+        Ok(Stmt::if_stmt(keyword, condition, then_branch, else_branch))
+    }
+
+    fn return_statement(&mut self) -> JBreadResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        let mut value = None;
+        if !self.check(&TokenTypes::Semicolon) {
+            value = Some(Box::new(self.expression()?));
+        }
+        self.consume(TokenTypes::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::return_stmt(keyword, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_bool() {
+        let tokens = vec![
+            Token::new(
+                TokenTypes::True,
+                "true".to_string(),
+                Some(LiteralValue::Boolean(true)),
+                1,
+            ),
+            Token::new(TokenTypes::Eof, "".to_string(), None, 1),
+        ];
+        let mut parser = Parser::new(&tokens);
+
+        let parsed_literal_true = parser.expression();
+        assert!(parsed_literal_true.is_ok(), "Failed to parse true literal");
+        assert_eq!(
+            parsed_literal_true.unwrap(),
+            Expr::literal(LiteralValue::Boolean(true)),
+            "Parsed literal bool is not equal to expected literal true"
+        );
+
+        let tokens = vec![
+            Token::new(
+                TokenTypes::False,
+                "false".to_string(),
+                Some(LiteralValue::Boolean(false)),
+                1,
+            ),
+            Token::new(TokenTypes::Eof, "".to_string(), None, 1),
+        ];
+        let mut parser = Parser::new(&tokens);
+
+        let parsed_literal_false = parser.expression();
+        assert!(
+            parsed_literal_false.is_ok(),
+            "Failed to parse false literal"
+        );
+        assert_eq!(
+            parsed_literal_false.unwrap(),
+            Expr::literal(LiteralValue::Boolean(false)),
+            "Parsed literal bool is not equal to expected literal false"
+        );
+    }
+
+    #[test]
+    fn test_literal_nil() {
+        let tokens = vec![
+            Token::new(TokenTypes::Nil, "nil".to_string(), None, 1),
             Token::new(TokenTypes::Eof, "".to_string(), None, 1),
         ];
         let mut parser = Parser::new(&tokens);
@@ -399,7 +1459,7 @@ mod tests {
         assert!(parsed_literal_nil.is_ok(), "Failed to parse nil literal");
         assert_eq!(
             parsed_literal_nil.unwrap(),
-            Expr::Literal(Literal { value: None }),
+            Expr::literal(None),
             "Parsed literal nil is not equal to expected literal nil"
         );
     }
@@ -416,20 +1476,35 @@ mod tests {
         assert!(parsed_literal_nan.is_ok(), "Failed to parse nan literal");
         assert_eq!(
             parsed_literal_nan.unwrap(),
-            Expr::Literal(Literal {
-                value: Some(LiteralEnum::NaN)
-            }),
+            Expr::literal(LiteralValue::NaN),
             "Parsed literal nan is not equal to expected literal nan"
         );
     }
 
+    #[test]
+    fn test_literal_inf() {
+        let tokens = vec![
+            Token::new(TokenTypes::Infinity, "inf".to_string(), None, 1),
+            Token::new(TokenTypes::Eof, "".to_string(), None, 1),
+        ];
+        let mut parser = Parser::new(&tokens);
+
+        let parsed_literal_inf = parser.expression();
+        assert!(parsed_literal_inf.is_ok(), "Failed to parse inf literal");
+        assert_eq!(
+            parsed_literal_inf.unwrap(),
+            Expr::literal(LiteralValue::Number(f64::INFINITY)),
+            "Parsed literal inf is not equal to expected literal inf"
+        );
+    }
+
     #[test]
     fn test_literal_string() {
         let tokens = vec![
             Token::new(
                 TokenTypes::String,
                 "test".to_string(),
-                Some(LiteralEnum::String("test".to_string())),
+                Some(LiteralValue::String("test".to_string())),
                 1,
             ),
             Token::new(TokenTypes::Eof, "".to_string(), None, 1),
@@ -443,9 +1518,7 @@ mod tests {
         );
         assert_eq!(
             parsed_literal_string.unwrap(),
-            Expr::Literal(Literal {
-                value: Some(LiteralEnum::String("test".to_string()))
-            }),
+            Expr::literal(LiteralValue::String("test".to_string())),
             "Parsed literal string is not equal to expected literal string"
         );
     }
@@ -456,7 +1529,7 @@ mod tests {
             Token::new(
                 TokenTypes::Number,
                 "1".to_string(),
-                Some(LiteralEnum::Number(1.0)),
+                Some(LiteralValue::Number(1.0)),
                 1,
             ),
             Token::new(TokenTypes::Eof, "".to_string(), None, 1),
@@ -470,9 +1543,7 @@ mod tests {
         );
         assert_eq!(
             parsed_literal_number.unwrap(),
-            Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(1.0))
-            }),
+            Expr::literal(LiteralValue::Number(1.0)),
             "Parsed literal number is not equal to expected literal number"
         );
     }
@@ -484,7 +1555,7 @@ mod tests {
             Token::new(
                 TokenTypes::Number,
                 "1".to_string(),
-                Some(LiteralEnum::Number(1.0)),
+                Some(LiteralValue::Number(1.0)),
                 1,
             ),
             Token::new(TokenTypes::Eof, "".to_string(), None, 1),
@@ -496,12 +1567,10 @@ mod tests {
         assert!(parsed_unary.is_ok(), "Failed to parse unary");
         assert_eq!(
             parsed_unary.unwrap(),
-            Expr::Unary(Unary {
-                operator: Token::new(TokenTypes::Minus, "-".to_string(), None, 1),
-                right: Box::new(Expr::Literal(Literal {
-                    value: Some(LiteralEnum::Number(1.0))
-                }))
-            }),
+            Expr::unary(
+                Token::new(TokenTypes::Minus, "-".to_string(), None, 1),
+                Expr::literal(LiteralValue::Number(1.0)),
+            ),
             "Parsed unary is not equal to expected unary"
         );
     }
@@ -513,7 +1582,7 @@ mod tests {
             Token::new(
                 TokenTypes::Number,
                 "1".to_string(),
-                Some(LiteralEnum::Number(1.0)),
+                Some(LiteralValue::Number(1.0)),
                 1,
             ),
             Token::new(TokenTypes::RightParen, ")".to_string(), None, 1),
@@ -525,11 +1594,7 @@ mod tests {
         assert!(parsed_grouping.is_ok(), "Failed to parse grouping");
         assert_eq!(
             parsed_grouping.unwrap(),
-            Expr::Grouping(Grouping {
-                expression: Box::new(Expr::Literal(Literal {
-                    value: Some(LiteralEnum::Number(1.0))
-                }))
-            }),
+            Expr::grouping(Expr::literal(LiteralValue::Number(1.0))),
             "Parsed grouping is not equal to expected grouping"
         );
     }
@@ -540,14 +1605,14 @@ mod tests {
             Token::new(
                 TokenTypes::Number,
                 "1".to_string(),
-                Some(LiteralEnum::Number(1.0)),
+                Some(LiteralValue::Number(1.0)),
                 1,
             ),
             Token::new(TokenTypes::Plus, "+".to_string(), None, 1),
             Token::new(
                 TokenTypes::Number,
                 "1".to_string(),
-                Some(LiteralEnum::Number(1.0)),
+                Some(LiteralValue::Number(1.0)),
                 1,
             ),
             Token::new(TokenTypes::Eof, "".to_string(), None, 1),
@@ -558,15 +1623,11 @@ mod tests {
         assert!(parsed_binary.is_ok(), "Failed to parse binary");
         assert_eq!(
             parsed_binary.unwrap(),
-            Expr::Binary(Binary {
-                left: Box::new(Expr::Literal(Literal {
-                    value: Some(LiteralEnum::Number(1.0))
-                })),
-                operator: Token::new(TokenTypes::Plus, "+".to_string(), None, 1),
-                right: Box::new(Expr::Literal(Literal {
-                    value: Some(LiteralEnum::Number(1.0))
-                }))
-            }),
+            Expr::binary(
+                Expr::literal(LiteralValue::Number(1.0)),
+                Token::new(TokenTypes::Plus, "+".to_string(), None, 1),
+                Expr::literal(LiteralValue::Number(1.0)),
+            ),
             "Parsed binary is not equal to expected binary"
         );
     }
@@ -580,25 +1641,23 @@ mod tests {
             Token::new(
                 TokenTypes::Number,
                 "1".to_string(),
-                Some(LiteralEnum::Number(1.0)),
+                Some(LiteralValue::Number(1.0)),
                 1,
             ),
             Token::new(TokenTypes::Semicolon, ";".to_string(), None, 1),
         ];
         let mut parser = Parser::new(&tokens);
 
-        let parsed_var_decl = parser.statement();
+        let parsed_var_decl = parser.declaration();
         dbg!(&parsed_var_decl);
 
         assert!(parsed_var_decl.is_ok(), "Failed to parse var decl");
         assert_eq!(
             parsed_var_decl.unwrap(),
-            Stmt::Var(Var {
-                name: Token::new(TokenTypes::Identifier, "test".to_string(), None, 1),
-                initializer: Some(Box::new(Expr::Literal(Literal {
-                    value: Some(LiteralEnum::Number(1.0))
-                })))
-            }),
+            Stmt::var(
+                Token::new(TokenTypes::Identifier, "test".to_string(), None, 1),
+                Some(Box::new(Expr::literal(LiteralValue::Number(1.0)))),
+            ),
             "Parsed var decl is not equal to expected var decl"
         );
     }
@@ -611,7 +1670,7 @@ mod tests {
             Token::new(
                 TokenTypes::Number,
                 "1".to_string(),
-                Some(LiteralEnum::Number(1.0)),
+                Some(LiteralValue::Number(1.0)),
                 1,
             ),
             Token::new(TokenTypes::Semicolon, ";".to_string(), None, 1),
@@ -622,12 +1681,10 @@ mod tests {
         assert!(parsed_var_assign.is_ok(), "Failed to parse var assign");
         assert_eq!(
             parsed_var_assign.unwrap(),
-            Expr::Assign(Assign {
-                name: Token::new(TokenTypes::Identifier, "test".to_string(), None, 1),
-                value: Box::new(Expr::Literal(Literal {
-                    value: Some(LiteralEnum::Number(1.0))
-                }))
-            }),
+            Expr::assign(
+                Token::new(TokenTypes::Identifier, "test".to_string(), None, 1),
+                Expr::literal(LiteralValue::Number(1.0)),
+            ),
             "Parsed var assign is not equal to expected var assign"
         );
     }
@@ -639,7 +1696,7 @@ mod tests {
             Token::new(
                 TokenTypes::Number,
                 "1".to_string(),
-                Some(LiteralEnum::Number(1.0)),
+                Some(LiteralValue::Number(1.0)),
                 1,
             ),
             Token::new(TokenTypes::Semicolon, ";".to_string(), None, 1),
@@ -650,22 +1707,36 @@ mod tests {
         assert!(parsed_print.is_ok(), "Failed to parse print");
         assert_eq!(
             parsed_print.unwrap(),
-            Stmt::Print(Print {
-                expression: Box::new(Expr::Literal(Literal {
-                    value: Some(LiteralEnum::Number(1.0))
-                }))
-            }),
+            Stmt::print(vec![Expr::literal(LiteralValue::Number(1.0))]),
             "Parsed print is not equal to expected print"
         );
     }
 
+    #[test]
+    fn test_print_several_values() {
+        let parse = |source: &str| {
+            let mut scanner = crate::Scanner::new(source);
+            Parser::new(scanner.scan_tokens()).parse()
+        };
+        let stmts = parse("print 1, \"a\", x = 2;").unwrap();
+        assert_eq!(
+            crate::AstPrinter::default().print_stmt(&stmts[0]),
+            "(print 1 a (= x 2))"
+        );
+
+        // Nothing to print is a mistake, `println()` prints an empty line.
+        let error = parse("print;").unwrap_err();
+        assert_eq!(error.to_string(), "\"Expected Expression\" at 1:6 in ;");
+        assert!(parse("print 1,;").is_err());
+    }
+
     #[test]
     fn test_stmt_expression() {
         let tokens = vec![
             Token::new(
                 TokenTypes::Number,
                 "1".to_string(),
-                Some(LiteralEnum::Number(1.0)),
+                Some(LiteralValue::Number(1.0)),
                 1,
             ),
             Token::new(TokenTypes::Semicolon, ";".to_string(), None, 1),
@@ -679,11 +1750,7 @@ mod tests {
         );
         assert_eq!(
             parsed_stmt_expression.unwrap(),
-            Stmt::Expression(Expression {
-                expression: Box::new(Expr::Literal(Literal {
-                    value: Some(LiteralEnum::Number(1.0))
-                }))
-            }),
+            Stmt::expression(Expr::literal(LiteralValue::Number(1.0))),
             "Parsed stmt expression is not equal to expected stmt expression"
         );
     }
@@ -702,8 +1769,877 @@ mod tests {
         assert!(parsed_block.is_ok(), "Failed to parse block");
         assert_eq!(
             parsed_block.unwrap(),
-            Stmt::Block(Block { statements: vec![] }),
+            Stmt::block(vec![]),
             "Parsed block is not equal to expected block"
         );
     }
+
+    #[test]
+    fn test_const_declaration() {
+        let mut scanner = crate::Scanner::new("const PI = 3.14;");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        assert_eq!(
+            crate::AstPrinter::default().print_stmt(&stmts[0]),
+            "(const PI 3.14)"
+        );
+
+        for (source, message) in [
+            (
+                "const PI;",
+                "\"Expected '=' after constant name\" at 1:9 in ;",
+            ),
+            (
+                "const PI, E = 1, 2;",
+                "\"Expected '=' after constant name\" at 1:9 in ,",
+            ),
+        ] {
+            let mut scanner = crate::Scanner::new(source);
+            let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::UnexpectedToken, "{}", source);
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_const_is_contextual() {
+        let mut scanner = crate::Scanner::new(
+            "var const = 1; const = const + 1; fun f(const) { return const; } \
+             const const = 2; print const.const;",
+        );
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut printer = crate::AstPrinter::default();
+        let printed: Vec<String> = stmts.iter().map(|stmt| printer.print_stmt(stmt)).collect();
+        assert_eq!(
+            printed,
+            [
+                "(var const 1)",
+                "(expr (= const (+ const 1)))",
+                "(fun f (const) (return const))",
+                "(const const 2)",
+                "(print (. const const))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_import_is_contextual() {
+        let mut scanner =
+            crate::Scanner::new("import \"a.jbr\"; var import = 1; print import; import(1);");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut printer = crate::AstPrinter::default();
+        let printed: Vec<String> = stmts.iter().map(|stmt| printer.print_stmt(stmt)).collect();
+        assert_eq!(
+            printed,
+            [
+                "(import \"a.jbr\")",
+                "(var import 1)",
+                "(print import)",
+                "(expr (call import 1))",
+            ]
+        );
+
+        let mut scanner = crate::Scanner::new("import \"a.jbr\"\nprint 1;");
+        let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "\"Expect ';' after import.\" at 1:8 in \"a.jbr\""
+        );
+    }
+
+    #[test]
+    fn test_switch() {
+        let parse = |source: &str| {
+            let mut scanner = crate::Scanner::new(source);
+            Parser::new(scanner.scan_tokens()).parse()
+        };
+        let stmts = parse(
+            "switch (x) { case 1: print 1; print 2; case \"a\": default: print 3; } \
+             switch(1); var case, default = 1, case;",
+        )
+        .unwrap();
+        let mut printer = crate::AstPrinter::default();
+        let printed: Vec<String> = stmts.iter().map(|stmt| printer.print_stmt(stmt)).collect();
+        assert_eq!(
+            printed,
+            [
+                "(switch x (case 1 (print 1) (print 2)) (case a) (default (print 3)))",
+                "(expr (call switch 1))",
+                "(var (case default) 1 case)",
+            ]
+        );
+
+        let error = parse("switch (x) { default: print 1; default: print 2; }").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::DuplicateDefault);
+        assert_eq!(
+            error.to_string(),
+            "\"A switch can only have one 'default' arm.\" at 1:32 in default"
+        );
+        let error = parse("switch (x) { print 1; }").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnexpectedToken);
+        let error = parse("switch (x) { case 1 print 1; }").unwrap_err();
+        assert!(error.to_string().contains("Expect ':' after case value."));
+    }
+
+    #[test]
+    fn test_if_chains() {
+        let parse = |source: &str| {
+            let mut scanner = crate::Scanner::new(source);
+            Parser::new(scanner.scan_tokens()).parse()
+        };
+        let stmts = parse(
+            "if (a) print 1; else if (b) print 2; elif (c) { print 3; } else print 4; \
+             if (a) if (b) print 1; else print 2; \
+             var elif = 1; elif(2); if (a) print 1; elif(3) + 1;",
+        )
+        .unwrap();
+        let mut printer = crate::AstPrinter::default();
+        let printed: Vec<String> = stmts.iter().map(|stmt| printer.print_stmt(stmt)).collect();
+        assert_eq!(
+            printed,
+            [
+                "(if a (print 1) elif b (print 2) elif c (block (print 3)) else (print 4))",
+                // The `else` goes with the closest `if`.
+                "(if a (if b (print 1) else (print 2)))",
+                "(var elif 1)",
+                "(expr (call elif 2))",
+                "(if a (print 1))",
+                "(expr (+ (call elif 3) 1))",
+            ]
+        );
+        // Each `else if` is the else branch of the `if` before it.
+        match &stmts[0] {
+            Stmt::If(first) => match first.else_branch.as_deref() {
+                Some(Stmt::If(second)) => {
+                    assert!(matches!(second.else_branch.as_deref(), Some(Stmt::If(_))))
+                }
+                branch => panic!("Expected an if, got {:?}", branch),
+            },
+            stmt => panic!("Expected an if, got {:?}", stmt),
+        }
+
+        let error = parse("print 1; elif (a) { print 2; }").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "\"'elif' without a preceding 'if'.\" at 1:10 in elif"
+        );
+        let error = parse("print 1; elif (a) print 2;").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "\"'elif' without a preceding 'if'.\" at 1:10 in elif"
+        );
+        let error = parse("elif (true) x = 1;").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("'elif' without a preceding 'if'."));
+        let error = parse("{ print 1; } else print 2;").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("'else' without a preceding 'if'."));
+        let error = parse("if (a) var b = 1;").unwrap_err();
+        assert!(error.to_string().contains("declaration needs a block here"));
+        let error = parse("if a print 1;").unwrap_err();
+        assert!(error.to_string().contains("Expect '(' after 'if'."));
+    }
+
+    #[test]
+    fn test_missing_token_blames_its_line() {
+        let error = |source: &str| {
+            let mut scanner = crate::Scanner::new(source);
+            let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+            (error.kind().clone(), error.to_string())
+        };
+        // Not the next statement's line.
+        assert_eq!(
+            error("var a = 1\n\nprint a;"),
+            (
+                ErrorKind::UnexpectedToken,
+                "\"Expected ';' after variable declaration\" at 1:9 in 1".to_string()
+            )
+        );
+        // The end of input is on the last line, final newline or not.
+        assert_eq!(
+            error("print a\n"),
+            (
+                ErrorKind::UnexpectedEof,
+                "\"Expect ';' after value.\" at 1:8 in ".to_string()
+            )
+        );
+        // On the same line the token that's there instead is to blame.
+        assert_eq!(
+            error("print a print b;").1,
+            "\"Expect ';' after value.\" at 1:9 in print"
+        );
+    }
+
+    #[test]
+    fn test_invalid_assignment_target() {
+        let tokens = vec![
+            Token::new(
+                TokenTypes::Number,
+                "1".to_string(),
+                Some(LiteralValue::Number(1.0)),
+                1,
+            ),
+            Token::new(TokenTypes::Equal, "=".to_string(), None, 1),
+            Token::new(
+                TokenTypes::Number,
+                "2".to_string(),
+                Some(LiteralValue::Number(2.0)),
+                1,
+            ),
+            Token::new(TokenTypes::Eof, "".to_string(), None, 1),
+        ];
+        let mut parser = Parser::new(&tokens);
+
+        let parsed_assign = parser.expression();
+        assert!(parsed_assign.is_err(), "Parsed invalid assignment target");
+        assert_eq!(
+            parsed_assign.unwrap_err().kind(),
+            &ErrorKind::InvalidAssignmentTarget
+        );
+    }
+
+    #[test]
+    fn test_reserved_word_as_name() {
+        let parse = |source: &str| {
+            let mut scanner = crate::Scanner::new(source);
+            Parser::new(scanner.scan_tokens()).parse()
+        };
+        for (source, message) in [
+            (
+                "var class = 1;",
+                "\"'class' is a reserved word and cannot be used as a variable name\" at 1:5 in class",
+            ),
+            (
+                "var true = 1;",
+                "\"'true' is a literal value and cannot be used as a variable name\" at 1:5 in true",
+            ),
+            (
+                "print 1;\nif = 1;",
+                "\"'if' is a reserved word and cannot be used as a variable name\" at 2:1 in if",
+            ),
+            (
+                "class = 3;",
+                "\"'class' is a reserved word and cannot be used as a variable name\" at 1:1 in class",
+            ),
+            (
+                "x = nil = 3;",
+                "\"'nil' is a literal value and cannot be used as a variable name\" at 1:5 in nil",
+            ),
+            (
+                "fun f(a, return) {}",
+                "\"'return' is a reserved word and cannot be used as a parameter name\" at 1:10 in \
+                 return",
+            ),
+        ] {
+            let error = parse(source).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::ReservedWord, "{}", source);
+            assert_eq!(error.to_string(), message);
+        }
+
+        let (printed, errors) = recover("class = 3; print 1;");
+        assert_eq!((printed, errors.len()), (vec!["(print 1)".to_string()], 1));
+
+        // Contextual keywords are still names, and other tokens keep their
+        // errors.
+        assert!(parse("var switch = 1; var classy, iffy = 2, 3; case = 4;").is_ok());
+        assert_eq!(
+            parse("var = 1;").unwrap_err().kind(),
+            &ErrorKind::UnexpectedToken
+        );
+        assert_eq!(
+            parse("(true) = 1;").unwrap_err().kind(),
+            &ErrorKind::InvalidAssignmentTarget
+        );
+    }
+
+    #[test]
+    fn test_disabled_keywords() {
+        let profile = LanguageProfile::default()
+            .without(TokenTypes::While)
+            .without(TokenTypes::This)
+            .without(TokenTypes::Switch)
+            .without(TokenTypes::Else);
+        let parse = |source: &str| {
+            let (tokens, _) = Scanner::with_profile(SourceRef::new("", source), &profile).scan();
+            Parser::new(&tokens)
+                .with_profile(&profile)
+                .parse_recovering()
+        };
+        for (source, message) in [
+            (
+                "var x = 1;\nwhile (x) { x = x - 1; }",
+                "\"The 'while' statement is disabled in this environment.\" at 2:1 in while",
+            ),
+            (
+                "print this;",
+                "\"The 'this' keyword is disabled in this environment.\" at 1:7 in this",
+            ),
+            (
+                "switch (1) { default: print 1; }",
+                "\"The 'switch' statement is disabled in this environment.\" at 1:1 in switch",
+            ),
+            (
+                "if (true) print 1; else print 2;",
+                "\"The 'else' keyword is disabled in this environment.\" at 1:20 in else",
+            ),
+        ] {
+            let outcome = parse(source);
+            assert_eq!(outcome.errors.len(), 1, "{}", source);
+            assert_eq!(outcome.errors[0].kind(), &ErrorKind::DisabledKeyword);
+            assert_eq!(outcome.errors[0].to_string(), message);
+        }
+        // The statements around a disabled one still parse.
+        assert_eq!(
+            parse("var x = 1;\nwhile (x) {}\nprint x;").statements.len(),
+            2
+        );
+        // Contextual keywords are still names.
+        assert!(parse("var switch = 1; print switch;").errors.is_empty());
+
+        let profile = profile.with_disabled_keywords(crate::DisabledKeywords::Identifier);
+        let (tokens, _) =
+            Scanner::with_profile(SourceRef::new("", "var while = \"${this}\";"), &profile).scan();
+        let stmts = Parser::new(&tokens).with_profile(&profile).parse().unwrap();
+        assert_eq!(
+            crate::AstPrinter::default().print_stmt(&stmts[0]),
+            "(var while (interpolate this))"
+        );
+    }
+
+    #[test]
+    fn test_chained_comparison() {
+        let parse = |source: &str| {
+            let mut scanner = crate::Scanner::new(source);
+            Parser::new(scanner.scan_tokens()).parse()
+        };
+        let error = parse("var x = 5;\nif (0 < x <= 10) print x;").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ChainedComparison);
+        assert_eq!(
+            error.to_string(),
+            "\"Chained comparisons are not supported, write 'a < b' and 'b <= c' as separate \
+             conditions.\" at 2:11 in <="
+        );
+        assert!(parse("a > b > c;").is_err());
+
+        // Written on purpose, or not chaining comparisons at all.
+        for source in [
+            "(a < b) < c;",
+            "a < (b < c);",
+            "(a < b) == c;",
+            "a < b == c < d;",
+        ] {
+            assert!(parse(source).is_ok(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_repeat() {
+        let tokens = vec![
+            Token::new(TokenTypes::Repeat, "repeat".to_string(), None, 1),
+            Token::new(TokenTypes::LeftParen, "(".to_string(), None, 1),
+            Token::new(
+                TokenTypes::Number,
+                "5".to_string(),
+                Some(LiteralValue::Number(5.0)),
+                1,
+            ),
+            Token::new(TokenTypes::RightParen, ")".to_string(), None, 1),
+            Token::new(TokenTypes::LeftBrace, "{".to_string(), None, 1),
+            Token::new(TokenTypes::RightBrace, "}".to_string(), None, 1),
+            Token::new(TokenTypes::Eof, "".to_string(), None, 1),
+        ];
+        let mut parser = Parser::new(&tokens);
+
+        let parsed_repeat = parser.statement();
+        assert!(parsed_repeat.is_ok(), "Failed to parse repeat");
+        assert_eq!(
+            parsed_repeat.unwrap(),
+            Stmt::repeat(
+                Token::new(TokenTypes::Repeat, "repeat".to_string(), None, 1),
+                Expr::literal(LiteralValue::Number(5.0)),
+                Stmt::block(vec![]),
+            ),
+            "Parsed repeat is not equal to expected repeat"
+        );
+    }
+
+    #[test]
+    fn test_function_and_calls() {
+        let mut scanner = crate::Scanner::new(
+            "fun add(a, b) { return a + b; } fun noop() { return; } print add(1, 2)(3);",
+        );
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut printer = crate::AstPrinter::default();
+        let printed: Vec<String> = stmts.iter().map(|stmt| printer.print_stmt(stmt)).collect();
+        assert_eq!(
+            printed,
+            vec![
+                "(fun add (a b) (return (+ a b)))",
+                "(fun noop () (return))",
+                "(print (call (call add 1 2) 3))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lambdas() {
+        let mut scanner = crate::Scanner::new(
+            "var twice = fun (x) { return x + x; };
+            apply((a, b) -> a + b, () -> { print 1; });
+            fun (x) {}(1);
+            print (x);",
+        );
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut printer = crate::AstPrinter::default();
+        let printed: Vec<String> = stmts.iter().map(|stmt| printer.print_stmt(stmt)).collect();
+        assert_eq!(
+            printed,
+            vec![
+                "(var twice (lambda (x) (return (+ x x))))",
+                "(expr (call apply (lambda (a b) (return (+ a b))) (lambda () (print 1))))",
+                "(expr (call (lambda (x)) 1))",
+                "(print (group x))",
+            ]
+        );
+
+        let error = |source: &str| {
+            let mut scanner = crate::Scanner::new(source);
+            Parser::new(scanner.scan_tokens())
+                .parse()
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("var f = fun (x);"),
+            "\"Expect '{' before function body.\" at 1:16 in ;"
+        );
+        assert_eq!(
+            error("var f = (x, 1) -> x;"),
+            "\"Expect ')' after expression.\" at 1:11 in ,"
+        );
+    }
+
+    #[test]
+    fn test_call_missing_paren() {
+        let mut scanner = crate::Scanner::new("f(1, 2;");
+        let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnexpectedToken);
+    }
+
+    #[test]
+    fn test_unexpected_eof() {
+        for source in ["(1 + 2", "{ print 1;", "var a = 1", "f(1, 2"] {
+            let mut scanner = crate::Scanner::new(source);
+            let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::UnexpectedEof, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_parse_expression() {
+        let mut scanner = crate::Scanner::new("1 + 2 * 3");
+        let expr = Parser::new(scanner.scan_tokens())
+            .parse_expression()
+            .unwrap();
+        assert_eq!(crate::AstPrinter::default().print(expr), "(+ 1 (* 2 3))");
+
+        let mut scanner = crate::Scanner::new("total");
+        let expr = Parser::new(scanner.scan_tokens())
+            .parse_expression()
+            .unwrap();
+        assert_eq!(crate::AstPrinter::default().print(expr), "total");
+
+        for source in ["1 + 2;", "1 + 2 3"] {
+            let mut scanner = crate::Scanner::new(source);
+            let error = Parser::new(scanner.scan_tokens())
+                .parse_expression()
+                .unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::UnexpectedToken, "{}", source);
+        }
+
+        let mut scanner = crate::Scanner::new("1 + 2 3");
+        let error = Parser::new(scanner.scan_tokens())
+            .parse_expression()
+            .unwrap_err();
+        assert!(error.to_string().contains("at 1:7"), "{}", error);
+    }
+
+    #[test]
+    fn test_spans() {
+        let mut scanner = crate::Scanner::new("var total = (1 +\n  2) * f(x);\nprint total;");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let Stmt::Var(var) = &stmts[0] else {
+            panic!("{:?}", stmts[0]);
+        };
+        assert_eq!(var.span, Span::new(0, 29));
+        let Some(Expr::Binary(product)) = var.initializer.as_deref() else {
+            panic!("{:?}", var.initializer);
+        };
+        assert_eq!(product.span, Span::new(12, 28));
+        // A grouping covers both of its parentheses.
+        let Expr::Grouping(grouping) = &*product.left else {
+            panic!("{:?}", product.left);
+        };
+        assert_eq!(grouping.span, Span::new(12, 21));
+        assert_eq!(grouping.expression.span(), Span::new(13, 20));
+        assert_eq!(product.right.span(), Span::new(24, 28));
+        assert_eq!(stmts[1].span(), Span::new(30, 42));
+
+        let mut printer = crate::AstPrinter::with_spans(scanner.source().clone());
+        assert_eq!(
+            printer.print_stmt(&stmts[1]),
+            "(print @3:1-3:12 total@3:7-3:11)"
+        );
+        assert_eq!(
+            printer.print(grouping.expression.as_ref().clone()),
+            "(+ @1:14-2:3 1@1:14-1:14 2@2:3-2:3)"
+        );
+    }
+
+    #[test]
+    fn test_interpolation_spans() {
+        let mut scanner = crate::Scanner::new("print \"a ${b + 1} c\";");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let Stmt::Print(print) = &stmts[0] else {
+            panic!("{:?}", stmts[0]);
+        };
+        let Expr::Interpolation(interpolation) = &print.expressions[0] else {
+            panic!("{:?}", print.expressions);
+        };
+        assert_eq!(interpolation.span, Span::new(6, 20));
+        let spans: Vec<Span> = interpolation.parts.iter().map(Expr::span).collect();
+        assert_eq!(
+            spans,
+            [Span::new(7, 9), Span::new(11, 16), Span::new(17, 19)]
+        );
+    }
+
+    #[test]
+    fn test_warnings_have_spans() {
+        let mut scanner = crate::Scanner::new("var a = 1;\n(a);");
+        let mut parser = Parser::new(scanner.scan_tokens());
+        parser.parse().unwrap();
+        let diagnostics = parser.take_diagnostics();
+        assert_eq!(diagnostics.warnings()[0].span, Some(Span::new(11, 14)));
+    }
+
+    #[test]
+    fn test_leading_dot_number() {
+        let mut scanner = crate::Scanner::new(".5 + 1");
+        let expr = Parser::new(scanner.scan_tokens())
+            .parse_expression()
+            .unwrap();
+        assert_eq!(crate::AstPrinter::default().print(expr), "(+ 0.5 1)");
+    }
+
+    #[test]
+    fn test_class_and_properties() {
+        let mut scanner = crate::Scanner::new(
+            "class Point { init(x) { this.x = x; } norm() { return this.x; } } p.a.b = p.c();",
+        );
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut printer = crate::AstPrinter::default();
+        let printed: Vec<String> = stmts.iter().map(|stmt| printer.print_stmt(stmt)).collect();
+        assert_eq!(
+            printed,
+            vec![
+                "(class Point (fun init (x) (expr (.= x this x))) (fun norm () (return (. x this))))",
+                "(expr (.= b (. a p) (call (. c p))))",
+            ]
+        );
+
+        let mut scanner = crate::Scanner::new("a.b.c; a.b().c;");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut printer = crate::AstPrinter::default();
+        assert_eq!(printer.print_stmt(&stmts[0]), "(expr (. c (. b a)))");
+        assert_eq!(printer.print_stmt(&stmts[1]), "(expr (. c (call (. b a))))");
+
+        let mut scanner = crate::Scanner::new("a.;");
+        let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnexpectedToken);
+
+        let mut scanner = crate::Scanner::new("p.a() = 1;");
+        let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidAssignmentTarget);
+    }
+
+    #[test]
+    fn test_lists_and_indexing() {
+        let mut scanner = crate::Scanner::new("xs[0][i + 1] = [1, [], f()[2]];");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        assert_eq!(
+            crate::AstPrinter::default().print_stmt(&stmts[0]),
+            "(expr (index= (index xs 0) (+ i 1) (list 1 (list) (index (call f) 2))))"
+        );
+
+        let mut scanner = crate::Scanner::new("[1, 2;");
+        let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnexpectedToken);
+    }
+
+    #[test]
+    fn test_map_literals() {
+        let mut scanner = crate::Scanner::new("var m = {\"a\": 1, k: {},}; { m[\"a\"]; }");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let printer = &mut crate::AstPrinter::default();
+        assert_eq!(
+            printer.print_stmt(&stmts[0]),
+            "(var m (map (a 1) (k (map))))"
+        );
+        // `{` starting a statement is still a block.
+        assert!(matches!(stmts[1], Stmt::Block(_)));
+
+        let mut scanner = crate::Scanner::new("var m = {\"a\" 1};");
+        let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "\"Expect ':' after map key.\" at 1:14 in 1"
+        );
+    }
+
+    #[test]
+    fn test_declarations_need_a_block() {
+        let mut scanner = crate::Scanner::new(
+            "var a = 1; repeat (2) { var b = a; } switch (a) { case 1: var c = 2; }",
+        );
+        assert!(Parser::new(scanner.scan_tokens()).parse().is_ok());
+
+        for source in [
+            "repeat (2) var b = 1;",
+            "repeat (2) fun f() {}",
+            "repeat (2) class A {}",
+        ] {
+            let mut scanner = crate::Scanner::new(source);
+            let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::UnexpectedToken, "{}", source);
+            assert!(
+                error.to_string().contains("declaration needs a block here"),
+                "{}",
+                error
+            );
+        }
+
+        // `const` not followed by a name is still read as an expression.
+        let mut scanner = crate::Scanner::new("var const = 1; repeat (2) const = 3;");
+        assert!(Parser::new(scanner.scan_tokens()).parse().is_ok());
+    }
+
+    fn recover(source: &str) -> (Vec<String>, Vec<JBreadErrors>) {
+        let mut scanner = crate::Scanner::new(source);
+        let outcome = Parser::new(scanner.scan_tokens()).parse_recovering();
+        let mut printer = crate::AstPrinter::default();
+        let printed = outcome
+            .statements
+            .iter()
+            .map(|stmt| printer.print_stmt(stmt))
+            .collect();
+        (printed, outcome.errors)
+    }
+
+    #[test]
+    fn test_recovery_keeps_surrounding_statements() {
+        let (printed, errors) = recover("print 1; var = 2; print 3;\nvar b = 4 5; var c = 6;");
+        assert_eq!(printed, vec!["(print 1)", "(print 3)", "(var c 6)"]);
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "\"Expected a variable name\" at 1:14 in =",
+                "\"Expected ';' after variable declaration\" at 2:11 in 5",
+            ]
+        );
+
+        // Spans of the surviving statements still point into the source.
+        let mut scanner = crate::Scanner::new("print (; var a = 1;");
+        let outcome = Parser::new(scanner.scan_tokens()).parse_recovering();
+        match &outcome.statements[..] {
+            [Stmt::Var(var)] => assert_eq!(var.name.span, crate::Span::new(13, 14)),
+            stmts => panic!("Expected a single var, got {:?}", stmts),
+        }
+    }
+
+    #[test]
+    fn test_recovery_inside_blocks() {
+        let (printed, errors) =
+            recover("{ print 1; print ; var x = 2; 1 +; print x; } repeat (2) { f(; } print 5;");
+        assert_eq!(
+            printed,
+            vec![
+                "(block (print 1) (var x 2) (print x))",
+                "(repeat 2 (block))",
+                "(print 5)",
+            ]
+        );
+        assert_eq!(errors.len(), 3);
+
+        // Braces opened inside a broken statement are skipped as a whole.
+        let (printed, errors) = recover("fun f( { print 1; } print 2; } print 3;");
+        assert_eq!(printed, vec!["(print 2)", "(print 3)"]);
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_stray_closing_delimiters() {
+        let (printed, errors) = recover("1 + 2; )\nprint 3; ] }\nprint 4;");
+        assert_eq!(printed, vec!["(expr (+ 1 2))", "(print 3)", "(print 4)"]);
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "\"Unexpected ')'\" at 1:8 in )",
+                "\"Unexpected ']'\" at 2:10 in ]",
+                "\"Unexpected '}'\" at 2:12 in }",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_statement_keyword_as_expression() {
+        let error = |source: &str| {
+            let mut scanner = crate::Scanner::new(source);
+            Parser::new(scanner.scan_tokens())
+                .parse()
+                .unwrap_err()
+                .to_string()
+        };
+        for keyword in ["print", "var", "class", "return", "assert", "repeat"] {
+            assert_eq!(
+                error(&format!("var x =\n  {} 5;", keyword)),
+                format!(
+                    "\"'{}' is a statement and cannot be used as an expression\" at 2:3 in {}",
+                    keyword, keyword
+                )
+            );
+        }
+        // `fun` starts a lambda in an expression.
+        assert_eq!(
+            error("var x =\n  fun 5;"),
+            "\"Expect '(' after 'fun'.\" at 2:7 in 5"
+        );
+        assert_eq!(
+            error("print a\n== = 1;"),
+            "\"Unexpected '=': did you mean '=='?\" at 2:4 in ="
+        );
+        assert_eq!(
+            error("var a = = 1;"),
+            "\"Unexpected '=': did you mean '=='?\" at 1:9 in ="
+        );
+
+        // The keyword isn't consumed, so its statement still parses.
+        let (printed, errors) = recover("var y = print 5;");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(printed, vec!["(print 5)"]);
+    }
+
+    #[test]
+    fn test_degenerate_input() {
+        let (printed, errors) = recover("");
+        assert!(printed.is_empty() && errors.is_empty());
+        let (printed, errors) = recover("// nothing but a comment\n");
+        assert!(printed.is_empty() && errors.is_empty());
+
+        for source in [
+            ";",
+            ")",
+            "=",
+            "\"unterminated",
+            "(((",
+            "var ;",
+            "",
+            "}",
+            "]",
+            "= ;",
+        ] {
+            let tokens = crate::Scanner::new(source).scan().0;
+            // Either result is fine, as long as it's not a panic.
+            let _ = Parser::new(&tokens).parse();
+            let _ = Parser::new(&tokens).parse_recovering();
+            let _ = Parser::new(&tokens).parse_expression();
+            let _ = Parser::statement_end(&tokens, 0);
+        }
+    }
+
+    #[test]
+    fn test_parse_reports_first_error() {
+        let mut scanner = crate::Scanner::new("print 1; { print ; } var = 2;");
+        let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnexpectedToken);
+        assert_eq!(error.to_string(), "\"Expected Expression\" at 1:18 in ;");
+
+        let (printed, errors) = recover("} print 1; }");
+        assert_eq!(printed, vec!["(print 1)"]);
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_power_precedence() {
+        let mut scanner =
+            crate::Scanner::new("-2 ** 2; 2 ** 3 ** 2; 2 * 3 ** 2; 2 ** -1; f(1) ** xs[0];");
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut printer = crate::AstPrinter::default();
+        let printed: Vec<String> = stmts.iter().map(|stmt| printer.print_stmt(stmt)).collect();
+        assert_eq!(
+            printed,
+            vec![
+                "(expr (- (** 2 2)))",
+                "(expr (** 2 (** 3 2)))",
+                "(expr (* 2 (** 3 2)))",
+                "(expr (** 2 (- 1)))",
+                "(expr (** (call f 1) (index xs 0)))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bitwise_precedence() {
+        let mut scanner = crate::Scanner::new(
+            "1 | 2 & 3; 1 ^ 2 | 3 ^ 4; a == b | c; 1 << 2 + 3; 1 < 2 >> 1; a & b < c; ~a & -b;",
+        );
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut printer = crate::AstPrinter::default();
+        let printed: Vec<String> = stmts.iter().map(|stmt| printer.print_stmt(stmt)).collect();
+        assert_eq!(
+            printed,
+            vec![
+                "(expr (| 1 (& 2 3)))",
+                "(expr (| (^ 1 2) (^ 3 4)))",
+                "(expr (== a (| b c)))",
+                "(expr (<< 1 (+ 2 3)))",
+                "(expr (< 1 (>> 2 1)))",
+                "(expr (& a (< b c)))",
+                "(expr (& (~ a) (- b)))",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multiple_and_chained_assignment() {
+        let mut scanner = crate::Scanner::new(
+            "a = b = 3; var c = a = 1; var a, b = 1, 2; var x, y; a, b = b, a;",
+        );
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut printer = crate::AstPrinter::default();
+        let printed: Vec<String> = stmts.iter().map(|stmt| printer.print_stmt(stmt)).collect();
+        assert_eq!(
+            printed,
+            vec![
+                "(expr (= a (= b 3)))",
+                "(var c (= a 1))",
+                "(var (a b) 1 2)",
+                "(var (x y))",
+                "(= (a b) b a)",
+            ]
+        );
+
+        for source in ["var a, b = 1;", "var a = 1, 2;", "a, b = 1;"] {
+            let mut scanner = crate::Scanner::new(source);
+            let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::ArityMismatch);
+        }
+
+        let mut scanner = crate::Scanner::new("a.x, b = 1, 2;");
+        let error = Parser::new(scanner.scan_tokens()).parse().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidAssignmentTarget);
+    }
 }
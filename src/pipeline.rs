@@ -0,0 +1,507 @@
+use std::{fmt::Write, marker::PhantomData};
+
+use crate::{
+    ast::{Assign, MultiAssign, Stmt, This, Variable},
+    errors::Diagnostics,
+    parser::Parser,
+    resolver::ExprId,
+    walk::{walk_expr, walk_program, ExprWalker, StmtWalker},
+    AllowDirective, AstPrinter, JBreadResult, Resolution, Resolver, Scanner, Token,
+};
+#[cfg(feature = "interpreter")]
+use crate::{
+    errors::SourceMap, fold_constants, interpreter::Interpreter, ConstantPropagator, OutputBuffer,
+};
+
+/// A stage of the pipeline that can be shown by [`Pipeline::explain`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExplainSection {
+    Tokens,
+    Ast,
+    /// How many scopes up each variable reference was resolved to.
+    Resolution,
+    /// The statements the optimizer rewrote, before and after.
+    Optimizations,
+    Execution,
+}
+
+impl ExplainSection {
+    pub const ALL: [ExplainSection; 5] = [
+        ExplainSection::Tokens,
+        ExplainSection::Ast,
+        ExplainSection::Resolution,
+        ExplainSection::Optimizations,
+        ExplainSection::Execution,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExplainSection::Tokens => "tokens",
+            ExplainSection::Ast => "ast",
+            ExplainSection::Resolution => "resolution",
+            ExplainSection::Optimizations => "optimizations",
+            ExplainSection::Execution => "execution",
+        }
+    }
+
+    /// Parses a comma separated list such as `tokens,ast`.
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, String> {
+        list.split(',')
+            .map(|name| {
+                Self::ALL
+                    .iter()
+                    .find(|section| section.name() == name.trim())
+                    .copied()
+                    .ok_or(format!("Unknown explain section \"{}\"", name.trim()))
+            })
+            .collect()
+    }
+}
+
+/// A [`Pipeline`] that only scans, parses and resolves. Nothing reachable
+/// from it can execute the program.
+pub struct Checked;
+
+/// A [`Pipeline`] that may also execute the program.
+#[cfg(feature = "interpreter")]
+pub struct Executed;
+
+/// What the analysis stages produced, obtained without an interpreter.
+#[derive(Debug)]
+pub struct Analysis {
+    pub tokens: Vec<Token>,
+    pub directives: Vec<AllowDirective>,
+    pub stmts: Vec<Stmt>,
+    /// Warnings found while parsing.
+    pub diagnostics: Diagnostics,
+    pub resolution: Resolution,
+}
+
+/// Runs a source through each stage and renders what every stage produced.
+///
+/// A pipeline starts out [`Checked`] and has to be turned into an
+/// [`Executed`] one with [`executing`](Pipeline::executing) before anything
+/// can run, so analysis can't execute a program by accident:
+///
+/// ```compile_fail
+/// j_bread::Pipeline::new("print 1;").execute();
+/// ```
+///
+/// ```
+/// let analysis = j_bread::Pipeline::new("print 1;").analyze().unwrap();
+/// assert_eq!(analysis.tokens.len(), 4);
+/// ```
+pub struct Pipeline<'a, State = Checked> {
+    source: &'a str,
+    sections: Vec<ExplainSection>,
+    state: PhantomData<State>,
+}
+
+impl<'a> Pipeline<'a, Checked> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            sections: ExplainSection::ALL.to_vec(),
+            state: PhantomData,
+        }
+    }
+
+    #[cfg(feature = "interpreter")]
+    pub fn executing(self) -> Pipeline<'a, Executed> {
+        Pipeline {
+            source: self.source,
+            sections: self.sections,
+            state: PhantomData,
+        }
+    }
+
+    /// Renders the tokens, AST and resolution sections. The optimizations
+    /// and execution ones are skipped, folding constants takes an
+    /// interpreter.
+    pub fn explain(&self) -> String {
+        self.explain_analysis().0
+    }
+}
+
+#[cfg(feature = "interpreter")]
+impl<'a> Pipeline<'a, Executed> {
+    /// Parses, resolves and interprets the source.
+    pub fn execute(&self) -> JBreadResult<()> {
+        self.execute_in(&mut Interpreter::default())
+    }
+
+    /// Like [`execute`](Self::execute) but in `interpreter`, e.g. one
+    /// writing to an [`OutputBuffer`](crate::OutputBuffer).
+    pub fn execute_in(&self, interpreter: &mut Interpreter) -> JBreadResult<()> {
+        let analysis = self.analyze()?;
+        interpreter.set_locals(analysis.resolution.locals);
+        interpreter.interpret(&analysis.stmts).map(drop)
+    }
+
+    pub fn explain(&self) -> String {
+        let (mut result, analyzed) = self.explain_analysis();
+        let Some((stmts, resolution)) = analyzed else {
+            return result;
+        };
+
+        if self.sections.contains(&ExplainSection::Optimizations) {
+            result.push_str(&explain_optimizations(&stmts));
+        }
+
+        if self.sections.contains(&ExplainSection::Execution) {
+            // What the program prints comes before how it ended.
+            result.push_str(&section_header("execution", None));
+            let output = OutputBuffer::new();
+            let mut interpreter = Interpreter::default()
+                .with_output(output.clone())
+                .with_error_output(output.clone());
+            interpreter.set_locals(resolution.locals);
+            let outcome = interpreter.interpret(&stmts);
+            result.push_str(&output.take());
+            match outcome {
+                Ok(_) => result.push_str("ok\n"),
+                Err(error) => {
+                    result.push_str(&error.render(&SourceMap::new(self.source)));
+                    result.push('\n');
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl<'a, State> Pipeline<'a, State> {
+    pub fn with_sections(mut self, sections: &[ExplainSection]) -> Self {
+        self.sections = sections.to_vec();
+        self
+    }
+
+    /// Scans, parses and resolves the source.
+    pub fn analyze(&self) -> JBreadResult<Analysis> {
+        let mut scanner = Scanner::new(self.source);
+        scanner.scan_tokens();
+        if let Some(error) = scanner.errors().first() {
+            return Err(error.clone());
+        }
+        let mut parser = Parser::new(scanner.scanned());
+        let stmts = parser.parse()?;
+        let resolution = Resolver::default().resolve(&stmts)?;
+        Ok(Analysis {
+            tokens: scanner.scanned().to_vec(),
+            directives: scanner.directives().to_vec(),
+            stmts,
+            diagnostics: parser.take_diagnostics(),
+            resolution,
+        })
+    }
+
+    /// Renders every section up to execution, along with the resolved
+    /// program unless a stage failed.
+    fn explain_analysis(&self) -> (String, Option<(Vec<Stmt>, Resolution)>) {
+        let scanner = Scanner::new(self.source);
+        let source = scanner.source().clone();
+        let source_map = source.map();
+        let (tokens, errors) = scanner.scan();
+        let mut result = String::new();
+
+        if self.sections.contains(&ExplainSection::Tokens) {
+            result.push_str(&explain_tokens(&tokens));
+        }
+
+        if !errors.is_empty() {
+            result.push_str(&section_header("scan error", None));
+            for error in errors {
+                result.push_str(&error.render(source_map));
+                result.push('\n');
+            }
+            return (result, None);
+        }
+
+        let outcome = Parser::new(&tokens).parse_recovering();
+        if !outcome.errors.is_empty() {
+            result.push_str(&section_header("parse error", None));
+            for error in outcome.errors {
+                result.push_str(&error.render(source_map));
+                result.push('\n');
+            }
+            return (result, None);
+        }
+        let stmts = outcome.statements;
+
+        if self.sections.contains(&ExplainSection::Ast) {
+            result.push_str(&explain_ast(&stmts));
+        }
+
+        match Resolver::default().resolve(&stmts) {
+            Ok(resolution) => {
+                if self.sections.contains(&ExplainSection::Resolution) {
+                    result.push_str(&explain_resolution(&stmts, &resolution));
+                }
+                (result, Some((stmts, resolution)))
+            }
+            Err(error) => {
+                result.push_str(&section_header("resolve error", None));
+                result.push_str(&error.render(source_map));
+                result.push('\n');
+                (result, None)
+            }
+        }
+    }
+}
+
+fn section_header(name: &str, count: Option<usize>) -> String {
+    match count {
+        Some(count) => format!("== {} ({}) ==\n", name, count),
+        None => format!("== {} ==\n", name),
+    }
+}
+
+/// Lists tokens in their compact form, one source line per row.
+pub fn explain_tokens(tokens: &[Token]) -> String {
+    let mut result = section_header("tokens", Some(tokens.len()));
+    let mut line = None;
+    for token in tokens {
+        if line == Some(token.line()) {
+            result.push(' ');
+        } else {
+            if line.is_some() {
+                result.push('\n');
+            }
+            line = Some(token.line());
+            let _ = write!(result, "{:>4} | ", token.line());
+        }
+        let _ = write!(result, "{}", token);
+    }
+    result.push('\n');
+    result
+}
+
+/// Prints each statement as an s-expression on its own row.
+pub fn explain_ast(stmts: &[Stmt]) -> String {
+    let mut printer = AstPrinter::default();
+    let mut result = section_header("ast", Some(stmts.len()));
+    for stmt in stmts {
+        result.push_str(&printer.print_stmt(stmt));
+        result.push('\n');
+    }
+    result
+}
+
+/// Lists each variable reference with how many scopes up it was resolved
+/// to, in source order. Globals are looked up by name when run.
+pub fn explain_resolution(stmts: &[Stmt], resolution: &Resolution) -> String {
+    let mut references = References::default();
+    walk_program(&mut references, stmts);
+    let mut result = section_header("resolution", Some(references.0.len()));
+    for (name, id) in references.0 {
+        let _ = write!(result, "{:>4} | {} -> ", name.line(), name.lexeme);
+        match resolution.locals.get(&id) {
+            Some(distance) => {
+                let _ = writeln!(result, "local at distance {}", distance);
+            }
+            None => result.push_str("global\n"),
+        }
+    }
+    result
+}
+
+/// The name and node of each reference the resolver resolves.
+#[derive(Default)]
+struct References(Vec<(Token, ExprId)>);
+
+impl ExprWalker for References {
+    fn visit_expr_variable(&mut self, expr: &Variable) {
+        self.0.push((expr.name.clone(), ExprId::of(expr)));
+    }
+
+    fn visit_expr_assign(&mut self, expr: &Assign) {
+        self.0.push((expr.name.clone(), ExprId::of(expr)));
+        walk_expr(self, &expr.value);
+    }
+
+    fn visit_expr_this(&mut self, expr: &This) {
+        self.0.push((expr.keyword.clone(), ExprId::of(expr)));
+    }
+}
+
+impl StmtWalker for References {
+    fn visit_stmt_multi_assign(&mut self, stmt: &MultiAssign) {
+        for target in stmt.targets.iter() {
+            self.0.push((target.name.clone(), ExprId::of(target)));
+        }
+        for value in stmt.values.iter() {
+            walk_expr(self, value);
+        }
+    }
+}
+
+/// Shows each statement that propagating and folding constants rewrote,
+/// before and after.
+#[cfg(feature = "interpreter")]
+pub fn explain_optimizations(stmts: &[Stmt]) -> String {
+    let optimized = fold_constants(ConstantPropagator::default().propagate(stmts));
+    let mut printer = AstPrinter::default();
+    let rewritten: Vec<_> = stmts
+        .iter()
+        .zip(optimized.iter())
+        .map(|(before, after)| {
+            (
+                before,
+                printer.print_stmt(before),
+                printer.print_stmt(after),
+            )
+        })
+        .filter(|(_, before, after)| before != after)
+        .collect();
+    let mut result = section_header("optimizations", Some(rewritten.len()));
+    for (stmt, before, after) in rewritten {
+        match stmt.line() {
+            Some(line) => {
+                let _ = write!(result, "{:>4} | ", line);
+            }
+            None => result.push_str("     | "),
+        }
+        let _ = writeln!(result, "{} => {}", before, after);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: &str = "var a = 1;\nprint a + 2;";
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_explain_all_sections() {
+        assert_eq!(
+            Pipeline::new(PROGRAM).executing().explain(),
+            [
+                "== tokens (11) ==",
+                "   1 | Var(var) Identifier(a) Equal(=) Number(1) Semicolon(;)",
+                "   2 | Print(print) Identifier(a) Plus(+) Number(2) Semicolon(;) Eof",
+                "== ast (2) ==",
+                "(var a 1)",
+                "(print (+ a 2))",
+                "== resolution (1) ==",
+                "   2 | a -> global",
+                "== optimizations (1) ==",
+                "   2 | (print (+ a 2)) => (print 3)",
+                "== execution ==",
+                "3",
+                "ok",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_explain_section_toggling() {
+        let sections = ExplainSection::parse_list("ast").unwrap();
+        let explained = Pipeline::new(PROGRAM).with_sections(&sections).explain();
+        assert!(explained.starts_with("== ast (2) ==\n"));
+        assert!(!explained.contains("== tokens"));
+        assert!(!explained.contains("== execution"));
+    }
+
+    #[test]
+    fn test_explain_errors() {
+        let explained = Pipeline::new("var = 1;").explain();
+        assert!(explained.contains("== tokens (5) =="));
+        assert!(explained.contains("== parse error =="));
+        assert!(!explained.contains("== ast"));
+
+        // Every recovered error is listed.
+        let explained = Pipeline::new("var = 1; print ;").explain();
+        assert_eq!(explained.matches("Expect").count(), 2, "{}", explained);
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_explain_runtime_error() {
+        let explained = Pipeline::new("print b;").executing().explain();
+        assert!(explained.contains("== execution ==\n\"Undefined variable\""));
+    }
+
+    #[test]
+    fn test_parse_section_list() {
+        assert_eq!(
+            ExplainSection::parse_list("tokens, execution"),
+            Ok(vec![ExplainSection::Tokens, ExplainSection::Execution])
+        );
+        assert!(ExplainSection::parse_list("tokens,bogus").is_err());
+    }
+
+    #[test]
+    fn test_checked_explain_skips_execution() {
+        let explained = Pipeline::new(PROGRAM).explain();
+        assert!(explained.contains("== ast (2) =="));
+        assert!(explained.contains("== resolution (1) =="));
+        assert!(!explained.contains("== optimizations"));
+        assert!(!explained.contains("== execution"));
+    }
+
+    #[test]
+    fn test_explain_resolution() {
+        let sections = [ExplainSection::Resolution];
+        let explained = Pipeline::new("var a = 1;\n{ var b = a;\n{ b = b + 1; } }")
+            .with_sections(&sections)
+            .explain();
+        assert_eq!(
+            explained,
+            [
+                "== resolution (3) ==",
+                "   2 | a -> global",
+                "   3 | b -> local at distance 1",
+                "   3 | b -> local at distance 1",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_explain_captures_output() {
+        let sections = [ExplainSection::Optimizations, ExplainSection::Execution];
+        let explained = Pipeline::new("var a = 2;\nprint a * 3;\nprint \"a\" + 1 > 2;")
+            .executing()
+            .with_sections(&sections)
+            .explain();
+        assert!(
+            explained.starts_with("== optimizations (1) ==\n   2 | (print (* a 3)) => (print 6)\n")
+        );
+        assert!(
+            explained.contains("== execution ==\n6\n\""),
+            "{}",
+            explained
+        );
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_execute() {
+        assert!(Pipeline::new(PROGRAM).executing().execute().is_ok());
+        let error = Pipeline::new("print b;").executing().execute().unwrap_err();
+        assert_eq!(error.kind(), &crate::ErrorKind::UndefinedVariable);
+
+        let output = crate::OutputBuffer::new();
+        let mut interpreter = Interpreter::default().with_output(output.clone());
+        Pipeline::new(PROGRAM)
+            .executing()
+            .execute_in(&mut interpreter)
+            .unwrap();
+        assert!(!output.take().is_empty());
+
+        // Statements that survived a parse error are never run.
+        let error = Pipeline::new("print 1; print ; print 2;")
+            .executing()
+            .execute_in(&mut interpreter)
+            .unwrap_err();
+        assert_eq!(error.kind(), &crate::ErrorKind::UnexpectedToken);
+        assert!(output.take().is_empty());
+    }
+}
@@ -0,0 +1,72 @@
+//! One configuration object for embedders narrowing the language down, e.g.
+//! to a rules language without classes or functions.
+
+use crate::{ScannerOptions, TokenTypes};
+
+/// What a keyword a [`LanguageProfile`] disables turns into.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DisabledKeywords {
+    /// Parsing fails with
+    /// [`ErrorKind::DisabledKeyword`](crate::ErrorKind::DisabledKeyword)
+    /// where the keyword is used, naming it.
+    #[default]
+    Reject,
+    /// It scans as an identifier, so programs can use it as a name.
+    Identifier,
+}
+
+/// Which keywords a program may use and the limits it runs under. The
+/// default enables everything and limits nothing beyond the built-in
+/// limits, which is how the language behaves without one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LanguageProfile {
+    /// Keywords left out of the language. Disabling one of `nan` and `NaN`
+    /// disables both.
+    pub disabled: Vec<TokenTypes>,
+    pub disabled_keywords: DisabledKeywords,
+    /// Sources with more lines fail to scan, see [`ScannerOptions`].
+    pub max_lines: Option<usize>,
+    /// Replaces [`MAX_NESTING_DEPTH`](crate::parser::MAX_NESTING_DEPTH), see
+    /// [`Parser::with_max_nesting_depth`](crate::Parser::with_max_nesting_depth).
+    pub max_nesting_depth: Option<usize>,
+    /// How many steps a program may take, see `Interpreter::with_step_limit`.
+    /// Only applies to the interpreters [`JuniorBread`](crate::JuniorBread)
+    /// creates.
+    pub max_steps: Option<u64>,
+}
+
+impl LanguageProfile {
+    /// Disables the keyword scanned as `keyword`, e.g.
+    /// [`TokenTypes::While`].
+    pub fn without(mut self, keyword: TokenTypes) -> Self {
+        if !self.disabled.contains(&keyword) {
+            self.disabled.push(keyword);
+        }
+        self
+    }
+
+    pub fn with_disabled_keywords(mut self, handling: DisabledKeywords) -> Self {
+        self.disabled_keywords = handling;
+        self
+    }
+
+    pub fn is_disabled(&self, token_type: &TokenTypes) -> bool {
+        self.disabled.contains(token_type)
+    }
+
+    /// Whether parsing fails where `token_type` is used as a keyword.
+    pub(crate) fn rejects(&self, token_type: &TokenTypes) -> bool {
+        self.disabled_keywords == DisabledKeywords::Reject && self.is_disabled(token_type)
+    }
+
+    /// Whether `token_type` scans as an identifier.
+    pub(crate) fn renames(&self, token_type: &TokenTypes) -> bool {
+        self.disabled_keywords == DisabledKeywords::Identifier && self.is_disabled(token_type)
+    }
+
+    pub fn scanner_options(&self) -> ScannerOptions {
+        ScannerOptions {
+            max_lines: self.max_lines,
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use std::{
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use crate::{parser::Parser, ErrorKind, Scanner};
+
+pub const PROMPT: &str = "jbread> ";
+pub const CONTINUATION_PROMPT: &str = "... ";
+
+/// A line starting with `:` that controls the session instead of being run.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// `:quit` or `:q`, ends the session.
+    Quit,
+    /// `:env`, prints every variable in scope.
+    Env,
+    /// `:reset`, forgets every variable.
+    Reset,
+    /// `:load path`, runs a file in the session.
+    Load(String),
+    /// `:save path`, writes the session's variables to a file.
+    Save(String),
+    /// `:restore path`, defines the variables a `:save` wrote.
+    Restore(String),
+    /// `:ast expr`, prints the tree of an expression without evaluating it.
+    Ast(String),
+    /// `:paste`, reads lines up to one holding only `.` and runs them as
+    /// one entry, which [`read_entry`] takes care of.
+    Paste,
+    /// A command that doesn't exist or is missing its argument, with why.
+    Invalid(String),
+}
+
+/// The command `entry` spells, `None` if it's source to run instead.
+pub fn parse_command(entry: &str) -> Option<Command> {
+    let entry = entry.trim().strip_prefix(':')?;
+    let (name, argument) = match entry.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (entry, ""),
+    };
+    let command = match (name, argument) {
+        ("quit" | "q", "") => Command::Quit,
+        ("env", "") => Command::Env,
+        ("reset", "") => Command::Reset,
+        ("paste", "") => Command::Paste,
+        ("quit" | "q" | "env" | "reset" | "paste", _) => {
+            Command::Invalid(format!(":{} takes no argument", name))
+        }
+        ("load", "") => Command::Invalid(":load needs a path".to_string()),
+        ("load", path) => Command::Load(path.to_string()),
+        ("save", "") => Command::Invalid(":save needs a path".to_string()),
+        ("save", path) => Command::Save(path.to_string()),
+        ("restore", "") => Command::Invalid(":restore needs a path".to_string()),
+        ("restore", path) => Command::Restore(path.to_string()),
+        ("ast", "") => Command::Invalid(":ast needs an expression".to_string()),
+        ("ast", expr) => Command::Ast(expr.to_string()),
+        _ => Command::Invalid(format!("Unknown command :{}", name)),
+    };
+    Some(command)
+}
+
+/// Whether `source` only failed to parse because it ended early, like an
+/// unclosed block or string, so the REPL should keep reading lines into it.
+pub fn is_incomplete(source: &str) -> bool {
+    let (tokens, mut errors) = Scanner::new(source).scan();
+    errors.extend(Parser::new(&tokens).parse_recovering().errors);
+    // Anything else wrong with it is reported right away.
+    !errors.is_empty()
+        && errors.iter().all(|error| {
+            matches!(
+                error.kind(),
+                ErrorKind::UnexpectedEof | ErrorKind::UnterminatedString
+            )
+        })
+}
+
+/// Lays out the scopes of an environment snapshot for `:env`, each
+/// enclosing scope indented one level further than the one it encloses.
+pub fn format_scopes<V: Display>(scopes: &[Vec<(String, V)>]) -> String {
+    let mut result = String::new();
+    for (depth, scope) in scopes.iter().enumerate() {
+        let indent = "  ".repeat(depth);
+        if depth > 0 {
+            result.push_str(&format!("{}enclosing:\n", "  ".repeat(depth - 1)));
+        }
+        if scope.is_empty() {
+            result.push_str(&format!("{}(empty)\n", indent));
+        }
+        for (name, value) in scope {
+            result.push_str(&format!("{}{} = {}\n", indent, name, value));
+        }
+    }
+    result
+}
+
+/// Prompts for and reads one entry, following it with continuation lines
+/// until it's complete. End of input on a continuation line drops what was
+/// read so far and starts over, on the first line it returns `None`.
+///
+/// After `:paste` the lines up to one holding only `.` are the entry,
+/// without prompts and however incomplete, so a pasted script is scanned
+/// and run as a whole.
+pub fn read_entry(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<Option<String>> {
+    let mut entry = String::new();
+    loop {
+        let prompt = if entry.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        write!(output, "{}", prompt)?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            if entry.is_empty() {
+                writeln!(output)?;
+                return Ok(None);
+            }
+            writeln!(output)?;
+            entry.clear();
+            continue;
+        }
+        entry.push_str(&line);
+        match parse_command(&entry) {
+            Some(Command::Paste) => match read_paste(input, output)? {
+                Some(pasted) if !pasted.trim().is_empty() => return Ok(Some(pasted)),
+                _ => {
+                    entry.clear();
+                    continue;
+                }
+            },
+            Some(_) => return Ok(Some(entry)),
+            None => {}
+        }
+        if entry.trim().is_empty() {
+            entry.clear();
+        } else if !is_incomplete(&entry) {
+            return Ok(Some(entry));
+        }
+    }
+}
+
+/// Reads the lines of a `:paste` up to one holding only `.`, `None` when
+/// the input ends first.
+fn read_paste(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<Option<String>> {
+    writeln!(output, "(pasting, a line holding only '.' runs it)")?;
+    let mut pasted = String::new();
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(output)?;
+            return Ok(None);
+        }
+        if line.trim() == "." {
+            return Ok(Some(pasted));
+        }
+        pasted.push_str(&line);
+    }
+}
+
+/// The entries of a REPL session, also appended to a file when there is
+/// one so they carry over to the next session.
+#[derive(Default)]
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// `~/.jbread_history`, if the home directory is known.
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".jbread_history"))
+    }
+
+    /// Loads the entries saved in `path`, which needn't exist yet.
+    pub fn persistent(path: PathBuf) -> Self {
+        let entries = fs::read_to_string(&path)
+            .map(|contents| contents.lines().map(unescape).collect())
+            .unwrap_or_default();
+        Self {
+            entries,
+            path: Some(path),
+        }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Records `entry`, saving it if the history is persistent. It's
+    /// recorded even when saving fails, which should only be warned about,
+    /// losing history shouldn't end the session.
+    pub fn push(&mut self, entry: &str) -> io::Result<()> {
+        let entry = entry.trim_end().to_string();
+        let mut saved = Ok(());
+        if let Some(path) = &self.path {
+            saved = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", escape(&entry)))
+                .map_err(|error| {
+                    io::Error::new(
+                        error.kind(),
+                        format!("Couldn't save history to {}: {}", path.display(), error),
+                    )
+                });
+        }
+        self.entries.push(entry);
+        saved
+    }
+}
+
+/// Entries span lines, the file keeps one per line.
+fn escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut result = String::new();
+    let mut chars = line.chars();
+    while let Some(chr) = chars.next() {
+        if chr != '\\' {
+            result.push(chr);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_incomplete() {
+        for source in [
+            "{ var a = 1;",
+            "fun f(a) {",
+            "print (1 +",
+            "var xs = [1, 2,",
+            "class A { f() { print 1; }",
+            "repeat (3)",
+            "print \"first line",
+            "{ print \"a\nb",
+        ] {
+            assert!(is_incomplete(source), "{}", source);
+        }
+        for source in [
+            "var a = ;",
+            "{ var a = ; ",
+            "print 1;",
+            "{ var a = 1; }",
+            "",
+            "}",
+            "var 1a = \"open",
+        ] {
+            assert!(!is_incomplete(source), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let cases = [
+            (":quit", Command::Quit),
+            (" :q \n", Command::Quit),
+            (":env", Command::Env),
+            (":reset", Command::Reset),
+            (":paste", Command::Paste),
+            (
+                ":load  lib/math.jbr ",
+                Command::Load("lib/math.jbr".to_string()),
+            ),
+            (":ast 1 + 2 * 3", Command::Ast("1 + 2 * 3".to_string())),
+            (":save s.json", Command::Save("s.json".to_string())),
+            (":restore s.json", Command::Restore("s.json".to_string())),
+            (":load", Command::Invalid(":load needs a path".to_string())),
+            (
+                ":restore",
+                Command::Invalid(":restore needs a path".to_string()),
+            ),
+            (
+                ":ast",
+                Command::Invalid(":ast needs an expression".to_string()),
+            ),
+            (
+                ":env x",
+                Command::Invalid(":env takes no argument".to_string()),
+            ),
+            (
+                ":exit",
+                Command::Invalid("Unknown command :exit".to_string()),
+            ),
+            (":", Command::Invalid("Unknown command :".to_string())),
+        ];
+        for (entry, command) in cases {
+            assert_eq!(parse_command(entry), Some(command), "{}", entry);
+        }
+        assert_eq!(parse_command("print 1;"), None);
+    }
+
+    #[test]
+    fn test_format_scopes() {
+        let scopes = vec![
+            vec![("a".to_string(), 2.0)],
+            vec![],
+            vec![("a".to_string(), 1.5), ("b".to_string(), f64::NAN)],
+        ];
+        assert_eq!(
+            format_scopes(&scopes),
+            "a = 2\nenclosing:\n  (empty)\n  enclosing:\n    a = 1.5\n    b = NaN\n"
+        );
+    }
+
+    fn read_all(input: &str) -> (Vec<String>, String) {
+        let mut input = io::Cursor::new(input.as_bytes());
+        let mut output = Vec::new();
+        let mut entries = Vec::new();
+        while let Some(entry) = read_entry(&mut input, &mut output).unwrap() {
+            entries.push(entry);
+        }
+        (entries, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_read_entry_continues_blocks() {
+        let (entries, output) = read_all("print 1;\n{\n  print 2;\n}\n\nvar a = ;\n:ast (1 +\n");
+        assert_eq!(
+            entries,
+            [
+                "print 1;\n",
+                "{\n  print 2;\n}\n",
+                "var a = ;\n",
+                ":ast (1 +\n"
+            ]
+        );
+        assert_eq!(
+            output,
+            "jbread> jbread> ... ... jbread> jbread> jbread> jbread> \n"
+        );
+    }
+
+    #[test]
+    fn test_read_entry_continues_strings() {
+        let (entries, output) = read_all("print \"one\ntwo\";\nprint 3;\n");
+        assert_eq!(entries, ["print \"one\ntwo\";\n", "print 3;\n"]);
+        assert_eq!(output, "jbread> ... jbread> jbread> \n");
+    }
+
+    #[test]
+    fn test_read_entry_paste() {
+        let (entries, output) = read_all(
+            ":paste\nvar a = 1;\n{\n  print a;\n.\n:paste\n  .\nprint 2;\n:paste\nprint 3;\n",
+        );
+        assert_eq!(entries, ["var a = 1;\n{\n  print a;\n", "print 2;\n"]);
+        let hint = "(pasting, a line holding only '.' runs it)\n";
+        assert_eq!(
+            output,
+            format!(
+                "jbread> {0}jbread> {0}jbread> jbread> {0}\njbread> \n",
+                hint
+            )
+        );
+    }
+
+    #[test]
+    fn test_read_entry_drops_unfinished_at_end() {
+        let (entries, output) = read_all("print 1;\n{ print 2;\n");
+        assert_eq!(entries, ["print 1;\n"]);
+        assert_eq!(output, "jbread> jbread> ... \njbread> \n");
+    }
+
+    #[test]
+    fn test_history_persists() {
+        let path = std::env::temp_dir().join(format!("jbread_history_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut history = History::persistent(path.clone());
+        history.push("print 1;\n").unwrap();
+        history.push("{\n  print \"\\\\n\";\n}\n").unwrap();
+        let entries = history.entries().to_vec();
+        assert_eq!(entries, ["print 1;", "{\n  print \"\\\\n\";\n}"]);
+
+        assert_eq!(History::persistent(path.clone()).entries(), entries);
+        assert!(History::default().entries().is_empty());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,601 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{
+        Assert, Assign, Binary, Block, Call, Class, Const, Expression, Function, Get, Grouping, If,
+        Import, Index, IndexAssign, Interpolation, Lambda, ListLiteral, Literal, MapLiteral,
+        MultiAssign, MultiVar, Print, Repeat, Return, Set, Stmt, Switch, This, Unary, Var,
+        Variable, VisitorExpr, VisitorStmt,
+    },
+    errors::{Error, ErrorKind, JBreadErrors, JBreadResult},
+    AstNode, AstStmt, LintDiagnostic, Position, Symbol, Token, TokenTypes,
+};
+
+/// Code attached to the warning for locals that are never read.
+pub const UNUSED_VARIABLE: &str = "unused-variable";
+
+/// Identity of an AST node, taken from its address. Every expression is
+/// boxed, so the id stays valid for as long as the program isn't dropped or
+/// rebuilt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(usize);
+
+impl ExprId {
+    pub fn of<T>(node: &T) -> Self {
+        Self(node as *const T as usize)
+    }
+}
+
+/// The outcome of resolving a program.
+#[derive(Debug, Default)]
+pub struct Resolution {
+    /// Number of scopes between each local variable reference and its
+    /// declaration. Globals have no entry.
+    pub locals: HashMap<ExprId, usize>,
+    pub warnings: Vec<LintDiagnostic>,
+}
+
+struct Local {
+    name: Token,
+    defined: bool,
+    used: bool,
+}
+
+/// Statically resolves local variables before interpretation.
+///
+/// Only block scopes are tracked, the global scope stays dynamic so the REPL
+/// can redeclare and reference globals across inputs.
+#[derive(Default)]
+pub struct Resolver {
+    scopes: Vec<HashMap<Symbol, Local>>,
+    /// Names declared later on in each open scope.
+    pending: Vec<HashSet<Symbol>>,
+    /// Number of function bodies enclosing the current statement.
+    function_depth: usize,
+    /// Number of class bodies enclosing the current statement.
+    class_depth: usize,
+    resolution: Resolution,
+}
+
+impl Resolver {
+    pub fn resolve(mut self, stmts: &[Stmt]) -> JBreadResult<Resolution> {
+        for stmt in stmts {
+            stmt.accept(&mut self)?;
+        }
+        Ok(self.resolution)
+    }
+
+    fn error(&self, token: &Token, kind: ErrorKind, message: &str) -> JBreadErrors {
+        JBreadErrors::ResolveError(Error::at(
+            kind,
+            token,
+            token.lexeme.to_string(),
+            message.to_string(),
+        ))
+    }
+
+    fn begin_scope(&mut self, statements: &[Stmt]) {
+        let pending = statements
+            .iter()
+            .flat_map(|stmt| match stmt {
+                Stmt::Var(var) => vec![var.name.lexeme.clone()],
+                Stmt::Const(constant) => vec![constant.name.lexeme.clone()],
+                Stmt::MultiVar(var) => var.names.iter().map(|name| name.lexeme.clone()).collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+        self.scopes.push(HashMap::new());
+        self.pending.push(pending);
+    }
+
+    fn end_scope(&mut self) {
+        self.pending.pop();
+        let mut unused: Vec<Local> = self
+            .scopes
+            .pop()
+            .unwrap_or_default()
+            .into_values()
+            .filter(|local| !local.used)
+            .collect();
+        unused.sort_by_key(|local| local.name.span.start);
+        for local in unused {
+            self.resolution.warnings.push(LintDiagnostic::new(
+                UNUSED_VARIABLE,
+                &format!("Local variable '{}' is never used", local.name.lexeme),
+                Position::new(local.name.line(), local.name.column()),
+            ));
+        }
+    }
+
+    fn declare(&mut self, name: &Token) -> JBreadResult<()> {
+        let Some(scope) = self.scopes.last_mut() else {
+            return Ok(());
+        };
+        if scope.contains_key(&name.lexeme) {
+            return Err(self.error(
+                name,
+                ErrorKind::VariableRedeclared,
+                "Variable already declared in this scope",
+            ));
+        }
+        scope.insert(
+            name.lexeme.clone(),
+            Local {
+                name: name.clone(),
+                defined: false,
+                used: false,
+            },
+        );
+        Ok(())
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            if let Some(local) = scope.get_mut(&name.lexeme) {
+                local.defined = true;
+            }
+            self.pending.last_mut().unwrap().remove(&name.lexeme);
+        }
+    }
+
+    fn resolve_function(&mut self, params: &[Token], body: &[Stmt]) -> JBreadResult<()> {
+        self.function_depth += 1;
+        self.begin_scope(body);
+        let result = params.iter().try_for_each(|param| {
+            self.declare(param)?;
+            self.define(param);
+            // Unused parameters are part of the signature, don't warn.
+            if let Some(local) = self.scopes.last_mut().unwrap().get_mut(&param.lexeme) {
+                local.used = true;
+            }
+            Ok(())
+        });
+        let result = result.and_then(|_| body.iter().try_for_each(|stmt| stmt.accept(self)));
+        self.end_scope();
+        self.function_depth -= 1;
+        result
+    }
+
+    fn resolve_local(&mut self, id: ExprId, name: &Token, read: bool) -> JBreadResult<()> {
+        for (depth, scope) in self.scopes.iter_mut().rev().enumerate() {
+            if let Some(local) = scope.get_mut(&name.lexeme) {
+                if read && !local.defined {
+                    return Err(self.error(
+                        name,
+                        ErrorKind::UseBeforeDeclaration,
+                        "Cannot read a local variable in its own initializer",
+                    ));
+                }
+                local.used |= read;
+                self.resolution.locals.insert(id, depth);
+                return Ok(());
+            }
+            if self.pending[self.pending.len() - 1 - depth].contains(&name.lexeme) {
+                return Err(self.error(
+                    name,
+                    ErrorKind::UseBeforeDeclaration,
+                    "Variable used before its declaration in the same scope",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl VisitorExpr for Resolver {
+    type Result = JBreadResult<()>;
+
+    fn visit_expr_binary(&mut self, expr: &Binary) -> Self::Result {
+        expr.left.accept(self)?;
+        expr.right.accept(self)
+    }
+
+    fn visit_expr_grouping(&mut self, expr: &Grouping) -> Self::Result {
+        expr.expression.accept(self)
+    }
+
+    fn visit_expr_literal(&mut self, _expr: &Literal) -> Self::Result {
+        Ok(())
+    }
+
+    fn visit_expr_unary(&mut self, expr: &Unary) -> Self::Result {
+        expr.right.accept(self)
+    }
+
+    fn visit_expr_variable(&mut self, expr: &Variable) -> Self::Result {
+        self.resolve_local(ExprId::of(expr), &expr.name, true)
+    }
+
+    fn visit_expr_assign(&mut self, expr: &Assign) -> Self::Result {
+        expr.value.accept(self)?;
+        self.resolve_local(ExprId::of(expr), &expr.name, false)
+    }
+
+    fn visit_expr_call(&mut self, expr: &Call) -> Self::Result {
+        expr.callee.accept(self)?;
+        expr.arguments
+            .iter()
+            .try_for_each(|argument| argument.accept(self))
+    }
+
+    fn visit_expr_get(&mut self, expr: &Get) -> Self::Result {
+        expr.object.accept(self)
+    }
+
+    fn visit_expr_set(&mut self, expr: &Set) -> Self::Result {
+        expr.value.accept(self)?;
+        expr.object.accept(self)
+    }
+
+    fn visit_expr_list_literal(&mut self, expr: &ListLiteral) -> Self::Result {
+        expr.elements
+            .iter()
+            .try_for_each(|element| element.accept(self))
+    }
+
+    fn visit_expr_map_literal(&mut self, expr: &MapLiteral) -> Self::Result {
+        expr.entries.iter().try_for_each(|entry| {
+            entry.key.accept(self)?;
+            entry.value.accept(self)
+        })
+    }
+
+    fn visit_expr_index(&mut self, expr: &Index) -> Self::Result {
+        expr.object.accept(self)?;
+        expr.index.accept(self)
+    }
+
+    fn visit_expr_index_assign(&mut self, expr: &IndexAssign) -> Self::Result {
+        expr.object.accept(self)?;
+        expr.index.accept(self)?;
+        expr.value.accept(self)
+    }
+
+    fn visit_expr_interpolation(&mut self, expr: &Interpolation) -> Self::Result {
+        expr.parts.iter().try_for_each(|part| part.accept(self))
+    }
+
+    fn visit_expr_lambda(&mut self, expr: &Lambda) -> Self::Result {
+        self.resolve_function(&expr.params, &expr.body.0)
+    }
+
+    fn visit_expr_this(&mut self, expr: &This) -> Self::Result {
+        if self.class_depth == 0 {
+            return Err(self.error(
+                &expr.keyword,
+                ErrorKind::ThisOutsideClass,
+                "Cannot use 'this' outside of a class",
+            ));
+        }
+        self.resolve_local(ExprId::of(expr), &expr.keyword, true)
+    }
+}
+
+impl VisitorStmt for Resolver {
+    type Result = JBreadResult<()>;
+
+    fn visit_stmt_expression(&mut self, expr: &Expression) -> Self::Result {
+        expr.expression.accept(self)
+    }
+
+    fn visit_stmt_print(&mut self, expr: &Print) -> Self::Result {
+        for expression in expr.expressions.iter() {
+            expression.accept(self)?;
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_assert(&mut self, expr: &Assert) -> Self::Result {
+        expr.condition.accept(self)?;
+        match &expr.message {
+            Some(message) => message.accept(self),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_stmt_var(&mut self, expr: &Var) -> Self::Result {
+        self.declare(&expr.name)?;
+        if let Some(initializer) = &expr.initializer {
+            initializer.accept(self)?;
+        }
+        self.define(&expr.name);
+        Ok(())
+    }
+
+    fn visit_stmt_const(&mut self, expr: &Const) -> Self::Result {
+        self.declare(&expr.name)?;
+        expr.initializer.accept(self)?;
+        self.define(&expr.name);
+        Ok(())
+    }
+
+    fn visit_stmt_import(&mut self, expr: &Import) -> Self::Result {
+        if !self.scopes.is_empty() {
+            return Err(self.error(
+                &expr.keyword,
+                ErrorKind::ImportOutsideTopLevel,
+                "Can only import from top-level code",
+            ));
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_multi_var(&mut self, expr: &MultiVar) -> Self::Result {
+        for name in expr.names.iter() {
+            self.declare(name)?;
+        }
+        for initializer in expr.initializers.iter() {
+            initializer.accept(self)?;
+        }
+        for name in expr.names.iter() {
+            self.define(name);
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_multi_assign(&mut self, expr: &MultiAssign) -> Self::Result {
+        for value in expr.values.iter() {
+            value.accept(self)?;
+        }
+        for target in expr.targets.iter() {
+            self.resolve_local(ExprId::of(target), &target.name, false)?;
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_block(&mut self, expr: &Block) -> Self::Result {
+        self.begin_scope(&expr.statements);
+        let result = expr
+            .statements
+            .iter()
+            .try_for_each(|stmt| stmt.accept(self));
+        self.end_scope();
+        result
+    }
+
+    /// Each arm's body is a block of its own.
+    fn visit_stmt_switch(&mut self, expr: &Switch) -> Self::Result {
+        expr.subject.accept(self)?;
+        for arm in expr.arms.iter() {
+            if let Some(value) = &arm.value {
+                value.accept(self)?;
+            }
+            self.begin_scope(&arm.body);
+            let result = arm.body.iter().try_for_each(|stmt| stmt.accept(self));
+            self.end_scope();
+            result?;
+        }
+        Ok(())
+    }
+
+    fn visit_stmt_if(&mut self, expr: &If) -> Self::Result {
+        expr.condition.accept(self)?;
+        expr.then_branch.accept(self)?;
+        match &expr.else_branch {
+            Some(else_branch) => else_branch.accept(self),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_stmt_repeat(&mut self, expr: &Repeat) -> Self::Result {
+        expr.count.accept(self)?;
+        expr.body.accept(self)
+    }
+
+    fn visit_stmt_function(&mut self, expr: &Function) -> Self::Result {
+        // Defined before resolving the body so the function can recurse.
+        self.declare(&expr.name)?;
+        self.define(&expr.name);
+        self.resolve_function(&expr.params, &expr.body)
+    }
+
+    fn visit_stmt_class(&mut self, expr: &Class) -> Self::Result {
+        self.declare(&expr.name)?;
+        self.define(&expr.name);
+
+        // Methods close over a scope holding only `this`, matching the
+        // environment they're bound to at runtime.
+        self.class_depth += 1;
+        self.begin_scope(&[]);
+        let this = Token::new(TokenTypes::This, "this".to_string(), None, expr.name.line());
+        self.scopes.last_mut().unwrap().insert(
+            this.lexeme.clone(),
+            Local {
+                name: this,
+                defined: true,
+                used: true,
+            },
+        );
+        let result = expr
+            .methods
+            .iter()
+            .try_for_each(|method| self.resolve_function(&method.params, &method.body));
+        self.end_scope();
+        self.class_depth -= 1;
+        result
+    }
+
+    fn visit_stmt_return(&mut self, expr: &Return) -> Self::Result {
+        if self.function_depth == 0 {
+            return Err(self.error(
+                &expr.keyword,
+                ErrorKind::ReturnOutsideFunction,
+                "Cannot return from top-level code",
+            ));
+        }
+        match &expr.value {
+            Some(value) => value.accept(self),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Expr, parser::Parser, Scanner};
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let mut scanner = Scanner::new(source);
+        let mut parser = Parser::new(scanner.scan_tokens());
+        parser.parse().unwrap()
+    }
+
+    /// Looks up the resolved depth of the expression a statement evaluates.
+    fn expression_depth(resolution: &Resolution, stmt: &Stmt) -> Option<usize> {
+        let expr = match stmt {
+            Stmt::Print(print) => &print.expressions[0],
+            Stmt::Expression(expression) => expression.expression.as_ref(),
+            _ => return None,
+        };
+        let id = match expr {
+            Expr::Variable(variable) => ExprId::of(variable),
+            Expr::Assign(assign) => ExprId::of(assign),
+            _ => return None,
+        };
+        resolution.locals.get(&id).copied()
+    }
+
+    fn block(stmt: &Stmt) -> &[Stmt] {
+        match stmt {
+            Stmt::Block(block) => &block.statements,
+            _ => panic!("Expected a block"),
+        }
+    }
+
+    #[test]
+    fn test_shadowing_across_scopes() {
+        let stmts = parse("var a = 1; { var a = 2; { var a = 3; print a; } print a; } print a;");
+        let resolution = Resolver::default().resolve(&stmts).unwrap();
+
+        let outer = block(&stmts[1]);
+        let inner = block(&outer[1]);
+        assert_eq!(expression_depth(&resolution, &inner[1]), Some(0));
+        assert_eq!(expression_depth(&resolution, &outer[2]), Some(0));
+        assert_eq!(expression_depth(&resolution, &stmts[2]), None);
+        assert!(resolution.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_enclosing_depth() {
+        let stmts = parse("{ var a = 1; { { a = 2; } } print a; }");
+        let resolution = Resolver::default().resolve(&stmts).unwrap();
+
+        let outer = block(&stmts[0]);
+        let innermost = block(&block(&outer[1])[0]);
+        assert_eq!(expression_depth(&resolution, &innermost[0]), Some(2));
+        assert_eq!(expression_depth(&resolution, &outer[2]), Some(0));
+    }
+
+    #[test]
+    fn test_redeclaration_in_block() {
+        let stmts = parse("{ var a; var a; }");
+        let error = Resolver::default().resolve(&stmts).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::VariableRedeclared);
+
+        // Globals can be redeclared, e.g. across REPL inputs.
+        assert!(Resolver::default().resolve(&parse("var a; var a;")).is_ok());
+    }
+
+    #[test]
+    fn test_use_before_declaration() {
+        let stmts = parse("var a = 1; { print a; var a = 2; print a; }");
+        let error = Resolver::default().resolve(&stmts).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UseBeforeDeclaration);
+
+        let stmts = parse("{ var a = a; }");
+        let error = Resolver::default().resolve(&stmts).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UseBeforeDeclaration);
+
+        let stmts = parse("{ var a, b = 1, a; }");
+        let error = Resolver::default().resolve(&stmts).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UseBeforeDeclaration);
+    }
+
+    #[test]
+    fn test_unused_local_warning() {
+        let stmts = parse("var g = 1; { var a = 1; var b = 2; b = 3; print g; }");
+        let resolution = Resolver::default().resolve(&stmts).unwrap();
+        let names: Vec<&str> = resolution
+            .warnings
+            .iter()
+            .map(|warning| warning.message.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "Local variable 'a' is never used",
+                "Local variable 'b' is never used"
+            ]
+        );
+        assert_eq!(resolution.warnings[0].code, UNUSED_VARIABLE);
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_unused_warning_does_not_abort_run() {
+        let bread = crate::JuniorBread::new();
+        let mut interpreter = crate::interpreter::Interpreter::default();
+        bread
+            .run("var b = 1; { var a = 1; b = 2; }", &mut interpreter)
+            .unwrap();
+
+        let name = Token::new(crate::TokenTypes::Identifier, "b".to_string(), None, 1);
+        assert_eq!(
+            interpreter.environment.borrow().get(&name).unwrap(),
+            crate::Value::Number(2.0)
+        );
+    }
+
+    #[test]
+    fn test_function_scopes() {
+        let stmts = parse("{ var a = 1; fun f(b) { return a + b; } print f(2); }");
+        let resolution = Resolver::default().resolve(&stmts).unwrap();
+        assert_eq!(resolution.locals.len(), 3);
+        assert!(resolution.warnings.is_empty());
+
+        let body = match &block(&stmts[0])[1] {
+            Stmt::Function(function) => function.body.clone(),
+            _ => panic!("Expected a function"),
+        };
+        let Stmt::Return(Return {
+            value: Some(value), ..
+        }) = &body[0]
+        else {
+            panic!("Expected a return");
+        };
+        let Expr::Binary(binary) = value.as_ref() else {
+            panic!("Expected a binary expression");
+        };
+        let depth = |expr: &Expr| match expr {
+            Expr::Variable(variable) => resolution.locals.get(&ExprId::of(variable)).copied(),
+            _ => None,
+        };
+        assert_eq!(depth(&binary.left), Some(1));
+        assert_eq!(depth(&binary.right), Some(0));
+    }
+
+    #[test]
+    fn test_top_level_return() {
+        let error = Resolver::default()
+            .resolve(&parse("return 1;"))
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ReturnOutsideFunction);
+        assert!(Resolver::default()
+            .resolve(&parse("fun f() { { return; } }"))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_this_outside_class() {
+        let error = Resolver::default()
+            .resolve(&parse("fun f() { return this; }"))
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ThisOutsideClass);
+        assert!(Resolver::default()
+            .resolve(&parse("{ class A { f() { return this; } } A(); }"))
+            .unwrap()
+            .warnings
+            .is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+//! Random input for the scanner and parser, which have to return a result
+//! for anything, never panic. Seeded, so a failure reproduces; the fuzz
+//! targets under `fuzz/` search the same space without the fixed seeds.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{parser::Parser, LiteralValue, Scanner, Token, TokenTypes};
+
+/// Inputs that panicked once, kept so they keep not panicking.
+const REGRESSIONS: &[&str] = &[
+    "",
+    "\"",
+    "\"${",
+    "\"${\"",
+    "\"${}\"",
+    "\"${\"${\"}\"",
+    "é\"→",
+    "1.",
+    "1e",
+    "1e+",
+    "a.",
+    "a..b",
+    "print \"${)\";",
+    "class {",
+    "fun (",
+    "repeat (",
+    "var a, = 1;",
+    "a, b =",
+    "\u{FEFF}",
+    "\0",
+    "💥${",
+    "\"\\",
+];
+
+/// Fragments random sources are put together from, each a token or part of
+/// one.
+const FRAGMENTS: &[&str] = &[
+    "(",
+    ")",
+    "{",
+    "}",
+    "[",
+    "]",
+    ",",
+    ".",
+    "-",
+    "+",
+    ";",
+    "/",
+    "*",
+    "**",
+    "&",
+    "|",
+    "^",
+    "~",
+    "!",
+    "!=",
+    "=",
+    "==",
+    ">",
+    ">=",
+    "<",
+    "<=",
+    "<<",
+    ">>",
+    "a",
+    "b",
+    "this",
+    "1",
+    "2.5",
+    "1e3",
+    "NaN",
+    "inf",
+    "nil",
+    "true",
+    "false",
+    "\"s\"",
+    "\"",
+    "${",
+    "\"a${b}c\"",
+    "var",
+    "const",
+    "fun",
+    "class",
+    "print",
+    "return",
+    "repeat",
+    "assert",
+    "//",
+    "\n",
+    "é",
+    "→",
+    "\0",
+];
+
+/// xorshift64*, enough to pick fragments with.
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 33) as usize % bound
+    }
+}
+
+/// Runs `f` on `input`, failing with the input when it panics.
+fn survives(input: &str, f: impl FnOnce()) {
+    if panic::catch_unwind(AssertUnwindSafe(f)).is_err() {
+        panic!("panicked on {:?}", input);
+    }
+}
+
+fn scan_and_parse(source: &str) {
+    survives(source, || {
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_tokens();
+        let _ = Parser::new(&tokens).parse();
+        let _ = Parser::new(&tokens).parse_recovering();
+    });
+}
+
+#[test]
+fn test_regressions() {
+    for source in REGRESSIONS {
+        scan_and_parse(source);
+    }
+}
+
+#[test]
+fn test_random_bytes() {
+    let mut rng = Rng(0x6a62_7265_6164);
+    for _ in 0..2000 {
+        let bytes: Vec<u8> = (0..rng.below(40)).map(|_| rng.below(256) as u8).collect();
+        scan_and_parse(&String::from_utf8_lossy(&bytes));
+    }
+}
+
+#[test]
+fn test_random_fragments() {
+    let mut rng = Rng(0x5eed);
+    for _ in 0..3000 {
+        let source: Vec<&str> = (0..rng.below(30))
+            .map(|_| FRAGMENTS[rng.below(FRAGMENTS.len())])
+            .collect();
+        // With and without spaces, which changes how fragments join up.
+        scan_and_parse(&source.join(" "));
+        scan_and_parse(&source.concat());
+    }
+}
+
+#[test]
+fn test_tokens_without_eof() {
+    let empty: &[Token] = &[];
+    assert!(Parser::new(empty).parse().unwrap().is_empty());
+    let tokens = [Token::new(TokenTypes::Identifier, "a".to_string(), None, 3)];
+    let error = Parser::new(&tokens).parse().unwrap_err();
+    assert!(error.to_string().contains("line: 3"), "{}", error);
+}
+
+/// Tokens made by hand rather than scanned, which needn't end with an
+/// `Eof` or have lexemes matching their type.
+#[test]
+fn test_random_tokens() {
+    const TYPES: &[TokenTypes] = &[
+        TokenTypes::LeftParen,
+        TokenTypes::RightParen,
+        TokenTypes::LeftBrace,
+        TokenTypes::RightBrace,
+        TokenTypes::LeftBracket,
+        TokenTypes::RightBracket,
+        TokenTypes::Comma,
+        TokenTypes::Dot,
+        TokenTypes::Minus,
+        TokenTypes::Semicolon,
+        TokenTypes::Equal,
+        TokenTypes::EqualEqual,
+        TokenTypes::StarStar,
+        TokenTypes::Arrow,
+        TokenTypes::Identifier,
+        TokenTypes::String,
+        TokenTypes::TemplateString,
+        TokenTypes::Number,
+        TokenTypes::Class,
+        TokenTypes::Fun,
+        TokenTypes::Var,
+        TokenTypes::Return,
+        TokenTypes::Repeat,
+        TokenTypes::This,
+        TokenTypes::Eof,
+    ];
+    const LEXEMES: &[&str] = &["", "a", "\"${\"", "\"${a}\"", "\"${", "1"];
+    let mut rng = Rng(0x70c3);
+    for _ in 0..3000 {
+        let tokens: Vec<Token> = (0..rng.below(20))
+            .map(|line| {
+                let token_type = TYPES[rng.below(TYPES.len())].clone();
+                let literal = match token_type {
+                    TokenTypes::Number => Some(LiteralValue::Number(1.0)),
+                    TokenTypes::String => Some(LiteralValue::String("s".to_string())),
+                    _ => None,
+                };
+                let lexeme = LEXEMES[rng.below(LEXEMES.len())];
+                Token::new(token_type, lexeme.to_string(), literal, line as u32)
+            })
+            .collect();
+        let input: Vec<_> = tokens
+            .iter()
+            .map(|token| format!("{:?} {:?}", token.token_type, token.lexeme))
+            .collect();
+        let input = input.join(", ");
+        survives(&input, || {
+            let _ = Parser::new(&tokens).parse();
+        });
+    }
+}
@@ -1,92 +1,672 @@
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::{collections::HashMap, fs::File, io::Read};
 
 use crate::{
-    errors::{Error, JBreadErrors},
-    token::{Literal as LiteralEnum, Token},
-    JuniorBread, TokenTypes,
+    errors::{Error, ErrorKind, JBreadErrors, JBreadResult},
+    lint::{AllowDirective, Position, ALLOW_DIRECTIVE},
+    parser::MAX_NESTING_DEPTH,
+    token::{LiteralValue, Span, Token},
+    LanguageProfile, SourceRef, TokenTypes,
 };
+use std::sync::Arc;
+
+/// Bytes at the start of a source checked for a NUL, which text never has
+/// and executables and images nearly always do. The same heuristic as git.
+const BINARY_CHECK_LENGTH: usize = 8000;
+
+/// Limits for scanning source that isn't trusted.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScannerOptions {
+    /// Sources with more lines fail to scan with a single error.
+    pub max_lines: Option<usize>,
+}
+
+/// Reads the source named `name` from `reader`, failing cleanly on binary
+/// content and on invalid UTF-8 rather than scanning it. A leading byte
+/// order mark is dropped.
+pub fn read_source(name: &str, mut reader: impl Read) -> JBreadResult<String> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|error| JBreadErrors::io(name, &error))?;
+    let error = |kind, message: String| {
+        JBreadErrors::Io(Error::new(
+            kind,
+            0,
+            0,
+            name.to_string(),
+            format!("{}: {}", name, message),
+        ))
+    };
+    if bytes[..bytes.len().min(BINARY_CHECK_LENGTH)].contains(&0) {
+        return Err(error(
+            ErrorKind::BinaryContent,
+            "File does not appear to be a jBREAD source file (binary content detected)".to_string(),
+        ));
+    }
+    let source = String::from_utf8(bytes).map_err(|invalid| {
+        error(
+            ErrorKind::InvalidUtf8,
+            format!(
+                "Invalid UTF-8 at byte {}",
+                invalid.utf8_error().valid_up_to()
+            ),
+        )
+    })?;
+    match source.strip_prefix('\u{FEFF}') {
+        Some(rest) => Ok(rest.to_string()),
+        None => Ok(source),
+    }
+}
+
+/// [`read_source`] on the file at `path`.
+pub fn read_source_file(path: &str) -> JBreadResult<String> {
+    let file = File::open(path).map_err(|error| JBreadErrors::io(path, &error))?;
+    read_source(path, file)
+}
+
+/// What an ASCII character can begin, so the scanner decides with one
+/// lookup. Identifiers continue with [`IdentifierStart`](Self::IdentifierStart)
+/// and [`Digit`](Self::Digit) characters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CharClass {
+    IdentifierStart,
+    Digit,
+    Whitespace,
+    OperatorStart,
+    Quote,
+    Other,
+}
+
+/// The class of each byte. Only ASCII is looked up, the rest is `Other` and
+/// characters outside ASCII go through the char-based checks instead, which
+/// is where what Unicode allows is decided.
+const CHAR_CLASSES: [CharClass; 256] = {
+    let mut classes = [CharClass::Other; 256];
+    let mut byte = 0;
+    while byte < 128 {
+        classes[byte] = match byte as u8 {
+            b'a'..=b'z' | b'A'..=b'Z' | b'_' => CharClass::IdentifierStart,
+            b'0'..=b'9' => CharClass::Digit,
+            b' ' | b'\t' | b'\r' | b'\n' => CharClass::Whitespace,
+            b'"' => CharClass::Quote,
+            b'(' | b')' | b'{' | b'}' | b'[' | b']' | b',' | b'.' | b'-' | b'+' | b';' | b':'
+            | b'&' | b'|' | b'^' | b'~' | b'*' | b'!' | b'=' | b'<' | b'>' | b'/' => {
+                CharClass::OperatorStart
+            }
+            _ => CharClass::Other,
+        };
+        byte += 1;
+    }
+    classes
+};
+
+fn char_class(chr: char) -> CharClass {
+    if chr.is_ascii() {
+        CHAR_CLASSES[chr as usize]
+    } else {
+        CharClass::Other
+    }
+}
+
+/// Identifiers start with `_` or a Unicode `XID_Start` character, e.g. a
+/// letter of any script.
+fn is_identifier_start(chr: char) -> bool {
+    match char_class(chr) {
+        CharClass::IdentifierStart => true,
+        _ if chr.is_ascii() => false,
+        _ => unicode_ident::is_xid_start(chr),
+    }
+}
+
+/// Identifiers continue with `XID_Continue` characters, which add digits
+/// and combining marks to the starting ones.
+fn is_identifier_continue(chr: char) -> bool {
+    match char_class(chr) {
+        CharClass::IdentifierStart | CharClass::Digit => true,
+        _ if chr.is_ascii() => false,
+        _ => unicode_ident::is_xid_continue(chr),
+    }
+}
+
+/// Why a string literal doesn't end.
+enum Unterminated {
+    String,
+    /// The `${` at this offset isn't closed.
+    Interpolation(usize),
+    /// The string starting at this offset is nested too deeply in others'
+    /// interpolations.
+    TooDeep(usize),
+}
+
+/// The offset just past the quote closing the string whose contents start
+/// at `from`, and whether the string interpolates. The delimiters are all
+/// ASCII, which never turns up within a multi-byte character, so the
+/// source is searched byte by byte.
+fn string_end(bytes: &[u8], from: usize, nesting: usize) -> Result<(usize, bool), Unterminated> {
+    if nesting > MAX_NESTING_DEPTH {
+        return Err(Unterminated::TooDeep(from - 1));
+    }
+    let mut current = from;
+    let mut template = false;
+    while let Some(&byte) = bytes.get(current) {
+        match byte {
+            b'"' => return Ok((current + 1, template)),
+            b'$' if bytes.get(current + 1) == Some(&b'{') => {
+                template = true;
+                current = match interpolation_end(bytes, current + 2, nesting) {
+                    Ok(end) => end,
+                    Err(Unterminated::TooDeep(offset)) => {
+                        return Err(Unterminated::TooDeep(offset))
+                    }
+                    Err(_) => return Err(Unterminated::Interpolation(current)),
+                };
+            }
+            _ => current += 1,
+        }
+    }
+    Err(Unterminated::String)
+}
+
+/// The offset just past the `}` closing the interpolation whose code starts
+/// at `from`. Braces in strings within the code don't count.
+fn interpolation_end(bytes: &[u8], from: usize, nesting: usize) -> Result<usize, Unterminated> {
+    let mut depth = 0;
+    let mut current = from;
+    while let Some(&byte) = bytes.get(current) {
+        current += 1;
+        match byte {
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return Ok(current),
+            b'}' => depth -= 1,
+            b'"' => current = string_end(bytes, current, nesting + 1)?.0,
+            _ => {}
+        }
+    }
+    Err(Unterminated::String)
+}
+
+/// A piece of a [`TokenTypes::TemplateString`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplatePart {
+    /// Text and where it is in the source.
+    Text(String, Span),
+    /// The code between `${` and `}`, where it is in the source.
+    Code(Span),
+}
+
+/// Splits the lexeme of a template string scanned at offset `start` into
+/// its text and the code of its interpolations, in order. Empty text is
+/// left out.
+pub fn template_parts(lexeme: &str, start: usize) -> Vec<TemplatePart> {
+    let bytes = lexeme.as_bytes();
+    let last = bytes.len().saturating_sub(1);
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut text_start = 1;
+    let mut current = 1;
+    while current < last {
+        if bytes[current] == b'$' && bytes.get(current + 1) == Some(&b'{') {
+            if !text.is_empty() {
+                let span = Span::new(start + text_start, start + current);
+                parts.push(TemplatePart::Text(std::mem::take(&mut text), span));
+            }
+            // Scanned already, so only a lexeme made by hand can run out.
+            let end = interpolation_end(bytes, current + 2, 0).unwrap_or(last + 1);
+            parts.push(TemplatePart::Code(Span::new(
+                start + current + 2,
+                start + end - 1,
+            )));
+            current = end;
+        } else {
+            if text.is_empty() {
+                text_start = current;
+            }
+            let chr = lexeme[current..].chars().next().unwrap_or_default();
+            text.push(chr);
+            current += chr.len_utf8();
+        }
+    }
+    if !text.is_empty() {
+        let span = Span::new(start + text_start, start + current);
+        parts.push(TemplatePart::Text(text, span));
+    }
+    parts
+}
+
+/// Throughput counters of a scan, see [`Scanner::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScannerStats {
+    /// UTF-8 bytes of the characters consumed.
+    pub bytes_scanned: usize,
+    /// Tokens made, not counting the end of input.
+    pub tokens_produced: usize,
+    /// ASCII characters, classified with a table lookup.
+    pub fast_path_chars: usize,
+    /// Other characters, classified by the char-based checks.
+    pub slow_path_chars: usize,
+}
+
+/// Name of sources scanned without one.
+pub const UNNAMED_SOURCE: &str = "<source>";
 
 #[derive(Debug)]
 pub struct Scanner {
     tokens: Vec<Token>,
-    source: String,
+    /// Shared with every token scanned, its text is indexed by `start` and
+    /// `current`, which are byte offsets.
+    source: Arc<SourceRef>,
+    length: usize,
     start: usize,
     current: usize,
-    line: u32,
+    /// Lint suppression comments, kept aside since comments aren't tokens.
+    directives: Vec<AllowDirective>,
+    errors: Vec<JBreadErrors>,
+    stats: ScannerStats,
+    /// Keywords a profile has scan as identifiers.
+    renamed: Vec<TokenTypes>,
 }
 
+pub struct Keyword {
+    pub name: &'static str,
+    pub token_type: TokenTypes,
+    pub description: &'static str,
+}
+
+const RESERVED: &str = "Reserved for future use.";
+
+pub const KEYWORDS: &[Keyword] = &[
+    Keyword {
+        name: "and",
+        token_type: TokenTypes::And,
+        description: RESERVED,
+    },
+    Keyword {
+        name: "assert",
+        token_type: TokenTypes::Assert,
+        description: "Fails the program unless an expression is truthy.",
+    },
+    Keyword {
+        name: "case",
+        token_type: TokenTypes::Case,
+        description: "Starts a `switch` arm run when its value equals the subject. Only a \
+                      keyword at the start of a statement in a `switch`.",
+    },
+    Keyword {
+        name: "class",
+        token_type: TokenTypes::Class,
+        description: "Declares a class.",
+    },
+    Keyword {
+        name: "const",
+        token_type: TokenTypes::Const,
+        description: "Declares a variable that can't be reassigned. Only a keyword when a \
+                      name follows, otherwise a name itself.",
+    },
+    Keyword {
+        name: "default",
+        token_type: TokenTypes::Default,
+        description: "Starts the `switch` arm run when no `case` matches. Only a keyword at \
+                      the start of a statement in a `switch`.",
+    },
+    Keyword {
+        name: "elif",
+        token_type: TokenTypes::Elif,
+        description: "Short for `else if`. Only a keyword right after the branch of an `if`, \
+                      otherwise a name itself.",
+    },
+    Keyword {
+        name: "else",
+        token_type: TokenTypes::Else,
+        description: "Starts the branch of an `if` run when its condition is falsy.",
+    },
+    Keyword {
+        name: "false",
+        token_type: TokenTypes::False,
+        description: "The boolean false.",
+    },
+    Keyword {
+        name: "for",
+        token_type: TokenTypes::For,
+        description: RESERVED,
+    },
+    Keyword {
+        name: "fun",
+        token_type: TokenTypes::Fun,
+        description: "Declares a function.",
+    },
+    Keyword {
+        name: "if",
+        token_type: TokenTypes::If,
+        description: "Runs a statement when a condition is truthy, and the `else` branch \
+                      otherwise.",
+    },
+    Keyword {
+        name: "import",
+        token_type: TokenTypes::Import,
+        description: "Runs another file's top-level code once. Only a keyword when a file \
+                      path follows, otherwise a name itself.",
+    },
+    Keyword {
+        name: "inf",
+        token_type: TokenTypes::Infinity,
+        description: "Positive infinity.",
+    },
+    Keyword {
+        name: "nan",
+        token_type: TokenTypes::NaN,
+        description: "Not a number, what `0 / 0` gives. It equals nothing, itself included.",
+    },
+    Keyword {
+        name: "NaN",
+        token_type: TokenTypes::NaN,
+        description: "Same as `nan`, the way NaN prints.",
+    },
+    Keyword {
+        name: "nil",
+        token_type: TokenTypes::Nil,
+        description: "The absence of a value.",
+    },
+    Keyword {
+        name: "or",
+        token_type: TokenTypes::Or,
+        description: RESERVED,
+    },
+    Keyword {
+        name: "print",
+        token_type: TokenTypes::Print,
+        description: "Prints the value of an expression.",
+    },
+    Keyword {
+        name: "repeat",
+        token_type: TokenTypes::Repeat,
+        description: "Runs a statement a given number of times.",
+    },
+    Keyword {
+        name: "return",
+        token_type: TokenTypes::Return,
+        description: "Returns from the enclosing function.",
+    },
+    Keyword {
+        name: "super",
+        token_type: TokenTypes::Super,
+        description: RESERVED,
+    },
+    Keyword {
+        name: "switch",
+        token_type: TokenTypes::Switch,
+        description: "Runs the first arm whose `case` value equals a subject, or its \
+                      `default` arm. Arms don't fall through. Only a keyword when \
+                      `(subject) {` follows, otherwise a name itself.",
+    },
+    Keyword {
+        name: "this",
+        token_type: TokenTypes::This,
+        description: "The instance a method was called on.",
+    },
+    Keyword {
+        name: "true",
+        token_type: TokenTypes::True,
+        description: "The boolean true.",
+    },
+    Keyword {
+        name: "var",
+        token_type: TokenTypes::Var,
+        description: "Declares one or more variables.",
+    },
+    Keyword {
+        name: "while",
+        token_type: TokenTypes::While,
+        description: RESERVED,
+    },
+];
+
 lazy_static! {
-    static ref KEYWORDS_MAP: HashMap<&'static str, TokenTypes> = HashMap::from([
-        ("and", TokenTypes::And),
-        ("class", TokenTypes::Class),
-        ("else", TokenTypes::Else),
-        ("false", TokenTypes::False),
-        ("for", TokenTypes::For),
-        ("fun", TokenTypes::Fun),
-        ("if", TokenTypes::If),
-        ("nil", TokenTypes::Nil),
-        ("or", TokenTypes::Or),
-        ("print", TokenTypes::Print),
-        ("return", TokenTypes::Return),
-        ("super", TokenTypes::Super),
-        ("this", TokenTypes::This),
-        ("true", TokenTypes::True),
-        ("var", TokenTypes::Var),
-        ("while", TokenTypes::While),
-    ]);
+    static ref KEYWORDS_MAP: HashMap<&'static str, TokenTypes> = KEYWORDS
+        .iter()
+        .map(|keyword| (keyword.name, keyword.token_type.clone()))
+        .collect();
 }
 
 impl Default for Scanner {
     fn default() -> Self {
-        Self {
-            tokens: Vec::new(),
-            source: String::new(),
-            start: 0,
-            current: 0,
-            line: 1,
-        }
+        Self::new("")
     }
 }
 
 impl Scanner {
+    /// A scanner that fails with a single error instead of scanning a source
+    /// over the limits.
+    pub fn with_options(source: &str, options: ScannerOptions) -> Self {
+        Self::with_source(SourceRef::new(UNNAMED_SOURCE, source), options)
+    }
+
     pub fn new(source: &str) -> Self {
-        Self {
-            source: source.to_string(),
+        Self::with_options(source, ScannerOptions::default())
+    }
+
+    /// A scanner for the part of `source` in `span`, e.g. the code of an
+    /// interpolation, with tokens at their place in the whole source.
+    pub fn with_span(source: Arc<SourceRef>, span: Span) -> Self {
+        let mut scanner = Self::with_source(source, ScannerOptions::default());
+        scanner.start = span.start;
+        scanner.current = span.start;
+        scanner.length = span.end;
+        scanner
+    }
+
+    /// A scanner for `source`, which the tokens will refer to.
+    pub fn with_source(source: Arc<SourceRef>, options: ScannerOptions) -> Self {
+        let mut scanner = Self {
+            length: source.map().text().len(),
+            source,
             tokens: Vec::new(),
             start: 0,
             current: 0,
-            line: 1,
+            directives: Vec::new(),
+            errors: Vec::new(),
+            stats: ScannerStats::default(),
+            renamed: Vec::new(),
+        };
+        if let Some(max_lines) = options.max_lines {
+            let lines = scanner.source.map().line_count();
+            if lines > max_lines {
+                scanner.errors.push(JBreadErrors::ParseError(Error::new(
+                    ErrorKind::TooManyLines,
+                    max_lines as u32 + 1,
+                    1,
+                    "".to_string(),
+                    format!("Source has {} lines, more than {}", lines, max_lines),
+                )));
+                scanner.current = scanner.length;
+            }
         }
+        scanner
+    }
+
+    /// A scanner for `source` within the limits of `profile`, scanning the
+    /// keywords it disables as identifiers if that's what it asks for.
+    pub fn with_profile(source: Arc<SourceRef>, profile: &LanguageProfile) -> Self {
+        Self::with_source(source, profile.scanner_options()).renaming(profile)
+    }
+
+    /// Scans the keywords `profile` renames as identifiers, for scanners
+    /// of part of a source whose limits were already checked.
+    pub(crate) fn renaming(mut self, profile: &LanguageProfile) -> Self {
+        self.renamed = profile
+            .disabled
+            .iter()
+            .filter(|token_type| profile.renames(token_type))
+            .cloned()
+            .collect();
+        self
+    }
+
+    /// The source being scanned.
+    pub fn source(&self) -> &Arc<SourceRef> {
+        &self.source
+    }
+
+    /// What has been scanned so far.
+    pub fn stats(&self) -> ScannerStats {
+        self.stats
+    }
+
+    /// The tokens scanned so far.
+    pub fn scanned(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// What scanning found wrong so far.
+    pub fn errors(&self) -> &[JBreadErrors] {
+        &self.errors
+    }
+
+    pub fn directives(&self) -> &[AllowDirective] {
+        &self.directives
     }
 
+    /// Scans the whole source like [`scan`](Self::scan), keeping the
+    /// tokens and what went wrong for [`errors`](Self::errors),
+    /// [`directives`](Self::directives) and the like to be read alongside.
     pub fn scan_tokens(&mut self) -> &Vec<Token> {
+        self.scan_all();
+        &self.tokens
+    }
+
+    /// Scans the whole source into the tokens and the errors, without
+    /// reporting them.
+    pub fn scan(mut self) -> (Vec<Token>, Vec<JBreadErrors>) {
+        self.scan_all();
+        (self.tokens, self.errors)
+    }
+
+    /// Scans a token at a time as the iterator is advanced, ending after
+    /// the [`Eof`](TokenTypes::Eof). Yields the same tokens and errors as
+    /// [`scan`](Self::scan), in source order.
+    pub fn tokens(mut self) -> impl Iterator<Item = JBreadResult<Token>> {
+        let mut finished = false;
+        std::iter::from_fn(move || {
+            if finished {
+                return None;
+            }
+            let next = self.next_token();
+            finished = matches!(&next, Ok(token) if token.token_type == TokenTypes::Eof);
+            Some(next)
+        })
+    }
+
+    fn scan_all(&mut self) {
         while !self.is_at_end() {
             self.start = self.current;
             self.scan_single_token();
         }
-        self.tokens
-            .push(Token::new(TokenTypes::Eof, "".to_string(), None, self.line));
-        &self.tokens
+        let eof = self.eof();
+        self.tokens.push(eof);
+    }
+
+    /// The next token or error, scanning past whitespace and comments.
+    fn next_token(&mut self) -> JBreadResult<Token> {
+        loop {
+            // Scanning one lexeme makes at most one token or error.
+            if let Some(error) = self.errors.pop() {
+                return Err(error);
+            }
+            if let Some(token) = self.tokens.pop() {
+                return Ok(token);
+            }
+            if self.is_at_end() {
+                return Ok(self.eof());
+            }
+            self.start = self.current;
+            self.scan_single_token();
+        }
+    }
+
+    /// The end of input, on the last line: after a final newline there's
+    /// no line left, so it's put on that newline instead, at the end of the
+    /// line it ends.
+    fn eof(&self) -> Token {
+        let offset = match self.current.checked_sub(1) {
+            Some(last) if self.source.map().text().as_bytes().get(last) == Some(&b'\n') => last,
+            _ => self.current,
+        };
+        Token::scanned(
+            TokenTypes::Eof,
+            "",
+            None,
+            &self.source,
+            Span::new(offset, offset),
+        )
+    }
+
+    fn error(&mut self, kind: ErrorKind, message: &str) {
+        self.error_at(self.start, kind, message);
+    }
+
+    /// Reports what was scanned from `offset` on as wrong.
+    fn error_at(&mut self, offset: usize, kind: ErrorKind, message: &str) {
+        let (line, column) = self.source.position(offset);
+        self.errors.push(JBreadErrors::ParseError(
+            Error::new(kind, line, column, "".to_string(), message.to_string())
+                .with_span(Span::new(offset, self.current.max(offset)))
+                .with_source(&self.source),
+        ));
     }
 
     fn is_at_end(&self) -> bool {
-        self.current >= self.source.len()
+        self.current >= self.length
     }
 
     fn scan_single_token(&mut self) {
         let chr = self.advance();
+        match char_class(chr) {
+            CharClass::Whitespace => {}
+            CharClass::Digit => self.number(),
+            CharClass::Quote => self.string(),
+            CharClass::IdentifierStart => self.identifier(),
+            CharClass::Other if is_identifier_start(chr) => self.identifier(),
+            _ => self.operator(chr),
+        }
+    }
+
+    fn operator(&mut self, chr: char) {
         match chr {
             '(' => self.add_token(TokenTypes::LeftParen),
             ')' => self.add_token(TokenTypes::RightParen),
             '{' => self.add_token(TokenTypes::LeftBrace),
             '}' => self.add_token(TokenTypes::RightBrace),
+            '[' => self.add_token(TokenTypes::LeftBracket),
+            ']' => self.add_token(TokenTypes::RightBracket),
             ',' => self.add_token(TokenTypes::Comma),
+            // `.5` is a number.
+            '.' if self.peek().is_ascii_digit() => {
+                if self.digits() {
+                    self.number_end();
+                }
+            }
             '.' => self.add_token(TokenTypes::Dot),
-            '-' => self.add_token(TokenTypes::Minus),
+            '-' => {
+                if self.match_next('>') {
+                    self.add_token(TokenTypes::Arrow)
+                } else {
+                    self.add_token(TokenTypes::Minus)
+                }
+            }
             '+' => self.add_token(TokenTypes::Plus),
             ';' => self.add_token(TokenTypes::Semicolon),
-            '*' => self.add_token(TokenTypes::Star),
+            ':' => self.add_token(TokenTypes::Colon),
+            '&' => self.add_token(TokenTypes::Ampersand),
+            '|' => self.add_token(TokenTypes::Pipe),
+            '^' => self.add_token(TokenTypes::Caret),
+            '~' => self.add_token(TokenTypes::Tilde),
+            '*' => {
+                if self.match_next('*') {
+                    self.add_token(TokenTypes::StarStar)
+                } else {
+                    self.add_token(TokenTypes::Star)
+                }
+            }
             '!' => {
                 if self.match_next('=') {
                     self.add_token(TokenTypes::BangEqual)
@@ -102,14 +682,18 @@ impl Scanner {
                 }
             }
             '<' => {
-                if self.match_next('=') {
+                if self.match_next('<') {
+                    self.add_token(TokenTypes::LessLess)
+                } else if self.match_next('=') {
                     self.add_token(TokenTypes::LessEqual)
                 } else {
                     self.add_token(TokenTypes::Less)
                 }
             }
             '>' => {
-                if self.match_next('=') {
+                if self.match_next('>') {
+                    self.add_token(TokenTypes::GreaterGreater)
+                } else if self.match_next('=') {
                     self.add_token(TokenTypes::GreaterEqual)
                 } else {
                     self.add_token(TokenTypes::Greater)
@@ -117,137 +701,219 @@ impl Scanner {
             }
             '/' => {
                 if self.match_next('/') {
-                    while self.peek() != '\n' && !self.is_at_end() {
+                    while !self.is_at_end() && self.peek() != '\n' {
                         self.advance();
                     }
+                    self.directive();
                 } else {
                     self.add_token(TokenTypes::Slash)
                 }
             }
-            '\t' => (),
-            '\r' => (),
-            ' ' => (),
-            '\n' => self.line += 1,
-            '"' => self.string(),
-            ('0'..='9') => self.number(),
-            'N' => {
-                // Implement for NaN
-                if self.match_next('a') && self.match_next('N') {
-                    self.add_token(TokenTypes::NaN);
-                } else {
-                    JuniorBread::error(JBreadErrors::ParseError(Error::new(
-                        self.line,
-                        "".to_string(),
-                        "Unexpected character.".to_string(),
-                    )))
-                }
-            }
-            ('a'..='z') | ('A'..='Z') | '_' => self.identifier(),
-            _ => JuniorBread::error(JBreadErrors::ParseError(Error::new(
-                self.line,
-                "".to_string(),
-                "Unexpected character.".to_string(),
-            ))),
+            _ => self.error(ErrorKind::UnexpectedCharacter, "Unexpected character."),
         };
     }
 
+    /// Records the comment just scanned if it's a `jbread-allow` directive.
+    fn directive(&mut self) {
+        let text = &self.slice(self.start + 2, self.current);
+        let Some(codes) = text.trim_start().strip_prefix(ALLOW_DIRECTIVE) else {
+            return;
+        };
+        let (line, column) = self.source.position(self.start);
+        self.directives.push(AllowDirective {
+            codes: codes
+                .split(',')
+                .map(str::trim)
+                .filter(|code| !code.is_empty())
+                .map(str::to_string)
+                .collect(),
+            position: Position::new(line, column),
+            end: self.current,
+        });
+    }
+
     fn identifier(&mut self) {
-        while self.peek().is_alphanumeric() || self.peek() == '_' {
+        while is_identifier_continue(self.peek()) {
             self.advance();
         }
-        let text = self.source[self.start..self.current].to_string();
-        match KEYWORDS_MAP.get(&text.as_str()).clone() {
-            Some(token_type) => self.add_token(token_type.to_owned()),
-            None => self.add_token(TokenTypes::Identifier),
+        let text = self.slice(self.start, self.current);
+        match KEYWORDS_MAP.get(&text.as_str()) {
+            Some(token_type) if !self.renamed.contains(token_type) => {
+                self.add_token(token_type.to_owned())
+            }
+            _ => self.add_token(TokenTypes::Identifier),
         }
     }
 
     fn number(&mut self) {
-        while self.peek().is_ascii_digit() {
+        if !self.digits() {
+            return;
+        }
+        if self.peek() == '.' {
             self.advance();
+            if !self.peek().is_ascii_digit() {
+                self.malformed_number(self.current, "Expect digits after decimal point.");
+                return;
+            }
+            if !self.digits() {
+                return;
+            }
         }
-        if self.peek() == '.' && self.peek_next(1).is_ascii_digit() {
+        self.number_end();
+    }
+
+    /// The exponent of the number being scanned, if it has one, and then
+    /// the number itself.
+    fn number_end(&mut self) {
+        if matches!(self.peek(), 'e' | 'E') {
             self.advance();
-            while self.peek().is_ascii_digit() {
+            if matches!(self.peek(), '+' | '-') {
                 self.advance();
             }
+            if !self.peek().is_ascii_digit() {
+                self.malformed_number(self.start, "Expect digits in exponent.");
+                return;
+            }
+            if !self.digits() {
+                return;
+            }
+        }
+        let text = self.slice(self.start, self.current).replace('_', "");
+        match text.parse::<f64>() {
+            Ok(number) if number.is_finite() => {
+                self.add_token_with_value(TokenTypes::Number, LiteralValue::Number(number))
+            }
+            Ok(_) => self.error(
+                ErrorKind::NumberOutOfRange,
+                "Number literal is too large, use 'inf' for infinity.",
+            ),
+            Err(_) => self.error(ErrorKind::MalformedNumber, "Malformed number."),
         }
-        let number = self.source[self.start..self.current].to_string();
-        self.add_token_with_value(
-            TokenTypes::Number,
-            LiteralEnum::Number(number.parse::<f64>().unwrap()),
-        );
     }
 
-    fn string(&mut self) {
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+    /// Digits separated by single `_`s, false once a separator isn't
+    /// followed by a digit, which is reported.
+    fn digits(&mut self) -> bool {
+        loop {
+            while char_class(self.peek()) == CharClass::Digit {
+                self.advance();
+            }
+            if self.peek() != '_' {
+                return true;
             }
             self.advance();
+            if !self.peek().is_ascii_digit() {
+                self.malformed_number(self.current - 1, "Expect a digit after '_' in a number.");
+                return false;
+            }
         }
-        if self.is_at_end() {
-            JuniorBread::error(JBreadErrors::ParseError(Error::new(
-                self.line,
-                "".to_string(),
-                "Unterminated string.".to_string(),
-            )));
-            return;
-        }
-        self.advance();
-        let value = self.source[self.start + 1..self.current - 1].to_string();
-        self.add_token_with_value(TokenTypes::String, LiteralEnum::String(value));
     }
 
-    fn peek(&self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            self.source.chars().nth(self.current).unwrap()
+    /// Reports the number being scanned as malformed at `offset`, skipping
+    /// the rest of it so it isn't split into more tokens.
+    fn malformed_number(&mut self, offset: usize, message: &str) {
+        while is_identifier_continue(self.peek()) || self.peek() == '.' {
+            self.advance();
         }
+        self.error_at(offset, ErrorKind::MalformedNumber, message);
     }
 
-    fn peek_next(&self, count: usize) -> char {
-        if self.current + count >= self.source.len() {
-            '\0'
-        } else {
-            self.source.chars().nth(self.current + count).unwrap()
+    /// Strings may span lines. One with `${...}` in it is a template, whose
+    /// interpolations the parser scans and parses in turn.
+    fn string(&mut self) {
+        let source = self.source.clone();
+        let result = string_end(
+            &source.map().text().as_bytes()[..self.length],
+            self.current,
+            0,
+        );
+        let end = result.as_ref().map_or(self.length, |(end, _)| *end);
+        while self.current < end {
+            self.advance();
+        }
+        match result {
+            Ok((_, false)) => {
+                let value = self.slice(self.start + 1, self.current - 1);
+                self.add_token_with_value(TokenTypes::String, LiteralValue::String(value));
+            }
+            Ok((_, true)) => self.add_token(TokenTypes::TemplateString),
+            Err(Unterminated::String) => {
+                self.error(ErrorKind::UnterminatedString, "Unterminated string.")
+            }
+            Err(Unterminated::Interpolation(offset)) => self.error_at(
+                offset,
+                ErrorKind::UnterminatedInterpolation,
+                "Unterminated interpolation.",
+            ),
+            Err(Unterminated::TooDeep(offset)) => self.error_at(
+                offset,
+                ErrorKind::NestingTooDeep,
+                &format!("Strings nested deeper than {} levels", MAX_NESTING_DEPTH),
+            ),
         }
     }
 
+    /// Source text between two byte offsets.
+    fn slice(&self, start: usize, end: usize) -> String {
+        self.source.map().text()[start..end].to_string()
+    }
+
+    fn peek(&self) -> char {
+        self.source.map().text()[self.current.min(self.length)..self.length]
+            .chars()
+            .next()
+            .unwrap_or('\0')
+    }
+
     fn match_next(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-        if self.source.chars().nth(self.current).unwrap() != expected {
+        if self.is_at_end() || self.peek() != expected {
             return false;
         }
-        self.current += 1;
+        self.advance();
         true
     }
 
     fn advance(&mut self) -> char {
-        let chr = self.source.chars().nth(self.current).unwrap();
-        self.current += 1;
+        let chr = self.peek();
+        self.count(chr);
+        self.current += chr.len_utf8();
         chr
     }
 
+    fn count(&mut self, chr: char) {
+        self.stats.bytes_scanned += chr.len_utf8();
+        if chr.is_ascii() {
+            self.stats.fast_path_chars += 1;
+        } else {
+            self.stats.slow_path_chars += 1;
+        }
+    }
+
     fn add_token(&mut self, token_type: TokenTypes) {
-        let text = self.source[self.start..self.current].to_string();
-        self.tokens
-            .push(Token::new(token_type, text, None, self.line));
+        self.add_token_with_literal(token_type, None);
     }
 
-    fn add_token_with_value(&mut self, token_type: TokenTypes, literal: LiteralEnum) {
-        let text = self.source[self.start..self.current].to_string();
-        self.tokens
-            .push(Token::new(token_type, text, Some(literal), self.line));
+    fn add_token_with_value(&mut self, token_type: TokenTypes, literal: LiteralValue) {
+        self.add_token_with_literal(token_type, Some(literal));
+    }
+
+    fn add_token_with_literal(&mut self, token_type: TokenTypes, literal: Option<LiteralValue>) {
+        let text = self.slice(self.start, self.current);
+        self.stats.tokens_produced += 1;
+        self.tokens.push(Token::scanned(
+            token_type,
+            text,
+            literal,
+            &self.source,
+            Span::new(self.start, self.current),
+        ));
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{Location, Symbol};
 
     #[test]
     fn test_scanner_addition() {
@@ -259,27 +925,31 @@ mod tests {
             &vec![
                 Token {
                     token_type: TokenTypes::Number,
-                    literal: Some(LiteralEnum::Number(1.0)),
-                    lexeme: "1".to_string(),
-                    line: 1
+                    literal: Some(LiteralValue::Number(1.0)),
+                    lexeme: "1".into(),
+                    location: Location::Detached { line: 1, column: 1 },
+                    span: Span::new(0, 1),
                 },
                 Token {
                     token_type: TokenTypes::Plus,
                     literal: None,
-                    lexeme: "+".to_string(),
-                    line: 1
+                    lexeme: "+".into(),
+                    location: Location::Detached { line: 1, column: 3 },
+                    span: Span::new(2, 3),
                 },
                 Token {
                     token_type: TokenTypes::Number,
-                    literal: Some(LiteralEnum::Number(2.0)),
-                    lexeme: "2".to_string(),
-                    line: 1
+                    literal: Some(LiteralValue::Number(2.0)),
+                    lexeme: "2".into(),
+                    location: Location::Detached { line: 1, column: 5 },
+                    span: Span::new(4, 5),
                 },
                 Token {
                     token_type: TokenTypes::Eof,
                     literal: None,
-                    lexeme: "".to_string(),
-                    line: 1
+                    lexeme: "".into(),
+                    location: Location::Detached { line: 1, column: 6 },
+                    span: Span::new(5, 5),
                 }
             ]
         );
@@ -295,8 +965,12 @@ mod tests {
             &vec![Token {
                 token_type: TokenTypes::Eof,
                 literal: None,
-                lexeme: "".to_string(),
-                line: 1
+                lexeme: "".into(),
+                location: Location::Detached {
+                    line: 1,
+                    column: 21
+                },
+                span: Span::new(20, 20),
             }]
         );
     }
@@ -311,17 +985,692 @@ mod tests {
             &vec![
                 Token {
                     token_type: TokenTypes::String,
-                    literal: Some(LiteralEnum::String("This is a string".to_string())),
-                    lexeme: "\"This is a string\"".to_string(),
-                    line: 1
+                    literal: Some(LiteralValue::String("This is a string".to_string())),
+                    lexeme: "\"This is a string\"".into(),
+                    location: Location::Detached { line: 1, column: 1 },
+                    span: Span::new(0, 18),
                 },
                 Token {
                     token_type: TokenTypes::Eof,
                     literal: None,
-                    lexeme: "".to_string(),
-                    line: 1
+                    lexeme: "".into(),
+                    location: Location::Detached {
+                        line: 1,
+                        column: 19
+                    },
+                    span: Span::new(18, 18),
                 }
             ]
         );
     }
+
+    #[test]
+    fn test_scanner_exponent() {
+        for (source, value) in [("1e9", 1e9), ("1E-9", 1e-9), ("1.5e+2", 150.0)] {
+            let mut scanner = Scanner::new(source);
+            let tokens = scanner.scan_tokens();
+            assert_eq!(tokens.len(), 2, "{}", source);
+            assert_eq!(tokens[0].literal, Some(LiteralValue::Number(value)));
+            assert_eq!(tokens[0].lexeme, source);
+        }
+
+        // The malformed literal is reported and skipped rather than split
+        // into a number and an identifier.
+        let mut scanner = Scanner::new("1e");
+        let tokens = scanner.scan_tokens();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_type, TokenTypes::Eof);
+    }
+
+    #[test]
+    fn test_scanner_number_edges() {
+        for (source, value) in [
+            (".5", 0.5),
+            ("1_000_000", 1e6),
+            ("1_0.2_5", 10.25),
+            ("1.5e1_0", 1.5e10),
+            ("1e308", 1e308),
+        ] {
+            let (tokens, errors) = Scanner::new(source).scan();
+            assert!(errors.is_empty(), "{}", source);
+            assert_eq!(tokens.len(), 2, "{}", source);
+            assert_eq!(tokens[0].literal, Some(LiteralValue::Number(value)));
+            assert_eq!(tokens[0].lexeme, source);
+        }
+
+        // `.` is still a dot when no digit follows it.
+        let (tokens, _) = Scanner::new("a.b").scan();
+        assert_eq!(tokens[1].token_type, TokenTypes::Dot);
+
+        // Each is reported at its line and skipped whole.
+        for (source, kind, message, column) in [
+            (
+                "\n5.;",
+                ErrorKind::MalformedNumber,
+                "Expect digits after decimal point.",
+                3,
+            ),
+            (
+                "\n5.x;",
+                ErrorKind::MalformedNumber,
+                "Expect digits after decimal point.",
+                3,
+            ),
+            (
+                "\n1_;",
+                ErrorKind::MalformedNumber,
+                "Expect a digit after '_' in a number.",
+                2,
+            ),
+            (
+                "\n1__0;",
+                ErrorKind::MalformedNumber,
+                "Expect a digit after '_' in a number.",
+                2,
+            ),
+            (
+                "\n1_.5;",
+                ErrorKind::MalformedNumber,
+                "Expect a digit after '_' in a number.",
+                2,
+            ),
+            (
+                "\n1._5;",
+                ErrorKind::MalformedNumber,
+                "Expect digits after decimal point.",
+                3,
+            ),
+            (
+                "\n1e999;",
+                ErrorKind::NumberOutOfRange,
+                "Number literal is too large, use 'inf' for infinity.",
+                1,
+            ),
+        ] {
+            let (tokens, errors) = Scanner::new(source).scan();
+            assert_eq!(errors.len(), 1, "{}", source);
+            assert_eq!(errors[0].kind(), &kind, "{}", source);
+            assert_eq!(errors[0].error().message(), message, "{}", source);
+            assert_eq!(
+                (errors[0].error().line(), errors[0].error().column()),
+                (2, column),
+                "{}",
+                source
+            );
+            let types: Vec<_> = tokens
+                .iter()
+                .map(|token| token.token_type.clone())
+                .collect();
+            assert_eq!(
+                types,
+                [TokenTypes::Semicolon, TokenTypes::Eof],
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_scanner_star_star() {
+        let mut scanner = Scanner::new("2 ** 3 * 4");
+        let types: Vec<TokenTypes> = scanner
+            .scan_tokens()
+            .iter()
+            .map(|token| token.token_type.clone())
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenTypes::Number,
+                TokenTypes::StarStar,
+                TokenTypes::Number,
+                TokenTypes::Star,
+                TokenTypes::Number,
+                TokenTypes::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanner_bitwise() {
+        let mut scanner = Scanner::new("& | ^ ~ << <= < >> >= >");
+        let types: Vec<TokenTypes> = scanner
+            .scan_tokens()
+            .iter()
+            .map(|token| token.token_type.clone())
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenTypes::Ampersand,
+                TokenTypes::Pipe,
+                TokenTypes::Caret,
+                TokenTypes::Tilde,
+                TokenTypes::LessLess,
+                TokenTypes::LessEqual,
+                TokenTypes::Less,
+                TokenTypes::GreaterGreater,
+                TokenTypes::GreaterEqual,
+                TokenTypes::Greater,
+                TokenTypes::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanner_inf_keyword() {
+        let mut scanner = Scanner::new("inf");
+        let tokens = scanner.scan_tokens();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenTypes::Infinity);
+        assert_eq!(tokens[0].lexeme, "inf");
+    }
+
+    #[test]
+    fn test_scanner_nan_keyword() {
+        let mut scanner = Scanner::new("nan NaN Name NaNa");
+        let types: Vec<_> = scanner
+            .scan_tokens()
+            .iter()
+            .map(|token| token.token_type.clone())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                TokenTypes::NaN,
+                TokenTypes::NaN,
+                TokenTypes::Identifier,
+                TokenTypes::Identifier,
+                TokenTypes::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanner_disabled_keywords() {
+        let scan = |profile: &LanguageProfile| -> Vec<TokenTypes> {
+            let source = SourceRef::new(UNNAMED_SOURCE, "while class nan NaN");
+            let (tokens, _) = Scanner::with_profile(source, profile).scan();
+            tokens.into_iter().map(|token| token.token_type).collect()
+        };
+        let profile = LanguageProfile::default()
+            .without(TokenTypes::While)
+            .without(TokenTypes::NaN);
+        let keywords = [
+            TokenTypes::While,
+            TokenTypes::Class,
+            TokenTypes::NaN,
+            TokenTypes::NaN,
+            TokenTypes::Eof,
+        ];
+        // Rejecting them is up to the parser.
+        assert_eq!(scan(&profile), keywords);
+        assert_eq!(
+            scan(&profile.with_disabled_keywords(crate::DisabledKeywords::Identifier)),
+            [
+                TokenTypes::Identifier,
+                TokenTypes::Class,
+                TokenTypes::Identifier,
+                TokenTypes::Identifier,
+                TokenTypes::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanner_columns() {
+        let mut scanner = Scanner::new("var a = 1;\n  print a >= 10;");
+        let tokens = scanner.scan_tokens();
+        let positions: Vec<(&str, u32, u32, Span)> = tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.lexeme.as_str(),
+                    token.line(),
+                    token.column(),
+                    token.span,
+                )
+            })
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                ("var", 1, 1, Span::new(0, 3)),
+                ("a", 1, 5, Span::new(4, 5)),
+                ("=", 1, 7, Span::new(6, 7)),
+                ("1", 1, 9, Span::new(8, 9)),
+                (";", 1, 10, Span::new(9, 10)),
+                ("print", 2, 3, Span::new(13, 18)),
+                ("a", 2, 9, Span::new(19, 20)),
+                (">=", 2, 11, Span::new(21, 23)),
+                ("10", 2, 14, Span::new(24, 26)),
+                (";", 2, 16, Span::new(26, 27)),
+                ("", 2, 17, Span::new(27, 27)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanner_columns_with_tabs() {
+        let mut scanner = Scanner::new("\t\tx");
+        let tokens = scanner.scan_tokens();
+        assert_eq!(tokens[0].column(), 3);
+    }
+
+    #[test]
+    fn test_positions_at_line_boundaries() {
+        let source = SourceRef::new("a.jbr", "a\r\n\"two\nlines\" b\n\n;\n");
+        let (tokens, _) = Scanner::with_source(source.clone(), ScannerOptions::default()).scan();
+        let positions: Vec<(&str, u32, u32)> = tokens
+            .iter()
+            .map(|token| (token.lexeme.as_str(), token.line(), token.column()))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                ("a", 1, 1),
+                // A token over several lines is where it starts.
+                ("\"two\nlines\"", 2, 1),
+                ("b", 3, 8),
+                (";", 5, 1),
+                // The end of input is on the last line, not after it.
+                ("", 5, 2),
+            ]
+        );
+        assert!(tokens.iter().all(|token| token.source() == Some(&source)));
+    }
+
+    #[test]
+    fn test_source_endings() {
+        use TokenTypes::*;
+        let statement = [Print, Number, Semicolon];
+        for (source, types, eof, error) in [
+            ("", &[][..], (1, 1), None),
+            ("print 1;", &statement[..], (1, 9), None),
+            ("print 1;\n", &statement[..], (1, 9), None),
+            ("print 1;\n\n", &statement[..], (2, 1), None),
+            ("print 1;\r\n", &statement[..], (1, 10), None),
+            // A lone `\r` isn't a line break, only whitespace.
+            (
+                "print 1;\rprint 2;",
+                &[Print, Number, Semicolon, Print, Number, Semicolon][..],
+                (1, 18),
+                None,
+            ),
+            ("print 1; // done", &statement[..], (1, 17), None),
+            ("print 1;\n// done", &statement[..], (2, 8), None),
+            ("\"", &[][..], (1, 2), Some((1, 1))),
+            // Where the string starts, not where input runs out.
+            (
+                "print 1;\n\"open\nstring",
+                &statement[..],
+                (3, 7),
+                Some((2, 1)),
+            ),
+        ] {
+            let (tokens, errors) = Scanner::new(source).scan();
+            let (last, tokens) = tokens.split_last().unwrap();
+            assert_eq!(
+                tokens
+                    .iter()
+                    .map(|token| token.token_type.clone())
+                    .collect::<Vec<_>>(),
+                types,
+                "{:?}",
+                source
+            );
+            assert_eq!(last.token_type, Eof);
+            assert_eq!((last.line(), last.column()), eof, "{:?}", source);
+            let lines = crate::SourceMap::new(source).line_count().max(1) as u32;
+            assert_eq!(last.line(), lines, "{:?}", source);
+            let errors: Vec<_> = errors
+                .iter()
+                .map(|error| {
+                    (
+                        error.kind().clone(),
+                        error.error().line(),
+                        error.error().column(),
+                    )
+                })
+                .collect();
+            let expected: Vec<_> = error
+                .map(|(line, column)| (ErrorKind::UnterminatedString, line, column))
+                .into_iter()
+                .collect();
+            assert_eq!(errors, expected, "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn test_allow_directives() {
+        let mut scanner = Scanner::new("// plain comment\n  // jbread-allow: a, b ,\nprint 1;");
+        scanner.scan_tokens();
+        assert_eq!(
+            scanner.directives(),
+            &[AllowDirective {
+                codes: vec!["a".to_string(), "b".to_string()],
+                position: Position::new(2, 3),
+                end: 42,
+            }]
+        );
+    }
+
+    const REPRESENTATIVE: &str = "// jbread-allow: unused\nclass A {\n  f(x) { return x ** 2e3; }\n}\nvar s = \"multi\nline\"; # NaN\nrepeat (3) print [1.5, s][0] >= 10 != !true;\nvar bad = 1e;\n\"open";
+
+    #[test]
+    fn test_streaming_matches_eager() {
+        let (tokens, errors) = Scanner::new(REPRESENTATIVE).scan();
+        let streamed: Vec<JBreadResult<Token>> = Scanner::new(REPRESENTATIVE).tokens().collect();
+        let (ok, err): (Vec<_>, Vec<_>) = streamed.into_iter().partition(Result::is_ok);
+
+        assert_eq!(
+            ok.into_iter().map(Result::unwrap).collect::<Vec<_>>(),
+            tokens
+        );
+        assert_eq!(tokens.last().unwrap().token_type, TokenTypes::Eof);
+        let messages =
+            |errors: Vec<JBreadErrors>| errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            messages(err.into_iter().map(Result::unwrap_err).collect()),
+            messages(errors.clone())
+        );
+        assert_eq!(
+            errors.iter().map(JBreadErrors::kind).collect::<Vec<_>>(),
+            [
+                &ErrorKind::UnexpectedCharacter,
+                &ErrorKind::MalformedNumber,
+                &ErrorKind::UnterminatedString
+            ]
+        );
+    }
+
+    #[test]
+    fn test_streaming_is_lazy() {
+        let mut tokens = Scanner::new("print 1; $").tokens();
+        assert_eq!(
+            tokens.next().unwrap().unwrap().token_type,
+            TokenTypes::Print
+        );
+        assert_eq!(tokens.next().unwrap().unwrap().lexeme, "1");
+        assert_eq!(
+            tokens.next().unwrap().unwrap().token_type,
+            TokenTypes::Semicolon
+        );
+        assert_eq!(
+            tokens.next().unwrap().unwrap_err().kind(),
+            &ErrorKind::UnexpectedCharacter
+        );
+        assert_eq!(tokens.next().unwrap().unwrap().token_type, TokenTypes::Eof);
+        assert!(tokens.next().is_none());
+    }
+
+    #[test]
+    fn test_read_source() {
+        let read = |bytes: &[u8]| read_source("a.jbr", bytes);
+
+        let error = read(b"\x7fELF\x02\x01\x01\0\0\0print 1;").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::BinaryContent);
+        assert_eq!(
+            error.to_string(),
+            "a.jbr: File does not appear to be a jBREAD source file (binary content detected)"
+        );
+
+        let error = read(
+            "print \"€\";\nprint \""
+                .as_bytes()
+                .iter()
+                .chain(&[0xe2, 0x82])
+                .copied()
+                .collect::<Vec<_>>()
+                .as_slice(),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidUtf8);
+        assert_eq!(error.to_string(), "a.jbr: Invalid UTF-8 at byte 20");
+
+        assert_eq!(
+            read("\u{FEFF}print \"é\";".as_bytes()).unwrap(),
+            "print \"é\";"
+        );
+        assert_eq!(read(b"").unwrap(), "");
+        // Past the bytes checked a NUL is only a character the scanner rejects.
+        let late_nul = format!("{}\0", " ".repeat(BINARY_CHECK_LENGTH));
+        assert_eq!(read(late_nul.as_bytes()).unwrap(), late_nul);
+    }
+
+    #[test]
+    fn test_max_lines() {
+        let options = ScannerOptions { max_lines: Some(3) };
+        for source in [
+            "print 1;\nprint 2;\nprint 3;",
+            "1;\n2;\n3;\n",
+            "\"a\nb\nc\"",
+            "",
+        ] {
+            let (tokens, errors) = Scanner::with_options(source, options).scan();
+            assert!(errors.is_empty(), "{}", source);
+            assert_eq!(tokens, Scanner::new(source).scan().0);
+        }
+
+        let source = "print 1;\nprint 2;\n\nprint 3;\n";
+        let (tokens, errors) = Scanner::with_options(source, options).scan();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind(), &ErrorKind::TooManyLines);
+        assert_eq!(
+            errors[0].to_string(),
+            "\"Source has 4 lines, more than 3\" at 4:1 in "
+        );
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_type, TokenTypes::Eof);
+
+        let streamed: Vec<_> = Scanner::with_options(source, options).tokens().collect();
+        assert_eq!(streamed.len(), 2);
+        assert!(streamed[0].is_err());
+    }
+
+    #[test]
+    fn test_ascii_takes_the_fast_path() {
+        let program = "var total = 0; // sums\nrepeat (10) { total = total + 1.5e2 ** 2; }\n\
+                       fun add(a, b) { return a >= b != !false; } print \"done\";\n";
+        let source = program.repeat(2000);
+        let mut scanner = Scanner::new(&source);
+        let tokens = scanner.scan_tokens().len();
+        let stats = scanner.stats();
+        assert_eq!(stats.fast_path_chars, source.len());
+        assert_eq!(stats.slow_path_chars, 0);
+        assert_eq!(stats.bytes_scanned, source.len());
+        assert_eq!(stats.tokens_produced, tokens - 1);
+    }
+
+    #[test]
+    fn test_mixed_ascii_and_multibyte() {
+        let source =
+            "var caf\u{e9}1 = \"\u{fc}\u{65e5}\"; // \u{f1}\nprint caf\u{e9}1 + \"\u{e9}\";";
+        let mut scanner = Scanner::new(source);
+        let tokens: Vec<_> = scanner
+            .scan_tokens()
+            .iter()
+            .map(|token| {
+                (
+                    token.token_type.clone(),
+                    token.lexeme.clone(),
+                    token.column(),
+                    (token.span.start, token.span.end),
+                )
+            })
+            .collect();
+        let token = |token_type, lexeme: &str, column, start, end| {
+            (token_type, Symbol::intern(lexeme), column, (start, end))
+        };
+        assert_eq!(
+            tokens,
+            vec![
+                token(TokenTypes::Var, "var", 1, 0, 3),
+                token(TokenTypes::Identifier, "caf\u{e9}1", 5, 4, 10),
+                token(TokenTypes::Equal, "=", 11, 11, 12),
+                token(TokenTypes::String, "\"\u{fc}\u{65e5}\"", 13, 13, 20),
+                token(TokenTypes::Semicolon, ";", 17, 20, 21),
+                token(TokenTypes::Print, "print", 1, 28, 33),
+                token(TokenTypes::Identifier, "caf\u{e9}1", 7, 34, 40),
+                token(TokenTypes::Plus, "+", 13, 41, 42),
+                token(TokenTypes::String, "\"\u{e9}\"", 15, 43, 47),
+                token(TokenTypes::Semicolon, ";", 18, 47, 48),
+                token(TokenTypes::Eof, "", 19, 48, 48),
+            ]
+        );
+        // Spans are byte offsets, so they slice the source.
+        for (_, lexeme, _, (start, end)) in &tokens {
+            assert_eq!(&source[*start..*end], lexeme.as_str());
+        }
+        let stats = scanner.stats();
+        assert_eq!(stats.slow_path_chars, 6);
+        assert_eq!(stats.fast_path_chars, source.chars().count() - 6);
+        assert_eq!(stats.bytes_scanned, source.len());
+
+        // Outside identifiers and strings other characters are still errors.
+        let (_, errors) = Scanner::new("\u{2192} = 1;").scan();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind(), &ErrorKind::UnexpectedCharacter);
+    }
+
+    #[test]
+    fn test_multi_line_strings() {
+        let (tokens, errors) = Scanner::new("var a = \"one\ntwo\n\";\nprint a;").scan();
+        assert!(errors.is_empty());
+        assert_eq!(
+            tokens[3].literal,
+            Some(LiteralValue::String("one\ntwo\n".to_string()))
+        );
+        let positions: Vec<_> = tokens[4..]
+            .iter()
+            .map(|token| (token.lexeme.to_string(), token.line(), token.column()))
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                (";".to_string(), 3, 2),
+                ("print".to_string(), 4, 1),
+                ("a".to_string(), 4, 7),
+                (";".to_string(), 4, 8),
+                ("".to_string(), 4, 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_template_strings() {
+        let source = "print \"sum is ${a + (b * 2)}!\" + \"${ \"}\" }${{}}\";";
+        let (tokens, errors) = Scanner::new(source).scan();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let types: Vec<_> = tokens
+            .iter()
+            .map(|token| token.token_type.clone())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                TokenTypes::Print,
+                TokenTypes::TemplateString,
+                TokenTypes::Plus,
+                TokenTypes::TemplateString,
+                TokenTypes::Semicolon,
+                TokenTypes::Eof,
+            ]
+        );
+        let code = |span: Span| source[span.start..span.end].to_string();
+        let parts = template_parts(tokens[1].lexeme.as_str(), tokens[1].span.start);
+        let TemplatePart::Code(span) = parts[1] else {
+            panic!("{:?}", parts);
+        };
+        let TemplatePart::Text(text, text_span) = &parts[0] else {
+            panic!("{:?}", parts);
+        };
+        assert_eq!(
+            (text.as_str(), code(*text_span)),
+            ("sum is ", "sum is ".to_string())
+        );
+        assert_eq!(code(span), "a + (b * 2)");
+        let TemplatePart::Text(text, text_span) = &parts[2] else {
+            panic!("{:?}", parts);
+        };
+        assert_eq!((text.as_str(), code(*text_span)), ("!", "!".to_string()));
+        // Braces in strings and blocks within the code don't end it.
+        let parts = template_parts(tokens[3].lexeme.as_str(), tokens[3].span.start);
+        let spans: Vec<_> = parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Code(span) => code(*span),
+                TemplatePart::Text(text, _) => panic!("{:?}", text),
+            })
+            .collect();
+        assert_eq!(spans, [" \"}\" ", "{}"]);
+
+        // Plain strings are unchanged, a lone `$` or `{` included.
+        let (tokens, _) = Scanner::new("\"$ {a} $\"").scan();
+        assert_eq!(tokens[0].token_type, TokenTypes::String);
+    }
+
+    #[test]
+    fn test_unterminated_interpolation() {
+        let (_, errors) = Scanner::new("print \"a\n  ${b + \"}\n;").scan();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind(), &ErrorKind::UnterminatedInterpolation);
+        assert_eq!(
+            errors[0].to_string(),
+            "\"Unterminated interpolation.\" at 2:3 in "
+        );
+        // From the `${` to where input runs out.
+        assert_eq!(errors[0].error().span(), Some(Span::new(11, 21)));
+
+        let depth = 2 * MAX_NESTING_DEPTH;
+        let nested = format!("{}1{}", "\"${".repeat(depth), "}\"".repeat(depth));
+        let (_, errors) = Scanner::new(&nested).scan();
+        assert_eq!(errors[0].kind(), &ErrorKind::NestingTooDeep);
+    }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let source = "\u{fc}ber = \"\u{65e5}\u{672c}\"; \u{3c0}_2 = \u{fc}ber;\n_\u{e9}t\u{e9};";
+        let (tokens, errors) = Scanner::new(source).scan();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let tokens: Vec<_> = tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.token_type.clone(),
+                    token.lexeme.to_string(),
+                    token.line(),
+                    token.column(),
+                )
+            })
+            .collect();
+        let token =
+            |token_type, lexeme: &str, line, column| (token_type, lexeme.to_string(), line, column);
+        assert_eq!(
+            tokens,
+            vec![
+                token(TokenTypes::Identifier, "\u{fc}ber", 1, 1),
+                token(TokenTypes::Equal, "=", 1, 6),
+                token(TokenTypes::String, "\"\u{65e5}\u{672c}\"", 1, 8),
+                token(TokenTypes::Semicolon, ";", 1, 12),
+                token(TokenTypes::Identifier, "\u{3c0}_2", 1, 14),
+                token(TokenTypes::Equal, "=", 1, 18),
+                token(TokenTypes::Identifier, "\u{fc}ber", 1, 20),
+                token(TokenTypes::Semicolon, ";", 1, 24),
+                token(TokenTypes::Identifier, "_\u{e9}t\u{e9}", 2, 1),
+                token(TokenTypes::Semicolon, ";", 2, 5),
+                token(TokenTypes::Eof, "", 2, 6),
+            ]
+        );
+        let (tokens, _) = Scanner::new("\"\u{e9}\u{65e5}\" + 1").scan();
+        assert_eq!(
+            tokens[0].literal,
+            Some(LiteralValue::String("\u{e9}\u{65e5}".to_string()))
+        );
+        assert_eq!(tokens[2].lexeme.to_string(), "1");
+
+        // Digits and combining marks only continue a name.
+        let (_, errors) = Scanner::new("\u{301}a = 1;").scan();
+        assert_eq!(errors[0].kind(), &ErrorKind::UnexpectedCharacter);
+    }
 }
@@ -0,0 +1,266 @@
+//! Interpreters isolated from each other and from the rest of the process,
+//! for hosts running many programs at once.
+
+use std::{
+    cell::RefCell,
+    panic,
+    rc::Rc,
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    interpreter::{HostInterface, Interpreter, InterpreterOptions, SystemHost},
+    parser::Parser,
+    JBreadResult, LintDiagnostic, Options, Resolver, Scanner, ScannerOptions, SourceRef,
+    Suppressions, UNNAMED_SOURCE,
+};
+
+/// What a [`Session`] sets its interpreter up with.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SessionOptions {
+    pub interpreter: InterpreterOptions,
+    pub scanner: ScannerOptions,
+}
+
+/// Collects printed lines instead of writing them to stdout.
+struct CapturingHost {
+    printed: Rc<RefCell<String>>,
+}
+
+impl HostInterface for CapturingHost {
+    fn random_seed(&self) -> u64 {
+        SystemHost.random_seed()
+    }
+
+    fn print(&self, line: &str) {
+        let mut printed = self.printed.borrow_mut();
+        printed.push_str(line);
+        printed.push('\n');
+    }
+
+    fn write(&self, text: &str) {
+        self.printed.borrow_mut().push_str(text);
+    }
+}
+
+/// What running one program produced.
+struct Outcome {
+    result: JBreadResult<()>,
+    output: String,
+    warnings: Vec<LintDiagnostic>,
+}
+
+/// The interpreter of a session, on the thread it was started on.
+struct Worker {
+    interpreter: Interpreter,
+    printed: Rc<RefCell<String>>,
+    scanner_options: ScannerOptions,
+}
+
+impl Worker {
+    fn new(options: SessionOptions) -> Self {
+        let printed = Rc::new(RefCell::new(String::new()));
+        let mut interpreter = Interpreter::with_host(CapturingHost {
+            printed: printed.clone(),
+        });
+        interpreter.set_options(options.interpreter);
+        Self {
+            interpreter,
+            printed,
+            scanner_options: options.scanner,
+        }
+    }
+
+    fn run(&mut self, source: &str) -> Outcome {
+        self.printed.borrow_mut().clear();
+        let mut warnings = Vec::new();
+        let result = self.execute(source, &mut warnings);
+        Outcome {
+            result,
+            output: self.printed.take(),
+            warnings,
+        }
+    }
+
+    fn execute(&mut self, source: &str, warnings: &mut Vec<LintDiagnostic>) -> JBreadResult<()> {
+        let mut scanner =
+            Scanner::with_source(SourceRef::new(UNNAMED_SOURCE, source), self.scanner_options);
+        scanner.scan_tokens();
+        if let Some(error) = scanner.errors().first() {
+            return Err(error.clone());
+        }
+        let tokens = scanner.scanned();
+        let mut parser = Parser::new(tokens);
+        let stmts = parser.parse()?;
+        let mut diagnostics = parser.take_diagnostics().into_lint();
+        let resolution = Resolver::default().resolve(&stmts)?;
+        diagnostics.extend(resolution.warnings);
+        let mut suppressions = Suppressions::new(
+            scanner.directives(),
+            tokens,
+            &Options::default().known_codes(),
+        );
+        *warnings = suppressions.apply(diagnostics);
+        self.interpreter.set_locals(resolution.locals);
+        self.interpreter.interpret(&stmts).map(drop)
+    }
+}
+
+/// An interpreter of its own along with what its last run printed and
+/// warned about. Nothing is shared with other sessions, so sessions on
+/// different threads never see each other's variables or errors.
+///
+/// Values and interned names are only valid on the thread they were made
+/// on, so the interpreter lives on a thread the session starts for it and
+/// programs are handed over to it. That leaves the session itself free to
+/// move between threads, e.g. in a pool.
+pub struct Session {
+    programs: Option<Sender<String>>,
+    outcomes: Receiver<Outcome>,
+    worker: Option<JoinHandle<()>>,
+    output: String,
+    warnings: Vec<LintDiagnostic>,
+    had_error: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::with_options(SessionOptions::default())
+    }
+
+    pub fn with_options(options: SessionOptions) -> Self {
+        let (programs, received) = mpsc::channel::<String>();
+        let (sent, outcomes) = mpsc::channel();
+        let worker = thread::spawn(move || {
+            let mut worker = Worker::new(options);
+            for source in received {
+                if sent.send(worker.run(&source)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            programs: Some(programs),
+            outcomes,
+            worker: Some(worker),
+            output: String::new(),
+            warnings: Vec::new(),
+            had_error: false,
+        }
+    }
+
+    /// Runs `source` in the session's interpreter, keeping the globals it
+    /// defines for the next run. A panic while running is raised here.
+    pub fn run(&mut self, source: &str) -> JBreadResult<()> {
+        let outcome = self
+            .programs
+            .as_ref()
+            .and_then(|programs| programs.send(source.to_string()).ok())
+            .and_then(|()| self.outcomes.recv().ok());
+        let Some(outcome) = outcome else {
+            self.programs = None;
+            match self.worker.take().map(JoinHandle::join) {
+                Some(Err(payload)) => panic::resume_unwind(payload),
+                _ => panic!("Session used after its interpreter panicked"),
+            }
+        };
+        self.output = outcome.output;
+        self.warnings = outcome.warnings;
+        self.had_error |= outcome.result.is_err();
+        outcome.result
+    }
+
+    /// What the last run printed, up to the error if it failed.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// The warnings about the last program run, minus those suppressed by
+    /// `jbread-allow` directives.
+    pub fn warnings(&self) -> &[LintDiagnostic] {
+        &self.warnings
+    }
+
+    /// Whether any run of this session failed.
+    pub fn had_error(&self) -> bool {
+        self.had_error
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // Closing the channel ends the worker's loop.
+        self.programs = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ErrorKind, NO_EFFECT};
+
+    fn is_send<T: Send>() {}
+
+    #[test]
+    fn test_session_is_send() {
+        is_send::<Session>();
+        is_send::<crate::JBreadErrors>();
+    }
+
+    #[test]
+    fn test_run_keeps_globals() {
+        let mut session = Session::new();
+        session.run("var a = 1;").unwrap();
+        session.run("a = a + 1; print a;").unwrap();
+        assert_eq!(session.output(), "2\n");
+        assert!(!session.had_error());
+
+        let error = session.run("print \"a\"; print -\"a\";").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::TypeMismatch { .. }));
+        assert_eq!(session.output(), "a\n");
+        assert!(session.had_error());
+
+        session.run("a;").unwrap();
+        assert_eq!(session.warnings()[0].code, NO_EFFECT);
+        session.run("print a;").unwrap();
+        assert!(session.warnings().is_empty());
+
+        let error = session.run("print 1; print \"open;").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::UnterminatedString);
+        assert_eq!(session.output(), "");
+        assert!(session.had_error());
+    }
+
+    #[test]
+    fn test_sessions_are_isolated_across_threads() {
+        let threads: Vec<_> = (0..8)
+            .map(|index| {
+                thread::spawn(move || {
+                    let mut session = Session::new();
+                    session.run(&format!("var shared = {};", index)).unwrap();
+                    if index % 2 == 0 {
+                        assert!(session.run("print undefined;").is_err());
+                    }
+                    session
+                })
+            })
+            .collect();
+        // Each session moved back to this thread still has its own globals.
+        for (index, thread) in threads.into_iter().enumerate() {
+            let mut session = thread.join().unwrap();
+            session.run("print shared;").unwrap();
+            assert_eq!(session.output(), format!("{}\n", index));
+            assert_eq!(session.had_error(), index % 2 == 0);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    fmt::{Debug, Display},
+    sync::Arc,
+};
+
+use crate::errors::SourceMap;
+
+/// A source scanned in a run, its name and text. Every token scanned from it
+/// holds one, so lines and columns are only worked out when asked for and
+/// errors can show the source they came from.
+pub struct SourceRef {
+    name: String,
+    map: SourceMap,
+}
+
+impl SourceRef {
+    pub fn new(name: &str, text: &str) -> Arc<Self> {
+        Arc::new(Self {
+            name: name.to_string(),
+            map: SourceMap::new(text),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn map(&self) -> &SourceMap {
+        &self.map
+    }
+
+    /// 1-based line and column of the character at byte `offset`.
+    pub fn position(&self, offset: usize) -> (u32, u32) {
+        self.map.position(offset)
+    }
+}
+
+/// The text is left out, it's what the tokens are already showing.
+impl Debug for SourceRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SourceRef({})", self.name)
+    }
+}
+
+impl Display for SourceRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Sources are only equal to themselves, two with the same text are still
+/// different runs.
+impl PartialEq for SourceRef {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position() {
+        let source = SourceRef::new("a.jbr", "ab\n\ncaf\u{e9} x\r\nend");
+        assert_eq!(source.position(0), (1, 1));
+        assert_eq!(source.position(1), (1, 2));
+        // The newline ends its line.
+        assert_eq!(source.position(2), (1, 3));
+        assert_eq!(source.position(3), (2, 1));
+        // Offsets count bytes, columns count characters.
+        assert_eq!(source.position(10), (3, 6));
+        // Within a character is at its column.
+        assert_eq!(source.position(8), (3, 4));
+        assert_eq!(source.position(13), (4, 1));
+        // The end of the source, where the Eof token sits.
+        assert_eq!(source.position(16), (4, 4));
+        assert_eq!(source.name(), "a.jbr");
+        assert_eq!(format!("{:?}", source), "SourceRef(a.jbr)");
+    }
+}
@@ -0,0 +1,15 @@
+//! Room on the stack for the recursive walks over programs: parsing, every
+//! visitor of the tree and evaluation.
+
+/// How close a walk may get to the end of the stack before a level is run
+/// on a new segment of it, and how large those segments are. A level takes
+/// a few KiB in a debug build.
+const RED_ZONE: usize = 128 * 1024;
+const SEGMENT: usize = 2 * 1024 * 1024;
+
+/// Runs `run`, first growing the stack onto the heap if it's nearly used
+/// up, so deep nesting is bounded by the limits of the parser and
+/// interpreter rather than by the stack of the thread it happens on.
+pub(crate) fn grow<R>(run: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, SEGMENT, run)
+}
@@ -0,0 +1,405 @@
+//! How very long identifiers and very deep expressions behave against the
+//! parser's and interpreter's limits, for generated code that runs into
+//! them. Depths scale with [`STRESS_DEPTH`].
+
+use std::{fmt::Write, time::Instant};
+
+#[cfg(feature = "interpreter")]
+use crate::{
+    ast::Expr,
+    interpreter::{Interpreter, Value, MAX_EVALUATION_DEPTH},
+    LiteralValue, Token, TokenTypes,
+};
+use crate::{
+    ast::Stmt,
+    parser::{Parser, MAX_EXPRESSION_DEPTH, MAX_IDENTIFIER_LENGTH, MAX_NESTING_DEPTH},
+    token::TOKEN_CLONES,
+    AstPrinter, ConstantPropagator, ErrorKind, JBreadResult, Resolver, Scanner,
+};
+
+/// The length of the chains below, which must stay under the expression
+/// depth limit along with the few levels the statements around them take.
+const STRESS_DEPTH: usize = MAX_EXPRESSION_DEPTH / 2;
+
+fn parse(source: &str) -> JBreadResult<Vec<Stmt>> {
+    let mut scanner = Scanner::new(source);
+    Parser::new(scanner.scan_tokens()).parse()
+}
+
+/// Parses `source` and puts it through every pass that walks the tree, so
+/// any of them overflowing the stack fails the test.
+fn walk(source: &str) -> Vec<Stmt> {
+    let stmts = parse(source).unwrap();
+    Resolver::default().resolve(&stmts).unwrap();
+    ConstantPropagator::default().propagate(&stmts);
+    let mut printer = AstPrinter::default();
+    for stmt in stmts.iter() {
+        printer.print_stmt(stmt);
+    }
+    stmts
+}
+
+/// `object[index(0)][index(1)]...` with `depth` indexes.
+fn index_chain(object: &str, depth: usize, index: impl Fn(usize) -> String) -> String {
+    let mut chain = object.to_string();
+    for level in 0..depth {
+        write!(chain, "[{}]", index(level)).unwrap();
+    }
+    chain
+}
+
+#[cfg(feature = "interpreter")]
+fn run(source: &str) -> Interpreter {
+    let stmts = walk(source);
+    let mut interpreter = Interpreter::default();
+    interpreter.set_locals(Resolver::default().resolve(&stmts).unwrap().locals);
+    interpreter.interpret(&stmts).unwrap();
+    interpreter
+}
+
+#[cfg(feature = "interpreter")]
+fn lookup(interpreter: &Interpreter, name: &str) -> Value {
+    let token = Token::new(TokenTypes::Identifier, name.to_string(), None, 1);
+    interpreter.environment.borrow().get(&token).unwrap()
+}
+
+#[test]
+fn test_identifier_length_cap() {
+    let longest = "a".repeat(MAX_IDENTIFIER_LENGTH);
+    let too_long = "a".repeat(MAX_IDENTIFIER_LENGTH + 1);
+    assert!(parse(&format!("var {0} = 1; print {0}.{0};", longest)).is_ok());
+
+    for source in [
+        format!("var {} = 1;", too_long),
+        format!("print {};", too_long),
+        format!("fun f({}) {{}}", too_long),
+        format!("print a.{};", too_long),
+        format!("class {} {{}}", too_long),
+    ] {
+        let error = parse(&source).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::IdentifierTooLong, "{}", source);
+        assert!(error.to_string().starts_with(&format!(
+            "\"Identifier is longer than {} characters\"",
+            MAX_IDENTIFIER_LENGTH
+        )));
+    }
+}
+
+#[test]
+fn test_index_chain_parses_in_linear_time() {
+    let clones = |depth: usize| {
+        let source = format!(
+            "print {};",
+            index_chain("a", depth, |level| level.to_string())
+        );
+        TOKEN_CLONES.with(|clones| clones.set(0));
+        parse(&source).unwrap();
+        TOKEN_CLONES.with(|clones| clones.get())
+    };
+    // Each link costs the same no matter how deep the chain already is.
+    let (quarter, half, full) = (
+        clones(STRESS_DEPTH / 4),
+        clones(STRESS_DEPTH / 2),
+        clones(STRESS_DEPTH),
+    );
+    assert_eq!(full - half, 2 * (half - quarter));
+
+    let source = format!("print {};", index_chain("a", STRESS_DEPTH, |_| "0".into()));
+    let start = Instant::now();
+    walk(&source);
+    assert!(start.elapsed().as_secs() < 5, "took {:?}", start.elapsed());
+}
+
+/// Wraps 0 in `STRESS_DEPTH` lists and defines `probe(i)`, which returns 0
+/// and counts a miss whenever it isn't the `i`th call.
+#[cfg(feature = "interpreter")]
+const PROBED: &str = "
+var nested = 0;
+repeat (DEPTH) nested = [nested];
+var calls = 0;
+var misses = 0;
+fun probe(i) {
+    calls = calls + 1;
+    misses = misses + (calls - i) * (calls - i);
+    return 0;
+}
+";
+
+#[cfg(feature = "interpreter")]
+#[test]
+fn test_index_chain_evaluates_innermost_first() {
+    let chain = index_chain("nested", STRESS_DEPTH, |level| {
+        format!("probe({})", level + 1)
+    });
+    let interpreter = run(&format!(
+        "{}var found = {};",
+        PROBED.replace("DEPTH", &STRESS_DEPTH.to_string()),
+        chain
+    ));
+    assert_eq!(lookup(&interpreter, "found"), Value::Number(0.0));
+    assert_eq!(
+        lookup(&interpreter, "calls"),
+        Value::Number(STRESS_DEPTH as f64)
+    );
+    assert_eq!(lookup(&interpreter, "misses"), Value::Number(0.0));
+}
+
+#[cfg(feature = "interpreter")]
+#[test]
+fn test_deep_chain_assignment_evaluates_each_index_once() {
+    let target = index_chain("nested", STRESS_DEPTH, |level| {
+        format!("probe({})", level + 1)
+    });
+    let check = index_chain("nested", STRESS_DEPTH, |_| "0".into());
+    let interpreter = run(&format!(
+        "{}{} = probe({}) + 7; var stored = {};",
+        PROBED.replace("DEPTH", &STRESS_DEPTH.to_string()),
+        target,
+        STRESS_DEPTH + 1,
+        check
+    ));
+    // The indexes in order and then the value, each exactly once.
+    assert_eq!(
+        lookup(&interpreter, "calls"),
+        Value::Number(STRESS_DEPTH as f64 + 1.0)
+    );
+    assert_eq!(lookup(&interpreter, "misses"), Value::Number(0.0));
+    assert_eq!(lookup(&interpreter, "stored"), Value::Number(7.0));
+}
+
+/// What the parser recurses into, nested `depth` levels deep.
+fn nested_shapes(depth: usize) -> Vec<(&'static str, String)> {
+    let repeat = |text: &str| text.repeat(depth);
+    vec![
+        (
+            "grouping",
+            format!("print {}1{};", repeat("("), repeat(")")),
+        ),
+        ("list", format!("print {}1{};", repeat("["), repeat("]"))),
+        (
+            "argument",
+            format!("print {}1{};", repeat("f("), repeat(")")),
+        ),
+        ("index", format!("print {}0{};", repeat("a["), repeat("]"))),
+        ("unary", format!("print {}1;", repeat("-"))),
+        ("power", format!("print 1{};", repeat(" ** 1"))),
+        ("assignment", format!("{}1;", repeat("a = "))),
+        ("block", format!("{}{}", repeat("{"), repeat("}"))),
+        ("repeat", format!("{}print 1;", repeat("repeat (1) "))),
+    ]
+}
+
+/// What the parser builds in a loop, `length` links long.
+fn chain_shapes(length: usize) -> Vec<(&'static str, String)> {
+    let repeat = |text: &str| text.repeat(length);
+    vec![
+        ("binary", format!("print 1{};", repeat(" + 1"))),
+        (
+            "index",
+            format!("print {};", index_chain("a", length, |_| "0".into())),
+        ),
+        ("property", format!("print a{};", repeat(".b"))),
+        ("call", format!("print f{};", repeat("()"))),
+    ]
+}
+
+#[test]
+fn test_limits_are_errors() {
+    for (shape, source) in nested_shapes(MAX_NESTING_DEPTH + 1) {
+        let error = parse(&source).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::NestingTooDeep, "{}", shape);
+        assert!(error.to_string().starts_with(&format!(
+            "\"Nested deeper than {} levels\"",
+            MAX_NESTING_DEPTH
+        )));
+    }
+    for (shape, source) in chain_shapes(MAX_EXPRESSION_DEPTH + 1) {
+        let error = parse(&source).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::NestingTooDeep, "{}", shape);
+        assert!(error.to_string().starts_with(&format!(
+            "\"Expression deeper than {} levels\"",
+            MAX_EXPRESSION_DEPTH
+        )));
+    }
+}
+
+#[test]
+fn test_pathological_nesting_is_error() {
+    for (shape, source) in nested_shapes(10_000) {
+        let error = parse(&source).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::NestingTooDeep, "{}", shape);
+    }
+
+    let parse_within = |source: &str, limit| {
+        let mut scanner = Scanner::new(source);
+        Parser::new(scanner.scan_tokens())
+            .with_max_nesting_depth(limit)
+            .parse()
+    };
+    let grouped = |depth| format!("print {}1{};", "(".repeat(depth), ")".repeat(depth));
+    assert!(parse_within(&grouped(6), 8).is_ok());
+    let error = parse_within(&grouped(7), 8).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("\"Nested deeper than 8 levels\""));
+}
+
+#[cfg(feature = "interpreter")]
+#[test]
+fn test_realistic_nesting_runs() {
+    let depth = 256;
+    let interpreter = run(&format!(
+        "var grouped = {}1{}; var inner = 0; {}inner = inner + 1;{}",
+        "(".repeat(depth),
+        ")".repeat(depth),
+        "{".repeat(depth),
+        "}".repeat(depth)
+    ));
+    assert_eq!(lookup(&interpreter, "grouped"), Value::Number(1.0));
+    assert_eq!(lookup(&interpreter, "inner"), Value::Number(1.0));
+}
+
+#[test]
+fn test_just_under_limits_is_walked() {
+    // The statement and `print` take the first two levels, and the index
+    // inside the last link of a chain one more.
+    for (_, source) in nested_shapes(MAX_NESTING_DEPTH - 2) {
+        walk(&source);
+    }
+    for (_, source) in chain_shapes(MAX_EXPRESSION_DEPTH - 3) {
+        walk(&source);
+    }
+}
+
+#[cfg(feature = "interpreter")]
+#[test]
+fn test_just_under_limits_runs() {
+    let interpreter = run(&format!(
+        "var sum = 0{}; var negated = {}1; var grouped = {}1{};",
+        " + 1".repeat(MAX_EXPRESSION_DEPTH - 2),
+        "-".repeat(MAX_NESTING_DEPTH - 2),
+        "(".repeat(MAX_NESTING_DEPTH - 2),
+        ")".repeat(MAX_NESTING_DEPTH - 2)
+    ));
+    assert_eq!(
+        lookup(&interpreter, "sum"),
+        Value::Number((MAX_EXPRESSION_DEPTH - 2) as f64)
+    );
+    assert_eq!(lookup(&interpreter, "negated"), Value::Number(1.0));
+    assert_eq!(lookup(&interpreter, "grouped"), Value::Number(1.0));
+}
+
+#[cfg(feature = "interpreter")]
+#[test]
+fn test_deep_tree_is_runtime_error() {
+    // Built by hand, as the parser wouldn't produce it.
+    let minus = Token::new(TokenTypes::Minus, "-".to_string(), None, 1);
+    let negated = |depth| {
+        (0..depth).fold(Expr::literal(LiteralValue::Number(1.0)), |expr, _| {
+            Expr::unary(minus.clone(), expr)
+        })
+    };
+    let stmts = vec![Stmt::print(vec![negated(10_000)])];
+    let error = Interpreter::default().interpret(&stmts).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::NestingTooDeep);
+    assert!(error.to_string().starts_with(&format!(
+        "\"Nested deeper than {} levels while running\"",
+        MAX_EVALUATION_DEPTH
+    )));
+
+    // The statement and `print` take two levels.
+    let stmts = vec![Stmt::var(
+        Token::new(TokenTypes::Identifier, "a".to_string(), None, 1),
+        Some(Box::new(negated(MAX_EVALUATION_DEPTH - 2))),
+    )];
+    let mut interpreter = Interpreter::default();
+    interpreter.interpret(&stmts).unwrap();
+    assert_eq!(lookup(&interpreter, "a"), Value::Number(1.0));
+    interpreter.set_max_depth(MAX_EVALUATION_DEPTH - 2);
+    assert!(interpreter.interpret(&stmts).is_err());
+}
+
+#[cfg(feature = "interpreter")]
+#[test]
+fn test_unbounded_recursion_is_runtime_error() {
+    let stmts = parse("fun f(n) { return f(n + 1); } f(0);").unwrap();
+    let mut interpreter = Interpreter::default();
+    interpreter.set_locals(Resolver::default().resolve(&stmts).unwrap().locals);
+    let error = interpreter.interpret(&stmts).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::StackOverflow);
+
+    // Deeper bodies run out of evaluation depth before the call depth.
+    let mut interpreter = Interpreter::default();
+    interpreter.set_locals(Resolver::default().resolve(&stmts).unwrap().locals);
+    interpreter.set_max_call_depth(MAX_EVALUATION_DEPTH);
+    let error = interpreter.interpret(&stmts).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::NestingTooDeep);
+
+    // Each call takes a few levels, realistic recursion stays well within.
+    let interpreter = run("fun f(n) { repeat (n) return f(n - 1) + 1; return 0; } var a = f(100);");
+    assert_eq!(lookup(&interpreter, "a"), Value::Number(100.0));
+
+    // The stack grows with the calls, even on a thread with a small one.
+    let deep = std::thread::Builder::new()
+        .stack_size(256 * 1024)
+        .spawn(|| {
+            let interpreter =
+                run("fun f(n) { if (n == 0) return 0; return f(n - 1) + 1; } var a = f(1000);");
+            lookup(&interpreter, "a") == Value::Number(1000.0)
+        })
+        .unwrap();
+    assert!(deep.join().unwrap());
+}
+
+/// Nested blocks, each assigning the variable it shadows from values read
+/// out of the enclosing scopes and a call writing a global.
+#[cfg(feature = "interpreter")]
+fn nested_assignments(depth: usize) -> String {
+    let mut source = String::from(
+        "var a = 1; var total = 0; var calls = 0; \
+         fun f(n) { calls = calls + 1; return n; } ",
+    );
+    for _ in 0..depth {
+        source.push_str(
+            "{ var x = a + 1; a = a + f(x); \
+             { var a = x * 2; a = a + f(x); total = total + a; ",
+        );
+    }
+    source.push_str(&"} }".repeat(depth));
+    source
+}
+
+#[cfg(feature = "interpreter")]
+#[test]
+fn test_nested_assignments_read_outer_and_shadowed() {
+    let depth = MAX_NESTING_DEPTH / 4;
+    let interpreter = run(&nested_assignments(depth));
+    let (mut a, mut total) = (1.0, 0.0);
+    for _ in 0..depth {
+        let x = a + 1.0;
+        a = x * 3.0;
+        total += a;
+    }
+    assert_eq!(lookup(&interpreter, "a"), Value::Number(3.0));
+    assert_eq!(lookup(&interpreter, "total"), Value::Number(total));
+    assert_eq!(
+        lookup(&interpreter, "calls"),
+        Value::Number(2.0 * depth as f64)
+    );
+}
+
+#[cfg(feature = "interpreter")]
+#[test]
+fn test_busy_environment_is_error() {
+    for source in ["var a = 1;", "a = 2;", "print a;", "{ var b = 1; b = a; }"] {
+        let stmts = parse(source).unwrap();
+        let mut interpreter = run("var a = 1;");
+        let environment = interpreter.environment.clone();
+        // Someone else writing to the globals, e.g. a host callback.
+        let writer = environment.borrow_mut();
+        let error = interpreter.interpret(&stmts).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::EnvironmentBusy, "{}", source);
+        drop(writer);
+        interpreter.interpret(&stmts).unwrap();
+    }
+}
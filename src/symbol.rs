@@ -0,0 +1,182 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    rc::Rc,
+};
+
+/// Interned symbols are only dropped once the table has grown past this many.
+const MIN_PRUNE_AT: usize = 1024;
+
+/// A string interned on this thread. Every symbol spelled the same shares
+/// one allocation, so cloning one bumps a count and comparing or hashing
+/// one only looks at the pointer, while it still reads as the original text.
+#[derive(Clone)]
+pub struct Symbol(Rc<str>);
+
+struct Interner {
+    symbols: HashSet<Rc<str>>,
+    /// Size at which symbols nothing else holds any more are dropped, so a
+    /// long session doesn't keep every name it ever saw.
+    prune_at: usize,
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner {
+        symbols: HashSet::new(),
+        prune_at: MIN_PRUNE_AT,
+    });
+}
+
+impl Symbol {
+    pub fn intern(text: &str) -> Self {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(symbol) = interner.symbols.get(text) {
+                return Symbol(symbol.clone());
+            }
+            if interner.symbols.len() >= interner.prune_at {
+                interner
+                    .symbols
+                    .retain(|symbol| Rc::strong_count(symbol) > 1);
+                interner.prune_at = MIN_PRUNE_AT.max(interner.symbols.len() * 2);
+            }
+            let symbol: Rc<str> = Rc::from(text);
+            interner.symbols.insert(symbol.clone());
+            Symbol(symbol)
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(Rc::as_ptr(&self.0) as *const u8, state)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Alphabetical, like the text.
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(text: &str) -> Self {
+        Symbol::intern(text)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(text: String) -> Self {
+        Symbol::intern(&text)
+    }
+}
+
+impl From<&Symbol> for Symbol {
+    fn from(symbol: &Symbol) -> Self {
+        symbol.clone()
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self.as_str(), f)
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        Ok(Symbol::intern(&text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning() {
+        let name = Symbol::intern("counter");
+        let again = Symbol::from("counter".to_string());
+        assert_eq!(name, again);
+        assert!(Rc::ptr_eq(&name.0, &again.0));
+        assert_ne!(name, Symbol::intern("count"));
+        assert_eq!(name, "counter");
+        assert_eq!(name.to_string(), "counter");
+        assert_eq!(format!("{:?}", name), "\"counter\"");
+        assert!(Symbol::intern("a") < Symbol::intern("b"));
+    }
+
+    #[test]
+    fn test_unused_symbols_pruned() {
+        let kept = Symbol::intern("kept");
+        for index in 0..MIN_PRUNE_AT * 4 {
+            Symbol::intern(&format!("name{}", index));
+        }
+        let size = INTERNER.with(|interner| interner.borrow().symbols.len());
+        assert!(size <= MIN_PRUNE_AT * 2, "{}", size);
+        assert_eq!(kept, Symbol::intern("kept"));
+    }
+}
@@ -1,19 +1,32 @@
-use crate::errors::{Error, JBreadErrors};
+use std::{fmt::Display, sync::Arc};
+
+use crate::{
+    errors::{Error, ErrorKind, JBreadErrors, JBreadResult},
+    SourceRef, Symbol,
+};
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenTypes {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
     Plus,
     Semicolon,
+    Colon,
     Slash,
     Star,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens.
     Bang,
@@ -24,26 +37,41 @@ pub enum TokenTypes {
     GreaterEqual,
     Less,
     LessEqual,
+    StarStar,
+    LessLess,
+    GreaterGreater,
+    Arrow,
 
     // Literals.
     Identifier,
     String,
+    /// A string with `${...}` interpolations, split by [`template_parts`](crate::template_parts).
+    TemplateString,
     Number,
     NaN,
+    Infinity,
 
     // Keywords.
     And,
+    Assert,
+    Case,
     Class,
+    Const,
+    Default,
+    Elif,
     Else,
     False,
     Fun,
     For,
     If,
+    Import,
     Nil,
     Or,
     Print,
+    Repeat,
     Return,
     Super,
+    Switch,
     This,
     True,
     Var,
@@ -52,21 +80,110 @@ pub enum TokenTypes {
     Eof,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Literal {
+/// A literal as written in the source. The interpreter computes with its
+/// own `Value`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LiteralValue {
     String(String),
-    Number(f64),
+    Number(#[cfg_attr(feature = "serde", serde(with = "serde_number"))] f64),
     Boolean(bool),
     NaN,
 }
 
-impl TryInto<f64> for Literal {
-    type Error = JBreadErrors;
+impl TokenTypes {
+    /// How an operator or punctuation token is spelled in the source.
+    pub fn symbol(&self) -> Option<&'static str> {
+        let symbol = match self {
+            TokenTypes::LeftParen => "(",
+            TokenTypes::RightParen => ")",
+            TokenTypes::LeftBrace => "{",
+            TokenTypes::RightBrace => "}",
+            TokenTypes::LeftBracket => "[",
+            TokenTypes::RightBracket => "]",
+            TokenTypes::Comma => ",",
+            TokenTypes::Dot => ".",
+            TokenTypes::Minus => "-",
+            TokenTypes::Plus => "+",
+            TokenTypes::Semicolon => ";",
+            TokenTypes::Slash => "/",
+            TokenTypes::Star => "*",
+            TokenTypes::Ampersand => "&",
+            TokenTypes::Pipe => "|",
+            TokenTypes::Caret => "^",
+            TokenTypes::Tilde => "~",
+            TokenTypes::Bang => "!",
+            TokenTypes::BangEqual => "!=",
+            TokenTypes::Equal => "=",
+            TokenTypes::EqualEqual => "==",
+            TokenTypes::Greater => ">",
+            TokenTypes::GreaterEqual => ">=",
+            TokenTypes::Less => "<",
+            TokenTypes::LessEqual => "<=",
+            TokenTypes::StarStar => "**",
+            TokenTypes::LessLess => "<<",
+            TokenTypes::GreaterGreater => ">>",
+            TokenTypes::Arrow => "->",
+            _ => return None,
+        };
+        Some(symbol)
+    }
+}
 
-    fn try_into(self) -> Result<f64, Self::Error> {
+/// JSON has no representation for non-finite floats, so `inf` and `-inf` are
+/// written as strings and everything else as a plain number.
+#[cfg(feature = "serde")]
+mod serde_number {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Number(f64),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(number: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if number.is_finite() {
+            serializer.serialize_f64(*number)
+        } else {
+            serializer.serialize_str(&number.to_string())
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(number) => Ok(number),
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Former name of [`LiteralValue`], kept so downstream code keeps compiling.
+#[deprecated(note = "renamed to `LiteralValue`")]
+pub type Literal = LiteralValue;
+
+impl LiteralValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LiteralValue::String(_) => "string",
+            LiteralValue::Number(_) => "number",
+            LiteralValue::Boolean(_) => "boolean",
+            LiteralValue::NaN => "NaN",
+        }
+    }
+
+    /// Like converting with `try_into`, without giving up the value.
+    pub fn to_number(&self) -> JBreadResult<f64> {
         match self {
-            Literal::Number(number) => Ok(number),
-            _ => Err(JBreadErrors::RunTimeException(Error::new(
+            LiteralValue::Number(number) => Ok(*number),
+            LiteralValue::NaN => Ok(f64::NAN),
+            other => Err(JBreadErrors::RunTimeException(Error::new(
+                ErrorKind::TypeMismatch {
+                    expected: "number".to_string(),
+                    found: other.type_name().to_string(),
+                },
+                0,
                 0,
                 "Number".to_string(),
                 "Cannot convert non-number to number".to_string(),
@@ -75,13 +192,50 @@ impl TryInto<f64> for Literal {
     }
 }
 
-impl TryInto<String> for Literal {
+impl Display for LiteralValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LiteralValue::String(string) => write!(f, "{}", string),
+            LiteralValue::Number(number) => write!(f, "{}", number),
+            LiteralValue::Boolean(boolean) => write!(f, "{}", boolean),
+            LiteralValue::NaN => write!(f, "NaN"),
+        }
+    }
+}
+
+/// NaN equals NaN here so ASTs holding it can still be compared.
+impl PartialEq for LiteralValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LiteralValue::String(left), LiteralValue::String(right)) => left == right,
+            (LiteralValue::Number(left), LiteralValue::Number(right)) => left == right,
+            (LiteralValue::Boolean(left), LiteralValue::Boolean(right)) => left == right,
+            (LiteralValue::NaN, LiteralValue::NaN) => true,
+            _ => false,
+        }
+    }
+}
+
+impl TryInto<f64> for LiteralValue {
+    type Error = JBreadErrors;
+
+    fn try_into(self) -> Result<f64, Self::Error> {
+        self.to_number()
+    }
+}
+
+impl TryInto<String> for LiteralValue {
     type Error = JBreadErrors;
 
     fn try_into(self) -> Result<String, Self::Error> {
         match self {
-            Literal::String(string) => Ok(string),
-            _ => Err(JBreadErrors::RunTimeException(Error::new(
+            LiteralValue::String(string) => Ok(string),
+            other => Err(JBreadErrors::RunTimeException(Error::new(
+                ErrorKind::TypeMismatch {
+                    expected: "string".to_string(),
+                    found: other.type_name().to_string(),
+                },
+                0,
                 0,
                 "String".to_string(),
                 "Cannot convert non-string to string".to_string(),
@@ -90,13 +244,18 @@ impl TryInto<String> for Literal {
     }
 }
 
-impl TryInto<bool> for Literal {
+impl TryInto<bool> for LiteralValue {
     type Error = JBreadErrors;
 
     fn try_into(self) -> Result<bool, Self::Error> {
         match self {
-            Literal::Boolean(boolean) => Ok(boolean),
-            _ => Err(JBreadErrors::RunTimeException(Error::new(
+            LiteralValue::Boolean(boolean) => Ok(boolean),
+            other => Err(JBreadErrors::RunTimeException(Error::new(
+                ErrorKind::TypeMismatch {
+                    expected: "boolean".to_string(),
+                    found: other.type_name().to_string(),
+                },
+                0,
                 0,
                 "Boolean".to_string(),
                 "Cannot convert non-boolean to boolean".to_string(),
@@ -105,26 +264,202 @@ impl TryInto<bool> for Literal {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Byte offsets of a token in the source, `end` being exclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+/// Where a token's line and column come from.
+#[derive(Debug, Clone)]
+pub enum Location {
+    /// Scanned from the source, they're looked up from the span.
+    Source(Arc<SourceRef>),
+    /// Made by hand or loaded from JSON, they're given.
+    Detached { line: u32, column: u32 },
+}
+
+// Tests count clones, see `src/budgets.rs`.
+#[derive(Debug)]
+#[cfg_attr(not(test), derive(Clone))]
 pub struct Token {
     pub token_type: TokenTypes,
-    pub lexeme: String,
-    pub literal: Option<Literal>,
-    pub line: u32,
+    pub lexeme: Symbol,
+    pub literal: Option<LiteralValue>,
+    pub location: Location,
+    pub span: Span,
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Tokens cloned on this thread.
+    pub(crate) static TOKEN_CLONES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+#[cfg(test)]
+impl Clone for Token {
+    fn clone(&self) -> Self {
+        TOKEN_CLONES.with(|clones| clones.set(clones.get() + 1));
+        Self {
+            token_type: self.token_type.clone(),
+            lexeme: self.lexeme.clone(),
+            literal: self.literal.clone(),
+            location: self.location.clone(),
+            span: self.span,
+        }
+    }
 }
 
 impl Token {
     pub fn new(
         token_type: TokenTypes,
-        lexeme: String,
-        literal: Option<Literal>,
+        lexeme: impl Into<Symbol>,
+        literal: Option<LiteralValue>,
         line: u32,
     ) -> Self {
         Self {
             token_type,
-            lexeme,
-            line,
+            lexeme: lexeme.into(),
+            literal,
+            location: Location::Detached { line, column: 0 },
+            span: Span::default(),
+        }
+    }
+
+    /// A token scanned from `span` of `source`.
+    pub fn scanned(
+        token_type: TokenTypes,
+        lexeme: impl Into<Symbol>,
+        literal: Option<LiteralValue>,
+        source: &Arc<SourceRef>,
+        span: Span,
+    ) -> Self {
+        Self {
+            token_type,
+            lexeme: lexeme.into(),
             literal,
+            location: Location::Source(source.clone()),
+            span,
+        }
+    }
+
+    pub fn with_position(mut self, column: u32, span: Span) -> Self {
+        if let Location::Detached { line, .. } = self.location {
+            self.location = Location::Detached { line, column };
         }
+        self.span = span;
+        self
+    }
+
+    /// The source scanned, `None` when made by hand.
+    pub fn source(&self) -> Option<&Arc<SourceRef>> {
+        match &self.location {
+            Location::Source(source) => Some(source),
+            Location::Detached { .. } => None,
+        }
+    }
+
+    pub fn line(&self) -> u32 {
+        self.position().0
+    }
+
+    /// 1-based column of the first character, 0 when unknown.
+    pub fn column(&self) -> u32 {
+        self.position().1
+    }
+
+    fn position(&self) -> (u32, u32) {
+        match &self.location {
+            Location::Source(source) => source.position(self.span.start),
+            Location::Detached { line, column } => (*line, *column),
+        }
+    }
+}
+
+/// Tokens are equal when they read the same at the same place, whether
+/// scanned or not.
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.token_type == other.token_type
+            && self.lexeme == other.lexeme
+            && self.literal == other.literal
+            && self.span == other.span
+            && self.position() == other.position()
+    }
+}
+
+/// The same fields as always, with the position looked up, so programs
+/// saved before tokens held their source still load.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedToken<L, S> {
+    token_type: TokenTypes,
+    lexeme: S,
+    literal: L,
+    line: u32,
+    column: u32,
+    span: Span,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Token {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let token = SerializedToken {
+            token_type: self.token_type.clone(),
+            lexeme: &self.lexeme,
+            literal: &self.literal,
+            line: self.line(),
+            column: self.column(),
+            span: self.span,
+        };
+        serde::Serialize::serialize(&token, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Token {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let token = SerializedToken::<Option<LiteralValue>, Symbol>::deserialize(deserializer)?;
+        Ok(Self {
+            token_type: token.token_type,
+            lexeme: token.lexeme,
+            literal: token.literal,
+            location: Location::Detached {
+                line: token.line,
+                column: token.column,
+            },
+            span: token.span,
+        })
+    }
+}
+
+impl Display for Token {
+    /// Compact form used when listing tokens, e.g. `Identifier(a)`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.token_type {
+            TokenTypes::Eof => write!(f, "Eof"),
+            _ => write!(f, "{:?}({})", self.token_type, self.lexeme),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_literal_alias() {
+        let value: Literal = Literal::Number(1.0);
+        assert_eq!(value, LiteralValue::Number(1.0));
+        assert_eq!(crate::Literal::NaN, crate::LiteralValue::NaN);
     }
 }
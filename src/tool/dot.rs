@@ -0,0 +1,404 @@
+use crate::{
+    ast::{Expr, Stmt, VisitorExpr, VisitorStmt},
+    token::LiteralValue,
+    AstNode, AstStmt,
+};
+
+/// Renders a program as a Graphviz digraph, one node per AST node. Nodes are
+/// numbered in the order they're visited, so the same program always gives
+/// the same graph.
+#[derive(Default)]
+pub struct AstDotPrinter {
+    next_id: usize,
+    output: String,
+}
+
+impl AstDotPrinter {
+    /// The digraph of `stmts`, all hanging off a `Program` node.
+    pub fn print_program(stmts: &[Stmt]) -> String {
+        let mut printer = Self::default();
+        let root = printer.node("Program");
+        for stmt in stmts {
+            let child = stmt.accept(&mut printer);
+            printer.edge(root, child, None);
+        }
+        format!("digraph ast {{\n{}}}\n", printer.output)
+    }
+
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.output
+            .push_str(&format!("  node{} [label=\"{}\"];\n", id, escape(label)));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: Option<&str>) {
+        match label {
+            Some(label) => self.output.push_str(&format!(
+                "  node{} -> node{} [label=\"{}\"];\n",
+                from,
+                to,
+                escape(label)
+            )),
+            None => self
+                .output
+                .push_str(&format!("  node{} -> node{};\n", from, to)),
+        }
+    }
+
+    /// A node labelled `label` with an edge to each of `children`.
+    fn branch(&mut self, label: &str, children: &[(&str, &Expr)]) -> usize {
+        let id = self.node(label);
+        for (edge, child) in children {
+            let child = child.accept(self);
+            self.edge(id, child, Some(edge));
+        }
+        id
+    }
+
+    /// A node labelled `label` with an edge to each of `exprs`, numbered.
+    fn list(&mut self, label: &str, exprs: &[Expr]) -> usize {
+        let id = self.node(label);
+        for (index, expr) in exprs.iter().enumerate() {
+            let child = expr.accept(self);
+            self.edge(id, child, Some(&index.to_string()));
+        }
+        id
+    }
+
+    fn statements(&mut self, id: usize, stmts: &[Stmt]) {
+        for stmt in stmts {
+            let child = stmt.accept(self);
+            self.edge(id, child, None);
+        }
+    }
+}
+
+/// Escapes `label` to sit between the quotes of a DOT string.
+fn escape(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    for chr in label.chars() {
+        match chr {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            chr => escaped.push(chr),
+        }
+    }
+    escaped
+}
+
+fn literal(value: &Option<LiteralValue>) -> String {
+    match value {
+        Some(LiteralValue::String(string)) => format!("\"{}\"", string),
+        Some(value) => value.to_string(),
+        None => "nil".to_string(),
+    }
+}
+
+impl VisitorExpr for AstDotPrinter {
+    type Result = usize;
+
+    fn visit_expr_binary(&mut self, expr: &crate::ast::Binary) -> usize {
+        let label = format!("Binary({})", expr.operator.lexeme);
+        self.branch(&label, &[("left", &expr.left), ("right", &expr.right)])
+    }
+
+    fn visit_expr_grouping(&mut self, expr: &crate::ast::Grouping) -> usize {
+        self.branch("Grouping", &[("expression", &expr.expression)])
+    }
+
+    fn visit_expr_literal(&mut self, expr: &crate::ast::Literal) -> usize {
+        self.node(&format!("Literal({})", literal(&expr.value)))
+    }
+
+    fn visit_expr_unary(&mut self, expr: &crate::ast::Unary) -> usize {
+        let label = format!("Unary({})", expr.operator.lexeme);
+        self.branch(&label, &[("right", &expr.right)])
+    }
+
+    fn visit_expr_variable(&mut self, expr: &crate::ast::Variable) -> usize {
+        self.node(&format!("Variable({})", expr.name.lexeme))
+    }
+
+    fn visit_expr_assign(&mut self, expr: &crate::ast::Assign) -> usize {
+        let label = format!("Assign({})", expr.name.lexeme);
+        self.branch(&label, &[("value", &expr.value)])
+    }
+
+    fn visit_expr_call(&mut self, expr: &crate::ast::Call) -> usize {
+        let id = self.branch("Call", &[("callee", &expr.callee)]);
+        for (index, argument) in expr.arguments.iter().enumerate() {
+            let child = argument.accept(self);
+            self.edge(id, child, Some(&format!("arg{}", index)));
+        }
+        id
+    }
+
+    fn visit_expr_get(&mut self, expr: &crate::ast::Get) -> usize {
+        let label = format!("Get({})", expr.name.lexeme);
+        self.branch(&label, &[("object", &expr.object)])
+    }
+
+    fn visit_expr_set(&mut self, expr: &crate::ast::Set) -> usize {
+        let label = format!("Set({})", expr.name.lexeme);
+        self.branch(&label, &[("object", &expr.object), ("value", &expr.value)])
+    }
+
+    fn visit_expr_list_literal(&mut self, expr: &crate::ast::ListLiteral) -> usize {
+        self.list("List", &expr.elements)
+    }
+
+    fn visit_expr_map_literal(&mut self, expr: &crate::ast::MapLiteral) -> usize {
+        let id = self.node("Map");
+        for entry in expr.entries.iter() {
+            let key = entry.key.accept(self);
+            self.edge(id, key, Some("key"));
+            let value = entry.value.accept(self);
+            self.edge(key, value, Some("value"));
+        }
+        id
+    }
+
+    fn visit_expr_index(&mut self, expr: &crate::ast::Index) -> usize {
+        self.branch("Index", &[("object", &expr.object), ("index", &expr.index)])
+    }
+
+    fn visit_expr_index_assign(&mut self, expr: &crate::ast::IndexAssign) -> usize {
+        self.branch(
+            "IndexAssign",
+            &[
+                ("object", &expr.object),
+                ("index", &expr.index),
+                ("value", &expr.value),
+            ],
+        )
+    }
+
+    fn visit_expr_this(&mut self, _expr: &crate::ast::This) -> usize {
+        self.node("This")
+    }
+
+    fn visit_expr_interpolation(&mut self, expr: &crate::ast::Interpolation) -> usize {
+        self.list("Interpolation", &expr.parts)
+    }
+
+    fn visit_expr_lambda(&mut self, expr: &crate::ast::Lambda) -> usize {
+        let params: Vec<&str> = expr
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+        let id = self.node(&format!("Lambda({})", params.join(", ")));
+        for body in expr.body.0.iter() {
+            let child = body.accept(self);
+            self.edge(id, child, Some("body"));
+        }
+        id
+    }
+}
+
+impl VisitorStmt for AstDotPrinter {
+    type Result = usize;
+
+    fn visit_stmt_expression(&mut self, stmt: &crate::ast::Expression) -> usize {
+        self.branch("Expression", &[("expression", &stmt.expression)])
+    }
+
+    fn visit_stmt_print(&mut self, stmt: &crate::ast::Print) -> usize {
+        match stmt.expressions.as_slice() {
+            [expression] => self.branch("Print", &[("expression", expression)]),
+            expressions => self.list("Print", expressions),
+        }
+    }
+
+    fn visit_stmt_assert(&mut self, stmt: &crate::ast::Assert) -> usize {
+        match &stmt.message {
+            Some(message) => self.branch(
+                "Assert",
+                &[("condition", &stmt.condition), ("message", message)],
+            ),
+            None => self.branch("Assert", &[("condition", &stmt.condition)]),
+        }
+    }
+
+    fn visit_stmt_var(&mut self, stmt: &crate::ast::Var) -> usize {
+        let label = format!("Var({})", stmt.name.lexeme);
+        match &stmt.initializer {
+            Some(initializer) => self.branch(&label, &[("initializer", initializer)]),
+            None => self.node(&label),
+        }
+    }
+
+    fn visit_stmt_const(&mut self, stmt: &crate::ast::Const) -> usize {
+        let label = format!("Const({})", stmt.name.lexeme);
+        self.branch(&label, &[("initializer", &stmt.initializer)])
+    }
+
+    fn visit_stmt_import(&mut self, stmt: &crate::ast::Import) -> usize {
+        self.node(&format!("Import({})", stmt.path.lexeme))
+    }
+
+    fn visit_stmt_multi_var(&mut self, stmt: &crate::ast::MultiVar) -> usize {
+        let names: Vec<&str> = stmt.names.iter().map(|name| name.lexeme.as_str()).collect();
+        self.list(&format!("Var({})", names.join(", ")), &stmt.initializers)
+    }
+
+    fn visit_stmt_multi_assign(&mut self, stmt: &crate::ast::MultiAssign) -> usize {
+        let targets: Vec<&str> = stmt
+            .targets
+            .iter()
+            .map(|target| target.name.lexeme.as_str())
+            .collect();
+        self.list(&format!("Assign({})", targets.join(", ")), &stmt.values)
+    }
+
+    fn visit_stmt_block(&mut self, stmt: &crate::ast::Block) -> usize {
+        let id = self.node("Block");
+        self.statements(id, &stmt.statements);
+        id
+    }
+
+    fn visit_stmt_switch(&mut self, stmt: &crate::ast::Switch) -> usize {
+        let id = self.branch("Switch", &[("subject", &stmt.subject)]);
+        for arm in stmt.arms.iter() {
+            let child = match &arm.value {
+                Some(value) => self.branch("Case", &[("value", value)]),
+                None => self.node("Default"),
+            };
+            self.statements(child, &arm.body);
+            self.edge(id, child, None);
+        }
+        id
+    }
+
+    fn visit_stmt_if(&mut self, stmt: &crate::ast::If) -> usize {
+        let id = self.branch("If", &[("condition", &stmt.condition)]);
+        let then_branch = stmt.then_branch.accept(self);
+        self.edge(id, then_branch, Some("then"));
+        if let Some(else_branch) = &stmt.else_branch {
+            let else_branch = else_branch.accept(self);
+            self.edge(id, else_branch, Some("else"));
+        }
+        id
+    }
+
+    fn visit_stmt_repeat(&mut self, stmt: &crate::ast::Repeat) -> usize {
+        let id = self.branch("Repeat", &[("count", &stmt.count)]);
+        let body = stmt.body.accept(self);
+        self.edge(id, body, Some("body"));
+        id
+    }
+
+    fn visit_stmt_function(&mut self, stmt: &crate::ast::Function) -> usize {
+        let params: Vec<&str> = stmt
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+        let id = self.node(&format!(
+            "Function({}({}))",
+            stmt.name.lexeme,
+            params.join(", ")
+        ));
+        for body in stmt.body.iter() {
+            let child = body.accept(self);
+            self.edge(id, child, Some("body"));
+        }
+        id
+    }
+
+    fn visit_stmt_return(&mut self, stmt: &crate::ast::Return) -> usize {
+        match &stmt.value {
+            Some(value) => self.branch("Return", &[("value", value)]),
+            None => self.node("Return"),
+        }
+    }
+
+    fn visit_stmt_class(&mut self, stmt: &crate::ast::Class) -> usize {
+        let id = self.node(&format!("Class({})", stmt.name.lexeme));
+        for method in stmt.methods.iter() {
+            let child = self.visit_stmt_function(method);
+            self.edge(id, child, Some("method"));
+        }
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::Parser, Scanner};
+
+    fn dot(source: &str) -> String {
+        let tokens = Scanner::new(source).scan().0;
+        AstDotPrinter::print_program(&Parser::new(&tokens).parse().unwrap())
+    }
+
+    #[test]
+    fn test_print_program() {
+        assert_eq!(
+            dot("{ var a = 1 + 2 * 3; print a - -a; }"),
+            "\
+digraph ast {
+  node0 [label=\"Program\"];
+  node1 [label=\"Block\"];
+  node2 [label=\"Var(a)\"];
+  node3 [label=\"Binary(+)\"];
+  node4 [label=\"Literal(1)\"];
+  node3 -> node4 [label=\"left\"];
+  node5 [label=\"Binary(*)\"];
+  node6 [label=\"Literal(2)\"];
+  node5 -> node6 [label=\"left\"];
+  node7 [label=\"Literal(3)\"];
+  node5 -> node7 [label=\"right\"];
+  node3 -> node5 [label=\"right\"];
+  node2 -> node3 [label=\"initializer\"];
+  node1 -> node2;
+  node8 [label=\"Print\"];
+  node9 [label=\"Binary(-)\"];
+  node10 [label=\"Variable(a)\"];
+  node9 -> node10 [label=\"left\"];
+  node11 [label=\"Unary(-)\"];
+  node12 [label=\"Variable(a)\"];
+  node11 -> node12 [label=\"right\"];
+  node9 -> node11 [label=\"right\"];
+  node8 -> node9 [label=\"expression\"];
+  node1 -> node8;
+  node0 -> node1;
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_escaped_labels() {
+        // Strings can't hold quotes in source, but can once built by hand.
+        let stmt = Stmt::print(vec![Expr::literal(LiteralValue::String(
+            r#"say "hi" \ bye"#.to_string(),
+        ))]);
+        let graph = AstDotPrinter::print_program(&[stmt]);
+        assert!(
+            graph.contains(r#"node2 [label="Literal(\"say \"hi\" \\ bye\")"];"#),
+            "{}",
+            graph
+        );
+        assert_eq!(escape("a\nb"), "a\\nb");
+    }
+
+    #[test]
+    fn test_repeat_edges() {
+        let graph = dot("repeat (2) print nil;");
+        assert!(
+            graph.contains("node1 -> node2 [label=\"count\"];"),
+            "{}",
+            graph
+        );
+        assert!(
+            graph.contains("node1 -> node3 [label=\"body\"];"),
+            "{}",
+            graph
+        );
+    }
+}
@@ -0,0 +1,709 @@
+use crate::{
+    ast::{
+        Assert, Assign, Binary, Block, Call, Class, Const, Expr, Expression, Function, Get,
+        Grouping, If, Import, Index, IndexAssign, Interpolation, Lambda, ListLiteral, Literal,
+        MapLiteral, MultiAssign, MultiVar, Print, Repeat, Return, Set, Stmt, Switch, This, Unary,
+        Var, Variable, VisitorExpr, VisitorStmt,
+    },
+    errors::{Error, ErrorKind, JBreadErrors, JBreadResult},
+    parser::Parser,
+    token::LiteralValue,
+    AstNode, AstStmt, Scanner, SourceRef, TokenTypes, UNNAMED_SOURCE,
+};
+
+const INDENT: &str = "    ";
+
+/// How tightly an expression binds, from the grammar's `assignment` rule
+/// down to `primary`. An operand binding looser than its place in the
+/// grammar needs parentheses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Assignment,
+    Equality,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Comparison,
+    Shift,
+    Term,
+    Factor,
+    Unary,
+    Power,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    fn of_operator(operator: &TokenTypes) -> Self {
+        match operator {
+            TokenTypes::EqualEqual | TokenTypes::BangEqual => Self::Equality,
+            TokenTypes::Pipe => Self::BitOr,
+            TokenTypes::Caret => Self::BitXor,
+            TokenTypes::Ampersand => Self::BitAnd,
+            TokenTypes::Greater
+            | TokenTypes::GreaterEqual
+            | TokenTypes::Less
+            | TokenTypes::LessEqual => Self::Comparison,
+            TokenTypes::LessLess | TokenTypes::GreaterGreater => Self::Shift,
+            TokenTypes::Minus | TokenTypes::Plus => Self::Term,
+            TokenTypes::Slash | TokenTypes::Star => Self::Factor,
+            _ => Self::Power,
+        }
+    }
+
+    /// The next tighter level, what the right operand of a left-associative
+    /// operator has to bind at.
+    fn tighter(self) -> Self {
+        match self {
+            Self::Assignment => Self::Equality,
+            Self::Equality => Self::BitOr,
+            Self::BitOr => Self::BitXor,
+            Self::BitXor => Self::BitAnd,
+            Self::BitAnd => Self::Comparison,
+            Self::Comparison => Self::Shift,
+            Self::Shift => Self::Term,
+            Self::Term => Self::Factor,
+            Self::Factor => Self::Unary,
+            Self::Unary => Self::Power,
+            Self::Power => Self::Call,
+            Self::Call | Self::Primary => Self::Primary,
+        }
+    }
+}
+
+/// An expression's source along with how tightly it binds.
+struct Operand {
+    text: String,
+    precedence: Precedence,
+}
+
+impl Operand {
+    fn new(text: String, precedence: Precedence) -> Self {
+        Self { text, precedence }
+    }
+}
+
+#[derive(Default)]
+struct Formatter {
+    output: String,
+    depth: usize,
+}
+
+/// The canonical source of `stmts`: four spaces per block level, one
+/// statement per line, spaces around binary operators and parentheses only
+/// where precedence needs them. Parsing the result gives the same
+/// statements, minus redundant groupings.
+pub fn format_program(stmts: &[Stmt]) -> String {
+    let mut formatter = Formatter::default();
+    formatter.statements(stmts);
+    formatter.output
+}
+
+/// Parses `source` and formats it. Comments aren't part of the AST, so
+/// rather than drop them a source with any fails to format.
+pub fn format_source(source: &str) -> JBreadResult<String> {
+    let source = SourceRef::new(UNNAMED_SOURCE, source);
+    let (tokens, errors) = Scanner::with_source(source.clone(), Default::default()).scan();
+    if let Some(error) = errors.into_iter().next() {
+        return Err(error);
+    }
+    let text = source.map().text();
+    let mut end = 0;
+    for token in &tokens {
+        let gap = &text[end..token.span.start];
+        if let Some(offset) = gap.find("//") {
+            let (line, column) = source.position(end + offset);
+            return Err(JBreadErrors::CompileError(Error::new(
+                ErrorKind::Unsupported,
+                line,
+                column,
+                "//".to_string(),
+                "Formatting would drop this comment".to_string(),
+            )));
+        }
+        end = token.span.end;
+    }
+    let stmts = Parser::new(&tokens).parse()?;
+    Ok(format_program(&stmts))
+}
+
+impl Formatter {
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.depth {
+            self.output.push_str(INDENT);
+        }
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    /// One statement per line, with functions and classes set apart by a
+    /// blank line.
+    fn statements(&mut self, stmts: &[Stmt]) {
+        for (index, stmt) in stmts.iter().enumerate() {
+            let declaration = matches!(stmt, Stmt::Function(_) | Stmt::Class(_));
+            let follows_declaration =
+                index > 0 && matches!(stmts[index - 1], Stmt::Function(_) | Stmt::Class(_));
+            if index > 0 && (declaration || follows_declaration) {
+                self.output.push('\n');
+            }
+            stmt.accept(self);
+        }
+    }
+
+    /// A block's statements one level deeper, closed on its own line.
+    fn body(&mut self, opening: &str, stmts: &[Stmt]) {
+        if stmts.is_empty() {
+            self.line(&format!("{}{{}}", opening));
+            return;
+        }
+        self.line(&format!("{}{{", opening));
+        self.depth += 1;
+        self.statements(stmts);
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    /// `opening` followed by `stmt` on the same line, a block's statements
+    /// going one level deeper.
+    fn clause(&mut self, opening: &str, stmt: &Stmt) {
+        match stmt {
+            Stmt::Block(block) => self.body(opening, &block.statements),
+            stmt => {
+                // Formatted on its own, then joined to the opening line.
+                let mut formatter = Formatter::default();
+                stmt.accept(&mut formatter);
+                let (first, rest) = formatter.output.split_once('\n').unwrap_or_default();
+                self.line(&format!("{}{}", opening, first));
+                for line in rest.lines() {
+                    self.line(line);
+                }
+            }
+        }
+    }
+
+    fn function(&mut self, keyword: &str, function: &Function) {
+        let params: Vec<&str> = function
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+        let opening = format!(
+            "{}{}({}) ",
+            keyword,
+            function.name.lexeme,
+            params.join(", ")
+        );
+        self.body(&opening, &function.body);
+    }
+
+    fn expr(&mut self, expr: &Expr) -> String {
+        self.operand(expr, Precedence::Assignment)
+    }
+
+    /// `expr` parenthesized if it binds looser than `at`.
+    fn operand(&mut self, expr: &Expr, at: Precedence) -> String {
+        let operand = expr.accept(self);
+        if operand.precedence < at {
+            format!("({})", operand.text)
+        } else {
+            operand.text
+        }
+    }
+
+    fn list(&mut self, exprs: &[Expr]) -> String {
+        let exprs: Vec<String> = exprs.iter().map(|expr| self.expr(expr)).collect();
+        exprs.join(", ")
+    }
+}
+
+impl VisitorExpr for Formatter {
+    type Result = Operand;
+
+    fn visit_expr_binary(&mut self, expr: &Binary) -> Operand {
+        let precedence = Precedence::of_operator(&expr.operator.token_type);
+        // `**` is right-associative and its left operand is a call.
+        // A comparison of a comparison only parses parenthesized.
+        let (left, right) = match precedence {
+            Precedence::Power => (Precedence::Call, Precedence::Unary),
+            Precedence::Comparison => (precedence.tighter(), precedence.tighter()),
+            _ => (precedence, precedence.tighter()),
+        };
+        let text = format!(
+            "{} {} {}",
+            self.operand(&expr.left, left),
+            expr.operator.lexeme,
+            self.operand(&expr.right, right)
+        );
+        Operand::new(text, precedence)
+    }
+
+    fn visit_expr_grouping(&mut self, expr: &Grouping) -> Operand {
+        match &*expr.expression {
+            // Kept, they mark an assignment used as a value on purpose.
+            Expr::Assign(_) | Expr::Set(_) | Expr::IndexAssign(_) => {
+                let text = format!("({})", self.expr(&expr.expression));
+                Operand::new(text, Precedence::Primary)
+            }
+            inner => inner.accept(self),
+        }
+    }
+
+    fn visit_expr_literal(&mut self, expr: &Literal) -> Operand {
+        let text = match &expr.value {
+            Some(LiteralValue::String(string)) => format!("\"{}\"", string),
+            Some(LiteralValue::Number(number)) => number.to_string(),
+            Some(LiteralValue::Boolean(boolean)) => boolean.to_string(),
+            Some(LiteralValue::NaN) => "NaN".to_string(),
+            None => "nil".to_string(),
+        };
+        // Only folded constants are negative, and they read as a negation.
+        let precedence = match text.starts_with('-') {
+            true => Precedence::Unary,
+            false => Precedence::Primary,
+        };
+        Operand::new(text, precedence)
+    }
+
+    fn visit_expr_unary(&mut self, expr: &Unary) -> Operand {
+        let right = self.operand(&expr.right, Precedence::Unary);
+        Operand::new(
+            format!("{}{}", expr.operator.lexeme, right),
+            Precedence::Unary,
+        )
+    }
+
+    fn visit_expr_variable(&mut self, expr: &Variable) -> Operand {
+        Operand::new(expr.name.lexeme.to_string(), Precedence::Primary)
+    }
+
+    fn visit_expr_assign(&mut self, expr: &Assign) -> Operand {
+        let text = format!("{} = {}", expr.name.lexeme, self.expr(&expr.value));
+        Operand::new(text, Precedence::Assignment)
+    }
+
+    fn visit_expr_call(&mut self, expr: &Call) -> Operand {
+        let callee = self.operand(&expr.callee, Precedence::Call);
+        let text = format!("{}({})", callee, self.list(&expr.arguments));
+        Operand::new(text, Precedence::Call)
+    }
+
+    fn visit_expr_get(&mut self, expr: &Get) -> Operand {
+        let object = self.operand(&expr.object, Precedence::Call);
+        Operand::new(format!("{}.{}", object, expr.name.lexeme), Precedence::Call)
+    }
+
+    fn visit_expr_set(&mut self, expr: &Set) -> Operand {
+        let object = self.operand(&expr.object, Precedence::Call);
+        let text = format!(
+            "{}.{} = {}",
+            object,
+            expr.name.lexeme,
+            self.expr(&expr.value)
+        );
+        Operand::new(text, Precedence::Assignment)
+    }
+
+    fn visit_expr_list_literal(&mut self, expr: &ListLiteral) -> Operand {
+        Operand::new(
+            format!("[{}]", self.list(&expr.elements)),
+            Precedence::Primary,
+        )
+    }
+
+    fn visit_expr_map_literal(&mut self, expr: &MapLiteral) -> Operand {
+        let entries: Vec<String> = expr
+            .entries
+            .iter()
+            .map(|entry| format!("{}: {}", self.expr(&entry.key), self.expr(&entry.value)))
+            .collect();
+        Operand::new(format!("{{{}}}", entries.join(", ")), Precedence::Primary)
+    }
+
+    fn visit_expr_index(&mut self, expr: &Index) -> Operand {
+        let object = self.operand(&expr.object, Precedence::Call);
+        let text = format!("{}[{}]", object, self.expr(&expr.index));
+        Operand::new(text, Precedence::Call)
+    }
+
+    fn visit_expr_index_assign(&mut self, expr: &IndexAssign) -> Operand {
+        let object = self.operand(&expr.object, Precedence::Call);
+        let text = format!(
+            "{}[{}] = {}",
+            object,
+            self.expr(&expr.index),
+            self.expr(&expr.value)
+        );
+        Operand::new(text, Precedence::Assignment)
+    }
+
+    fn visit_expr_interpolation(&mut self, expr: &Interpolation) -> Operand {
+        let mut text = String::from("\"");
+        for part in expr.parts.iter() {
+            match part {
+                // An interpolated string literal reads the same as text.
+                Expr::Literal(Literal {
+                    value: Some(LiteralValue::String(chunk)),
+                    ..
+                }) => text.push_str(chunk),
+                part => {
+                    let code = self.expr(part);
+                    text.push_str(&format!("${{{}}}", code));
+                }
+            }
+        }
+        text.push('"');
+        Operand::new(text, Precedence::Primary)
+    }
+
+    fn visit_expr_this(&mut self, _expr: &This) -> Operand {
+        Operand::new("this".to_string(), Precedence::Primary)
+    }
+
+    fn visit_expr_lambda(&mut self, expr: &Lambda) -> Operand {
+        let params: Vec<&str> = expr
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+        let params = params.join(", ");
+        // An arrow's expression body takes everything after it, so the arrow
+        // binds as loosely as an assignment.
+        if let [Stmt::Return(Return {
+            keyword,
+            value: Some(value),
+            ..
+        })] = expr.body.0.as_slice()
+        {
+            if keyword.token_type == TokenTypes::Arrow {
+                let text = format!("({}) -> {}", params, self.expr(value));
+                return Operand::new(text, Precedence::Assignment);
+            }
+        }
+        // The body's lines are indented as deep as the statement holding the
+        // lambda, whose line already has the first one's indentation.
+        let mut formatter = Formatter {
+            depth: self.depth,
+            ..Formatter::default()
+        };
+        formatter.body(&format!("fun ({}) ", params), &expr.body.0);
+        let text = formatter.output.trim().to_string();
+        Operand::new(text, Precedence::Primary)
+    }
+}
+
+impl VisitorStmt for Formatter {
+    type Result = ();
+
+    fn visit_stmt_expression(&mut self, stmt: &Expression) {
+        let expression = self.expr(&stmt.expression);
+        self.line(&format!("{};", expression));
+    }
+
+    fn visit_stmt_print(&mut self, stmt: &Print) {
+        let expressions = self.list(&stmt.expressions);
+        self.line(&format!("print {};", expressions));
+    }
+
+    fn visit_stmt_assert(&mut self, stmt: &Assert) {
+        let condition = self.expr(&stmt.condition);
+        match &stmt.message {
+            Some(message) => {
+                let message = self.expr(message);
+                self.line(&format!("assert {}, {};", condition, message))
+            }
+            None => self.line(&format!("assert {};", condition)),
+        }
+    }
+
+    fn visit_stmt_var(&mut self, stmt: &Var) {
+        match &stmt.initializer {
+            Some(initializer) => {
+                let initializer = self.expr(initializer);
+                self.line(&format!("var {} = {};", stmt.name.lexeme, initializer))
+            }
+            None => self.line(&format!("var {};", stmt.name.lexeme)),
+        }
+    }
+
+    fn visit_stmt_const(&mut self, stmt: &Const) {
+        let initializer = self.expr(&stmt.initializer);
+        self.line(&format!("const {} = {};", stmt.name.lexeme, initializer));
+    }
+
+    fn visit_stmt_import(&mut self, stmt: &Import) {
+        self.line(&format!("import {};", stmt.path.lexeme));
+    }
+
+    fn visit_stmt_multi_var(&mut self, stmt: &MultiVar) {
+        let names: Vec<&str> = stmt.names.iter().map(|name| name.lexeme.as_str()).collect();
+        match stmt.initializers.is_empty() {
+            true => self.line(&format!("var {};", names.join(", "))),
+            false => {
+                let initializers = self.list(&stmt.initializers);
+                self.line(&format!("var {} = {};", names.join(", "), initializers))
+            }
+        }
+    }
+
+    fn visit_stmt_multi_assign(&mut self, stmt: &MultiAssign) {
+        let targets: Vec<&str> = stmt
+            .targets
+            .iter()
+            .map(|target| target.name.lexeme.as_str())
+            .collect();
+        let values = self.list(&stmt.values);
+        self.line(&format!("{} = {};", targets.join(", "), values));
+    }
+
+    fn visit_stmt_block(&mut self, stmt: &Block) {
+        self.body("", &stmt.statements);
+    }
+
+    /// Arms one level deeper than the `switch`, their bodies another.
+    fn visit_stmt_switch(&mut self, stmt: &Switch) {
+        let subject = self.expr(&stmt.subject);
+        self.line(&format!("switch ({}) {{", subject));
+        self.depth += 1;
+        for arm in stmt.arms.iter() {
+            match &arm.value {
+                Some(value) => {
+                    let value = self.expr(value);
+                    self.line(&format!("case {}:", value))
+                }
+                None => self.line("default:"),
+            }
+            self.depth += 1;
+            self.statements(&arm.body);
+            self.depth -= 1;
+        }
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    fn visit_stmt_repeat(&mut self, stmt: &Repeat) {
+        let opening = format!("repeat ({}) ", self.expr(&stmt.count));
+        self.clause(&opening, &stmt.body);
+    }
+
+    /// An `else if` chain stays at the depth of its first `if`, each `else`
+    /// joining the `}` of the branch before it.
+    fn visit_stmt_if(&mut self, stmt: &If) {
+        let mut opening = format!("if ({}) ", self.expr(&stmt.condition));
+        let mut branch = stmt;
+        loop {
+            self.clause(&opening, &branch.then_branch);
+            let Some(else_branch) = &branch.else_branch else {
+                return;
+            };
+            let mut keyword = "else ".to_string();
+            if matches!(&*branch.then_branch, Stmt::Block(_)) {
+                // Taken back to go before the `else`.
+                let end = self.output.len() - 1;
+                let start = self.output[..end].rfind('\n').map_or(0, |start| start + 1);
+                keyword = format!("{} else ", self.output[start..end].trim_start());
+                self.output.truncate(start);
+            }
+            match &**else_branch {
+                Stmt::If(next) => {
+                    opening = format!("{}if ({}) ", keyword, self.expr(&next.condition));
+                    branch = next;
+                }
+                else_branch => return self.clause(&keyword, else_branch),
+            }
+        }
+    }
+
+    fn visit_stmt_function(&mut self, stmt: &Function) {
+        self.function("fun ", stmt);
+    }
+
+    fn visit_stmt_return(&mut self, stmt: &Return) {
+        match &stmt.value {
+            Some(value) => {
+                let value = self.expr(value);
+                self.line(&format!("return {};", value))
+            }
+            None => self.line("return;"),
+        }
+    }
+
+    fn visit_stmt_class(&mut self, stmt: &Class) {
+        self.line(&format!("class {} {{", stmt.name.lexeme));
+        self.depth += 1;
+        for (index, method) in stmt.methods.iter().enumerate() {
+            if index > 0 {
+                self.output.push('\n');
+            }
+            self.function("", method);
+        }
+        self.depth -= 1;
+        self.line("}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::AstPrinter;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let mut scanner = Scanner::new(source);
+        Parser::new(scanner.scan_tokens()).parse().unwrap()
+    }
+
+    fn format(source: &str) -> String {
+        format_program(&parse(source))
+    }
+
+    /// The shape of `stmts`, without positions and with groupings dropped,
+    /// which the formatter is free to remove.
+    fn structure(stmts: &[Stmt]) -> Vec<String> {
+        stmts
+            .iter()
+            .map(|stmt| {
+                let printed = AstPrinter::default().print_stmt(stmt);
+                let mut kept = Vec::new();
+                let mut result = String::new();
+                let mut rest = printed.as_str();
+                while let Some(chr) = rest.chars().next() {
+                    if let Some(after) = rest.strip_prefix("(group ") {
+                        kept.push(false);
+                        rest = after;
+                        continue;
+                    }
+                    match chr {
+                        '(' => kept.push(true),
+                        ')' if !kept.pop().unwrap_or(true) => {
+                            rest = &rest[1..];
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    result.push(chr);
+                    rest = &rest[chr.len_utf8()..];
+                }
+                result
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_minimal_parentheses() {
+        assert_eq!(format("(1 + 2) * 3;"), "(1 + 2) * 3;\n");
+        assert_eq!(format("1 + (2 * 3);"), "1 + 2 * 3;\n");
+        assert_eq!(format("1 - (2 - 3);"), "1 - (2 - 3);\n");
+        assert_eq!(format("(1 - 2) - 3;"), "1 - 2 - 3;\n");
+        assert_eq!(format("(a == b) == (c | d);"), "a == b == c | d;\n");
+        assert_eq!(format("(a == b) | c;"), "(a == b) | c;\n");
+        assert_eq!(format("2 ** (3 ** 2);"), "2 ** 3 ** 2;\n");
+        assert_eq!(format("(2 ** 3) ** 2;"), "(2 ** 3) ** 2;\n");
+        assert_eq!(format("(-2) ** 2;"), "(-2) ** 2;\n");
+        assert_eq!(format("-(2 ** 2);"), "-2 ** 2;\n");
+        assert_eq!(format("-(a + b);"), "-(a + b);\n");
+        assert_eq!(format("(f)(x)[0].y;"), "f(x)[0].y;\n");
+        assert_eq!(format("(a + b).c;"), "(a + b).c;\n");
+        assert_eq!(format("a = (b = 1);"), "a = (b = 1);\n");
+        assert_eq!(format("print 1 + (a = 2);"), "print 1 + (a = 2);\n");
+        assert_eq!(format("(a < b) < c;"), "(a < b) < c;\n");
+        assert_eq!(format("(a + b) < c == d;"), "a + b < c == d;\n");
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(
+            format(
+                "var a=1;fun f(x,y){ return x+y; }class A{ init(){this.a=[1,2];} m(){} } \
+                 repeat(2){ print f(a,2); } repeat ((a = 1)) print a; {}"
+            ),
+            "var a = 1;\n\
+             \n\
+             fun f(x, y) {\n    return x + y;\n}\n\
+             \n\
+             class A {\n    init() {\n        this.a = [1, 2];\n    }\n\n    m() {}\n}\n\
+             \n\
+             repeat (2) {\n    print f(a, 2);\n}\n\
+             repeat ((a = 1)) print a;\n\
+             {}\n"
+        );
+        assert_eq!(
+            format("var a, b = 1, 2.50; a, b = b, a; const c = 1e3; assert a != b, \"x\";"),
+            "var a, b = 1, 2.5;\na, b = b, a;\nconst c = 1000;\nassert a != b, \"x\";\n"
+        );
+        assert_eq!(format("print a,b+1 ,\"c\";"), "print a, b + 1, \"c\";\n");
+    }
+
+    #[test]
+    fn test_interpolation() {
+        assert_eq!(
+            format("print \"a ${ 1+2 } b ${ f( x ) }\";"),
+            "print \"a ${1 + 2} b ${f(x)}\";\n"
+        );
+    }
+
+    #[test]
+    fn test_lambdas() {
+        assert_eq!(
+            format("{ var f = fun(x){ print x; }; g((a,b)->a+b); }"),
+            "{\n    var f = fun (x) {\n        print x;\n    };\n    g((a, b) -> a + b);\n}\n"
+        );
+        assert_eq!(format("((x) -> x)(1);"), "((x) -> x)(1);\n");
+        assert_eq!(
+            format("f = () -> { return 1; };"),
+            "f = fun () {\n    return 1;\n};\n"
+        );
+    }
+
+    #[test]
+    fn test_switch() {
+        assert_eq!(
+            format("switch(x){case 1:print 1;print 2; default: {print 3;}}"),
+            "switch (x) {\n    case 1:\n        print 1;\n        print 2;\n    default:\n        {\n            print 3;\n        }\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_else_if_chain_stays_flat() {
+        assert_eq!(
+            format("if(a){print 1;}elif(b)print 2; else if (c) {} else {print 3;}"),
+            "if (a) {\n    print 1;\n} else if (b) print 2;\nelse if (c) {} else {\n    print 3;\n}\n"
+        );
+        assert_eq!(
+            format("{ if (a) if (b) print 1; else print 2; }"),
+            "{\n    if (a) if (b) print 1;\n    else print 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_comments_are_not_dropped() {
+        assert_eq!(format_source("print  1;").unwrap(), "print 1;\n");
+        let error = format_source("print 1;\n  // note\n").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Unsupported);
+        assert!(error.to_string().contains("at 2:3"), "{}", error);
+        assert!(format_source("print \"// kept\";").is_ok());
+    }
+
+    #[test]
+    fn test_round_trip_scripts() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/scripts");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let source = fs::read_to_string(&path).unwrap();
+            let mut scanner = Scanner::new(&source);
+            // Scripts checking parse errors have nothing to format.
+            let Ok(stmts) = Parser::new(scanner.scan_tokens()).parse() else {
+                continue;
+            };
+            let formatted = format_program(&stmts);
+            assert_eq!(
+                structure(&parse(&formatted)),
+                structure(&stmts),
+                "{}:\n{}",
+                path.display(),
+                formatted
+            );
+            assert_eq!(format(&formatted), formatted, "{}", path.display());
+        }
+    }
+}
@@ -84,6 +84,62 @@
 /// }
 /// //.. and so on for Unary
 /// ```
+///
+/// ## Constructors
+///
+/// A variant may name a constructor after its visitor method. It's generated
+/// on the enum, taking each field as anything that converts into it, so the
+/// boxing is done there:
+/// ```ignore
+/// [
+///     Binary {
+///         left: Box<Expr>,
+///         operator: Token,
+///         right: Box<Expr>
+///     },
+///     visit_binary_expr,
+///     binary
+/// ],
+/// // Expr::binary(left, operator, right) == Expr::Binary(Binary { .. })
+/// ```
+/// Every variant also gets `From<Binary> for Expr`.
+///
+/// ## Spans
+///
+/// Every struct gets a `span` field after the ones listed, where the node is
+/// in the source. Constructors leave it empty, the parser sets it with
+/// `with_span`, and `span()` on the enum reads it whatever the variant.
+/// [`StripSpans`](crate::StripSpans) empties the spans of a node and of every
+/// node under it, for comparing trees regardless of where they were parsed.
+/// Attributes for the field go in a `span [...]` after the enum name, e.g.
+/// `span [#[serde(default)]],` so trees saved without spans still load.
+///
+/// ## Field attributes
+///
+/// Attributes before a field are kept on it, e.g. a serde `alias` so trees
+/// saved before it was renamed still load.
+///
+/// ## Walkers
+///
+/// `walker Name` after the enum name generates a trait with the visitor's
+/// methods, each defaulting to walking the node's children. A pass overrides
+/// the ones it cares about and calls [`Walk::walk`](crate::Walk) on a node to
+/// start. When children are of another AST, that AST's walker goes after a
+/// colon, e.g. `walker StmtWalker: ExprWalker`.
+///
+/// ## Attaching attributes
+///
+/// Any outer attributes placed before the trait name are forwarded to every
+/// generated struct and to the enum, e.g. to add extra derives:
+/// ```ignore
+/// define_ast!(
+///     #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+///     AstNode,
+///     VisitorExpr,
+///     Expr,
+///     // ..variants
+/// )
+/// ```
 #[macro_export]
 macro_rules! define_ast {
     (
@@ -109,25 +165,32 @@ macro_rules! define_ast {
         )*
     };
 
+    // Forwards a bracketed group of attributes onto a single item.
+    (@attach [$($attr:tt)*] $($item:tt)*) => {
+        $($attr)*
+        $($item)*
+    };
+
     (
+        @visitor $attrs:tt $span_attrs:tt
         $trait_name:ident,
         $visitor_trait:ident,
         $enum_name:ident,
+        $(walker $walker:ident $(: $walker_super:path)?,)?
         $([
             $name:ident {
-                $($field:ident: $type:ty),*
+                $($(#[$field_meta:meta])* $field:ident: $type:ty),*
             },
-            $method_name:ident],
+            $method_name:ident
+            $(, $constructor:ident)?
+            $(,)?],
         )+
     )
     => {
         // Generate the structs
-        $(
-        #[derive(Debug, Clone, PartialEq)]
-        pub struct $name {
-            $(pub $field: $type),*
-        }
-        )*
+        $($crate::define_ast!(
+            @struct $attrs $span_attrs $name { $($(#[$field_meta])* $field: $type),* }
+        );)*
 
         // Implement trait for each struct
         $(impl $trait_name for $name {
@@ -143,18 +206,293 @@ macro_rules! define_ast {
         }
 
         // Create an Enum for the structs
-        #[derive(Debug, Clone, PartialEq)]
-        pub enum $enum_name {
-            $($name($name),)*
-        }
+        $crate::define_ast!(@attach $attrs
+            #[derive(Debug, Clone, PartialEq)]
+            pub enum $enum_name {
+                $($name($name),)*
+            }
+        );
 
         // Implement trait for the enum
         impl $trait_name for $enum_name {
             fn accept<V: $visitor_trait>(&self, visitor: &mut V) -> V::Result {
-                match self {
+                $crate::stack::grow(|| match self {
                     $(Self::$name(val) => visitor.$method_name(val),)*
+                })
+            }
+        }
+
+        $(impl From<$name> for $enum_name {
+            fn from(node: $name) -> Self {
+                Self::$name(node)
+            }
+        })*
+
+        impl $enum_name {
+            /// Where the node is in the source, empty when it wasn't parsed
+            /// from one.
+            pub fn span(&self) -> $crate::Span {
+                match self {
+                    $(Self::$name(node) => node.span,)*
+                }
+            }
+
+            /// The node, placed at `span`.
+            pub fn with_span(mut self, span: $crate::Span) -> Self {
+                match &mut self {
+                    $(Self::$name(node) => node.span = span,)*
+                }
+                self
+            }
+        }
+
+        $(impl $crate::StripSpans for $name {
+            fn strip_spans(&mut self) {
+                self.span = $crate::Span::default();
+                $($crate::StripSpans::strip_spans(&mut self.$field);)*
+            }
+        })*
+
+        impl $crate::StripSpans for $enum_name {
+            fn strip_spans(&mut self) {
+                match self {
+                    $(Self::$name(node) => $crate::StripSpans::strip_spans(node),)*
+                }
+            }
+        }
+
+        $($crate::define_ast!(
+            @constructor $enum_name, $name, [$($constructor)?], { $($field: $type),* }
+        );)*
+
+        $crate::define_ast!(
+            @walker [$($walker $(: $walker_super)?)?], $enum_name,
+            $([$name { $($field),* }, $method_name],)+
+        );
+    };
+
+    (
+        @struct [$($attr:tt)*] [$(#[$span_meta:meta])*]
+        $name:ident { $($(#[$field_meta:meta])* $field:ident: $type:ty),* }
+    ) => {
+        $($attr)*
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct $name {
+            $($(#[$field_meta])* pub $field: $type,)*
+            $(#[$span_meta])*
+            pub span: $crate::Span,
+        }
+    };
+
+    (@constructor $enum_name:ident, $name:ident, [], $fields:tt) => {};
+
+    (
+        @constructor $enum_name:ident, $name:ident, [$constructor:ident],
+        { $($field:ident: $type:ty),* }
+    ) => {
+        impl $enum_name {
+            pub fn $constructor($($field: impl Into<$type>),*) -> Self {
+                Self::$name($name {
+                    $($field: $field.into(),)*
+                    span: $crate::Span::default(),
+                })
+            }
+        }
+    };
+
+    (@walker [], $($rest:tt)*) => {};
+
+    (
+        @walker [$walker:ident $(: $walker_super:path)?], $enum_name:ident,
+        $([$name:ident { $($field:ident),* }, $method_name:ident],)+
+    ) => {
+        pub trait $walker: Sized $(+ $walker_super)? {
+            $(fn $method_name(&mut self, node: &$name) {
+                $($crate::Walk::walk(&node.$field, self);)*
+            })*
+        }
+
+        $(impl<W: $walker> $crate::Walk<W> for $name {
+            fn walk(&self, walker: &mut W) {
+                walker.$method_name(self)
+            }
+        })*
+
+        impl<W: $walker> $crate::Walk<W> for $enum_name {
+            fn walk(&self, walker: &mut W) {
+                match self {
+                    $(Self::$name(node) => walker.$method_name(node),)*
                 }
             }
         }
     };
+
+    (
+        $(#[$meta:meta])*
+        $trait_name:ident,
+        $visitor_trait:ident,
+        $enum_name:ident,
+        span [$(#[$span_meta:meta])*],
+        $($variants:tt)+
+    ) => {
+        $crate::define_ast!(
+            @visitor [$(#[$meta])*] [$(#[$span_meta])*]
+            $trait_name,
+            $visitor_trait,
+            $enum_name,
+            $($variants)+
+        );
+    };
+
+    (
+        $(#[$meta:meta])*
+        $trait_name:ident,
+        $visitor_trait:ident,
+        $enum_name:ident,
+        $($variants:tt)+
+    ) => {
+        $crate::define_ast!(
+            @visitor [$(#[$meta])*] []
+            $trait_name,
+            $visitor_trait,
+            $enum_name,
+            $($variants)+
+        );
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ast::{ExprWalker, StmtWalker, Variable},
+        parser::Parser,
+        Scanner, Span, StripSpans, Token, TokenTypes, Walk,
+    };
+
+    pub trait ShapeNode {
+        fn accept<V: VisitorShape>(&self, visitor: &mut V) -> V::Result;
+    }
+
+    define_ast!(
+        ShapeNode,
+        VisitorShape,
+        Shape,
+        walker ShapeWalker,
+        [Square { name: Token }, visit_square, square],
+        [
+            Group {
+                shapes: Vec<Shape>,
+                name: Token
+            },
+            visit_group,
+            group
+        ],
+        // No constructor, it's built through `From`.
+        [Frame { inner: Box<Shape> }, visit_frame],
+    );
+
+    fn name(lexeme: &str) -> Token {
+        Token::new(TokenTypes::Identifier, lexeme.to_string(), None, 1)
+    }
+
+    /// Counts the squares by hand, visiting every node itself.
+    struct Counter;
+
+    impl VisitorShape for Counter {
+        type Result = usize;
+
+        fn visit_square(&mut self, _shape: &Square) -> usize {
+            1
+        }
+
+        fn visit_group(&mut self, shape: &Group) -> usize {
+            shape.shapes.iter().map(|shape| shape.accept(self)).sum()
+        }
+
+        fn visit_frame(&mut self, shape: &Frame) -> usize {
+            shape.inner.accept(self)
+        }
+    }
+
+    /// Collects the squares' names, leaving the rest to the walker.
+    #[derive(Default)]
+    struct Squares(Vec<String>);
+
+    impl ShapeWalker for Squares {
+        fn visit_square(&mut self, shape: &Square) {
+            self.0.push(shape.name.lexeme.to_string());
+        }
+    }
+
+    #[test]
+    fn test_generated_ast() {
+        let shape = Shape::group(
+            vec![
+                Shape::square(name("a")),
+                Frame {
+                    inner: Box::new(Shape::group(vec![Shape::square(name("b"))], name("inner"))),
+                    span: Span::new(4, 9),
+                }
+                .into(),
+                Shape::square(name("c")),
+            ],
+            name("outer"),
+        );
+        match &shape {
+            Shape::Group(Group { shapes, name, .. }) => {
+                assert_eq!(shapes.len(), 3);
+                assert_eq!(name.lexeme, "outer");
+                assert_eq!(shapes[0], Shape::square(self::name("a")));
+            }
+            shape => panic!("Expected a group, got {:?}", shape),
+        }
+        assert_eq!(shape.accept(&mut Counter), 3);
+
+        let mut squares = Squares::default();
+        shape.walk(&mut squares);
+        assert_eq!(squares.0, ["a", "b", "c"]);
+
+        // Spans are left empty by constructors and emptied all the way down.
+        let mut shape = shape.with_span(Span::new(0, 12));
+        assert_eq!(shape.span(), Span::new(0, 12));
+        let Shape::Group(group) = &shape else {
+            unreachable!()
+        };
+        assert_eq!(group.shapes[0].span(), Span::default());
+        assert_eq!(group.shapes[1].span(), Span::new(4, 9));
+        shape.strip_spans();
+        let Shape::Group(group) = &shape else {
+            unreachable!()
+        };
+        assert_eq!(group.span, Span::default());
+        assert_eq!(group.shapes[1].span(), Span::default());
+    }
+
+    /// Collects the variables read, whichever statement they're in.
+    #[derive(Default)]
+    struct Reads(Vec<String>);
+
+    impl ExprWalker for Reads {
+        fn visit_expr_variable(&mut self, expr: &Variable) {
+            self.0.push(expr.name.lexeme.to_string());
+        }
+    }
+
+    impl StmtWalker for Reads {}
+
+    #[test]
+    fn test_walking_expressions_in_statements() {
+        let mut scanner = Scanner::new(
+            "var a = b; fun f(x) { return [x, c[d]]; } a, e = g.h, i = 1; \
+             class C { m() { repeat (n) print this.p; } }",
+        );
+        let stmts = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut reads = Reads::default();
+        stmts.walk(&mut reads);
+        assert_eq!(
+            reads.0,
+            ["b", "x", "c", "d", "a", "e", "g", "n"],
+            "assignment targets are walked as the variables they are"
+        );
+    }
 }
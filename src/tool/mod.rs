@@ -1,7 +1,12 @@
+mod dot;
+mod format;
 mod generate_ast;
 mod print_ast;
+mod reference;
 mod traits;
 
-pub use generate_ast::*;
+pub use dot::*;
+pub use format::*;
 pub use print_ast::*;
+pub use reference::*;
 pub use traits::*;
@@ -1,10 +1,16 @@
+use std::sync::Arc;
+
 use crate::{
-    ast::{Binary, Grouping, Literal, Stmt, Unary, VisitorExpr, VisitorStmt},
-    token::Literal as LiteralEnum,
-    AstNode,
+    ast::{Binary, Expr, Grouping, Literal, Stmt, Unary, VisitorExpr, VisitorStmt},
+    token::LiteralValue,
+    AstNode, AstStmt, SourceRef, Span,
 };
 
-pub struct AstPrinter {}
+#[derive(Default)]
+pub struct AstPrinter {
+    /// Where the printed nodes come from, when their spans are shown.
+    spans: Option<Arc<SourceRef>>,
+}
 
 impl VisitorExpr for AstPrinter {
     type Result = String;
@@ -23,10 +29,10 @@ impl VisitorExpr for AstPrinter {
     fn visit_expr_literal(&mut self, expr: &Literal) -> String {
         if let Some(literal) = &expr.value {
             match literal {
-                LiteralEnum::String(s) => s.clone(),
-                LiteralEnum::Number(n) => n.to_string(),
-                LiteralEnum::Boolean(boolean) => boolean.to_string(),
-                LiteralEnum::NaN => "NaN".to_string(),
+                LiteralValue::String(s) => s.clone(),
+                LiteralValue::Number(n) => n.to_string(),
+                LiteralValue::Boolean(boolean) => boolean.to_string(),
+                LiteralValue::NaN => "NaN".to_string(),
             }
         } else {
             "nil".to_string()
@@ -38,91 +44,381 @@ impl VisitorExpr for AstPrinter {
     }
 
     fn visit_expr_variable(&mut self, expr: &crate::ast::Variable) -> Self::Result {
-        todo!()
+        expr.name.lexeme.to_string()
     }
 
     fn visit_expr_assign(&mut self, expr: &crate::ast::Assign) -> Self::Result {
-        todo!()
+        let name = format!("= {}", expr.name.lexeme);
+        self.parenthesize(&name, vec![expr.value.clone()])
+    }
+
+    fn visit_expr_get(&mut self, expr: &crate::ast::Get) -> Self::Result {
+        let name = format!(". {}", expr.name.lexeme);
+        self.parenthesize(&name, vec![expr.object.clone()])
+    }
+
+    fn visit_expr_set(&mut self, expr: &crate::ast::Set) -> Self::Result {
+        let name = format!(".= {}", expr.name.lexeme);
+        self.parenthesize(&name, vec![expr.object.clone(), expr.value.clone()])
+    }
+
+    fn visit_expr_list_literal(&mut self, expr: &crate::ast::ListLiteral) -> Self::Result {
+        let mut result = String::from("(list");
+        for element in expr.elements.iter() {
+            result.push(' ');
+            result.push_str(&self.expr(element));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_expr_map_literal(&mut self, expr: &crate::ast::MapLiteral) -> Self::Result {
+        let mut result = String::from("(map");
+        for entry in expr.entries.iter() {
+            result.push_str(&format!(
+                " ({} {})",
+                self.expr(&entry.key),
+                self.expr(&entry.value)
+            ));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_expr_index(&mut self, expr: &crate::ast::Index) -> Self::Result {
+        self.parenthesize("index", vec![expr.object.clone(), expr.index.clone()])
+    }
+
+    fn visit_expr_index_assign(&mut self, expr: &crate::ast::IndexAssign) -> Self::Result {
+        self.parenthesize(
+            "index=",
+            vec![expr.object.clone(), expr.index.clone(), expr.value.clone()],
+        )
+    }
+
+    fn visit_expr_this(&mut self, _expr: &crate::ast::This) -> Self::Result {
+        "this".to_string()
+    }
+
+    fn visit_expr_interpolation(&mut self, expr: &crate::ast::Interpolation) -> Self::Result {
+        let mut result = String::from("(interpolate");
+        for part in expr.parts.iter() {
+            result.push(' ');
+            result.push_str(&self.expr(part));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_expr_lambda(&mut self, expr: &crate::ast::Lambda) -> Self::Result {
+        let params: Vec<&str> = expr
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+        let mut result = format!("(lambda ({})", params.join(" "));
+        for stmt in expr.body.0.iter() {
+            result.push(' ');
+            result.push_str(&self.stmt(stmt));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_expr_call(&mut self, expr: &crate::ast::Call) -> Self::Result {
+        let mut result = format!("(call {}", self.expr(&expr.callee));
+        for argument in expr.arguments.iter() {
+            result.push(' ');
+            result.push_str(&self.expr(argument));
+        }
+        result.push(')');
+        result
     }
 }
 
 impl VisitorStmt for AstPrinter {
-    type Result = Stmt;
+    type Result = String;
 
     fn visit_stmt_expression(&mut self, expr: &crate::ast::Expression) -> Self::Result {
-        todo!()
+        self.parenthesize("expr", vec![expr.expression.clone()])
     }
 
     fn visit_stmt_print(&mut self, expr: &crate::ast::Print) -> Self::Result {
-        todo!()
+        let mut result = String::from("(print");
+        for expression in expr.expressions.iter() {
+            result.push(' ');
+            result.push_str(&self.expr(expression));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_stmt_assert(&mut self, expr: &crate::ast::Assert) -> Self::Result {
+        let mut exprs = vec![expr.condition.clone()];
+        exprs.extend(expr.message.clone());
+        self.parenthesize("assert", exprs)
     }
 
     fn visit_stmt_var(&mut self, expr: &crate::ast::Var) -> Self::Result {
-        todo!()
+        let name = format!("var {}", expr.name.lexeme);
+        match &expr.initializer {
+            Some(initializer) => self.parenthesize(&name, vec![initializer.clone()]),
+            None => format!("({})", name),
+        }
+    }
+
+    fn visit_stmt_const(&mut self, expr: &crate::ast::Const) -> Self::Result {
+        let name = format!("const {}", expr.name.lexeme);
+        self.parenthesize(&name, vec![expr.initializer.clone()])
+    }
+
+    fn visit_stmt_import(&mut self, expr: &crate::ast::Import) -> Self::Result {
+        format!("(import {})", expr.path.lexeme)
+    }
+
+    fn visit_stmt_multi_var(&mut self, expr: &crate::ast::MultiVar) -> Self::Result {
+        let names: Vec<&str> = expr.names.iter().map(|name| name.lexeme.as_str()).collect();
+        let mut result = format!("(var ({})", names.join(" "));
+        for initializer in expr.initializers.iter() {
+            result.push(' ');
+            result.push_str(&self.expr(initializer));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_stmt_multi_assign(&mut self, expr: &crate::ast::MultiAssign) -> Self::Result {
+        let targets: Vec<&str> = expr
+            .targets
+            .iter()
+            .map(|target| target.name.lexeme.as_str())
+            .collect();
+        let mut result = format!("(= ({})", targets.join(" "));
+        for value in expr.values.iter() {
+            result.push(' ');
+            result.push_str(&self.expr(value));
+        }
+        result.push(')');
+        result
     }
 
     fn visit_stmt_block(&mut self, expr: &crate::ast::Block) -> Self::Result {
-        todo!()
+        let mut result = String::from("(block");
+        for stmt in expr.statements.iter() {
+            result.push(' ');
+            result.push_str(&self.stmt(stmt));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_stmt_switch(&mut self, expr: &crate::ast::Switch) -> Self::Result {
+        let mut result = format!("(switch {}", self.expr(&expr.subject));
+        for arm in expr.arms.iter() {
+            match &arm.value {
+                Some(value) => result.push_str(&format!(" (case {}", self.expr(value))),
+                None => result.push_str(" (default"),
+            }
+            for stmt in arm.body.iter() {
+                result.push(' ');
+                result.push_str(&self.stmt(stmt));
+            }
+            result.push(')');
+        }
+        result.push(')');
+        result
+    }
+
+    /// An `else if` chain stays flat, e.g. `(if a (print 1) elif b (print 2)
+    /// else (print 3))`.
+    fn visit_stmt_if(&mut self, expr: &crate::ast::If) -> Self::Result {
+        let mut result = format!(
+            "(if {} {}",
+            self.expr(&expr.condition),
+            self.stmt(&expr.then_branch)
+        );
+        let mut else_branch = &expr.else_branch;
+        while let Some(branch) = else_branch {
+            match &**branch {
+                Stmt::If(next) => {
+                    result.push_str(&format!(
+                        " elif {} {}",
+                        self.expr(&next.condition),
+                        self.stmt(&next.then_branch)
+                    ));
+                    else_branch = &next.else_branch;
+                }
+                branch => {
+                    result.push_str(&format!(" else {}", self.stmt(branch)));
+                    break;
+                }
+            }
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_stmt_repeat(&mut self, expr: &crate::ast::Repeat) -> Self::Result {
+        let count = self.expr(&expr.count);
+        format!("(repeat {} {})", count, self.stmt(&expr.body))
+    }
+
+    fn visit_stmt_function(&mut self, expr: &crate::ast::Function) -> Self::Result {
+        let params: Vec<&str> = expr
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect();
+        let mut result = format!("(fun {} ({})", expr.name.lexeme, params.join(" "));
+        for stmt in expr.body.iter() {
+            result.push(' ');
+            result.push_str(&self.stmt(stmt));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_stmt_class(&mut self, expr: &crate::ast::Class) -> Self::Result {
+        let mut result = format!("(class {}", expr.name.lexeme);
+        for method in expr.methods.iter() {
+            result.push(' ');
+            let printed = self.visit_stmt_function(method);
+            result.push_str(&self.locate(printed, method.span));
+        }
+        result.push(')');
+        result
+    }
+
+    fn visit_stmt_return(&mut self, expr: &crate::ast::Return) -> Self::Result {
+        match &expr.value {
+            Some(value) => self.parenthesize("return", vec![value.clone()]),
+            None => "(return)".to_string(),
+        }
     }
 }
 
 impl AstPrinter {
-    pub fn print<'b, T: AstNode>(&'b mut self, expr: T) -> String {
-        expr.accept(self).into()
+    /// A printer that marks each node with where it is in `source`, e.g.
+    /// `(+ @1:7-1:11 1@1:7-1:7 2@1:11-1:11)`. Nodes without a span are left
+    /// bare.
+    pub fn with_spans(source: Arc<SourceRef>) -> Self {
+        Self {
+            spans: Some(source),
+        }
     }
 
-    pub fn parenthesize<'b, T: AstNode>(&'b mut self, name: &str, exprs: Vec<Box<T>>) -> String {
+    pub fn print(&mut self, expr: Expr) -> String {
+        self.expr(&expr)
+    }
+
+    pub fn print_stmt(&mut self, stmt: &Stmt) -> String {
+        self.stmt(stmt)
+    }
+
+    pub fn parenthesize(&mut self, name: &str, exprs: Vec<Box<Expr>>) -> String {
         let mut result = String::new();
-        result.push_str("(");
+        result.push('(');
         result.push_str(name);
         for expr in exprs.iter() {
-            result.push_str(" ");
-            result.push_str(expr.accept(self).as_str());
+            result.push(' ');
+            result.push_str(self.expr(expr).as_str());
         }
-        result.push_str(")");
+        result.push(')');
         result
     }
-}
 
-impl Default for AstPrinter {
-    fn default() -> Self {
-        Self {}
+    fn expr(&mut self, expr: &Expr) -> String {
+        let printed = expr.accept(self);
+        self.locate(printed, expr.span())
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> String {
+        let printed = stmt.accept(self);
+        self.locate(printed, stmt.span())
+    }
+
+    /// `printed` marked with the first and last character `span` covers,
+    /// after the name of a parenthesized node and after a leaf.
+    fn locate(&self, mut printed: String, span: Span) -> String {
+        let Some(source) = &self.spans else {
+            return printed;
+        };
+        if span.end <= span.start {
+            return printed;
+        }
+        let (line, column) = source.position(span.start);
+        let (end_line, end_column) = source.position(span.end - 1);
+        let location = format!("@{}:{}-{}:{}", line, column, end_line, end_column);
+        if printed.starts_with('(') {
+            let name_end = printed.find([' ', ')']).unwrap_or(printed.len());
+            printed.insert_str(name_end, &format!(" {}", location));
+        } else {
+            printed.push_str(&location);
+        }
+        printed
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{ast::Expr, Token, TokenTypes};
+    use crate::{
+        ast::{Expr, Stmt},
+        Location, Span, Token, TokenTypes,
+    };
 
     #[test]
     fn test_creation() {
-        let expr = Expr::Binary(Binary {
-            right: Box::new(Expr::Binary(Binary {
-                right: Box::new(Expr::Literal(Literal {
-                    value: Some(LiteralEnum::Number(2.0)),
-                })),
-                operator: Token {
-                    token_type: TokenTypes::Minus,
-                    lexeme: "-".to_string(),
-                    literal: None,
-                    line: 1,
-                },
-                left: Box::new(Expr::Literal(Literal {
-                    value: Some(LiteralEnum::Number(1.0)),
-                })),
-            })),
-            operator: Token {
+        let expr = Expr::binary(
+            Expr::literal(LiteralValue::Number(2.0)),
+            Token {
                 token_type: TokenTypes::Plus,
-                lexeme: "+".to_string(),
+                lexeme: "+".into(),
                 literal: None,
-                line: 1,
+                location: Location::Detached { line: 1, column: 0 },
+                span: Span::default(),
             },
-            left: Box::new(Expr::Literal(Literal {
-                value: Some(LiteralEnum::Number(2.0)),
-            })),
-        });
+            Expr::binary(
+                Expr::literal(LiteralValue::Number(1.0)),
+                Token {
+                    token_type: TokenTypes::Minus,
+                    lexeme: "-".into(),
+                    literal: None,
+                    location: Location::Detached { line: 1, column: 0 },
+                    span: Span::default(),
+                },
+                Expr::literal(LiteralValue::Number(2.0)),
+            ),
+        );
         let mut printer = AstPrinter::default();
         assert_eq!(printer.print(expr), "(+ 2 (- 1 2))");
     }
+
+    #[test]
+    fn test_stmt_printing() {
+        let name = Token::new(TokenTypes::Identifier, "a".to_string(), None, 1);
+        let stmt = Stmt::block(vec![
+            Stmt::var(
+                name.clone(),
+                Some(Box::new(Expr::literal(LiteralValue::Number(1.0)))),
+            ),
+            Stmt::expression(Expr::assign(name.clone(), Expr::literal(None))),
+            Stmt::print(vec![Expr::variable(name)]),
+        ]);
+        let mut printer = AstPrinter::default();
+        assert_eq!(
+            printer.print_stmt(&stmt),
+            "(block (var a 1) (expr (= a nil)) (print a))"
+        );
+    }
+
+    #[test]
+    fn test_nan_printing() {
+        let mut scanner = crate::Scanner::new("-nan + NaN");
+        let expr = crate::Parser::new(scanner.scan_tokens())
+            .parse_expression()
+            .unwrap();
+        let mut printer = AstPrinter::default();
+        assert_eq!(printer.print(expr), "(+ (- NaN) NaN)");
+    }
 }
@@ -0,0 +1,113 @@
+#[cfg(feature = "interpreter")]
+use crate::interpreter::natives;
+use crate::{
+    parser::{Associativity, GRAMMAR, OPERATOR_LEVELS},
+    scanner::KEYWORDS,
+};
+
+/// Renders the language reference in markdown from the tables the scanner,
+/// parser and interpreter work from, so it can't fall out of date.
+pub fn generate_reference() -> String {
+    let mut result = String::from("# jBread language reference\n\n");
+    result.push_str("Generated by `j_bread --generate-reference`, don't edit by hand.\n\n");
+
+    result.push_str("## Operators\n\nFrom the lowest precedence to the highest.\n\n");
+    result.push_str("| Level | Name | Operators | Associativity |\n");
+    result.push_str("| --- | --- | --- | --- |\n");
+    for (level, operators) in OPERATOR_LEVELS.iter().enumerate() {
+        let symbols: Vec<String> = operators
+            .operators
+            .iter()
+            // Pipes would end the table cell, even inside code.
+            .map(|operator| format!("`{}`", operator.symbol().unwrap().replace('|', "\\|")))
+            .collect();
+        let associativity = match operators.associativity {
+            Associativity::Left => "left",
+            Associativity::Right => "right",
+            Associativity::Prefix => "prefix",
+            Associativity::Postfix => "postfix",
+        };
+        result.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            level + 1,
+            operators.name,
+            symbols.join(" "),
+            associativity
+        ));
+    }
+
+    result.push_str("\n## Keywords\n\n| Keyword | Meaning |\n| --- | --- |\n");
+    for keyword in KEYWORDS {
+        result.push_str(&format!(
+            "| `{}` | {} |\n",
+            keyword.name, keyword.description
+        ));
+    }
+
+    result.push_str("\n## Grammar\n\n```text\n");
+    result.push_str(GRAMMAR);
+    result.push_str("```\n");
+
+    #[cfg(feature = "interpreter")]
+    {
+        result.push_str("\n## Native functions\n\n| Function | Arguments | Description |\n");
+        result.push_str("| --- | --- | --- |\n");
+        for native in natives() {
+            result.push_str(&format!(
+                "| `{}({})` | {} | {} |\n",
+                native.name,
+                native.params.join(", "),
+                native.arity,
+                native.description
+            ));
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Scanner, TokenTypes};
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_reference_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/reference.md");
+        let committed = std::fs::read_to_string(path).unwrap_or_default();
+        assert!(
+            committed == generate_reference(),
+            "reference.md is out of date, regenerate it with \
+             `cargo run -- --generate-reference > reference.md`"
+        );
+    }
+
+    #[test]
+    fn test_operator_symbols_scan_back() {
+        for level in OPERATOR_LEVELS {
+            for operator in level.operators {
+                let symbol = operator.symbol().unwrap();
+                let mut scanner = Scanner::new(symbol);
+                assert_eq!(&scanner.scan_tokens()[0].token_type, operator, "{}", symbol);
+            }
+        }
+        for keyword in KEYWORDS {
+            let mut scanner = Scanner::new(keyword.name);
+            assert_eq!(scanner.scan_tokens()[0].token_type, keyword.token_type);
+        }
+        assert_eq!(TokenTypes::Identifier.symbol(), None);
+    }
+
+    #[cfg(feature = "interpreter")]
+    #[test]
+    fn test_native_params_match_arity() {
+        for native in natives() {
+            let params = match native.arity.is_variadic() {
+                true => native.arity.min + 1,
+                false => native.arity.max,
+            };
+            assert_eq!(native.params.len(), params, "{}", native.name);
+        }
+    }
+}
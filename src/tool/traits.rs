@@ -1,4 +1,9 @@
-use crate::ast::{VisitorExpr, VisitorStmt};
+use std::rc::Rc;
+
+use crate::{
+    ast::{VisitorExpr, VisitorStmt},
+    LiteralValue, Token,
+};
 
 pub trait AstStmt {
     fn accept<V: VisitorStmt>(&self, visitor: &mut V) -> V::Result;
@@ -7,3 +12,88 @@ pub trait AstStmt {
 pub trait AstNode {
     fn accept<K: VisitorExpr>(&self, visitor: &mut K) -> K::Result;
 }
+
+/// A node, or a field holding nodes, that a walker `W` can walk through. The
+/// nodes themselves implement it for their AST's walker, see
+/// [`define_ast!`](crate::define_ast).
+pub trait Walk<W> {
+    fn walk(&self, walker: &mut W);
+}
+
+impl<W, T: Walk<W>> Walk<W> for Box<T> {
+    fn walk(&self, walker: &mut W) {
+        T::walk(self, walker)
+    }
+}
+
+impl<W, T: Walk<W>> Walk<W> for Rc<T> {
+    fn walk(&self, walker: &mut W) {
+        T::walk(self, walker)
+    }
+}
+
+impl<W, T: Walk<W>> Walk<W> for Option<T> {
+    fn walk(&self, walker: &mut W) {
+        if let Some(node) = self {
+            node.walk(walker)
+        }
+    }
+}
+
+impl<W, T: Walk<W>> Walk<W> for Vec<T> {
+    fn walk(&self, walker: &mut W) {
+        for node in self {
+            node.walk(walker)
+        }
+    }
+}
+
+/// Leaves, there's nothing under them to walk.
+impl<W> Walk<W> for Token {
+    fn walk(&self, _walker: &mut W) {}
+}
+
+impl<W> Walk<W> for LiteralValue {
+    fn walk(&self, _walker: &mut W) {}
+}
+
+/// A node, or a field holding nodes, whose spans can be emptied. The nodes
+/// themselves implement it through [`define_ast!`](crate::define_ast).
+pub trait StripSpans {
+    fn strip_spans(&mut self);
+}
+
+impl<T: StripSpans> StripSpans for Box<T> {
+    fn strip_spans(&mut self) {
+        T::strip_spans(self)
+    }
+}
+
+impl<T: StripSpans + Clone> StripSpans for Rc<T> {
+    fn strip_spans(&mut self) {
+        Rc::make_mut(self).strip_spans()
+    }
+}
+
+impl<T: StripSpans> StripSpans for Option<T> {
+    fn strip_spans(&mut self) {
+        if let Some(node) = self {
+            node.strip_spans()
+        }
+    }
+}
+
+impl<T: StripSpans> StripSpans for Vec<T> {
+    fn strip_spans(&mut self) {
+        self.iter_mut().for_each(T::strip_spans)
+    }
+}
+
+/// A token keeps its own span, it's where the token was scanned.
+impl StripSpans for Token {
+    fn strip_spans(&mut self) {}
+}
+
+impl StripSpans for LiteralValue {
+    fn strip_spans(&mut self) {}
+}
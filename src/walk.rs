@@ -0,0 +1,70 @@
+//! Walking a parsed program without hand-rolling the recursion.
+//!
+//! The walker traits are generated next to the AST by
+//! [`define_ast!`](crate::define_ast): every method defaults to visiting the
+//! node's children in source order, so a pass only overrides the nodes it
+//! looks at. An overridden method calls [`walk_expr`] or [`walk_stmt`] on the
+//! children it still wants visited.
+
+use crate::{
+    ast::{Expr, Stmt},
+    Walk,
+};
+
+pub use crate::ast::{ExprWalker, StmtWalker};
+
+/// A pass over whole programs, i.e. both an [`ExprWalker`] and a
+/// [`StmtWalker`]. Implemented for every `StmtWalker`.
+pub trait Walker: StmtWalker {}
+
+impl<W: StmtWalker> Walker for W {}
+
+/// Visits `expr` with `walker`, through the method for its kind.
+pub fn walk_expr<W: ExprWalker>(walker: &mut W, expr: &Expr) {
+    expr.walk(walker)
+}
+
+/// Visits `stmt` with `walker`, through the method for its kind.
+pub fn walk_stmt<W: StmtWalker>(walker: &mut W, stmt: &Stmt) {
+    stmt.walk(walker)
+}
+
+/// Visits each statement of a program in turn.
+pub fn walk_program<W: Walker>(walker: &mut W, program: &[Stmt]) {
+    for stmt in program {
+        walk_stmt(walker, stmt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Binary, parser::Parser, Scanner};
+
+    /// Records the operators of binary expressions, the left one first.
+    #[derive(Default)]
+    struct Operators(Vec<String>);
+
+    impl ExprWalker for Operators {
+        fn visit_expr_binary(&mut self, expr: &Binary) {
+            walk_expr(self, &expr.left);
+            self.0.push(expr.operator.lexeme.to_string());
+            walk_expr(self, &expr.right);
+        }
+    }
+
+    impl StmtWalker for Operators {}
+
+    #[test]
+    fn test_walk_in_source_order() {
+        let mut scanner = Scanner::new(
+            "print 1 + 2 * 3; fun f(x) { return x - 1; } \
+             class C { m() { print [this.a == 1, \"${2 / 3}\"]; } } \
+             var f = (x) -> x > 1;",
+        );
+        let program = Parser::new(scanner.scan_tokens()).parse().unwrap();
+        let mut operators = Operators::default();
+        walk_program(&mut operators, &program);
+        assert_eq!(operators.0, ["+", "*", "-", "==", "/", ">"]);
+    }
+}
@@ -0,0 +1,179 @@
+//! Runs the programs under `tests/compat` with the interpreter's defaults
+//! and fails when one no longer behaves as it did when written.
+//!
+//! Each program starts with comments saying what it was written against and
+//! what running it gives:
+//!
+//! ```text
+//! // written for: 0.1.0
+//! // expect: first line printed
+//! // expect: second line printed
+//! // expect exit: 70
+//! ```
+//!
+//! The exit status is 0 unless given. A program whose behavior changed on
+//! purpose is listed in `tests/compat/breaking.txt` along with the heading
+//! in `CHANGELOG.md` recording the change, and then has to behave
+//! differently, so the list doesn't outlive the break.
+
+#![cfg(feature = "interpreter")]
+
+use std::{fs, path::Path, process::Command};
+
+const COMPAT_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/compat");
+
+struct Program {
+    name: String,
+    written_for: (u32, u32, u32),
+    expected: Vec<String>,
+    exit_code: i32,
+}
+
+impl Program {
+    fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let mut written_for = None;
+        let mut expected = Vec::new();
+        let mut exit_code = 0;
+        for line in source.lines() {
+            let Some(comment) = line.strip_prefix("//") else {
+                break;
+            };
+            let comment = comment.trim_start();
+            if let Some(version) = comment.strip_prefix("written for:") {
+                written_for = Some(version_of(version.trim())?);
+            } else if let Some(code) = comment.strip_prefix("expect exit:") {
+                exit_code = code
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("{}: bad exit status {:?}", name, code.trim()))?;
+            } else if let Some(output) = comment.strip_prefix("expect:") {
+                expected.push(output.strip_prefix(' ').unwrap_or(output).to_string());
+            }
+        }
+        Ok(Self {
+            name: name.to_string(),
+            written_for: written_for.ok_or(format!("{}: no `written for:` version", name))?,
+            expected,
+            exit_code,
+        })
+    }
+
+    /// What running it printed and the status it exited with.
+    fn run(&self) -> (Vec<String>, i32) {
+        let output = Command::new(env!("CARGO_BIN_EXE_j_bread"))
+            .arg(Path::new(COMPAT_DIR).join(&self.name))
+            .output()
+            .expect("the interpreter runs");
+        let printed = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect();
+        (printed, output.status.code().unwrap_or(-1))
+    }
+}
+
+fn version_of(version: &str) -> Result<(u32, u32, u32), String> {
+    let parts: Vec<u32> = version
+        .split('.')
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("bad version {:?}", version))?;
+    match parts.as_slice() {
+        [major, minor, patch] => Ok((*major, *minor, *patch)),
+        _ => Err(format!("bad version {:?}", version)),
+    }
+}
+
+/// The programs listed as broken on purpose, with their changelog heading.
+fn breaking_changes() -> Vec<(String, String)> {
+    fs::read_to_string(Path::new(COMPAT_DIR).join("breaking.txt"))
+        .expect("tests/compat/breaking.txt exists")
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, heading) = line
+                .split_once(':')
+                .unwrap_or_else(|| panic!("breaking.txt: no heading in {:?}", line));
+            (name.trim().to_string(), heading.trim().to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn test_compat_programs() {
+    let current = version_of(env!("CARGO_PKG_VERSION")).unwrap();
+    let changelog = fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/CHANGELOG.md"))
+        .expect("CHANGELOG.md exists");
+    let breaking = breaking_changes();
+
+    let mut names: Vec<String> = fs::read_dir(COMPAT_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".jbr"))
+        .collect();
+    names.sort();
+    assert!(!names.is_empty(), "no programs in {}", COMPAT_DIR);
+
+    let mut failures = Vec::new();
+    for (name, _) in &breaking {
+        if !names.contains(name) {
+            failures.push(format!("{}: listed in breaking.txt but missing", name));
+        }
+    }
+    for name in &names {
+        let source = fs::read_to_string(Path::new(COMPAT_DIR).join(name)).unwrap();
+        let program = match Program::parse(name, &source) {
+            Ok(program) => program,
+            Err(error) => {
+                failures.push(error);
+                continue;
+            }
+        };
+        if program.written_for > current {
+            failures.push(format!("{}: written for a version after this one", name));
+        }
+        let (printed, exit_code) = program.run();
+        let unchanged = printed == program.expected && exit_code == program.exit_code;
+        match breaking.iter().find(|(broken, _)| broken == name) {
+            Some((_, heading)) => {
+                if !changelog
+                    .lines()
+                    .any(|line| line.trim_start_matches('#').trim() == heading)
+                {
+                    failures.push(format!(
+                        "{}: no {:?} heading in CHANGELOG.md",
+                        name, heading
+                    ));
+                }
+                if unchanged {
+                    failures.push(format!(
+                        "{}: runs as written again, take it out of breaking.txt",
+                        name
+                    ));
+                }
+            }
+            None if !unchanged => failures.push(format!(
+                "{}: expected {:?} exiting {}, got {:?} exiting {}. If that's on purpose, \
+                 list it in tests/compat/breaking.txt with a CHANGELOG.md entry",
+                name, program.expected, program.exit_code, printed, exit_code
+            )),
+            None => {}
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn test_program_header() {
+    let program = Program::parse(
+        "a.jbr",
+        "// written for: 0.1.2\n// expect: one\n//expect:  two\n// expect exit: 70\nprint 1;\n// expect: ignored",
+    )
+    .unwrap();
+    assert_eq!(program.written_for, (0, 1, 2));
+    assert_eq!(program.expected, ["one", " two"]);
+    assert_eq!(program.exit_code, 70);
+
+    assert!(Program::parse("b.jbr", "// expect: 1\n").is_err());
+    assert!(Program::parse("c.jbr", "// written for: 1.0\n").is_err());
+}
@@ -0,0 +1,146 @@
+//! Runs every program under `tests/scripts` through the whole pipeline in
+//! process and compares what it printed with what it says it prints.
+//!
+//! A script marks each line it prints with an `expect:` comment, in order,
+//! and a script that has to fail marks a piece of the error with
+//! `expect-error:`:
+//!
+//! ```text
+//! print 1 + 2; // expect: 3
+//! print missing; // expect-error: Undefined variable
+//! ```
+//!
+//! Covering a feature only takes another script.
+
+#![cfg(feature = "interpreter")]
+
+use std::{fs, path::Path};
+
+use j_bread::{Interpreter, JuniorBread, LanguageProfile, OutputBuffer};
+
+const SCRIPTS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/scripts");
+
+struct Script {
+    expected: Vec<String>,
+    error: Option<String>,
+}
+
+impl Script {
+    fn parse(source: &str) -> Self {
+        let mut expected = Vec::new();
+        let mut error = None;
+        for line in source.lines() {
+            let Some((_, comment)) = line.split_once("//") else {
+                continue;
+            };
+            let comment = comment.trim_start();
+            if let Some(message) = comment.strip_prefix("expect-error:") {
+                error = Some(message.trim().to_string());
+            } else if let Some(output) = comment.strip_prefix("expect:") {
+                expected.push(output.strip_prefix(' ').unwrap_or(output).to_string());
+            }
+        }
+        Self { expected, error }
+    }
+}
+
+/// Runs `source` in a fresh interpreter, returning the printed lines and
+/// the error it stopped with.
+fn run(source: &str) -> (Vec<String>, Option<String>) {
+    run_with(JuniorBread::new(), source)
+}
+
+fn run_with(bread: JuniorBread, source: &str) -> (Vec<String>, Option<String>) {
+    let output = OutputBuffer::new();
+    let mut interpreter = Interpreter::default()
+        .with_output(output.clone())
+        .with_error_output(OutputBuffer::new());
+    let result = bread.run(source, &mut interpreter);
+    let printed = output.take();
+    (
+        printed.lines().map(str::to_string).collect(),
+        result.err().map(|error| error.to_string()),
+    )
+}
+
+/// The names of the scripts, sorted.
+fn scripts() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(SCRIPTS_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".jbr"))
+        .collect();
+    names.sort();
+    assert!(!names.is_empty(), "no scripts in {}", SCRIPTS_DIR);
+    names
+}
+
+fn read_script(name: &str) -> String {
+    fs::read_to_string(Path::new(SCRIPTS_DIR).join(name)).unwrap()
+}
+
+#[test]
+fn test_scripts() {
+    let mut failures = Vec::new();
+    for name in &scripts() {
+        let source = read_script(name);
+        let script = Script::parse(&source);
+        let (printed, error) = run(&source);
+        if printed != script.expected {
+            failures.push(format!(
+                "{}: expected {:?}, printed {:?}",
+                name, script.expected, printed
+            ));
+        }
+        match (&script.error, &error) {
+            (Some(expected), Some(error)) if error.contains(expected.as_str()) => {}
+            (None, None) => {}
+            (expected, error) => failures.push(format!(
+                "{}: expected error {:?}, got {:?}",
+                name, expected, error
+            )),
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+/// Folding constants mustn't change what any script prints or fails with,
+/// the line of the failure included.
+#[test]
+fn test_scripts_with_constant_folding() {
+    for name in &scripts() {
+        let source = read_script(name);
+        assert_eq!(
+            run_with(JuniorBread::new().with_constant_folding(true), &source),
+            run(&source),
+            "{}",
+            name
+        );
+    }
+}
+
+/// A profile disabling nothing must behave exactly as no profile.
+#[test]
+fn test_scripts_with_default_profile() {
+    for name in &scripts() {
+        let source = read_script(name);
+        assert_eq!(
+            run_with(
+                JuniorBread::new().with_profile(LanguageProfile::default()),
+                &source
+            ),
+            run(&source),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_script_comments() {
+    let script = Script::parse(
+        "print 1; // expect: 1\n//expect:  two\nprint x; // expect-error: Undefined\n",
+    );
+    assert_eq!(script.expected, ["1", " two"]);
+    assert_eq!(script.error.as_deref(), Some("Undefined"));
+}